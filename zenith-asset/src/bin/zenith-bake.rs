@@ -0,0 +1,30 @@
+//! Bake raw resources of content/ folder into cache/ without launching the engine.
+//!
+//! Usage: zenith-bake [content subdirectory]
+
+use zenith_asset::manager::AssetManager;
+use zenith_core::log::{error, info};
+
+fn main() -> Result<(), anyhow::Error> {
+    zenith_task::initialize();
+    zenith_core::log::initialize()?;
+    zenith_asset::initialize()?;
+
+    let content_subdir = std::env::args().nth(1).unwrap_or_default();
+
+    let manager = AssetManager::new();
+    let report = manager.bake_all(&content_subdir)?;
+
+    for url in &report.baked {
+        info!("baked {:?}", url);
+    }
+    for (path, err) in &report.failures {
+        error!("failed to bake {:?}: {}", path, err);
+    }
+    info!("{} baked, {} failed", report.baked.len(), report.failures.len());
+
+    if !report.failures.is_empty() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
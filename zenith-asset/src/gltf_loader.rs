@@ -1,9 +1,17 @@
 use anyhow::{anyhow, Result};
+use std::ops::Deref;
 use std::path::{Path, PathBuf};
-use gltf::{buffer::Data as BufferData, image::Data as ImageData, Document, Primitive};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use glam::{Mat4, Quat, Vec3};
+use gltf::{buffer::Data as BufferData, image::Data as ImageData, Primitive};
+use gltf::texture::{MinFilter, WrappingMode};
+use memmap2::Mmap;
 use zenith_core::file::load_with_memory_mapping;
-use zenith_core::log::info;
-use crate::render::{Material, MaterialBuilder, Mesh, MeshBuilder, MeshCollection, TextureBuilder, TextureFormat, Vertex};
+use zenith_core::log::{info, target::ASSET};
+use zenith_core::math::Transform;
+use crate::render::{AddressMode, AlphaMode, ColorSpace, Material, MaterialBuilder, Mesh, MeshBuilder, MeshCollection, TextureBuilder, TextureFormat, Vertex};
+use bincode::Encode;
 use crate::{Asset, RawResourceBaker, AssetRegistry, RawResource, RawResourceLoader, AssetUrl, serialize_asset};
 use zenith_task::{submit, TaskResult};
 
@@ -16,38 +24,71 @@ impl GltfLoader {
     }
 }
 
+/// Per-phase durations and decoded byte counts collected while loading a gltf file.
+#[derive(Debug, Clone, Default)]
+pub struct LoadStats {
+    pub parse: Duration,
+    pub buffers: Duration,
+    pub images: Duration,
+    pub buffer_bytes: usize,
+    /// Bytes of external buffers which are memory mapped instead of copied.
+    pub mapped_buffer_bytes: usize,
+    /// Decoded bytes of each image, in the same order as the gltf images.
+    pub image_bytes: Vec<usize>,
+}
+
+impl LoadStats {
+    pub fn total(&self) -> Duration {
+        self.parse + self.buffers + self.images
+    }
+
+    pub fn total_image_bytes(&self) -> usize {
+        self.image_bytes.iter().sum()
+    }
+}
+
+/// Content of a gltf buffer.
+/// External buffer files are kept memory mapped, so they are never copied into memory as a whole.
+enum GltfBuffer {
+    Decoded(BufferData),
+    Mapped(Mmap),
+}
+
+impl Deref for GltfBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            GltfBuffer::Decoded(data) => &data.0,
+            GltfBuffer::Mapped(mmap) => &mmap[..],
+        }
+    }
+}
+
 pub struct RawGltf {
     path: PathBuf,
     gltf: gltf::Gltf,
-    buffers: Vec<BufferData>,
+    buffers: Vec<GltfBuffer>,
     images: Vec<ImageData>,
+    /// External buffer and image files, relative to the gltf file.
+    dependencies: Vec<PathBuf>,
 }
 
 impl RawResource for RawGltf {
     fn load_path(&self) -> &Path {
         self.path.as_path()
     }
+
+    fn dependencies(&self) -> &[PathBuf] {
+        &self.dependencies
+    }
 }
 
 impl RawResourceLoader for GltfLoader {
     type Raw = RawGltf;
 
     fn load(path: &Path) -> Result<Self::Raw> {
-        let mmap = load_with_memory_mapping(path)?;
-
-        let gltf = gltf::Gltf::from_slice(&mmap)
-            .map_err(|e| anyhow!("Failed to parse GLTF: {}", e))?;
-
-        let mut raw = RawGltf {
-            path: path.to_owned(),
-            gltf,
-            buffers: vec![],
-            images: vec![],
-        };
-        
-        Self::load_gltf(path, &mut raw)?;
-        
-        Ok(raw)
+        Self::load_with_stats(path, &mut LoadStats::default())
     }
 
     fn load_async(raw_content_path: &Path) -> TaskResult<Result<Self::Raw>> {
@@ -59,8 +100,21 @@ impl RawResourceLoader for GltfLoader {
     }
 }
 
+/// Levels of detail generated for each baked mesh, besides the full mesh.
+const BAKED_LOD_COUNT: usize = 3;
+
 pub struct RawGltfProcessor;
 
+/// A primitive reachable from the scene nodes.
+struct ScenePrimitive {
+    mesh: usize,
+    primitive: usize,
+    /// Index of the gltf material, None for the default material.
+    material: Option<usize>,
+    /// Transform from the mesh space to the scene space.
+    world_matrix: Mat4,
+}
+
 impl RawGltfProcessor {
     pub fn new() -> Self {
         Self
@@ -68,38 +122,65 @@ impl RawGltfProcessor {
 }
 
 impl RawGltfProcessor {
-    fn process_node(
-        base_directory: &PathBuf,
-        node: &gltf::Node,
-        buffers: &[BufferData],
-        registry: &AssetRegistry,
-        meshes_url: &mut Vec<AssetUrl>,
-        main_url: &str,
-    ) -> Result<()> {
+    /// Collect all primitives under the node in depth-first order.
+    /// `parent_matrix` is the accumulated transform of all ancestors of the node.
+    fn process_node(node: &gltf::Node, parent_matrix: Mat4, primitives: &mut Vec<ScenePrimitive>) {
+        let world_matrix = parent_matrix * Self::node_transform(node).to_matrix();
+
         if let Some(mesh) = node.mesh() {
-            for primitive in mesh.primitives() {
-                // TODO: abstract asset serialize and register logic
-                let mesh_asset = Self::bake_mesh(&primitive, buffers)?;
-                let url = mesh_asset.url(&main_url);
+            primitives.extend(mesh.primitives().map(|primitive| ScenePrimitive {
+                mesh: mesh.index(),
+                primitive: primitive.index(),
+                material: primitive.material().index(),
+                world_matrix,
+            }));
+        }
 
-                let asset_serialize_path = base_directory.join(&url);
-                serialize_asset(&mesh_asset, &asset_serialize_path)?;
+        for child in node.children() {
+            Self::process_node(&child, world_matrix, primitives);
+        }
+    }
 
-                meshes_url.push(url.clone());
-                registry.register(url, mesh_asset);
-            }
+    fn node_transform(node: &gltf::Node) -> Transform {
+        let (translation, rotation, scale) = node.transform().decomposed();
+        Transform {
+            translation: Vec3::from_array(translation),
+            rotation: Quat::from_array(rotation),
+            scale: Vec3::from_array(scale),
         }
+    }
 
-        for child in node.children() {
-            Self::process_node(base_directory, &child, buffers, registry, meshes_url, main_url)?;
+    /// Pair every mesh with the material its primitive references, multiple meshes may share one material.
+    /// Primitives without material use the one at `default_material`.
+    fn build_collection(
+        url: &AssetUrl,
+        meshes: Vec<(AssetUrl, Option<usize>, Mat4)>,
+        material_urls: &[AssetUrl],
+        default_material: usize,
+    ) -> Result<MeshCollection> {
+        let mut mesh_collection = MeshCollection::new(url);
+        for (mesh_url, material, transform) in meshes {
+            let material_index = material.unwrap_or(default_material);
+            let material_url = material_urls
+                .get(material_index)
+                .ok_or(anyhow!("Mesh {:?} references missing material {}", mesh_url, material_index))?;
+
+            mesh_collection.add_mesh(mesh_url, material_url.clone(), transform);
         }
+        Ok(mesh_collection)
+    }
 
-        Ok(())
+    /// Serialize the asset into cache/ folder and register it, return its url.
+    fn save_and_register<A: Asset + Encode>(asset: A, name: &str, registry: &AssetRegistry, base_directory: &Path) -> Result<AssetUrl> {
+        let url = asset.url(name);
+        serialize_asset(&asset, &base_directory.join(&url))?;
+        registry.register(url.clone(), asset);
+        Ok(url)
     }
 
     fn bake_mesh(
         primitive: &Primitive,
-        buffers: &[BufferData],
+        buffers: &[GltfBuffer],
     ) -> Result<Mesh> {
         let reader = primitive.reader(|buffer| Some(&*buffers[buffer.index()]));
 
@@ -132,22 +213,58 @@ impl RawGltfProcessor {
             return Err(anyhow!("Vertex attribute count mismatch"));
         }
 
+        let tangents = if let Some(tangents) = reader.read_tangents() {
+            tangents.collect::<Vec<_>>()
+        } else {
+            // Generate tangents from positions and uvs if missing
+            Self::generate_tangents(&positions, &normals, &tex_coords, &indices)
+        };
+
+        if positions.len() != tangents.len() {
+            return Err(anyhow!("Vertex attribute count mismatch"));
+        }
+
+        let tex_coords1 = reader
+            .read_tex_coords(1)
+            .map(|tex_coords| tex_coords.into_f32().collect::<Vec<_>>());
+        let colors = reader
+            .read_colors(0)
+            .map(|colors| colors.into_rgba_u8().collect::<Vec<_>>());
+
+        if tex_coords1.as_ref().is_some_and(|uvs| uvs.len() != positions.len())
+            || colors.as_ref().is_some_and(|colors| colors.len() != positions.len()) {
+            return Err(anyhow!("Vertex attribute count mismatch"));
+        }
+
         let vertices: Vec<Vertex> = positions
             .into_iter()
             .zip(normals.into_iter())
             .zip(tex_coords.into_iter())
-            .map(|((pos, norm), uv)| {
-                Vertex::new(
+            .zip(tangents.into_iter())
+            .enumerate()
+            .map(|(index, (((pos, norm), uv), tangent))| {
+                let mut vertex = Vertex::new(
                     glam::Vec3::from_array(pos),
                     glam::Vec3::from_array(norm),
                     glam::Vec2::from_array(uv),
-                )
+                    glam::Vec4::from_array(tangent),
+                );
+                if let Some(tex_coords1) = &tex_coords1 {
+                    vertex = vertex.with_tex_coord1(glam::Vec2::from_array(tex_coords1[index]));
+                }
+                if let Some(colors) = &colors {
+                    vertex = vertex.with_color(colors[index]);
+                }
+                vertex
             })
             .collect();
 
         let mesh = MeshBuilder::default()
             .vertices(vertices)
             .indices(indices)
+            .optimize(true)
+            .generate_lods(BAKED_LOD_COUNT)
+            .material(primitive.material().index())
             .build()?;
 
         Ok(mesh)
@@ -175,85 +292,175 @@ impl RawGltfProcessor {
         Ok(normals)
     }
 
-    fn bake_materials(gltf: &Document, images: &[ImageData]) -> Result<Vec<Material>> {
-        let mut materials = Vec::new();
-
-        for material in gltf.materials() {
-            let pbr = material.pbr_metallic_roughness();
+    /// Accumulate per-triangle tangents and bitangents, then orthogonalize them against the vertex normal.
+    /// The handedness of the bitangent is stored in w.
+    fn generate_tangents(
+        positions: &[[f32; 3]],
+        normals: &[[f32; 3]],
+        tex_coords: &[[f32; 2]],
+        indices: &[u32],
+    ) -> Vec<[f32; 4]> {
+        let mut tangents = vec![glam::Vec3::ZERO; positions.len()];
+        let mut bitangents = vec![glam::Vec3::ZERO; positions.len()];
+
+        for triangle in indices.chunks_exact(3) {
+            let (i0, i1, i2) = (triangle[0] as usize, triangle[1] as usize, triangle[2] as usize);
+
+            let p0 = glam::Vec3::from_array(positions[i0]);
+            let p1 = glam::Vec3::from_array(positions[i1]);
+            let p2 = glam::Vec3::from_array(positions[i2]);
+
+            let uv0 = glam::Vec2::from_array(tex_coords[i0]);
+            let uv1 = glam::Vec2::from_array(tex_coords[i1]);
+            let uv2 = glam::Vec2::from_array(tex_coords[i2]);
+
+            let edge1 = p1 - p0;
+            let edge2 = p2 - p0;
+            let delta_uv1 = uv1 - uv0;
+            let delta_uv2 = uv2 - uv0;
+
+            let det = delta_uv1.x * delta_uv2.y - delta_uv2.x * delta_uv1.y;
+            // degenerated uv mapping, skip this triangle
+            if det.abs() <= f32::EPSILON {
+                continue;
+            }
+            let r = 1.0 / det;
 
-            let mut builder = MaterialBuilder::default();
-            builder.base_color(pbr.base_color_factor())
-                .metallic(pbr.metallic_factor())
-                .roughness(pbr.roughness_factor())
-                .emissive(material.emissive_factor());
+            let tangent = (edge1 * delta_uv2.y - edge2 * delta_uv1.y) * r;
+            let bitangent = (edge2 * delta_uv1.x - edge1 * delta_uv2.x) * r;
 
-            if let Some(texture) = pbr.base_color_texture() {
-                let image_index = texture.texture().source().index();
-                if let Some(image_data) = images.get(image_index) {
-                    let tex = Self::create_texture_from_gltf_image(image_data)?;
-                    builder.base_color_tex(tex);
-                }
+            for index in [i0, i1, i2] {
+                tangents[index] += tangent;
+                bitangents[index] += bitangent;
             }
+        }
 
-            if let Some(texture) = pbr.metallic_roughness_texture() {
-                let image_index = texture.texture().source().index();
-                if let Some(image_data) = images.get(image_index) {
-                    let tex = Self::create_texture_from_gltf_image(image_data)?;
-                    builder.mra_tex(tex);
+        normals
+            .iter()
+            .zip(tangents.into_iter().zip(bitangents.into_iter()))
+            .map(|(normal, (tangent, bitangent))| {
+                let normal = glam::Vec3::from_array(*normal);
+
+                // Gram-Schmidt orthogonalize
+                let mut t = (tangent - normal * normal.dot(tangent)).normalize_or_zero();
+                if t == glam::Vec3::ZERO {
+                    // fallback to any vector perpendicular to the normal
+                    t = normal.any_orthonormal_vector();
                 }
+
+                let handedness = if normal.cross(t).dot(bitangent) < 0.0 { -1.0 } else { 1.0 };
+                [t.x, t.y, t.z, handedness]
+            })
+            .collect()
+    }
+
+    fn bake_material(material: &gltf::Material, images: &[ImageData]) -> Result<Material> {
+        let pbr = material.pbr_metallic_roughness();
+
+        let mut builder = MaterialBuilder::default();
+        builder.base_color(pbr.base_color_factor())
+            .metallic(pbr.metallic_factor())
+            .roughness(pbr.roughness_factor())
+            .emissive(material.emissive_factor())
+            .alpha_mode(match material.alpha_mode() {
+                gltf::material::AlphaMode::Opaque => AlphaMode::Opaque,
+                gltf::material::AlphaMode::Mask => AlphaMode::Mask,
+                gltf::material::AlphaMode::Blend => AlphaMode::Blend,
+            })
+            // glTF default cutoff
+            .alpha_cutoff(material.alpha_cutoff().unwrap_or(0.5))
+            .double_sided(material.double_sided());
+
+        if let Some(texture) = pbr.base_color_texture() {
+            let image_index = texture.texture().source().index();
+            if let Some(image_data) = images.get(image_index) {
+                let tex = Self::create_texture_from_gltf_image(image_data, &texture.texture().sampler(), ColorSpace::Srgb, Some(TextureFormat::Bc7RgbaUnorm))?;
+                builder.base_color_tex(tex);
             }
+        }
 
-            if let Some(texture) = material.normal_texture() {
-                let image_index = texture.texture().source().index();
-                if let Some(image_data) = images.get(image_index) {
-                    let tex = Self::create_texture_from_gltf_image(image_data)?;
-                    builder.normal_tex(tex);
-                }
+        if let Some(texture) = pbr.metallic_roughness_texture() {
+            let image_index = texture.texture().source().index();
+            if let Some(image_data) = images.get(image_index) {
+                let tex = Self::create_texture_from_gltf_image(image_data, &texture.texture().sampler(), ColorSpace::Linear, None)?;
+                builder.mra_tex(tex);
             }
+        }
 
-            // if let Some(texture) = material.occlusion_texture() {
-            //     let image_index = texture.texture().source().index();
-            //     if let Some(image_data) = images.get(image_index) {
-            //         pbr_material.textures.occlusion = Some(TextureData {
-            //             pixels: image_data.pixels.clone(),
-            //             width: image_data.width,
-            //             height: image_data.height,
-            //             format: image_data.format,
-            //         });
-            //     }
-            // }
-
-            if let Some(texture) = material.emissive_texture() {
-                let image_index = texture.texture().source().index();
-                if let Some(image_data) = images.get(image_index) {
-                    let tex = Self::create_texture_from_gltf_image(image_data)?;
-                    builder.emissive_tex(tex);
-                }
+        if let Some(texture) = material.normal_texture() {
+            let image_index = texture.texture().source().index();
+            if let Some(image_data) = images.get(image_index) {
+                let tex = Self::create_texture_from_gltf_image(image_data, &texture.texture().sampler(), ColorSpace::Linear, Some(TextureFormat::Bc5RgUnorm))?;
+                builder.normal_tex(tex);
             }
+        }
 
-            materials.push(builder.build()?);
+        if let Some(texture) = material.occlusion_texture() {
+            builder.occlusion_strength(texture.strength());
+
+            let image_index = texture.texture().source().index();
+            let mra_image_index = pbr.metallic_roughness_texture().map(|mra| mra.texture().source().index());
+
+            // packed ORM textures share the same image, keep only one copy
+            if mra_image_index == Some(image_index) {
+                builder.occlusion_in_mra(true);
+            } else if let Some(image_data) = images.get(image_index) {
+                let tex = Self::create_texture_from_gltf_image(image_data, &texture.texture().sampler(), ColorSpace::Linear, None)?;
+                builder.occlusion_tex(tex);
+            }
         }
 
-        if materials.is_empty() {
-            materials.push(MaterialBuilder::default().build()?);
+        if let Some(texture) = material.emissive_texture() {
+            let image_index = texture.texture().source().index();
+            if let Some(image_data) = images.get(image_index) {
+                let tex = Self::create_texture_from_gltf_image(image_data, &texture.texture().sampler(), ColorSpace::Srgb, None)?;
+                builder.emissive_tex(tex);
+            }
         }
 
-        Ok(materials)
+        Ok(builder.build()?)
     }
 
-    fn create_texture_from_gltf_image(image_data: &ImageData) -> Result<crate::render::Texture> {
+    /// `color_space` comes from the material slot the texture fills, e.g. normal maps are linear.
+    /// Mips are generated unless the sampler never minifies with them.
+    fn create_texture_from_gltf_image(
+        image_data: &ImageData,
+        sampler: &gltf::texture::Sampler,
+        color_space: ColorSpace,
+        compression: Option<TextureFormat>,
+    ) -> Result<crate::render::Texture> {
         // Convert GLTF format to wgpu-compatible format and pixels
         let (wgpu_pixels, texture_format) = Self::convert_gltf_pixels_to_wgpu(image_data);
 
-        TextureBuilder::default()
-            .width(image_data.width)
+        let uses_mips = !matches!(sampler.min_filter(), Some(MinFilter::Nearest | MinFilter::Linear));
+
+        let mut builder = TextureBuilder::default();
+        builder.width(image_data.width)
             .height(image_data.height)
             .format(texture_format)
+            .color_space(color_space)
             .pixels(wgpu_pixels)
+            .address_mode(Self::address_mode(sampler.wrap_s()), Self::address_mode(sampler.wrap_t()), AddressMode::Repeat)
+            .generate_mips(uses_mips);
+
+        // only RGBA8 images can be transcoded
+        if let Some(format) = compression.filter(|_| texture_format == TextureFormat::R8G8B8A8) {
+            builder.compress(format);
+        }
+
+        builder
             .build()
             .map_err(|e| anyhow!("Failed to build texture: {}", e))
     }
 
+    fn address_mode(wrapping_mode: WrappingMode) -> AddressMode {
+        match wrapping_mode {
+            WrappingMode::Repeat => AddressMode::Repeat,
+            WrappingMode::MirroredRepeat => AddressMode::MirrorRepeat,
+            WrappingMode::ClampToEdge => AddressMode::ClampToEdge,
+        }
+    }
+
     fn convert_gltf_pixels_to_wgpu(data: &ImageData) -> (Vec<u8>, TextureFormat) {
         match data.format {
             gltf::image::Format::R8G8B8 => {
@@ -308,10 +515,22 @@ impl RawGltfProcessor {
     }
 }
 
-impl RawResourceBaker for RawGltfProcessor {
-    type Raw = RawGltf;
+/// Shared input of the material and mesh bake steps of one gltf file.
+struct GltfBake {
+    document: gltf::Document,
+    buffers: Vec<GltfBuffer>,
+    images: Vec<ImageData>,
+    primitives: Vec<ScenePrimitive>,
+    /// Index of the default material, baked after all gltf materials only if any primitive uses it.
+    default_material: usize,
+    material_count: usize,
+    registry: &'static AssetRegistry,
+    base_directory: PathBuf,
+    url: AssetUrl,
+}
 
-    fn bake(raw: Self::Raw, registry: &AssetRegistry, base_directory: &PathBuf, url: &AssetUrl) -> Result<()> {
+impl GltfBake {
+    fn new(raw: RawGltf, registry: &'static AssetRegistry, base_directory: PathBuf, url: AssetUrl) -> Self {
         let RawGltf {
             gltf,
             buffers,
@@ -319,74 +538,184 @@ impl RawResourceBaker for RawGltfProcessor {
             ..
         } = raw;
 
-        let asset_url = url.path.to_str().ok_or(anyhow!(format!("Invalid asset url: {:?}", url)))?;
-
-        let materials = Self::bake_materials(&gltf, &images)?;
-        let mut material_urls = Vec::with_capacity(materials.len());
-        for material in materials {
-            // TODO: abstract asset serialize and register logic
-            let url = material.url(asset_url);
-
-            let asset_serialize_path = base_directory.join(&url);
-            serialize_asset(&material, &asset_serialize_path)?;
-
-            material_urls.push(url.clone());
-            registry.register(url, material);
-        }
-
-        let mut meshes_urls = Vec::with_capacity(material_urls.len());
-        for scene in gltf.scenes() {
+        let mut primitives = Vec::new();
+        for scene in gltf.document.scenes() {
             for node in scene.nodes() {
-                Self::process_node(&base_directory, &node, &buffers, registry, &mut meshes_urls, asset_url)?;
+                RawGltfProcessor::process_node(&node, Mat4::IDENTITY, &mut primitives);
             }
         }
 
-        assert_eq!(meshes_urls.len(), material_urls.len());
+        let default_material = gltf.document.materials().len();
+        let material_count = default_material + primitives.iter().any(|primitive| primitive.material.is_none()) as usize;
 
-        let mut mesh_collection = MeshCollection::new(&url);
-        for (mat, mesh) in material_urls.into_iter().zip(meshes_urls.into_iter()) {
-            mesh_collection.add_mesh(mesh, mat);
+        Self {
+            document: gltf.document,
+            buffers,
+            images,
+            primitives,
+            default_material,
+            material_count,
+            registry,
+            base_directory,
+            url,
         }
+    }
+
+    /// Every material and mesh has its own file, e.g. "mesh/cerberus/scene_0.mat".
+    fn indexed_name(&self, index: usize) -> String {
+        format!("{}_{}", self.url.path.with_extension("").display(), index)
+    }
+
+    fn bake_material(&self, index: usize) -> Result<AssetUrl> {
+        let material = match self.document.materials().nth(index) {
+            Some(material) => RawGltfProcessor::bake_material(&material, &self.images)?,
+            None => MaterialBuilder::default().build()?,
+        };
+        RawGltfProcessor::save_and_register(material, &self.indexed_name(index), self.registry, &self.base_directory)
+    }
+
+    fn bake_mesh(&self, index: usize) -> Result<AssetUrl> {
+        let ScenePrimitive { mesh: mesh_index, primitive: primitive_index, .. } = self.primitives[index];
+        let mesh = self.document.meshes().nth(mesh_index).unwrap();
+        let primitive = mesh.primitives().nth(primitive_index).unwrap();
+
+        let mesh_asset = RawGltfProcessor::bake_mesh(&primitive, &self.buffers)?;
+        RawGltfProcessor::save_and_register(mesh_asset, &self.indexed_name(index), self.registry, &self.base_directory)
+    }
+
+    /// Save the collection of all baked meshes, urls are in the order of materials and primitives.
+    fn finish(&self, material_urls: Vec<AssetUrl>, mesh_urls: Vec<AssetUrl>) -> Result<()> {
+        let asset_url = self.url.path.to_str().ok_or(anyhow!(format!("Invalid asset url: {:?}", self.url)))?;
+
+        let meshes = mesh_urls
+            .into_iter()
+            .zip(&self.primitives)
+            .map(|(mesh_url, primitive)| (mesh_url, primitive.material, primitive.world_matrix))
+            .collect();
+        let mesh_collection = RawGltfProcessor::build_collection(&self.url, meshes, &material_urls, self.default_material)?;
 
         let mesh_collection_url = mesh_collection.url(asset_url);
-        let asset_serialize_path = base_directory.join(&mesh_collection_url);
+        let asset_serialize_path = self.base_directory.join(&mesh_collection_url);
         serialize_asset(&mesh_collection, &asset_serialize_path)?;
 
-        info!("[{}] is loaded and serialized.", asset_url);
-        info!("{:?}", mesh_collection);
+        info!(target: ASSET, "[{}] is loaded and serialized.", asset_url);
+        info!(target: ASSET, "{:?}", mesh_collection);
 
         Ok(())
     }
 }
 
+/// Collect the results of `tasks` in order, chained by continuations so that no worker waits on another task.
+fn join_results<T: Send + 'static>(tasks: Vec<TaskResult<Result<T>>>) -> TaskResult<Result<Vec<T>>> {
+    let count = tasks.len();
+    tasks.into_iter().fold(submit(move || Ok(Vec::with_capacity(count))), |joined, task| {
+        joined.then(move |results: Result<Vec<T>>| task.map(move |result| {
+            let mut results = results?;
+            results.push(result?);
+            Ok(results)
+        }))
+    })
+}
+
+impl RawResourceBaker for RawGltfProcessor {
+    type Raw = RawGltf;
+
+    /// Materials and meshes are baked one after another on the calling thread.
+    fn bake(raw: Self::Raw, registry: &'static AssetRegistry, base_directory: &PathBuf, url: &AssetUrl) -> Result<()> {
+        let bake = GltfBake::new(raw, registry, base_directory.clone(), url.clone());
+
+        let material_urls = (0..bake.material_count)
+            .map(|index| bake.bake_material(index))
+            .collect::<Result<Vec<_>>>()?;
+        let mesh_urls = (0..bake.primitives.len())
+            .map(|index| bake.bake_mesh(index))
+            .collect::<Result<Vec<_>>>()?;
+
+        bake.finish(material_urls, mesh_urls)
+    }
+
+    /// Materials and meshes are baked in parallel, each in its own task, the collection is saved by a continuation of all of them.
+    fn bake_async(raw: Self::Raw, registry: &'static AssetRegistry, base_directory: PathBuf, url: AssetUrl) -> TaskResult<Result<()>> {
+        let bake = Arc::new(GltfBake::new(raw, registry, base_directory, url));
+
+        let material_tasks = (0..bake.material_count)
+            .map(|index| {
+                let bake = bake.clone();
+                submit(move || bake.bake_material(index))
+            })
+            .collect();
+        let mesh_tasks = (0..bake.primitives.len())
+            .map(|index| {
+                let bake = bake.clone();
+                submit(move || bake.bake_mesh(index))
+            })
+            .collect();
+
+        let mesh_urls = join_results(mesh_tasks);
+        join_results(material_tasks).then(move |material_urls| mesh_urls.map(move |mesh_urls| {
+            bake.finish(material_urls?, mesh_urls?)
+        }))
+    }
+
+    fn asset_url(raw_url: &AssetUrl) -> AssetUrl {
+        MeshCollection::new(raw_url).asset_url()
+    }
+}
+
 impl GltfLoader {
-    fn load_gltf<P: AsRef<Path>>(path: P, raw: &mut RawGltf) -> Result<()> {
+    /// Load a gltf file like [`RawResourceLoader::load`], recording per-phase durations and byte counts into `stats`.
+    pub fn load_with_stats(path: &Path, stats: &mut LoadStats) -> Result<RawGltf> {
+        let parse_start = Instant::now();
+        let mmap = load_with_memory_mapping(path)?;
+
+        let gltf = gltf::Gltf::from_slice(&mmap)
+            .map_err(|e| anyhow!("Failed to parse GLTF: {}", e))?;
+        stats.parse = parse_start.elapsed();
+
+        let mut raw = RawGltf {
+            path: path.to_owned(),
+            gltf,
+            buffers: vec![],
+            images: vec![],
+            dependencies: vec![],
+        };
+
+        Self::load_gltf(path, &mut raw, stats)?;
+
+        Ok(raw)
+    }
+
+    fn load_gltf<P: AsRef<Path>>(path: P, raw: &mut RawGltf, stats: &mut LoadStats) -> Result<()> {
         let base_dir = path.as_ref().parent().ok_or(anyhow!("Invalid gltf load path."))?;
 
         let buffer_count = raw.gltf.buffers().len();
         let image_count = raw.gltf.images().len();
 
+        let buffers_start = Instant::now();
         raw.buffers.clear();
+        raw.dependencies.clear();
         raw.buffers.reserve(buffer_count);
 
         for buffer in raw.gltf.buffers() {
             match buffer.source() {
                 gltf::buffer::Source::Uri(uri) => {
                     if uri.starts_with("data:") {
-                        info!("inspecting gltf buffer uri: {:?}", uri);
+                        info!(target: ASSET, "inspecting gltf buffer uri: {:?}", uri);
 
                         let mut blob = None;
                         let data = BufferData::from_source_and_blob(buffer.source(), None, &mut blob)
                             .map_err(|e| anyhow!("Failed to decode data URI: {}", e))?;
                         
-                        raw.buffers.push(data);
+                        raw.buffers.push(GltfBuffer::Decoded(data));
                     } else {
-                        info!("inspecting gltf buffer uri: {:?}", uri);
+                        info!(target: ASSET, "inspecting gltf buffer uri: {:?}", uri);
 
                         let buffer_path = base_dir.join(uri);
                         let mmap = load_with_memory_mapping(&buffer_path)?;
+                        raw.dependencies.push(PathBuf::from(uri));
 
-                        raw.buffers.push(BufferData(mmap[..].to_vec()));
+                        stats.mapped_buffer_bytes += mmap.len();
+                        raw.buffers.push(GltfBuffer::Mapped(mmap));
                     }
                 }
                 gltf::buffer::Source::Bin => {
@@ -395,6 +724,10 @@ impl GltfLoader {
             }
         }
 
+        stats.buffers = buffers_start.elapsed();
+        stats.buffer_bytes = raw.buffers.iter().map(|buffer| buffer.len()).sum();
+
+        let images_start = Instant::now();
         raw.images.clear();
         raw.images.reserve(image_count);
 
@@ -402,31 +735,43 @@ impl GltfLoader {
             match image.source() {
                 gltf::image::Source::Uri { uri, .. } => {
                     if uri.starts_with("data:") {
-                        info!("inspecting gltf image uri: {:?}", uri);
+                        info!(target: ASSET, "inspecting gltf image uri: {:?}", uri);
 
-                        let data = ImageData::from_source(image.source(), None, &raw.buffers)
+                        // data uri never references any buffer
+                        let data = ImageData::from_source(image.source(), None, &[])
                             .map_err(|e| anyhow!("Failed to decode image data uri: {}", e))?;
                         
                         raw.images.push(data);
                     } else {
-                        info!("inspecting gltf image uri: {:?}", uri);
+                        info!(target: ASSET, "inspecting gltf image uri: {:?}", uri);
 
                         let image_path = base_dir.join(uri);
                         let uri = uri.to_owned();
                         let mmap = load_with_memory_mapping(&image_path)?;
+                        raw.dependencies.push(PathBuf::from(&uri));
 
                         raw.images.push(Self::decode_image(&mmap, &uri).expect("Failed to decode gltf image"));
                     }
                 }
-                gltf::image::Source::View { .. } => {
-                    let data = ImageData::from_source(image.source(), None, &raw.buffers)
-                        .map_err(|e| anyhow!("Failed to decode embedded image: {}", e))?;
-                    
-                    raw.images.push(data);
+                gltf::image::Source::View { view, mime_type } => {
+                    // decode directly from the (possibly mapped) buffer range
+                    let buffer = raw.buffers
+                        .get(view.buffer().index())
+                        .ok_or(anyhow!("Embedded image references a missing buffer"))?;
+                    let begin = view.offset();
+                    let end = begin + view.length();
+                    let bytes = buffer
+                        .get(begin..end)
+                        .ok_or(anyhow!("Embedded image buffer view out of bound"))?;
+
+                    raw.images.push(Self::decode_image(bytes, mime_type)?);
                 }
             }
         }
 
+        stats.images = images_start.elapsed();
+        stats.image_bytes = raw.images.iter().map(|image| image.pixels.len()).collect();
+
         Ok(())
     }
 
@@ -470,4 +815,52 @@ impl GltfLoader {
             height,
         })
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(path: &str) -> AssetUrl {
+        PathBuf::from(path).into()
+    }
+
+    #[test]
+    fn meshes_share_material_by_index() {
+        let meshes = (0..3)
+            .map(|index| (url(&format!("scene_{}.mesh", index)), Some(0), Mat4::IDENTITY))
+            .collect();
+        let material_urls = [url("scene_0.mat")];
+
+        let collection = RawGltfProcessor::build_collection(&url("scene.gltf"), meshes, &material_urls, 1).unwrap();
+
+        assert_eq!(collection.meshes, [url("scene_0.mesh"), url("scene_1.mesh"), url("scene_2.mesh")]);
+        assert_eq!(collection.materials, [url("scene_0.mat"), url("scene_0.mat"), url("scene_0.mat")]);
+    }
+
+    #[test]
+    fn child_node_is_placed_relative_to_parent() {
+        let json = r#"{
+            "asset": { "version": "2.0" },
+            "scene": 0,
+            "scenes": [{ "nodes": [0] }],
+            "nodes": [
+                { "translation": [1, 0, 0], "children": [1] },
+                { "translation": [0, 2, 0], "scale": [2, 2, 2], "mesh": 0 }
+            ],
+            "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 } }] }],
+            "accessors": [{ "componentType": 5126, "count": 3, "type": "VEC3", "min": [0, 0, 0], "max": [1, 1, 1] }]
+        }"#;
+        let gltf = gltf::Gltf::from_slice(json.as_bytes()).unwrap();
+
+        let mut primitives = Vec::new();
+        for node in gltf.scenes().flat_map(|scene| scene.nodes()) {
+            RawGltfProcessor::process_node(&node, Mat4::IDENTITY, &mut primitives);
+        }
+
+        assert_eq!(primitives.len(), 1);
+        let world_matrix = primitives[0].world_matrix;
+        assert_eq!(world_matrix.transform_point3(Vec3::ZERO), Vec3::new(1., 2., 0.));
+        assert_eq!(world_matrix.transform_point3(Vec3::X), Vec3::new(3., 2., 0.));
+    }
+}
@@ -1,24 +1,29 @@
 use std::any::{Any, TypeId};
+use std::fmt::{Display, Formatter};
 use std::fs::File;
-use std::io::Write;
+use std::io::{Read, Write};
 use std::marker::PhantomData;
 use std::ops::Deref;
 use std::path::{Path, PathBuf};
+use std::ptr::NonNull;
 use std::sync::{Arc, OnceLock};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use anyhow::{anyhow, Result};
 use bincode::Encode;
 use derive_builder::Builder;
-use derive_more::From;
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
 use serde::de::DeserializeOwned;
 use zenith_core::collections::hashmap::HashMap;
 use zenith_core::file::load_with_memory_mapping;
-use zenith_task::TaskResult;
+use zenith_core::log::{info, warn, target::ASSET};
+use zenith_task::{submit, TaskResult};
+use crate::manager::{AssetLoadTask, AssetManager};
 
 pub mod render;
 pub mod manager;
 pub mod gltf_loader;
+pub mod texture_loader;
 
 static ASSET_REGISTRY: OnceLock<AssetRegistry> = OnceLock::new();
 
@@ -26,17 +31,52 @@ pub fn initialize() -> Result<()> {
     ASSET_REGISTRY.set(AssetRegistry::new()).map_err(|_| anyhow!("Failed to initialize asset registry!"))
 }
 
+/// Return the global asset registry.
+pub fn registry() -> &'static AssetRegistry {
+    ASSET_REGISTRY.get().expect("Asset registry is NOT initialized!")
+}
+
+/// Published on [`zenith_core::event_bus::global`] whenever an asset is registered, i.e. loaded or baked.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssetLoaded {
+    pub url: AssetUrl,
+}
+
 type AssetId = (AssetUrl, TypeId);
-type AssetMap = HashMap<AssetId, Arc<dyn Asset>>;
+type AssetMap = HashMap<AssetId, RegisteredAsset>;
+
+struct RegisteredAsset {
+    asset: Arc<dyn Asset>,
+    size_bytes: usize,
+    /// Logical timestamp of the last access, used to find the least-recently-used asset.
+    last_access: AtomicU64,
+}
 
-#[derive(Default)]
 pub struct AssetRegistry {
     assets_map: RwLock<AssetMap>,
+    access_clock: AtomicU64,
+    /// Access clock at the start of the current frame, assets accessed since are NOT evicted.
+    /// `u64::MAX` until the first frame begins.
+    frame_start: AtomicU64,
+    used_bytes: AtomicUsize,
+    budget_bytes: AtomicUsize,
 }
 
 unsafe impl Send for AssetRegistry {}
 unsafe impl Sync for AssetRegistry {}
 
+impl Default for AssetRegistry {
+    fn default() -> Self {
+        Self {
+            assets_map: Default::default(),
+            access_clock: AtomicU64::new(0),
+            frame_start: AtomicU64::new(u64::MAX),
+            used_bytes: AtomicUsize::new(0),
+            budget_bytes: AtomicUsize::new(usize::MAX),
+        }
+    }
+}
+
 impl AssetRegistry {
     pub fn new() -> Self {
         Self {
@@ -45,15 +85,122 @@ impl AssetRegistry {
     }
 
     /// Register an asset.
+    /// If the memory budget is exceeded, least-recently-used assets which are NOT referenced will be evicted,
+    /// except for this one and the ones accessed during the current frame.
     pub fn register<A: Asset>(&self, url: impl Into<AssetUrl>, asset: A) {
-        let key = (url.into(), TypeId::of::<A>());
-        self.assets_map.write().insert(key, Arc::new(asset));
+        let url = url.into();
+        self.insert((url.clone(), TypeId::of::<A>()), asset);
+
+        // subscribers may access the registry, publish after the lock is released
+        zenith_core::event_bus::global().publish(AssetLoaded { url });
+    }
+
+    fn insert<A: Asset>(&self, key: AssetId, asset: A) {
+        let size_bytes = asset.size_bytes();
+
+        let mut assets = self.assets_map.write();
+        let registered = RegisteredAsset {
+            asset: Arc::new(asset),
+            size_bytes,
+            last_access: AtomicU64::new(self.tick_access_clock()),
+        };
+
+        if let Some(replaced) = assets.insert(key.clone(), registered) {
+            self.used_bytes.fetch_sub(replaced.size_bytes, Ordering::Relaxed);
+        }
+        self.used_bytes.fetch_add(size_bytes, Ordering::Relaxed);
+
+        self.evict_over_budget(&mut assets, Some(&key));
     }
 
     /// Unregister an asset, return true if this asset was exists.
     pub fn unregister<A: Asset>(&self, url: impl Into<AssetUrl>) -> bool {
         let key = (url.into(), TypeId::of::<A>());
-        self.assets_map.write().remove(&key).is_some()
+
+        if let Some(removed) = self.assets_map.write().remove(&key) {
+            self.used_bytes.fetch_sub(removed.size_bytes, Ordering::Relaxed);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Set the approximate memory budget in bytes of all registered assets.
+    /// Unreferenced assets will be evicted in least-recently-used order when exceeding the budget.
+    pub fn set_budget(&self, bytes: usize) {
+        self.budget_bytes.store(bytes, Ordering::Relaxed);
+        self.evict_over_budget(&mut self.assets_map.write(), None);
+    }
+
+    /// Start a new frame, assets accessed before it may be evicted from now on.
+    /// Called by the engine once per frame.
+    pub fn begin_frame(&self) {
+        self.frame_start.store(self.access_clock.load(Ordering::Relaxed), Ordering::Relaxed);
+    }
+
+    /// Return the approximate memory in bytes used by all registered assets.
+    pub fn used_bytes(&self) -> usize {
+        self.used_bytes.load(Ordering::Relaxed)
+    }
+
+    fn tick_access_clock(&self) -> u64 {
+        self.access_clock.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Evict unreferenced assets NOT accessed during the current frame, `exempt` is never evicted, e.g. the one just registered.
+    fn evict_over_budget(&self, assets: &mut AssetMap, exempt: Option<&AssetId>) {
+        let budget = self.budget_bytes.load(Ordering::Relaxed);
+        if self.used_bytes() <= budget {
+            return;
+        }
+
+        // only assets nobody holds a live AssetRef to can be evicted
+        let frame_start = self.frame_start.load(Ordering::Relaxed);
+        let mut candidates = assets
+            .iter()
+            .filter(|(key, registered)| {
+                Arc::strong_count(&registered.asset) == 1
+                    && registered.last_access.load(Ordering::Relaxed) < frame_start
+                    && Some(*key) != exempt
+            })
+            .map(|(key, registered)| (registered.last_access.load(Ordering::Relaxed), key.clone()))
+            .collect::<Vec<_>>();
+        candidates.sort_unstable_by_key(|(last_access, _)| *last_access);
+
+        for (_, key) in candidates {
+            if self.used_bytes() <= budget {
+                break;
+            }
+
+            if let Some(evicted) = assets.remove(&key) {
+                info!(target: ASSET, "evict asset {:?} ({} bytes)", key.0, evicted.size_bytes);
+                self.used_bytes.fetch_sub(evicted.size_bytes, Ordering::Relaxed);
+            }
+        }
+
+        if self.used_bytes() > budget {
+            warn!(target: ASSET, "Asset memory budget exceeded ({} / {} bytes), all remaining assets are referenced or used this frame.", self.used_bytes(), budget);
+        }
+    }
+
+    /// Return urls of all registered assets of a specific type.
+    pub fn urls_of_type(&self, ty: AssetType) -> Vec<AssetUrl> {
+        self.assets_map
+            .read()
+            .keys()
+            .filter(|(url, _)| url.try_ty() == Some(ty))
+            .map(|(url, _)| url.clone())
+            .collect()
+    }
+
+    /// Return urls and types of all registered assets.
+    /// Assets with unrecognized extension are skipped.
+    pub fn iter_assets(&self) -> Vec<(AssetUrl, AssetType)> {
+        self.assets_map
+            .read()
+            .keys()
+            .filter_map(|(url, _)| url.try_ty().map(|ty| (url.clone(), ty)))
+            .collect()
     }
 
     /// Get an asset by url. Return None is this asset had NOT been loaded.
@@ -62,7 +209,10 @@ impl AssetRegistry {
         let key = (url, TypeId::of::<A>());
 
         assets.get(&key)
-            .map(Arc::clone)
+            .map(|registered| {
+                registered.last_access.store(self.tick_access_clock(), Ordering::Relaxed);
+                Arc::clone(&registered.asset)
+            })
             .and_then(AssetRef::new)
     }
 }
@@ -85,13 +235,13 @@ fn asset_type_extension(ty: AssetType) -> &'static str {
     }
 }
 
-fn extension_asset_type(extension: &str) -> AssetType {
+fn extension_asset_type(extension: &str) -> Option<AssetType> {
     match extension {
-        "mesh" => AssetType::Mesh,
-        "tex" => AssetType::Texture,
-        "mat" => AssetType::Material,
-        "mscl" => AssetType::MeshCollection,
-        _ => unreachable!()
+        "mesh" => Some(AssetType::Mesh),
+        "tex" => Some(AssetType::Texture),
+        "mat" => Some(AssetType::Material),
+        "mscl" => Some(AssetType::MeshCollection),
+        _ => None
     }
 }
 
@@ -103,26 +253,71 @@ impl AssetType {
 
 /// Url to unique identify an asset.
 /// This is a relative path start with words, points to a file located inside content/ folder.
+/// Urls are always normalized, so the same asset always hashes equal.
 /// TODO: Validation. AssetUrl should always have a valid extension.
 ///
 /// # Example
 ///
 /// ```
 /// use zenith_asset::AssetUrl;
-/// let asset_url = AssetUrl("mesh/cerberus/scene.mesh");
+/// let asset_url = AssetUrl::new("mesh/cerberus/scene.mesh");
 /// ```
-#[derive(Clone, Debug, From, Hash, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub struct AssetUrl {
     path: PathBuf,
 }
 
+impl From<PathBuf> for AssetUrl {
+    fn from(path: PathBuf) -> Self {
+        AssetUrl::new(path)
+    }
+}
+
 impl From<String> for AssetUrl {
     fn from(path: String) -> Self {
-        AssetUrl { path: path.into() }
+        AssetUrl::new(path)
+    }
+}
+
+impl From<&str> for AssetUrl {
+    fn from(path: &str) -> Self {
+        AssetUrl::new(path)
     }
 }
 
 impl AssetUrl {
+    /// Create a normalized url:
+    /// - separators are '/' on all platforms.
+    /// - '.' and empty components are removed, '..' is collapsed with its parent.
+    /// - the extension is lowercase.
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref().to_string_lossy().replace('\\', "/");
+
+        let mut components: Vec<&str> = Vec::new();
+        for component in path.split('/') {
+            match component {
+                "" | "." => {}
+                ".." if components.last().is_some_and(|last| *last != "..") => {
+                    components.pop();
+                }
+                _ => components.push(component),
+            }
+        }
+
+        let mut normalized = components.join("/");
+        if path.starts_with('/') {
+            normalized.insert(0, '/');
+        }
+
+        let mut path = PathBuf::from(normalized);
+        if let Some(extension) = path.extension().and_then(|extension| extension.to_str()) {
+            let extension = extension.to_lowercase();
+            path.set_extension(extension);
+        }
+
+        Self { path }
+    }
+
     /// Return an invalid url represents nothing.
     pub fn invalid() -> Self {
         Self {
@@ -132,12 +327,16 @@ impl AssetUrl {
 
     /// Return the asset type this AssetUrl points to.
     pub fn ty(&self) -> AssetType {
+        self.try_ty().unwrap_or_else(|| panic!("Unknown asset type of url: {:?}", self.path))
+    }
+
+    /// Return the asset type this AssetUrl points to, or None if the extension is not recognized.
+    pub fn try_ty(&self) -> Option<AssetType> {
         let extension = self
             .path
             .extension()
             .and_then(|os_str| os_str.to_str())
-            .map(|str| str.to_lowercase())
-            .unwrap_or("unknown".to_owned());
+            .map(|str| str.to_lowercase())?;
         extension_asset_type(&extension)
     }
 }
@@ -171,22 +370,56 @@ impl<A: Asset> AssetHandle<A> {
         }
     }
 
+    /// Return the url this handle points to.
+    pub fn url(&self) -> &AssetUrl {
+        &self.url
+    }
+
     /// Get the underlying asset data if this asset is successfully loaded and registered.
     pub fn get(&self) -> Option<AssetRef<'_, A>> {
         ASSET_REGISTRY.get().unwrap().get(self.url.clone())
     }
+
+    /// Start loading this asset if it had NOT been loaded yet, return the load task.
+    /// If this asset is already loaded, a completed task is returned.
+    pub fn get_or_load(&self, manager: &AssetManager) -> AssetLoadTask {
+        if self.get().is_some() {
+            return AssetLoadTask::completed();
+        }
+
+        manager.request_load_asset(AssetLoadRequestBuilder::default()
+            .url(self.url.clone())
+            .build()
+            .unwrap())
+    }
+
+    /// Blocking wait until this asset is loaded and return the underlying asset data.
+    pub fn get_blocking(&self, manager: &AssetManager) -> Option<AssetRef<'_, A>> {
+        self.get_or_load(manager).wait();
+        self.get()
+    }
 }
 
 /// Local asset reference which can only be used in a scope which restrict the borrowing lifetime.
 pub struct AssetRef<'a, T> {
-    asset: Arc<dyn Asset>,
+    // keep the asset alive as long as the typed pointer is in use
+    _asset: Arc<dyn Asset>,
+    typed: NonNull<T>,
     _marker: PhantomData<&'a T>,
 }
 
+// SAFETY: AssetRef is a shared, read-only reference to an asset, and all assets are Send + Sync.
+unsafe impl<'a, T: Asset> Send for AssetRef<'a, T> {}
+unsafe impl<'a, T: Asset> Sync for AssetRef<'a, T> {}
+
 impl<'a, T: Asset> AssetRef<'a, T> {
+    /// Downcast the asset once at construction. Return None if the asset is NOT of type T.
     fn new(asset: Arc<dyn Asset>) -> Option<Self> {
+        let typed = NonNull::from(asset.as_any().downcast_ref::<T>()?);
+
         Some(Self {
-            asset,
+            _asset: asset,
+            typed,
             _marker: PhantomData,
         })
     }
@@ -196,13 +429,14 @@ impl<'a, T: Asset> Deref for AssetRef<'a, T> {
     type Target = T;
 
     fn deref(&self) -> &Self::Target {
-        self.asset.as_ref().as_any().downcast_ref::<T>().unwrap()
+        // SAFETY: the pointer is derived from the asset owned by this reference, which is never mutated.
+        unsafe { self.typed.as_ref() }
     }
 }
 
 impl<'a, T: Asset> AsRef<T> for AssetRef<'a, T> {
     fn as_ref(&self) -> &T {
-        &self
+        self.deref()
     }
 }
 
@@ -215,6 +449,8 @@ pub trait Asset: Any + Send + Sync {
     fn as_any(&self) -> &dyn Any;
     fn url(&self, name: &str) -> AssetUrl;
     fn extension() -> &'static str where Self: Sized;
+    /// Approximate memory in bytes used by this asset.
+    fn size_bytes(&self) -> usize;
 }
 
 /// Data needed to send a raw resource load request.
@@ -228,6 +464,11 @@ pub struct RawResourceLoadRequest {
 /// Type represents a raw resource.
 pub trait RawResource: Sized {
     fn load_path(&self) -> &Path;
+
+    /// Files read besides the one at the load path, relative to its directory. Changing any of them rebakes the resource.
+    fn dependencies(&self) -> &[PathBuf] {
+        &[]
+    }
 }
 
 /// Raw resource loader interface.
@@ -242,7 +483,20 @@ pub trait RawResourceLoader {
 pub trait RawResourceBaker {
     type Raw: RawResource;
 
-    fn bake(raw: Self::Raw, registry: &AssetRegistry, directory: &PathBuf, url: &AssetUrl) -> Result<()>;
+    fn bake(raw: Self::Raw, registry: &'static AssetRegistry, directory: &PathBuf, url: &AssetUrl) -> Result<()>;
+
+    /// Bake on task workers, the task completes once all baked assets are saved.
+    /// Bakers splitting the work into subtasks chain them with continuations, a worker must NEVER block on another task.
+    fn bake_async(raw: Self::Raw, registry: &'static AssetRegistry, directory: PathBuf, url: AssetUrl) -> TaskResult<Result<()>>
+    where
+        Self: Sized + 'static,
+        Self::Raw: Send + 'static,
+    {
+        submit(move || Self::bake(raw, registry, &directory, &url))
+    }
+
+    /// Return the url of the top-level asset baked from the raw resource at `raw_url`.
+    fn asset_url(raw_url: &AssetUrl) -> AssetUrl;
 }
 
 /// Data needed to send an asset load request.
@@ -252,6 +506,78 @@ pub struct AssetLoadRequest {
     url: AssetUrl,
 }
 
+/// Version of the serialized asset format. Bump it whenever the layout of any serialized asset changes.
+pub const ASSET_FORMAT_VERSION: u32 = 7;
+
+const ASSET_MAGIC: [u8; 4] = *b"ZNTH";
+/// Magic bytes, asset type and format version.
+const ASSET_HEADER_SIZE: usize = ASSET_MAGIC.len() + 1 + size_of::<u32>();
+
+/// Error of a baked asset file which can NOT be read by this build, the asset should be rebaked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AssetFormatError {
+    /// The file is NOT a baked asset, or it is baked before assets had a header.
+    InvalidHeader,
+    TypeMismatch {
+        found: u8,
+        expected: AssetType,
+    },
+    VersionMismatch {
+        found: u32,
+        expected: u32,
+    },
+}
+
+impl Display for AssetFormatError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AssetFormatError::InvalidHeader => write!(f, "Invalid asset header"),
+            AssetFormatError::TypeMismatch { found, expected } => write!(f, "Asset type mismatch: found {}, expected {:?}", found, expected),
+            AssetFormatError::VersionMismatch { found, expected } => write!(f, "Asset format version mismatch: found {}, expected {}", found, expected),
+        }
+    }
+}
+
+impl std::error::Error for AssetFormatError {}
+
+fn encode_asset_header(ty: AssetType, version: u32) -> [u8; ASSET_HEADER_SIZE] {
+    let mut header = [0; ASSET_HEADER_SIZE];
+    header[..4].copy_from_slice(&ASSET_MAGIC);
+    header[4] = ty as u8;
+    header[5..].copy_from_slice(&version.to_le_bytes());
+    header
+}
+
+/// Check the header of a serialized asset, return the payload after it.
+fn check_asset_header(bytes: &[u8], ty: AssetType, version: u32) -> Result<&[u8], AssetFormatError> {
+    if bytes.len() < ASSET_HEADER_SIZE || bytes[..4] != ASSET_MAGIC {
+        return Err(AssetFormatError::InvalidHeader);
+    }
+
+    if bytes[4] != ty as u8 {
+        return Err(AssetFormatError::TypeMismatch { found: bytes[4], expected: ty });
+    }
+
+    let found = u32::from_le_bytes(bytes[5..ASSET_HEADER_SIZE].try_into().unwrap());
+    if found != version {
+        return Err(AssetFormatError::VersionMismatch { found, expected: version });
+    }
+
+    Ok(&bytes[ASSET_HEADER_SIZE..])
+}
+
+/// Check only the header of a baked asset file without reading the whole file.
+fn validate_asset_file(absolute_path: &Path, ty: AssetType) -> Result<()> {
+    let mut header = [0; ASSET_HEADER_SIZE];
+    let read = File::open(absolute_path)?.read(&mut header)?;
+    check_asset_header(&header[..read], ty, ASSET_FORMAT_VERSION)?;
+    Ok(())
+}
+
+fn asset_type_of<A: Asset>() -> Result<AssetType> {
+    extension_asset_type(A::extension()).ok_or(anyhow!("{} is NOT a serializable asset type", A::extension()))
+}
+
 fn serialize_asset<A: Asset + Encode>(asset: &A, absolute_path: &PathBuf) -> Result<()> {
     if let Some(parent) = absolute_path.parent() {
         std::fs::create_dir_all(parent)?;
@@ -261,17 +587,138 @@ fn serialize_asset<A: Asset + Encode>(asset: &A, absolute_path: &PathBuf) -> Res
     let encoded_data = bincode::encode_to_vec(asset, config)?;
 
     let mut file = File::create(absolute_path)?;
+    file.write_all(&encode_asset_header(asset_type_of::<A>()?, ASSET_FORMAT_VERSION))?;
     file.write_all(&encoded_data)?;
     file.flush()?;
 
     Ok(())
 }
 
+/// Deserialize a baked asset. Files of another format version fail with [`AssetFormatError`].
 fn deserialize_asset<A: Asset + Encode + DeserializeOwned>(absolute_path: &PathBuf) -> Result<A> {
     let mmap = load_with_memory_mapping(absolute_path)?;
+    let payload = check_asset_header(&mmap, asset_type_of::<A>()?, ASSET_FORMAT_VERSION)?;
 
-    let (asset, _): (A, usize) = bincode::serde::decode_from_slice(&mmap, bincode::config::standard())
-        .expect(&format!("Failed to deserialize asset {:?}", absolute_path));
+    let (asset, _): (A, usize) = bincode::serde::decode_from_slice(payload, bincode::config::standard())
+        .map_err(|err| anyhow!("Failed to deserialize asset {:?}: {}", absolute_path, err))?;
 
     Ok(asset)
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct DummyAsset {
+        size_bytes: usize,
+    }
+
+    impl Asset for DummyAsset {
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn url(&self, name: &str) -> AssetUrl {
+            let mut url = PathBuf::from(name);
+            url.set_extension(Self::extension());
+            url.into()
+        }
+
+        fn extension() -> &'static str {
+            "dummy"
+        }
+
+        fn size_bytes(&self) -> usize {
+            self.size_bytes
+        }
+    }
+
+    fn is_registered(registry: &AssetRegistry, url: &str) -> bool {
+        registry.get::<DummyAsset>(url.to_owned().into()).is_some()
+    }
+
+    #[test]
+    fn asset_url_normalizes_separators() {
+        let url = AssetUrl::new("mesh\\cerberus/scene.MESH");
+
+        assert_eq!(url, AssetUrl::new("mesh/cerberus/scene.mesh"));
+        assert_eq!(url.as_ref(), Path::new("mesh/cerberus/scene.mesh"));
+    }
+
+    #[test]
+    fn asset_url_collapses_redundant_components() {
+        let url = AssetUrl::new("mesh/cerberus/scene.mesh");
+
+        assert_eq!(AssetUrl::new("./mesh/./cerberus//scene.mesh"), url);
+        assert_eq!(AssetUrl::new("mesh/sponza/../cerberus/scene.mesh"), url);
+        assert_eq!(AssetUrl::from(PathBuf::from("mesh/./cerberus/scene.mesh")), url);
+        assert_eq!(AssetUrl::new("../mesh/scene.mesh").as_ref(), Path::new("../mesh/scene.mesh"));
+    }
+
+    #[test]
+    fn asset_of_old_format_version_is_rejected() {
+        let mut blob = encode_asset_header(AssetType::Mesh, 1).to_vec();
+        blob.extend_from_slice(&[1, 2, 3]);
+
+        assert_eq!(check_asset_header(&blob, AssetType::Mesh, 1), Ok(&[1, 2, 3][..]));
+        assert_eq!(check_asset_header(&blob, AssetType::Mesh, 2), Err(AssetFormatError::VersionMismatch { found: 1, expected: 2 }));
+        assert_eq!(check_asset_header(&blob, AssetType::Texture, 1), Err(AssetFormatError::TypeMismatch { found: AssetType::Mesh as u8, expected: AssetType::Texture }));
+        assert_eq!(check_asset_header(&blob[..4], AssetType::Mesh, 1), Err(AssetFormatError::InvalidHeader));
+    }
+
+    #[test]
+    fn register_past_budget_evicts_least_recently_used() {
+        let registry = AssetRegistry::new();
+        registry.set_budget(200);
+
+        registry.register("a.dummy".to_owned(), DummyAsset { size_bytes: 100 });
+        registry.register("b.dummy".to_owned(), DummyAsset { size_bytes: 100 });
+        // touch a, so b becomes the least recently used one
+        assert!(is_registered(&registry, "a.dummy"));
+
+        registry.register("c.dummy".to_owned(), DummyAsset { size_bytes: 100 });
+
+        assert!(is_registered(&registry, "a.dummy"));
+        assert!(!is_registered(&registry, "b.dummy"));
+        assert!(is_registered(&registry, "c.dummy"));
+        assert_eq!(registry.used_bytes(), 200);
+    }
+
+    #[test]
+    fn referenced_asset_is_never_evicted() {
+        let registry = AssetRegistry::new();
+        registry.set_budget(100);
+
+        registry.register("a.dummy".to_owned(), DummyAsset { size_bytes: 100 });
+        let held = registry.get::<DummyAsset>("a.dummy".to_owned().into()).unwrap();
+
+        registry.register("b.dummy".to_owned(), DummyAsset { size_bytes: 100 });
+
+        // the new asset is NOT evicted either, the budget stays exceeded
+        assert!(is_registered(&registry, "a.dummy"));
+        assert!(is_registered(&registry, "b.dummy"));
+        assert_eq!(registry.used_bytes(), 200);
+        assert_eq!(held.size_bytes, 100);
+    }
+
+    #[test]
+    fn asset_used_this_frame_is_not_evicted() {
+        let registry = AssetRegistry::new();
+        registry.set_budget(200);
+
+        registry.register("a.dummy".to_owned(), DummyAsset { size_bytes: 100 });
+        registry.register("b.dummy".to_owned(), DummyAsset { size_bytes: 100 });
+
+        registry.begin_frame();
+        // a is older than b, but used in this frame
+        assert!(is_registered(&registry, "a.dummy"));
+        registry.register("c.dummy".to_owned(), DummyAsset { size_bytes: 100 });
+        assert!(!is_registered(&registry, "b.dummy"));
+
+        // nothing left to evict but assets used this frame
+        registry.register("d.dummy".to_owned(), DummyAsset { size_bytes: 100 });
+        assert!(is_registered(&registry, "a.dummy"));
+        assert!(is_registered(&registry, "c.dummy"));
+        assert!(is_registered(&registry, "d.dummy"));
+        assert_eq!(registry.used_bytes(), 300);
+    }
+}
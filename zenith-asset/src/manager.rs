@@ -1,29 +1,163 @@
 ﻿use std::ffi::OsStr;
+use std::marker::PhantomData;
 use std::path::{Path, PathBuf};
-use zenith_core::log::info;
-use zenith_task::{submit, submit_after, TaskHandle};
+use std::sync::Arc;
+use anyhow::{anyhow, Result};
+use bincode::{Decode, Encode};
+use parking_lot::{Mutex, RwLock};
+use zenith_core::collections::hashmap::HashMap;
+use zenith_core::file::load_with_memory_mapping;
+use zenith_core::log::{error, info, warn, target::ASSET};
+use zenith_task::{submit, TaskHandle, TaskResult};
 use crate::gltf_loader::{GltfLoader, RawGltfProcessor};
-use crate::{RawResourceBaker, AssetLoadRequest, AssetType, RawResourceLoadRequest, RawResourceLoader, ASSET_REGISTRY, RawResourceLoadRequestBuilder, AssetLoadRequestBuilder, Asset, AssetUrl, deserialize_asset};
+use crate::texture_loader::{DdsLoader, Ktx2Loader, RawTextureProcessor};
+use crate::{RawResourceBaker, AssetLoadRequest, AssetType, RawResourceLoadRequest, RawResourceLoader, ASSET_REGISTRY, RawResourceLoadRequestBuilder, AssetLoadRequestBuilder, Asset, AssetUrl, deserialize_asset, validate_asset_file};
 use crate::render::{Material, Mesh, MeshCollection, Texture};
 
-fn workspace_root() -> PathBuf {
-    // Get the directory where Cargo.toml for the workspace is located
+/// Environment variable to override the content/ folder.
+pub const CONTENT_DIR_ENV: &str = "ZENITH_CONTENT_DIR";
+/// Environment variable to override the cache/ folder.
+pub const CACHE_DIR_ENV: &str = "ZENITH_CACHE_DIR";
+
+/// Version of the bake pipeline. Bump it to force rebaking all assets, e.g. when the serialization format changes.
+pub const BAKER_VERSION: u32 = 1;
+
+/// A file an asset is baked from, its size and modification time let unchanged files skip hashing.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+struct SourceStamp {
+    /// Relative to the directory of the raw resource.
+    path: String,
+    size: u64,
+    /// Nanoseconds since the unix epoch, 0 if the file system does NOT report it.
+    modified: u64,
+    hash: u64,
+}
+
+impl SourceStamp {
+    fn of_file(raw_dir: &Path, path: &Path) -> Result<Self> {
+        let file_path = raw_dir.join(path);
+        let (size, modified) = Self::file_stats(&file_path)?;
+        let bytes = load_with_memory_mapping(&file_path)?;
+
+        Ok(Self {
+            path: path.to_string_lossy().into_owned(),
+            size,
+            modified,
+            hash: xxhash_rust::xxh3::xxh3_64(&bytes),
+        })
+    }
+
+    fn file_stats(file_path: &Path) -> Result<(u64, u64)> {
+        let metadata = std::fs::metadata(file_path)?;
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+            .map_or(0, |duration| duration.as_nanos() as u64);
+
+        Ok((metadata.len(), modified))
+    }
+
+    /// Return the stamp of the file as it is now, or None if its content changed.
+    /// The file is hashed only if its size or modification time differs from the recorded ones.
+    fn refresh(&self, raw_dir: &Path) -> Result<Option<Self>> {
+        let file_path = raw_dir.join(&self.path);
+        if Self::file_stats(&file_path)? == (self.size, self.modified) {
+            return Ok(Some(self.clone()));
+        }
+
+        let current = Self::of_file(raw_dir, Path::new(&self.path))?;
+        Ok((current.hash == self.hash).then_some(current))
+    }
+}
+
+/// Sidecar of a baked asset, stored as "<asset>.meta" next to it in cache/ folder.
+#[derive(Debug, PartialEq, Eq, Encode, Decode)]
+struct BakeMeta {
+    baker_version: u32,
+    /// The raw resource first, then the files it reads, e.g. external buffers and images of a gltf file.
+    sources: Vec<SourceStamp>,
+}
+
+impl BakeMeta {
+    fn of_source(raw_path: &Path, dependencies: &[PathBuf]) -> Result<Self> {
+        let raw_dir = raw_path.parent().unwrap_or(Path::new(""));
+        let raw_name = Path::new(raw_path.file_name().ok_or(anyhow!("Invalid raw resource path {:?}", raw_path))?);
+
+        let sources = std::iter::once(raw_name)
+            .chain(dependencies.iter().map(PathBuf::as_path))
+            .map(|path| SourceStamp::of_file(raw_dir, path))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            baker_version: BAKER_VERSION,
+            sources,
+        })
+    }
+
+    /// Return the meta with the current stamps of all sources, or None if any of them changed.
+    fn refresh(&self, raw_path: &Path) -> Result<Option<Self>> {
+        let raw_dir = raw_path.parent().unwrap_or(Path::new(""));
+
+        let mut sources = Vec::with_capacity(self.sources.len());
+        for source in &self.sources {
+            match source.refresh(raw_dir)? {
+                Some(source) => sources.push(source),
+                None => return Ok(None),
+            }
+        }
+
+        Ok(Some(Self {
+            baker_version: self.baker_version,
+            sources,
+        }))
+    }
+
+    fn path(cached_file_path: &Path) -> PathBuf {
+        let mut path = cached_file_path.as_os_str().to_owned();
+        path.push(".meta");
+        path.into()
+    }
+
+    fn read(cached_file_path: &Path) -> Option<Self> {
+        let bytes = std::fs::read(Self::path(cached_file_path)).ok()?;
+        bincode::decode_from_slice(&bytes, bincode::config::standard())
+            .ok()
+            .map(|(meta, _)| meta)
+    }
+
+    fn write(&self, cached_file_path: &Path) -> Result<()> {
+        let bytes = bincode::encode_to_vec(self, bincode::config::standard())?;
+        std::fs::write(Self::path(cached_file_path), bytes)?;
+        Ok(())
+    }
+}
+
+/// Return the directory where Cargo.toml for the workspace is located.
+/// Only exists when running inside the source tree, e.g. `cargo run` during development.
+fn workspace_root() -> Option<PathBuf> {
     let mut current_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
     loop {
         let cargo_toml = current_dir.join("Cargo.toml");
         if cargo_toml.exists() {
             if let Ok(content) = std::fs::read_to_string(&cargo_toml) {
                 if content.contains("[workspace]") {
-                    return current_dir;
+                    return Some(current_dir);
                 }
             }
         }
         if !current_dir.pop() {
-            break;
+            return None;
         }
     }
-    // Fallback to parent directory of current crate
-    PathBuf::from(env!("CARGO_MANIFEST_DIR")).parent().unwrap().to_path_buf()
+}
+
+/// Root directory holding content/ and cache/ folders if they are NOT overridden.
+/// The workspace in development, otherwise the directory of the executable.
+fn default_root() -> PathBuf {
+    workspace_root()
+        .or_else(|| std::env::current_exe().ok()?.parent().map(Path::to_path_buf))
+        .unwrap_or_default()
 }
 
 /// Managing the loading, registering of assets and maintaining assets' cache.
@@ -32,6 +166,56 @@ fn workspace_root() -> PathBuf {
 pub struct AssetManager {
     cache_dir: PathBuf,
     content_dir: PathBuf,
+    /// Loads which had been requested but may NOT be finished yet, keyed by the resolved asset url.
+    in_flight_loads: Mutex<HashMap<AssetUrl, AssetLoadTask>>,
+    /// Loads finished since the last [`AssetManager::drain_finished_loads`].
+    finished_loads: Mutex<Vec<AssetUrl>>,
+    /// Loader and baker of raw resources, keyed by lowercase file extension without the dot.
+    raw_handlers: RwLock<HashMap<String, Arc<dyn RawResourceHandler>>>,
+}
+
+/// Type-erased pair of a [`RawResourceLoader`] and the [`RawResourceBaker`] of its raw resource.
+trait RawResourceHandler: Send + Sync {
+    fn asset_url(&self, raw_url: &AssetUrl) -> AssetUrl;
+
+    /// Load and bake the raw resource asynchronously, log on failure.
+    fn bake_async(&self, raw_content_path: PathBuf, cache_dir: PathBuf, relative_path: PathBuf) -> AssetLoadTask;
+
+    /// Load and bake the raw resource in a single task, returning any failure.
+    fn bake_task(&self, raw_content_path: PathBuf, cache_dir: PathBuf, relative_path: PathBuf) -> TaskResult<Result<()>>;
+}
+
+struct RawResourceHandlerImpl<L, B>(PhantomData<fn() -> (L, B)>);
+
+impl<L, B> RawResourceHandler for RawResourceHandlerImpl<L, B>
+where
+    L: RawResourceLoader + 'static,
+    L::Raw: Send + 'static,
+    B: RawResourceBaker<Raw = L::Raw> + 'static,
+{
+    fn asset_url(&self, raw_url: &AssetUrl) -> AssetUrl {
+        B::asset_url(raw_url)
+    }
+
+    fn bake_async(&self, raw_content_path: PathBuf, cache_dir: PathBuf, relative_path: PathBuf) -> AssetLoadTask {
+        let bake_asset_task = self
+            .bake_task(raw_content_path, cache_dir, relative_path)
+            .map(|baked| {
+                if let Err(err) = baked {
+                    error!(target: ASSET, "{}", err);
+                }
+            });
+
+        AssetLoadTask(vec![bake_asset_task.into_handle()])
+    }
+
+    fn bake_task(&self, raw_content_path: PathBuf, cache_dir: PathBuf, relative_path: PathBuf) -> TaskResult<Result<()>> {
+        let error_path = raw_content_path.clone();
+
+        L::load_async(&raw_content_path)
+            .then(move |raw| bake_and_record::<B>(raw, raw_content_path, cache_dir, relative_path))
+            .map(move |baked| baked.map_err(|err| anyhow!("Failed to bake asset {:?}: {}", error_path, err)))
+    }
 }
 
 /// Handle to represents an asset load task.
@@ -39,6 +223,16 @@ pub struct AssetManager {
 pub struct AssetLoadTask(Vec<TaskHandle>);
 
 impl AssetLoadTask {
+    /// Return a load task which had already finished.
+    pub fn completed() -> Self {
+        Self(vec![])
+    }
+
+    /// Return true if all inner tasks had finished.
+    pub fn is_completed(&self) -> bool {
+        self.0.iter().all(|handle| handle.completed())
+    }
+
     /// Blocking wait until the load task finished.
     pub fn wait(&self) {
         for handle in &self.0 {
@@ -47,123 +241,278 @@ impl AssetLoadTask {
     }
 }
 
+/// Outcome of [`AssetManager::bake_all`].
+#[derive(Debug, Default)]
+pub struct BakeReport {
+    /// Urls of the baked top-level assets, located in cache/ folder.
+    pub baked: Vec<AssetUrl>,
+    /// Raw resources which failed to bake, relative to content/ folder.
+    pub failures: Vec<(PathBuf, anyhow::Error)>,
+}
+
 impl AssetManager {
+    /// Create a manager with content/ and cache/ folders resolved in order:
+    /// 1. [`CONTENT_DIR_ENV`] and [`CACHE_DIR_ENV`] environment variables.
+    /// 2. The workspace root when running inside the source tree.
+    /// 3. The directory of the executable.
     pub fn new() -> Self {
-        let root = workspace_root();
-        Self {
-            cache_dir: root.to_owned().join("cache/"),
-            content_dir: root.join("content/"),
+        let root = default_root();
+        let content_dir = std::env::var_os(CONTENT_DIR_ENV)
+            .map(PathBuf::from)
+            .unwrap_or_else(|| root.join("content/"));
+        let cache_dir = std::env::var_os(CACHE_DIR_ENV)
+            .map(PathBuf::from)
+            .unwrap_or_else(|| root.join("cache/"));
+
+        Self::with_dirs(content_dir, cache_dir)
+    }
+
+    /// Create a manager which loads raw resources from `content_dir` and bakes assets into `cache_dir`.
+    /// Loaders of gltf, ktx2 and dds are registered by default.
+    pub fn with_dirs(content_dir: impl Into<PathBuf>, cache_dir: impl Into<PathBuf>) -> Self {
+        let manager = Self {
+            cache_dir: cache_dir.into(),
+            content_dir: content_dir.into(),
+            in_flight_loads: Mutex::new(HashMap::default()),
+            finished_loads: Mutex::new(Vec::new()),
+            raw_handlers: RwLock::new(HashMap::default()),
+        };
+
+        manager.register_loader::<GltfLoader, RawGltfProcessor>("gltf");
+        manager.register_loader::<Ktx2Loader, RawTextureProcessor>("ktx2");
+        manager.register_loader::<DdsLoader, RawTextureProcessor>("dds");
+        manager
+    }
+
+    /// Load raw resources with `extension` (case-insensitive, without the dot) by `L` and bake them by `P`.
+    /// A loader previously registered for the same extension is replaced.
+    pub fn register_loader<L, P>(&self, extension: &str)
+    where
+        L: RawResourceLoader + 'static,
+        L::Raw: Send + 'static,
+        P: RawResourceBaker<Raw = L::Raw> + 'static,
+    {
+        let extension = extension.trim_start_matches('.').to_lowercase();
+        let handler: Arc<dyn RawResourceHandler> = Arc::new(RawResourceHandlerImpl::<L, P>(PhantomData));
+
+        if self.raw_handlers.write().insert(extension.clone(), handler).is_some() {
+            info!(target: ASSET, "loader of raw asset extension {:?} is replaced", extension);
         }
     }
 
-    /// Send a load request to the asset manager.
+    fn raw_handler(&self, path: &Path) -> Result<Arc<dyn RawResourceHandler>> {
+        raw_extension(path)
+            .and_then(|extension| self.raw_handlers.read().get(&extension).cloned())
+            .ok_or(anyhow!("No loader is registered for raw asset {:?}", path))
+    }
+
+    pub fn content_dir(&self) -> &Path {
+        &self.content_dir
+    }
+
+    pub fn cache_dir(&self) -> &Path {
+        &self.cache_dir
+    }
+
+    /// Send a load request of a raw resource, dispatched by its extension to the registered loader.
+    /// The baked asset is loaded from cache/ folder if it is up to date, otherwise the raw resource is baked again.
     /// Loading will start immediately asynchronously.
     ///
     /// # Example
     ///
+    /// ```no_run
+    /// # use zenith_asset::manager::AssetManager;
+    /// # fn main() -> anyhow::Result<()> {
+    /// # zenith_task::initialize();
+    /// # zenith_asset::initialize()?;
+    /// let manager = AssetManager::new();
+    /// let gltf_path = "mesh/cerberus/scene.gltf";
+    /// let asset_load_task = manager.request_load(gltf_path)?;
+    /// # Ok(())
+    /// # }
     /// ```
-    /// let asset_url = "mesh/cerberus/****.gltf";
-    /// let asset_load_task = manager.request_load(gltf_path);
-    /// ```
-    pub fn request_load(&self, url: impl Into<PathBuf>) -> AssetLoadTask {
+    pub fn request_load(&self, url: impl Into<PathBuf>) -> Result<AssetLoadTask> {
+        let url = url.into();
+        let handler = self.raw_handler(&url)?;
+        let asset_url = handler.asset_url(&url.clone().into());
+
+        Ok(self.deduplicate_load(asset_url.clone(), || {
+            if self.should_bake_asset(&url, &asset_url) {
+                info!(target: ASSET, "load raw asset {:?}", url);
+
+                self.request_load_raw(handler.as_ref(), RawResourceLoadRequestBuilder::default()
+                    .relative_path(url)
+                    .build().unwrap())
+            } else {
+                info!(target: ASSET, "load asset {:?}", asset_url);
+
+                self.load_asset(AssetLoadRequestBuilder::default()
+                    .url(asset_url)
+                    .build().unwrap())
+            }
+        }))
+    }
+
+    /// Return the url of the asset baked from the raw resource at `url`, relative to content/ folder.
+    /// e.g. to match the urls returned by [`AssetManager::drain_finished_loads`].
+    pub fn asset_url(&self, url: impl Into<PathBuf>) -> Result<AssetUrl> {
         let url = url.into();
+        let handler = self.raw_handler(&url)?;
+        Ok(handler.asset_url(&url.into()))
+    }
 
-        if self.should_bake_asset(&url) {
-            info!("load raw asset {:?}", url);
+    /// Return the urls of requested loads which finished since the last call, in no particular order.
+    /// Poll it once per frame to react to loads instead of blocking on [`AssetLoadTask::wait`].
+    ///
+    /// A finished load may have failed, the failure is logged and the asset is NOT registered then.
+    pub fn drain_finished_loads(&self) -> Vec<AssetUrl> {
+        let mut in_flight_loads = self.in_flight_loads.lock();
+        let mut finished_loads = self.finished_loads.lock();
+        Self::collect_finished_loads(&mut in_flight_loads, &mut finished_loads);
 
-            self.request_load_raw(RawResourceLoadRequestBuilder::default()
-                .relative_path(url)
-                .build().unwrap())
-        } else {
-            info!("load asset {:?}", url);
+        std::mem::take(&mut finished_loads)
+    }
+
+    fn collect_finished_loads(in_flight_loads: &mut HashMap<AssetUrl, AssetLoadTask>, finished_loads: &mut Vec<AssetUrl>) {
+        in_flight_loads.retain(|asset_url, task| {
+            if task.is_completed() {
+                finished_loads.push(asset_url.clone());
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    /// If there is an unfinished load of the same asset, return it instead of loading it twice.
+    /// Finished loads are moved to `finished_loads` before lookup.
+    fn deduplicate_load(&self, asset_url: AssetUrl, load: impl FnOnce() -> AssetLoadTask) -> AssetLoadTask {
+        let mut in_flight_loads = self.in_flight_loads.lock();
+        Self::collect_finished_loads(&mut in_flight_loads, &mut self.finished_loads.lock());
 
-            // TODO: this should be validate as AssetUrl
-            let mut url = url;
-            url.set_extension(MeshCollection::extension());
+        if let Some(task) = in_flight_loads.get(&asset_url) {
+            info!(target: ASSET, "asset {:?} is already loading", asset_url);
+            return task.clone();
+        }
 
-            self.request_load_asset(AssetLoadRequestBuilder::default()
-                .url(url)
-                .build().unwrap())
+        let task = load();
+        if task.is_completed() {
+            self.finished_loads.lock().push(asset_url);
+        } else {
+            in_flight_loads.insert(asset_url, task.clone());
         }
+        task
     }
 
-    fn should_bake_asset(&self, path: &impl AsRef<Path>) -> bool {
+    /// The hashes of the raw resource and the files it reads, and the baker version are compared with the ones recorded on baking.
+    /// Files whose size and modification time did NOT change are NOT hashed again. Copies, checkouts and backups
+    /// may NOT preserve modification times, so a changed time only means the file is hashed again.
+    fn should_bake_asset(&self, path: &impl AsRef<Path>, asset_url: &AssetUrl) -> bool {
         let raw_path = self.content_dir.join(path.as_ref().to_owned());
-
-        let mesh_collection = MeshCollection::new(path);
-        let asset_url = mesh_collection.asset_url();
-        let cached_file_path = self.cache_dir.join(asset_url.path);
+        let cached_file_path = self.cache_dir.join(asset_url);
 
         // if no cache had been found, rebake
         if !cached_file_path.exists() {
             return true;
         }
 
-        let asset_metadata = match std::fs::metadata(cached_file_path) {
-            Ok(metadata) => metadata,
-            Err(_) => return false,
-        };
-
-        let source_metadata = match std::fs::metadata(raw_path) {
-            Ok(metadata) => metadata,
-            Err(_) => return false,
-        };
+        // if the cache is written in another asset format, rebake
+        if let Err(err) = validate_asset_file(&cached_file_path, asset_url.ty()) {
+            warn!(target: ASSET, "Cached asset {:?} is NOT readable: {}, rebake.", cached_file_path, err);
+            return true;
+        }
 
-        let asset_last_modified_time = match asset_metadata.modified() {
-            Ok(time) => time,
-            Err(_) => return false,
-        };
+        // without the raw resource, e.g. a shipped binary, the cache is all we have
+        if !raw_path.exists() {
+            return false;
+        }
 
-        let raw_last_modified_time = match source_metadata.modified() {
-            Ok(time) => time,
-            Err(_) => return false,
+        // if the baker had been changed, rebake
+        let Some(baked_meta) = BakeMeta::read(&cached_file_path).filter(|meta| meta.baker_version == BAKER_VERSION) else {
+            return true;
         };
 
-        // if the raw asset had been modified, rebake
-        raw_last_modified_time > asset_last_modified_time
+        // if the raw resource or any file it reads had been changed or removed, rebake
+        match baked_meta.refresh(&raw_path) {
+            Ok(Some(current_meta)) => {
+                // e.g. touched by a checkout, record the new times so the files are NOT hashed again
+                if current_meta != baked_meta {
+                    if let Err(err) = current_meta.write(&cached_file_path) {
+                        warn!(target: ASSET, "Failed to update the bake meta of {:?}: {}", cached_file_path, err);
+                    }
+                }
+                false
+            }
+            Ok(None) | Err(_) => true,
+        }
     }
 
-    fn request_load_raw(&self, load_request: RawResourceLoadRequest) -> AssetLoadTask {
-        // TODO: support other types of raw asset
-        assert_eq!(load_request.relative_path.extension(), Some(OsStr::new("gltf")));
+    /// Bake every raw resource with a registered loader under `content_subdir` of content/ folder into cache/,
+    /// regardless of whether the cache is up to date. Resources are baked in parallel on the task pool.
+    ///
+    /// Both the asset registry and the task pool must be initialized, but no window or render device is needed.
+    pub fn bake_all(&self, content_subdir: impl AsRef<Path>) -> Result<BakeReport> {
+        let mut raw_paths = Vec::new();
+        collect_raw_resources(&self.content_dir, content_subdir.as_ref(), &|path| self.raw_handler(path).is_ok(), &mut raw_paths)?;
+        raw_paths.sort();
+
+        let tasks: Vec<_> = raw_paths
+            .into_iter()
+            .map(|relative_path| {
+                let handler = self.raw_handler(&relative_path).unwrap();
+                let task = handler.bake_task(self.content_dir.join(&relative_path), self.cache_dir.clone(), relative_path.clone());
+                (relative_path, handler, task)
+            })
+            .collect();
+
+        let mut report = BakeReport::default();
+        for (relative_path, handler, task) in tasks {
+            match task.get_result() {
+                Ok(()) => report.baked.push(handler.asset_url(&relative_path.into())),
+                Err(err) => report.failures.push((relative_path, err)),
+            }
+        }
+
+        Ok(report)
+    }
 
+    fn request_load_raw(&self, handler: &dyn RawResourceHandler, load_request: RawResourceLoadRequest) -> AssetLoadTask {
         let raw_content_path = self.content_dir.join(&load_request.relative_path);
-        // TODO: support other types of raw asset
-        let raw_asset_load_task = GltfLoader::load_async(&raw_content_path);
-        
-        let inner_result = raw_asset_load_task.clone();
-        let cache_dir = self.cache_dir.clone();
-
-        let bake_asset_task = submit_after(move || {
-            inner_result.get_result().and_then(|raw| {
-                let asset_url = AssetUrl::from(load_request.relative_path);
-                RawGltfProcessor::bake(raw, ASSET_REGISTRY.get().unwrap(), &cache_dir, &asset_url)
-            }).expect(&format!("Failed to bake asset {:?}", raw_content_path));
-        }, [&raw_asset_load_task]);
+        handler.bake_async(raw_content_path, self.cache_dir.clone(), load_request.relative_path)
+    }
 
-        AssetLoadTask(vec![bake_asset_task.into_handle()])
+    /// Send a load request of a baked asset located in cache/ folder.
+    pub fn request_load_asset(&self, load_request: AssetLoadRequest) -> AssetLoadTask {
+        let asset_url = load_request.url.clone();
+        self.deduplicate_load(asset_url, || self.load_asset(load_request))
     }
 
-    fn request_load_asset(&self, load_request: AssetLoadRequest) -> AssetLoadTask {
+    fn load_asset(&self, load_request: AssetLoadRequest) -> AssetLoadTask {
         let asset_type = load_request.url.ty();
 
         let cache_asset_path = self.cache_dir.join(&load_request.url);
-        info!("Try to load baked asset: {:?}", cache_asset_path);
+        info!(target: ASSET, "Try to load baked asset: {:?}", cache_asset_path);
 
         // TODO: load dependencies
         // TODO: notice a 1-to-1 mapping between AsserType and static asset type, further abstract the deserialize logic
         if asset_type == AssetType::MeshCollection {
-            let asset: MeshCollection = deserialize_asset(&cache_asset_path).unwrap();
+            let asset: MeshCollection = match deserialize_asset(&cache_asset_path) {
+                Ok(asset) => asset,
+                Err(err) => {
+                    error!(target: ASSET, "Failed to load baked asset {:?}: {}", cache_asset_path, err);
+                    return AssetLoadTask::completed();
+                }
+            };
 
             let mut mesh_collection_handles = Vec::with_capacity(asset.meshes.len() + asset.materials.len());
             for mesh_url in &asset.meshes {
-                mesh_collection_handles.extend(self.request_load_asset(AssetLoadRequestBuilder::default()
+                mesh_collection_handles.extend(self.load_asset(AssetLoadRequestBuilder::default()
                     .url(mesh_url.clone())
                     .build().unwrap()).0);
             }
 
             for mat_url in &asset.materials {
-                mesh_collection_handles.extend(self.request_load_asset(AssetLoadRequestBuilder::default()
+                mesh_collection_handles.extend(self.load_asset(AssetLoadRequestBuilder::default()
                     .url(mat_url.clone())
                     .build().unwrap()).0);
             }
@@ -172,35 +521,235 @@ impl AssetManager {
         }
 
         let task = submit(move || {
-            match asset_type {
-                AssetType::Mesh => {
-                    let asset: Mesh = deserialize_asset(&cache_asset_path).unwrap();
-
-                    ASSET_REGISTRY
-                        .get()
-                        .unwrap()
-                        .register(load_request.url, asset);
-                }
-                AssetType::Texture => {
-                    let asset: Texture = deserialize_asset(&cache_asset_path).unwrap();
-
-                    ASSET_REGISTRY
-                        .get()
-                        .unwrap()
-                        .register(load_request.url, asset);
-                }
-                AssetType::Material => {
-                    let asset: Material = deserialize_asset(&cache_asset_path).unwrap();
-
-                    ASSET_REGISTRY
-                        .get()
-                        .unwrap()
-                        .register(load_request.url, asset);
-                }
+            let result = match asset_type {
+                AssetType::Mesh => deserialize_asset::<Mesh>(&cache_asset_path)
+                    .map(|asset| ASSET_REGISTRY.get().unwrap().register(load_request.url, asset)),
+                AssetType::Texture => deserialize_asset::<Texture>(&cache_asset_path)
+                    .map(|asset| ASSET_REGISTRY.get().unwrap().register(load_request.url, asset)),
+                AssetType::Material => deserialize_asset::<Material>(&cache_asset_path)
+                    .map(|asset| ASSET_REGISTRY.get().unwrap().register(load_request.url, asset)),
                 _ => unreachable!()
+            };
+
+            // the asset stays unregistered, an outdated cache is rebaked on the next raw load request
+            if let Err(err) = result {
+                error!(target: ASSET, "Failed to load baked asset {:?}: {}", cache_asset_path, err);
             }
         });
 
         AssetLoadTask(vec![task.into_handle()])
     }
-}
\ No newline at end of file
+}
+
+fn raw_extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(OsStr::to_str)
+        .map(str::to_lowercase)
+}
+
+/// Bake the loaded raw resource, then record the meta of its source next to the baked asset.
+fn bake_and_record<B>(raw: Result<B::Raw>, raw_content_path: PathBuf, cache_dir: PathBuf, relative_path: PathBuf) -> TaskResult<Result<()>>
+where
+    B: RawResourceBaker + 'static,
+    B::Raw: Send + 'static,
+{
+    let raw = match raw {
+        Ok(raw) => raw,
+        Err(err) => return submit(move || Err(err)),
+    };
+
+    let dependencies = raw.dependencies().to_vec();
+    let url = AssetUrl::from(relative_path);
+    B::bake_async(raw, ASSET_REGISTRY.get().unwrap(), cache_dir.clone(), url.clone()).map(move |baked| {
+        baked?;
+
+        let cached_file_path = cache_dir.join(B::asset_url(&url));
+        BakeMeta::of_source(&raw_content_path, &dependencies)?.write(&cached_file_path)
+    })
+}
+
+/// Recursively collect paths, relative to `content_dir`, of all raw resources inside `relative_dir` accepted by `is_bakeable`.
+fn collect_raw_resources(content_dir: &Path, relative_dir: &Path, is_bakeable: &dyn Fn(&Path) -> bool, paths: &mut Vec<PathBuf>) -> Result<()> {
+    let dir = content_dir.join(relative_dir);
+    let entries = std::fs::read_dir(&dir).map_err(|err| anyhow!("Failed to read content directory {:?}: {}", dir, err))?;
+
+    for entry in entries {
+        let entry = entry?;
+        let relative_path = relative_dir.join(entry.file_name());
+
+        if entry.file_type()?.is_dir() {
+            collect_raw_resources(content_dir, &relative_path, is_bakeable, paths)?;
+        } else if is_bakeable(&relative_path) {
+            paths.push(relative_path);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Once;
+    use std::time::Duration;
+    use super::*;
+
+    fn initialize() {
+        static INIT: Once = Once::new();
+        INIT.call_once(|| {
+            zenith_task::initialize();
+            crate::initialize().unwrap();
+        });
+    }
+
+    /// Content and cache folders inside a temporary directory, removed on drop even if the test fails.
+    struct TempContent {
+        root: PathBuf,
+        manager: AssetManager,
+    }
+
+    impl TempContent {
+        fn new(name: &str) -> Self {
+            initialize();
+
+            let root = std::env::temp_dir().join(format!("zenith-asset-{}-test-{}", name, std::process::id()));
+            let manager = AssetManager::with_dirs(root.join("content"), root.join("cache"));
+            Self { root, manager }
+        }
+
+        /// Write a 4x4 white texture at `relative_path` of content/ folder, return its full path.
+        fn write_dds(&self, relative_path: &str) -> PathBuf {
+            let path = self.manager.content_dir().join(relative_path);
+            write_dds(&path);
+            path
+        }
+
+        fn write(&self, relative_path: &str, contents: impl AsRef<[u8]>) -> PathBuf {
+            let path = self.manager.content_dir().join(relative_path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, contents).unwrap();
+            path
+        }
+    }
+
+    impl Drop for TempContent {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.root);
+        }
+    }
+
+    fn write_dds(path: &Path) {
+        let mut dds = ddsfile::Dds::new_dxgi(ddsfile::NewDxgiParams {
+            height: 4,
+            width: 4,
+            depth: None,
+            format: ddsfile::DxgiFormat::R8G8B8A8_UNorm,
+            mipmap_levels: None,
+            array_layers: None,
+            caps2: None,
+            is_cubemap: false,
+            resource_dimension: ddsfile::D3D10ResourceDimension::Texture2D,
+            alpha_mode: ddsfile::AlphaMode::Unknown,
+        }).unwrap();
+        dds.data = vec![255; 4 * 4 * 4];
+
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        dds.write(&mut std::fs::File::create(path).unwrap()).unwrap();
+    }
+
+    /// Move the modification time a second later, file systems may NOT tell apart writes within a few milliseconds.
+    fn touch_later(path: &Path) {
+        let modified = std::fs::metadata(path).unwrap().modified().unwrap() + Duration::from_secs(1);
+        std::fs::File::options().write(true).open(path).unwrap().set_modified(modified).unwrap();
+    }
+
+    #[test]
+    fn bake_into_custom_dirs() {
+        let content = TempContent::new("bake");
+        let manager = &content.manager;
+        content.write_dds("texture/white.dds");
+
+        let report = manager.bake_all("texture").unwrap();
+
+        assert!(report.failures.is_empty(), "{:?}", report.failures);
+        assert_eq!(report.baked, [AssetUrl::from(PathBuf::from("texture/white.tex"))]);
+        assert!(manager.cache_dir().join("texture/white.tex").exists());
+        assert!(!manager.should_bake_asset(&"texture/white.dds", &report.baked[0]));
+    }
+
+    #[test]
+    fn changed_source_is_rebaked() {
+        let content = TempContent::new("rebake");
+        let manager = &content.manager;
+        let raw_path = content.write_dds("texture/white.dds");
+
+        let report = manager.bake_all("texture").unwrap();
+        let asset_url = &report.baked[0];
+        assert!(!manager.should_bake_asset(&"texture/white.dds", asset_url));
+
+        // a new modification time alone does NOT matter, only the content
+        touch_later(&raw_path);
+        assert!(!manager.should_bake_asset(&"texture/white.dds", asset_url));
+
+        let mut bytes = std::fs::read(&raw_path).unwrap();
+        *bytes.last_mut().unwrap() = 0;
+        std::fs::write(&raw_path, bytes).unwrap();
+        touch_later(&raw_path);
+        assert!(manager.should_bake_asset(&"texture/white.dds", asset_url));
+    }
+
+    #[test]
+    fn changed_gltf_buffer_is_rebaked() {
+        let content = TempContent::new("dependency");
+        let manager = &content.manager;
+        content.write("mesh/empty.gltf", r#"{"asset":{"version":"2.0"},"buffers":[{"uri":"empty.bin","byteLength":4}]}"#);
+        let buffer_path = content.write("mesh/empty.bin", [0u8; 4]);
+
+        let report = manager.bake_all("mesh").unwrap();
+        assert!(report.failures.is_empty(), "{:?}", report.failures);
+        let asset_url = &report.baked[0];
+        assert!(!manager.should_bake_asset(&"mesh/empty.gltf", asset_url));
+
+        // the gltf file itself is untouched
+        std::fs::write(&buffer_path, [1u8; 4]).unwrap();
+        touch_later(&buffer_path);
+        assert!(manager.should_bake_asset(&"mesh/empty.gltf", asset_url));
+    }
+
+    #[test]
+    fn raw_loader_is_dispatched_by_extension() {
+        let content = TempContent::new("loader");
+        let manager = &content.manager;
+        content.write_dds("texture/white.img");
+
+        assert!(manager.request_load("texture/white.img").is_err());
+        assert!(manager.bake_all("texture").unwrap().baked.is_empty());
+
+        manager.register_loader::<DdsLoader, RawTextureProcessor>("IMG");
+        manager.request_load("texture/white.img").unwrap().wait();
+        assert!(manager.cache_dir().join("texture/white.tex").exists());
+    }
+
+    #[test]
+    fn finished_load_is_drained_once() {
+        let content = TempContent::new("finished");
+        let manager = &content.manager;
+        content.write_dds("texture/white.dds");
+
+        manager.request_load("texture/white.dds").unwrap().wait();
+
+        assert_eq!(manager.drain_finished_loads(), [manager.asset_url("texture/white.dds").unwrap()]);
+        assert!(manager.drain_finished_loads().is_empty());
+    }
+
+    #[test]
+    fn failed_load_is_drained() {
+        let content = TempContent::new("failed");
+        let manager = &content.manager;
+        content.write("texture/corrupt.dds", b"not a dds");
+
+        manager.request_load("texture/corrupt.dds").unwrap().wait();
+
+        assert_eq!(manager.drain_finished_loads(), [manager.asset_url("texture/corrupt.dds").unwrap()]);
+        assert!(!manager.cache_dir().join("texture/corrupt.tex").exists());
+    }
+}
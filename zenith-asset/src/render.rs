@@ -3,8 +3,10 @@ use std::path::{Path, PathBuf};
 use bincode::{Decode, Encode};
 use bytemuck::{NoUninit, Pod, Zeroable};
 use derive_builder::Builder;
-use glam::{Vec2, Vec3};
+use glam::{Mat4, Vec2, Vec3, Vec4};
 use serde::{Deserialize, Serialize};
+use zenith_core::collections::hashmap::HashMap;
+use zenith_core::log::{warn, target::ASSET};
 use super::{Asset, AssetUrl};
 
 #[repr(C)]
@@ -13,34 +15,166 @@ pub struct Vertex {
     pub position: [f32; 3],
     pub normal: [f32; 3],
     pub tex_coord: [f32; 2],
+    /// Tangent in xyz, handedness of the bitangent (+1 or -1) in w.
+    pub tangent: [f32; 4],
+    /// Secondary uv set, e.g. for lightmaps.
+    pub tex_coord1: [f32; 2],
+    /// RGBA8 color packed in little-endian order, R in the lowest byte.
+    pub color: u32,
 }
 
 impl Vertex {
-    pub fn new(position: Vec3, normal: Vec3, tex_coord: Vec2) -> Self {
+    pub const WHITE: u32 = u32::MAX;
+
+    /// Vertex with the secondary uv set same as the first one and a white color.
+    pub fn new(position: Vec3, normal: Vec3, tex_coord: Vec2, tangent: Vec4) -> Self {
         Self {
             position: position.to_array(),
             normal: normal.to_array(),
             tex_coord: tex_coord.to_array(),
+            tangent: tangent.to_array(),
+            tex_coord1: tex_coord.to_array(),
+            color: Self::WHITE,
+        }
+    }
+
+    pub fn with_tex_coord1(mut self, tex_coord1: Vec2) -> Self {
+        self.tex_coord1 = tex_coord1.to_array();
+        self
+    }
+
+    pub fn with_color(mut self, rgba: [u8; 4]) -> Self {
+        self.color = u32::from_le_bytes(rgba);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
+pub enum IndexFormat {
+    Uint16,
+    Uint32,
+}
+
+impl IndexFormat {
+    pub fn bytes_per_index(&self) -> u32 {
+        match self {
+            IndexFormat::Uint16 => 2,
+            IndexFormat::Uint32 => 4,
+        }
+    }
+
+    pub fn to_wgpu_format(&self) -> wgpu::IndexFormat {
+        match self {
+            IndexFormat::Uint16 => wgpu::IndexFormat::Uint16,
+            IndexFormat::Uint32 => wgpu::IndexFormat::Uint32,
+        }
+    }
+}
+
+/// Index data of a mesh, stored in the narrowest format which is able to address all vertices.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
+pub enum MeshIndices {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+impl From<Vec<u32>> for MeshIndices {
+    /// Pick 16-bit indices if the max index fits in, otherwise keep 32-bit indices.
+    fn from(indices: Vec<u32>) -> Self {
+        let max_index = indices.iter().copied().max().unwrap_or(0);
+
+        if max_index <= u16::MAX as u32 {
+            MeshIndices::U16(indices.into_iter().map(|index| index as u16).collect())
+        } else {
+            MeshIndices::U32(indices)
+        }
+    }
+}
+
+impl MeshIndices {
+    pub fn format(&self) -> IndexFormat {
+        match self {
+            MeshIndices::U16(_) => IndexFormat::Uint16,
+            MeshIndices::U32(_) => IndexFormat::Uint32,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            MeshIndices::U16(indices) => indices.len(),
+            MeshIndices::U32(indices) => indices.len(),
         }
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            MeshIndices::U16(indices) => bytemuck::cast_slice(indices),
+            MeshIndices::U32(indices) => bytemuck::cast_slice(indices),
+        }
+    }
+
+    /// Return all indices widened into u32.
+    pub fn to_u32(&self) -> Vec<u32> {
+        match self {
+            MeshIndices::U16(indices) => indices.iter().map(|index| *index as u32).collect(),
+            MeshIndices::U32(indices) => indices.clone(),
+        }
+    }
+
+    /// Return a line list of the three edges of each triangle, e.g. to draw a wireframe without polygon mode support.
+    /// Edges shared by two triangles are listed twice.
+    pub fn triangle_edges(&self) -> Vec<u32> {
+        self.to_u32()
+            .chunks_exact(3)
+            .flat_map(|triangle| [triangle[0], triangle[1], triangle[1], triangle[2], triangle[2], triangle[0]])
+            .collect()
+    }
+}
+
+/// Simplified level of detail of a mesh, drawn from the same vertices.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct MeshLod {
+    pub indices: MeshIndices,
+    /// Max deviation from the full mesh surface, in mesh space units.
+    pub error: f32,
 }
 
 #[derive(Debug, Clone, Builder, Serialize, Deserialize, Encode, Decode)]
 #[builder(setter(into))]
 pub struct Mesh<V = Vertex> {
     pub vertices: Vec<V>,
-    pub indices: Vec<u32>,
+    pub indices: MeshIndices,
     #[builder(default)]
     #[bincode(with_serde)]
     pub material: Option<usize>,
+    /// Coarser levels of detail from the finest, `indices` is the level 0.
+    #[builder(default)]
+    pub lods: Vec<MeshLod>,
 }
 
 impl<V: NoUninit> Mesh<V> {
     pub fn new(vertices: Vec<V>, indices: Vec<u32>, material: Option<usize>) -> Self {
         Self {
             vertices,
-            indices,
+            indices: indices.into(),
             material,
+            lods: Vec::new(),
+        }
+    }
+
+    /// Return the number of levels of detail, including the full mesh.
+    #[inline]
+    pub fn num_lods(&self) -> usize { 1 + self.lods.len() }
+
+    /// Return the indices and the error of a level of detail, level 0 is the full mesh with no error.
+    pub fn lod(&self, level: usize) -> (&MeshIndices, f32) {
+        match level {
+            0 => (&self.indices, 0.),
+            _ => (&self.lods[level - 1].indices, self.lods[level - 1].error),
         }
     }
     
@@ -49,10 +183,146 @@ impl<V: NoUninit> Mesh<V> {
     }
 
     pub fn indices_bytes(&self) -> &[u8] {
-        bytemuck::cast_slice(&self.indices)
+        self.indices.as_bytes()
+    }
+
+    /// Return the format of the index buffer.
+    pub fn index_format(&self) -> IndexFormat {
+        self.indices.format()
+    }
+}
+
+/// Distance under which vertex attributes are considered equal when welding, see [`MeshBuilder::optimize`].
+pub const WELD_EPSILON: f32 = 1e-5;
+
+impl MeshBuilder<Vertex> {
+    /// Weld identical vertices of the vertices and indices already set, then reorder the triangles for the
+    /// post-transform vertex cache and the vertices in first use order for fetch locality.
+    /// Attributes are compared snapped to a grid of [`WELD_EPSILON`], colors must be equal.
+    pub fn optimize(&mut self, optimize: bool) -> &mut Self {
+        if !optimize {
+            return self;
+        }
+        let (Some(vertices), Some(indices)) = (&self.vertices, &self.indices) else {
+            warn!(target: ASSET, "Only mesh with vertices and indices can be optimized, keep it as is.");
+            return self;
+        };
+        let indices = indices.to_u32();
+        if indices.iter().any(|index| *index as usize >= vertices.len()) {
+            warn!(target: ASSET, "Mesh has indices out of its vertices, keep it as is.");
+            return self;
+        }
+
+        let (vertices, indices) = weld_vertices(vertices, &indices);
+        let indices = meshopt::optimize_vertex_cache(&indices, vertices.len());
+        let (vertices, indices) = reorder_vertices_by_first_use(&vertices, &indices);
+
+        self.vertices = Some(vertices);
+        self.indices = Some(indices.into());
+        self
+    }
+}
+
+impl MeshBuilder<Vertex> {
+    /// Generate up to `count` levels of detail from the vertices and indices already set, each with about half
+    /// the triangles of the previous one. Levels the simplifier can NOT reduce further are dropped.
+    /// Call after [`MeshBuilder::optimize`], which reorders the vertices.
+    pub fn generate_lods(&mut self, count: usize) -> &mut Self {
+        let (Some(vertices), Some(indices)) = (&self.vertices, &self.indices) else {
+            warn!(target: ASSET, "Only mesh with vertices and indices can generate lods, keep it as is.");
+            return self;
+        };
+        let Ok(adapter) = meshopt::VertexDataAdapter::new(bytemuck::cast_slice(vertices), size_of::<Vertex>(), 0) else {
+            warn!(target: ASSET, "Failed to read the vertex positions, keep the mesh without lods.");
+            return self;
+        };
+        // the simplifier reports errors relative to the mesh extent
+        let error_scale = meshopt::simplify_scale(&adapter);
+
+        let mut lods = Vec::with_capacity(count);
+        let mut previous = indices.to_u32();
+        for _ in 0..count {
+            let target_count = (previous.len() / 2) / 3 * 3;
+            let mut relative_error = 0.;
+            let simplified = meshopt::simplify(
+                &previous,
+                &adapter,
+                target_count,
+                LOD_TARGET_ERROR,
+                meshopt::SimplifyOptions::None,
+                Some(&mut relative_error),
+            );
+            // stuck on the error budget or the topology, coarser levels would only repeat this one
+            if simplified.is_empty() || simplified.len() as f32 > previous.len() as f32 * LOD_MIN_REDUCTION {
+                break;
+            }
+
+            let error = relative_error * error_scale;
+            // errors of coarser levels are relative to the full mesh, never smaller than the finer ones
+            let error = lods.last().map_or(error, |lod: &MeshLod| error.max(lod.error));
+            lods.push(MeshLod {
+                indices: simplified.clone().into(),
+                error,
+            });
+            previous = simplified;
+        }
+
+        self.lods = Some(lods);
+        self
     }
 }
 
+/// Max relative deviation a level of detail may have from the full mesh, 5% of the mesh extent.
+const LOD_TARGET_ERROR: f32 = 0.05;
+/// A level of detail must keep at most this ratio of the triangles of the previous one.
+const LOD_MIN_REDUCTION: f32 = 0.8;
+
+/// Return the unique vertices and the indices remapped onto them.
+fn weld_vertices(vertices: &[Vertex], indices: &[u32]) -> (Vec<Vertex>, Vec<u32>) {
+    let snap = |values: &[f32]| values.iter().map(|value| (value / WELD_EPSILON).round() as i64).collect::<Vec<_>>();
+
+    let mut unique_vertices = Vec::with_capacity(vertices.len());
+    let mut remap = Vec::with_capacity(vertices.len());
+    let mut unique_indices = HashMap::with_capacity_and_hasher(vertices.len(), Default::default());
+    for vertex in vertices {
+        let key = (
+            snap(&vertex.position),
+            snap(&vertex.normal),
+            snap(&vertex.tex_coord),
+            snap(&vertex.tangent),
+            snap(&vertex.tex_coord1),
+            vertex.color,
+        );
+        let index = *unique_indices.entry(key).or_insert_with(|| {
+            unique_vertices.push(*vertex);
+            unique_vertices.len() as u32 - 1
+        });
+        remap.push(index);
+    }
+
+    let indices = indices.iter().map(|index| remap[*index as usize]).collect();
+    (unique_vertices, indices)
+}
+
+/// Renumber the vertices in the order the indices first reference them, unreferenced vertices are dropped.
+fn reorder_vertices_by_first_use(vertices: &[Vertex], indices: &[u32]) -> (Vec<Vertex>, Vec<u32>) {
+    let mut remap = vec![u32::MAX; vertices.len()];
+    let mut reordered = Vec::with_capacity(vertices.len());
+
+    let indices = indices
+        .iter()
+        .map(|index| {
+            let new_index = &mut remap[*index as usize];
+            if *new_index == u32::MAX {
+                *new_index = reordered.len() as u32;
+                reordered.push(vertices[*index as usize]);
+            }
+            *new_index
+        })
+        .collect();
+    (reordered, indices)
+}
+
 impl<V: 'static + Send + Sync> Asset for Mesh<V> {
     fn as_any(&self) -> &dyn Any {
         self
@@ -67,9 +337,79 @@ impl<V: 'static + Send + Sync> Asset for Mesh<V> {
     fn extension() -> &'static str {
         "mesh"
     }
+
+    fn size_bytes(&self) -> usize {
+        size_of_val(self.vertices.as_slice()) + self.indices.as_bytes().len()
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Encode, Decode)]
+/// Where a mesh lives inside [`PackedMeshes`], indices stay relative to the first vertex of the mesh.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PackedMeshRange {
+    pub base_vertex: i32,
+    pub first_index: u32,
+    pub index_count: u32,
+    pub vertex_count: u32,
+}
+
+/// Vertices and indices of many meshes concatenated, to be uploaded into one vertex and one index buffer.
+#[derive(Debug, Clone)]
+pub struct PackedMeshes<V = Vertex> {
+    pub vertices: Vec<V>,
+    pub indices: MeshIndices,
+    pub ranges: Vec<PackedMeshRange>,
+}
+
+impl<V: NoUninit> PackedMeshes<V> {
+    /// Pack the meshes in order, 16-bit indices are kept only if every mesh uses them.
+    pub fn pack<'a>(meshes: impl IntoIterator<Item = &'a Mesh<V>>) -> Self
+    where
+        V: 'a,
+    {
+        let meshes = meshes.into_iter().collect::<Vec<_>>();
+        let wide = meshes.iter().any(|mesh| mesh.index_format() == IndexFormat::Uint32);
+
+        let mut vertices = Vec::with_capacity(meshes.iter().map(|mesh| mesh.vertices.len()).sum());
+        let mut indices = if wide { MeshIndices::U32(vec![]) } else { MeshIndices::U16(vec![]) };
+        let mut ranges = Vec::with_capacity(meshes.len());
+
+        for mesh in meshes {
+            ranges.push(PackedMeshRange {
+                base_vertex: vertices.len() as i32,
+                first_index: indices.len() as u32,
+                index_count: mesh.indices.len() as u32,
+                vertex_count: mesh.vertices.len() as u32,
+            });
+
+            vertices.extend_from_slice(&mesh.vertices);
+            match (&mut indices, &mesh.indices) {
+                (MeshIndices::U16(packed), MeshIndices::U16(mesh_indices)) => packed.extend_from_slice(mesh_indices),
+                (MeshIndices::U32(packed), mesh_indices) => packed.extend(mesh_indices.to_u32()),
+                (MeshIndices::U16(_), MeshIndices::U32(_)) => unreachable!("32-bit indices widen the packed indices"),
+            }
+        }
+
+        Self {
+            vertices,
+            indices,
+            ranges,
+        }
+    }
+
+    pub fn vertices_bytes(&self) -> &[u8] {
+        bytemuck::cast_slice(&self.vertices)
+    }
+
+    pub fn indices_bytes(&self) -> &[u8] {
+        self.indices.as_bytes()
+    }
+
+    pub fn index_format(&self) -> IndexFormat {
+        self.indices.format()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
 pub enum TextureFormat {
     R8,
     R8G8,
@@ -78,10 +418,28 @@ pub enum TextureFormat {
     R16G16,
     R16G16B16A16,
     R32G32B32A32Float,
+    /// 4x4 blocks of four channels with 1-bit alpha.
+    Bc1RgbaUnorm,
+    /// 4x4 blocks of four channels.
+    Bc3RgbaUnorm,
+    /// 4x4 blocks of two channels, for normal maps. Always linear.
+    Bc5RgUnorm,
+    /// 4x4 blocks of four channels in high quality.
+    Bc7RgbaUnorm,
+}
+
+/// How the texels of a texture are encoded, decided by the role of the texture.
+/// Color maps (base color, emissive) are sRGB, data maps (normal, metallic-roughness) are linear.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
+pub enum ColorSpace {
+    #[default]
+    Srgb,
+    Linear,
 }
 
 impl TextureFormat {
-    pub fn bytes_per_pixel(&self) -> u32 {
+    /// Return the size in bytes of a block, which is a single pixel for uncompressed formats.
+    pub fn bytes_per_block(&self) -> u32 {
         match self {
             TextureFormat::R8 => 1,
             TextureFormat::R8G8 => 2,
@@ -90,21 +448,78 @@ impl TextureFormat {
             TextureFormat::R16G16 => 4,
             TextureFormat::R16G16B16A16 => 8,
             TextureFormat::R32G32B32A32Float => 16,
+            TextureFormat::Bc1RgbaUnorm => 8,
+            TextureFormat::Bc3RgbaUnorm => 16,
+            TextureFormat::Bc5RgUnorm => 16,
+            TextureFormat::Bc7RgbaUnorm => 16,
         }
     }
 
-    pub fn to_wgpu_format(&self) -> wgpu::TextureFormat {
-        match self {
+    /// Return the width and height in pixels of a block.
+    pub fn block_dimensions(&self) -> (u32, u32) {
+        if self.is_compressed() { (4, 4) } else { (1, 1) }
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        matches!(
+            self,
+            TextureFormat::Bc1RgbaUnorm | TextureFormat::Bc3RgbaUnorm | TextureFormat::Bc5RgUnorm | TextureFormat::Bc7RgbaUnorm
+        )
+    }
+
+    /// Return the bytes of a row of blocks, used as `bytes_per_row` on upload.
+    pub fn bytes_per_row(&self, width: u32) -> u32 {
+        width.div_ceil(self.block_dimensions().0) * self.bytes_per_block()
+    }
+
+    /// Return the number of block rows, used as `rows_per_image` on upload.
+    pub fn rows_per_image(&self, height: u32) -> u32 {
+        height.div_ceil(self.block_dimensions().1)
+    }
+
+    /// Return the wgpu format to sample the texels in the color space.
+    /// Only 8-bit color formats have sRGB variants, others are always linear.
+    pub fn to_wgpu_format(&self, color_space: ColorSpace) -> wgpu::TextureFormat {
+        let format = match self {
             TextureFormat::R8 => wgpu::TextureFormat::R8Unorm,
             TextureFormat::R8G8 => wgpu::TextureFormat::Rg8Unorm,
-            TextureFormat::R8G8B8A8 => wgpu::TextureFormat::Rgba8UnormSrgb,
+            TextureFormat::R8G8B8A8 => wgpu::TextureFormat::Rgba8Unorm,
             TextureFormat::R16 => wgpu::TextureFormat::R16Unorm,
             TextureFormat::R16G16 => wgpu::TextureFormat::Rg16Unorm,
             TextureFormat::R16G16B16A16 => wgpu::TextureFormat::Rgba16Unorm,
             TextureFormat::R32G32B32A32Float => wgpu::TextureFormat::Rgba32Float,
+            TextureFormat::Bc1RgbaUnorm => wgpu::TextureFormat::Bc1RgbaUnorm,
+            TextureFormat::Bc3RgbaUnorm => wgpu::TextureFormat::Bc3RgbaUnorm,
+            TextureFormat::Bc5RgUnorm => wgpu::TextureFormat::Bc5RgUnorm,
+            TextureFormat::Bc7RgbaUnorm => wgpu::TextureFormat::Bc7RgbaUnorm,
+        };
+
+        match color_space {
+            ColorSpace::Srgb => format.add_srgb_suffix(),
+            ColorSpace::Linear => format,
+        }
+    }
+}
+
+/// How texture coordinates outside [0, 1] are resolved when the texture is sampled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
+pub enum AddressMode {
+    /// Tile the texture, e.g. terrain and fabric.
+    #[default]
+    Repeat,
+    MirrorRepeat,
+    /// Stretch the edge texels, e.g. UI and decals.
+    ClampToEdge,
+}
+
+impl AddressMode {
+    pub fn to_wgpu(&self) -> wgpu::AddressMode {
+        match self {
+            AddressMode::Repeat => wgpu::AddressMode::Repeat,
+            AddressMode::MirrorRepeat => wgpu::AddressMode::MirrorRepeat,
+            AddressMode::ClampToEdge => wgpu::AddressMode::ClampToEdge,
         }
     }
-    
 }
 
 #[derive(Debug, Clone, Builder, Serialize, Deserialize, Encode, Decode)]
@@ -113,7 +528,227 @@ pub struct Texture {
     pub width: u32,
     pub height: u32,
     pub format: TextureFormat,
+    /// Pixels (or blocks) of all mip levels, tightly packed from the largest level.
     pub pixels: Vec<u8>,
+    #[builder(default = 1)]
+    pub mip_level_count: u32,
+    #[builder(default)]
+    pub color_space: ColorSpace,
+    /// Address modes of u, v and w the texture is intended to be sampled with.
+    #[builder(default, setter(custom))]
+    pub address_mode: [AddressMode; 3],
+}
+
+impl Texture {
+    /// Return the wgpu format to create the texture with.
+    pub fn wgpu_format(&self) -> wgpu::TextureFormat {
+        self.format.to_wgpu_format(self.color_space)
+    }
+
+    /// Return the descriptor of a trilinear sampler with the intended address modes of the texture.
+    pub fn sampler_descriptor<'a>(&self, label: Option<&'a str>) -> wgpu::SamplerDescriptor<'a> {
+        let [address_mode_u, address_mode_v, address_mode_w] = self.address_mode.map(|mode| mode.to_wgpu());
+        wgpu::SamplerDescriptor {
+            label,
+            address_mode_u,
+            address_mode_v,
+            address_mode_w,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        }
+    }
+
+    /// Return the number of mip levels of a full chain down to 1x1.
+    pub fn full_mip_level_count(width: u32, height: u32) -> u32 {
+        32 - width.max(height).max(1).leading_zeros()
+    }
+
+    /// Return the size in bytes of a mip level.
+    pub fn mip_level_size(&self, level: u32) -> usize {
+        let width = (self.width >> level).max(1);
+        let height = (self.height >> level).max(1);
+        (self.format.bytes_per_row(width) * self.format.rows_per_image(height)) as usize
+    }
+
+    /// Iterate (width, height, data) of each mip level.
+    pub fn mip_levels(&self) -> impl Iterator<Item = (u32, u32, &[u8])> {
+        let mut offset = 0;
+        (0..self.mip_level_count).map(move |level| {
+            let size = self.mip_level_size(level);
+            let data = &self.pixels[offset..offset + size];
+            offset += size;
+            ((self.width >> level).max(1), (self.height >> level).max(1), data)
+        })
+    }
+}
+
+impl TextureBuilder {
+    /// Set the address modes of u, v and w the texture is intended to be sampled with.
+    pub fn address_mode(&mut self, u: AddressMode, v: AddressMode, w: AddressMode) -> &mut Self {
+        self.address_mode = Some([u, v, w]);
+        self
+    }
+
+    /// Generate the full mip chain down to 1x1 from the level 0 pixels already set, see [`TextureBuilder::mip_levels`].
+    pub fn generate_mips(&mut self, generate: bool) -> &mut Self {
+        if generate {
+            self.mip_levels(u32::MAX);
+        }
+        self
+    }
+
+    /// Generate mip levels by box filtering the level 0 pixels already set, `count` includes level 0 and
+    /// is clamped to the full chain. sRGB textures are filtered in linear space.
+    /// Only uncompressed 8-bit textures can be filtered, others keep their current levels.
+    pub fn mip_levels(&mut self, count: u32) -> &mut Self {
+        let (Some(width), Some(height), Some(format), Some(pixels)) = (self.width, self.height, self.format, &self.pixels) else {
+            warn!(target: ASSET, "Only texture with known size and pixels can generate mips, keep the current levels.");
+            return self;
+        };
+
+        let channels = match format {
+            TextureFormat::R8 | TextureFormat::R8G8 | TextureFormat::R8G8B8A8 => format.bytes_per_block() as usize,
+            _ => {
+                warn!(target: ASSET, "Texture format {:?} can NOT generate mips, keep the current levels.", format);
+                return self;
+            }
+        };
+        // only RGBA8 has a sRGB variant, and its alpha is always linear
+        let srgb_channels = if format == TextureFormat::R8G8B8A8 && self.color_space.unwrap_or_default() == ColorSpace::Srgb { 3 } else { 0 };
+
+        let count = count.clamp(1, Texture::full_mip_level_count(width, height));
+        let level_0_size = (width * height) as usize * channels;
+        let mut mips = pixels[..level_0_size].to_vec();
+
+        let (mut level_width, mut level_height, mut level_offset) = (width, height, 0);
+        for _ in 1..count {
+            let (next_width, next_height) = ((level_width >> 1).max(1), (level_height >> 1).max(1));
+            let next_offset = mips.len();
+
+            for y in 0..next_height {
+                for x in 0..next_width {
+                    // clamp the footprint on odd or 1-texel wide levels
+                    let xs = [(2 * x).min(level_width - 1), (2 * x + 1).min(level_width - 1)];
+                    let ys = [(2 * y).min(level_height - 1), (2 * y + 1).min(level_height - 1)];
+
+                    for channel in 0..channels {
+                        let is_srgb = channel < srgb_channels;
+                        let sum: f32 = ys.iter()
+                            .flat_map(|&sy| xs.iter().map(move |&sx| (sy * level_width + sx) as usize))
+                            .map(|texel| {
+                                let value = mips[level_offset + texel * channels + channel] as f32 / 255.;
+                                if is_srgb { srgb_to_linear(value) } else { value }
+                            })
+                            .sum();
+
+                        let average = sum / 4.;
+                        let value = if is_srgb { linear_to_srgb(average) } else { average };
+                        mips.push((value * 255.).round() as u8);
+                    }
+                }
+            }
+
+            (level_width, level_height, level_offset) = (next_width, next_height, next_offset);
+        }
+
+        self.pixels = Some(mips);
+        self.mip_level_count = Some(count);
+        self
+    }
+
+    /// Transcode the RGBA8 pixels (of all mip levels) already set into a block compressed format.
+    /// Keep RGBA8 as the fallback if compression is unavailable, e.g. `texture-compression` feature is off
+    /// or the size is NOT a multiple of the block size.
+    pub fn compress(&mut self, format: TextureFormat) -> &mut Self {
+        if !cfg!(feature = "texture-compression") {
+            return self;
+        }
+
+        let (Some(width), Some(height), Some(TextureFormat::R8G8B8A8), Some(pixels)) = (self.width, self.height, self.format, &self.pixels) else {
+            warn!(target: ASSET, "Only RGBA8 texture with known size can be compressed, keep it uncompressed.");
+            return self;
+        };
+
+        let (block_width, block_height) = format.block_dimensions();
+        if width % block_width != 0 || height % block_height != 0 {
+            warn!(target: ASSET, "Texture size {}x{} is NOT a multiple of the block size, keep it uncompressed.", width, height);
+            return self;
+        }
+
+        let mut blocks = Vec::new();
+        let mut offset = 0;
+        for level in 0..self.mip_level_count.unwrap_or(1) {
+            let (level_width, level_height) = ((width >> level).max(1), (height >> level).max(1));
+            let level_pixels = &pixels[offset..offset + (level_width * level_height * 4) as usize];
+            offset += level_pixels.len();
+
+            // levels smaller than a block are padded by repeating their edge texels
+            let (padded_width, padded_height) = (level_width.next_multiple_of(block_width), level_height.next_multiple_of(block_height));
+            let padded = pad_rgba(level_pixels, level_width, level_height, padded_width, padded_height);
+
+            let Some(level_blocks) = compress_blocks(format, padded_width, padded_height, &padded) else {
+                warn!(target: ASSET, "Texture format {:?} is NOT a supported compression target, keep it uncompressed.", format);
+                return self;
+            };
+            blocks.extend(level_blocks);
+        }
+
+        self.format = Some(format);
+        self.pixels = Some(blocks);
+        self
+    }
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 { value / 12.92 } else { ((value + 0.055) / 1.055).powf(2.4) }
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 { value * 12.92 } else { 1.055 * value.powf(1. / 2.4) - 0.055 }
+}
+
+fn pad_rgba(rgba: &[u8], width: u32, height: u32, padded_width: u32, padded_height: u32) -> Vec<u8> {
+    if (width, height) == (padded_width, padded_height) {
+        return rgba.to_vec();
+    }
+
+    (0..padded_height)
+        .flat_map(|y| (0..padded_width).map(move |x| (y.min(height - 1) * width + x.min(width - 1)) as usize * 4))
+        .flat_map(|texel| rgba[texel..texel + 4].iter().copied())
+        .collect()
+}
+
+#[cfg(feature = "texture-compression")]
+fn compress_blocks(format: TextureFormat, width: u32, height: u32, rgba: &[u8]) -> Option<Vec<u8>> {
+    match format {
+        TextureFormat::Bc7RgbaUnorm => {
+            let surface = intel_tex_2::RgbaSurface {
+                data: rgba,
+                width,
+                height,
+                stride: width * 4,
+            };
+            Some(intel_tex_2::bc7::compress_blocks(&intel_tex_2::bc7::alpha_basic_settings(), &surface))
+        }
+        TextureFormat::Bc5RgUnorm => {
+            let rg: Vec<u8> = rgba.chunks_exact(4).flat_map(|pixel| [pixel[0], pixel[1]]).collect();
+            let surface = intel_tex_2::RgSurface {
+                data: &rg,
+                width,
+                height,
+                stride: width * 2,
+            };
+            Some(intel_tex_2::bc5::compress_blocks(&surface))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(not(feature = "texture-compression"))]
+fn compress_blocks(_format: TextureFormat, _width: u32, _height: u32, _rgba: &[u8]) -> Option<Vec<u8>> {
+    None
 }
 
 impl Asset for Texture {
@@ -130,6 +765,22 @@ impl Asset for Texture {
     fn extension() -> &'static str {
         "tex"
     }
+
+    fn size_bytes(&self) -> usize {
+        self.pixels.len()
+    }
+}
+
+/// How the alpha of the base color is interpreted, same as the glTF alpha modes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
+pub enum AlphaMode {
+    /// Alpha is ignored.
+    #[default]
+    Opaque,
+    /// Fully transparent below [`Material::alpha_cutoff`], fully opaque otherwise, e.g. foliage.
+    Mask,
+    /// Blended over what is behind, e.g. glass.
+    Blend,
 }
 
 #[derive(Debug, Clone, Builder, Serialize, Deserialize, Encode, Decode)]
@@ -143,6 +794,20 @@ pub struct Material {
     pub roughness: f32,
     #[builder(default = [0., 0., 0.])]
     pub emissive: [f32; 3],
+    /// How much of the occlusion map is applied, 0 means no occlusion.
+    #[builder(default = 1.0)]
+    pub occlusion_strength: f32,
+    /// Occlusion is stored in the R channel of `mra_tex` instead of `occlusion_tex`.
+    #[builder(default)]
+    pub occlusion_in_mra: bool,
+    #[builder(default)]
+    pub alpha_mode: AlphaMode,
+    /// Alpha threshold of [`AlphaMode::Mask`], unused by other modes.
+    #[builder(default = 0.5)]
+    pub alpha_cutoff: f32,
+    /// Back faces are drawn too, e.g. leaves and cloth modeled as a single plane.
+    #[builder(default)]
+    pub double_sided: bool,
 
     // TODO: replace with asset path reference
     #[builder(default)]
@@ -157,6 +822,29 @@ pub struct Material {
     #[builder(default)]
     #[bincode(with_serde)]
     pub emissive_tex: Option<Texture>,
+    #[builder(default)]
+    #[bincode(with_serde)]
+    pub occlusion_tex: Option<Texture>,
+}
+
+impl Material {
+    /// Faces to cull when drawing with this material, front faces wind counter-clockwise.
+    pub fn cull_mode(&self) -> Option<wgpu::Face> {
+        if self.double_sided {
+            None
+        } else {
+            Some(wgpu::Face::Back)
+        }
+    }
+
+    /// The texture holding ambient occlusion in its R channel, if any.
+    pub fn occlusion_texture(&self) -> Option<&Texture> {
+        if self.occlusion_in_mra {
+            self.mra_tex.as_ref()
+        } else {
+            self.occlusion_tex.as_ref()
+        }
+    }
 }
 
 impl Asset for Material {
@@ -173,6 +861,14 @@ impl Asset for Material {
     fn extension() -> &'static str {
         "mat"
     }
+
+    fn size_bytes(&self) -> usize {
+        [&self.base_color_tex, &self.mra_tex, &self.normal_tex, &self.emissive_tex, &self.occlusion_tex]
+            .into_iter()
+            .flatten()
+            .map(Texture::size_bytes)
+            .sum::<usize>() + size_of::<Self>()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Encode, Decode)]
@@ -182,6 +878,8 @@ pub struct MeshCollection {
     pub meshes: Vec<AssetUrl>,
     #[bincode(with_serde)]
     pub materials: Vec<AssetUrl>,
+    /// Column-major model matrix of each mesh, accumulated along the scene hierarchy.
+    pub transforms: Vec<[f32; 16]>,
 }
 
 impl Asset for MeshCollection {
@@ -198,6 +896,10 @@ impl Asset for MeshCollection {
     fn extension() -> &'static str {
         "mscl"
     }
+
+    fn size_bytes(&self) -> usize {
+        (self.meshes.len() + self.materials.len()) * size_of::<AssetUrl>() + self.transforms.len() * size_of::<[f32; 16]>()
+    }
 }
 
 impl MeshCollection {
@@ -206,12 +908,19 @@ impl MeshCollection {
             raw_asset_path: raw_asset_path.as_ref().into(),
             meshes: vec![],
             materials: vec![],
+            transforms: vec![],
         }
     }
 
-    pub fn add_mesh(&mut self, mesh_url: AssetUrl, mat_url: AssetUrl) {
+    pub fn add_mesh(&mut self, mesh_url: AssetUrl, mat_url: AssetUrl, transform: Mat4) {
         self.meshes.push(mesh_url);
         self.materials.push(mat_url);
+        self.transforms.push(transform.to_cols_array());
+    }
+
+    /// Return the model matrix of the mesh at `index`.
+    pub fn transform(&self, index: usize) -> Mat4 {
+        Mat4::from_cols_array(&self.transforms[index])
     }
 
     // "mesh/cerberus/scene.gltf" -> "mesh/cerberus/scene.mscl"
@@ -220,4 +929,259 @@ impl MeshCollection {
         baked_asset_path.set_extension(Self::extension());
         baked_asset_path.into()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn optimize_welds_duplicate_quad_corners() {
+        let corner = |x: f32, y: f32| Vertex::new(Vec3::new(x, y, 0.), Vec3::Z, Vec2::new(x, y), Vec4::X);
+        // two triangles with their own copy of the shared diagonal, one copy slightly off
+        let vertices = vec![
+            corner(0., 0.), corner(1., 0.), corner(1., 1.),
+            corner(0., 0.), corner(1., 1. + WELD_EPSILON * 0.1), corner(0., 1.),
+        ];
+
+        let mesh = MeshBuilder::default()
+            .vertices(vertices)
+            .indices(vec![0, 1, 2, 3, 4, 5])
+            .optimize(true)
+            .build()
+            .unwrap();
+
+        assert_eq!(mesh.vertices.len(), 4);
+        let indices = mesh.indices.to_u32();
+        assert_eq!(indices.len(), 6);
+        // both triangles still cover the same corners
+        let mut triangles: Vec<Vec<[f32; 3]>> = indices
+            .chunks_exact(3)
+            .map(|triangle| {
+                let mut corners: Vec<_> = triangle.iter().map(|index| mesh.vertices[*index as usize].position).collect();
+                corners.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                corners
+            })
+            .collect();
+        triangles.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(triangles, [
+            vec![[0., 0., 0.], [0., 1., 0.], [1., 1., 0.]],
+            vec![[0., 0., 0.], [1., 0., 0.], [1., 1., 0.]],
+        ]);
+    }
+
+    #[test]
+    fn lods_reduce_triangles() {
+        // a flat 16x16 grid simplifies down to a couple of triangles
+        let size = 17;
+        let vertices = (0..size * size)
+            .map(|index| {
+                let (x, y) = ((index % size) as f32, (index / size) as f32);
+                Vertex::new(Vec3::new(x, y, 0.), Vec3::Z, Vec2::ZERO, Vec4::X)
+            })
+            .collect::<Vec<_>>();
+        let indices = (0..size - 1)
+            .flat_map(|y| (0..size - 1).map(move |x| y * size + x))
+            .flat_map(|corner| [corner, corner + 1, corner + size + 1, corner, corner + size + 1, corner + size])
+            .collect::<Vec<u32>>();
+
+        let mesh = MeshBuilder::default()
+            .vertices(vertices)
+            .indices(indices)
+            .generate_lods(3)
+            .build()
+            .unwrap();
+
+        assert!(mesh.num_lods() > 1);
+        for level in 1..mesh.num_lods() {
+            let (indices, error) = mesh.lod(level);
+            let (finer_indices, finer_error) = mesh.lod(level - 1);
+            assert!(indices.len() < finer_indices.len());
+            assert!(error >= finer_error);
+        }
+    }
+
+    #[test]
+    fn small_mesh_uses_u16_indices() {
+        let vertices = vec![Vertex::new(Vec3::ZERO, Vec3::Z, Vec2::ZERO, Vec4::X); 3];
+        let mesh = Mesh::new(vertices, vec![0, 1, 2], None);
+
+        assert_eq!(mesh.index_format(), IndexFormat::Uint16);
+        assert_eq!(mesh.indices_bytes().len(), 3 * 2);
+        assert_eq!(mesh.indices.to_u32(), [0, 1, 2]);
+    }
+
+    #[test]
+    fn large_mesh_stays_on_u32_indices() {
+        let num_vertices = u16::MAX as usize + 2;
+        let vertices = vec![Vertex::new(Vec3::ZERO, Vec3::Z, Vec2::ZERO, Vec4::X); num_vertices];
+        let indices = vec![0, 1, num_vertices as u32 - 1];
+        let mesh = Mesh::new(vertices, indices.clone(), None);
+
+        assert_eq!(mesh.index_format(), IndexFormat::Uint32);
+        assert_eq!(mesh.indices_bytes().len(), 3 * 4);
+        assert_eq!(mesh.indices.to_u32(), indices);
+    }
+
+    #[test]
+    fn index_format_survives_serialization() {
+        let vertices = vec![Vertex::new(Vec3::ZERO, Vec3::Z, Vec2::ZERO, Vec4::X); 3];
+        let mesh = Mesh::new(vertices, vec![0, 1, 2], None);
+
+        let config = bincode::config::standard();
+        let encoded = bincode::encode_to_vec(&mesh, config).unwrap();
+        let (decoded, _): (Mesh, usize) = bincode::serde::decode_from_slice(&encoded, config).unwrap();
+
+        assert_eq!(decoded.index_format(), IndexFormat::Uint16);
+        assert_eq!(decoded.indices, mesh.indices);
+    }
+
+    #[test]
+    fn triangle_edges_close_each_triangle() {
+        let indices = MeshIndices::U16(vec![0, 1, 2, 2, 1, 3]);
+
+        assert_eq!(indices.triangle_edges(), [0, 1, 1, 2, 2, 0, 2, 1, 1, 3, 3, 2]);
+    }
+
+    #[test]
+    fn alpha_mode_survives_serialization() {
+        let material = MaterialBuilder::default()
+            .alpha_mode(AlphaMode::Mask)
+            .alpha_cutoff(0.3)
+            .build()
+            .unwrap();
+
+        let config = bincode::config::standard();
+        let encoded = bincode::encode_to_vec(&material, config).unwrap();
+        let (decoded, _): (Material, usize) = bincode::decode_from_slice(&encoded, config).unwrap();
+
+        assert_eq!(decoded.alpha_mode, AlphaMode::Mask);
+        assert_eq!(decoded.alpha_cutoff, 0.3);
+        assert_eq!(MaterialBuilder::default().build().unwrap().alpha_mode, AlphaMode::Opaque);
+    }
+
+    #[test]
+    fn compressed_row_pitch_counts_blocks() {
+        assert_eq!(TextureFormat::R8G8B8A8.bytes_per_row(6), 24);
+        assert_eq!(TextureFormat::Bc7RgbaUnorm.bytes_per_row(6), 2 * 16);
+        assert_eq!(TextureFormat::Bc5RgUnorm.rows_per_image(6), 2);
+    }
+
+    #[test]
+    fn color_space_picks_srgb_format() {
+        assert_eq!(TextureFormat::R8G8B8A8.to_wgpu_format(ColorSpace::Srgb), wgpu::TextureFormat::Rgba8UnormSrgb);
+        assert_eq!(TextureFormat::R8G8B8A8.to_wgpu_format(ColorSpace::Linear), wgpu::TextureFormat::Rgba8Unorm);
+        assert_eq!(TextureFormat::Bc7RgbaUnorm.to_wgpu_format(ColorSpace::Srgb), wgpu::TextureFormat::Bc7RgbaUnormSrgb);
+        assert_eq!(TextureFormat::Bc5RgUnorm.to_wgpu_format(ColorSpace::Srgb), wgpu::TextureFormat::Bc5RgUnorm);
+    }
+
+    #[test]
+    fn mip_levels_split_packed_pixels() {
+        let texture = TextureBuilder::default()
+            .width(8u32)
+            .height(8u32)
+            .format(TextureFormat::Bc7RgbaUnorm)
+            .pixels(vec![0u8; (4 + 1 + 1 + 1) * 16])
+            .mip_level_count(4u32)
+            .build()
+            .unwrap();
+
+        let levels: Vec<_> = texture.mip_levels().map(|(width, height, data)| (width, height, data.len())).collect();
+        assert_eq!(levels, [(8, 8, 4 * 16), (4, 4, 16), (2, 2, 16), (1, 1, 16)]);
+    }
+
+    #[test]
+    fn unaligned_texture_stays_uncompressed() {
+        let texture = TextureBuilder::default()
+            .width(3u32)
+            .height(3u32)
+            .format(TextureFormat::R8G8B8A8)
+            .pixels(vec![255u8; 3 * 3 * 4])
+            .compress(TextureFormat::Bc7RgbaUnorm)
+            .build()
+            .unwrap();
+
+        assert_eq!(texture.format, TextureFormat::R8G8B8A8);
+        assert_eq!(texture.pixels.len(), 3 * 3 * 4);
+    }
+
+    #[test]
+    fn generated_mips_cover_full_chain() {
+        let texture = TextureBuilder::default()
+            .width(4u32)
+            .height(2u32)
+            .format(TextureFormat::R8)
+            .pixels(vec![0u8, 255, 0, 255, 0, 255, 0, 255])
+            .generate_mips(true)
+            .build()
+            .unwrap();
+
+        assert_eq!(texture.mip_level_count, 3);
+        assert_eq!(texture.mip_levels().map(|(width, height, _)| (width, height)).collect::<Vec<_>>(), [(4, 2), (2, 1), (1, 1)]);
+        assert_eq!(&texture.pixels[8..], [128, 128, 128]);
+    }
+
+    #[test]
+    fn srgb_mips_are_filtered_in_linear_space() {
+        let texture = TextureBuilder::default()
+            .width(2u32)
+            .height(1u32)
+            .format(TextureFormat::R8G8B8A8)
+            .color_space(ColorSpace::Srgb)
+            .pixels(vec![0u8, 0, 0, 0, 255, 255, 255, 255])
+            .mip_levels(2)
+            .build()
+            .unwrap();
+
+        // half intensity in linear space is brighter than the naive average in sRGB space
+        assert_eq!(&texture.pixels[8..], [188, 188, 188, 128]);
+    }
+
+    #[test]
+    fn address_mode_defaults_to_repeat() {
+        let mut builder = TextureBuilder::default();
+        builder.width(1u32).height(1u32).format(TextureFormat::R8).pixels(vec![0u8]);
+        assert_eq!(builder.build().unwrap().address_mode, [AddressMode::Repeat; 3]);
+
+        let texture = builder
+            .address_mode(AddressMode::ClampToEdge, AddressMode::ClampToEdge, AddressMode::Repeat)
+            .build()
+            .unwrap();
+        let sampler = texture.sampler_descriptor(None);
+        assert_eq!(sampler.address_mode_u, wgpu::AddressMode::ClampToEdge);
+        assert_eq!(sampler.address_mode_w, wgpu::AddressMode::Repeat);
+    }
+
+    #[test]
+    fn packed_meshes_keep_local_indices() {
+        let vertex = Vertex::new(Vec3::ZERO, Vec3::Z, Vec2::ZERO, Vec4::X);
+        let first = Mesh::new(vec![vertex; 3], vec![0, 1, 2], None);
+        let second = Mesh::new(vec![vertex; 4], vec![0, 1, 2, 2, 3, 0], None);
+
+        let packed = PackedMeshes::pack([&first, &second]);
+
+        assert_eq!(packed.vertices.len(), 7);
+        assert_eq!(packed.index_format(), IndexFormat::Uint16);
+        assert_eq!(packed.indices.to_u32(), [0, 1, 2, 0, 1, 2, 2, 3, 0]);
+        assert_eq!(packed.ranges[1], PackedMeshRange {
+            base_vertex: 3,
+            first_index: 3,
+            index_count: 6,
+            vertex_count: 4,
+        });
+    }
+
+    #[test]
+    fn packed_meshes_widen_mixed_index_formats() {
+        let vertex = Vertex::new(Vec3::ZERO, Vec3::Z, Vec2::ZERO, Vec4::X);
+        let num_vertices = u16::MAX as usize + 2;
+        let small = Mesh::new(vec![vertex; 3], vec![0, 1, 2], None);
+        let large = Mesh::new(vec![vertex; num_vertices], vec![0, 1, num_vertices as u32 - 1], None);
+
+        let packed = PackedMeshes::pack([&small, &large]);
+
+        assert_eq!(packed.index_format(), IndexFormat::Uint32);
+        assert_eq!(packed.indices.to_u32(), [0, 1, 2, 0, 1, num_vertices as u32 - 1]);
+        assert_eq!(packed.ranges[1].base_vertex, 3);
+    }
+}
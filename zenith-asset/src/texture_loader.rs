@@ -0,0 +1,203 @@
+use anyhow::{anyhow, Result};
+use std::path::{Path, PathBuf};
+use zenith_core::file::load_with_memory_mapping;
+use zenith_core::log::{info, target::ASSET};
+use zenith_task::{submit, TaskResult};
+use crate::render::{ColorSpace, Texture, TextureBuilder, TextureFormat};
+use crate::{Asset, AssetRegistry, AssetUrl, RawResource, RawResourceBaker, RawResourceLoader, serialize_asset};
+
+/// Loader of pre-compressed `.ktx2` textures.
+#[derive(Debug, Clone)]
+pub struct Ktx2Loader;
+
+/// Loader of pre-compressed `.dds` textures.
+#[derive(Debug, Clone)]
+pub struct DdsLoader;
+
+/// A texture parsed from a container file, with all mip levels.
+pub struct RawTexture {
+    path: PathBuf,
+    texture: Texture,
+}
+
+impl RawResource for RawTexture {
+    fn load_path(&self) -> &Path {
+        self.path.as_path()
+    }
+}
+
+impl RawResourceLoader for Ktx2Loader {
+    type Raw = RawTexture;
+
+    fn load(path: &Path) -> Result<Self::Raw> {
+        let bytes = load_with_memory_mapping(path)?;
+        let reader = ktx2::Reader::new(&bytes[..]).map_err(|e| anyhow!("Invalid ktx2 file {:?}: {}", path, e))?;
+        let header = reader.header();
+
+        if header.supercompression_scheme.is_some() {
+            return Err(anyhow!("Supercompressed ktx2 file {:?} is NOT supported", path));
+        }
+        if header.face_count != 1 || header.layer_count > 1 || header.pixel_depth > 1 {
+            return Err(anyhow!("Only 2D ktx2 texture is supported: {:?}", path));
+        }
+
+        let (format, color_space) = header.format
+            .and_then(Self::texture_format)
+            .ok_or(anyhow!("Unsupported ktx2 format {:?} of {:?}", header.format, path))?;
+
+        let mut pixels = Vec::new();
+        for level in reader.levels() {
+            pixels.extend_from_slice(level.data);
+        }
+
+        let texture = TextureBuilder::default()
+            .width(header.pixel_width)
+            .height(header.pixel_height)
+            .format(format)
+            .color_space(color_space)
+            .pixels(pixels)
+            .mip_level_count(header.level_count.max(1))
+            .build()?;
+        validate_mip_levels(&texture, path)?;
+
+        Ok(RawTexture {
+            path: path.to_owned(),
+            texture,
+        })
+    }
+
+    fn load_async(raw_content_path: &Path) -> TaskResult<Result<Self::Raw>> {
+        let path = raw_content_path.to_owned();
+
+        submit(move || {
+            Self::load(&path)
+        })
+    }
+}
+
+impl Ktx2Loader {
+    fn texture_format(format: ktx2::Format) -> Option<(TextureFormat, ColorSpace)> {
+        match format {
+            ktx2::Format::R8_UNORM => Some((TextureFormat::R8, ColorSpace::Linear)),
+            ktx2::Format::R8G8_UNORM => Some((TextureFormat::R8G8, ColorSpace::Linear)),
+            ktx2::Format::R8G8B8A8_UNORM => Some((TextureFormat::R8G8B8A8, ColorSpace::Linear)),
+            ktx2::Format::R8G8B8A8_SRGB => Some((TextureFormat::R8G8B8A8, ColorSpace::Srgb)),
+            ktx2::Format::BC1_RGBA_UNORM_BLOCK => Some((TextureFormat::Bc1RgbaUnorm, ColorSpace::Linear)),
+            ktx2::Format::BC1_RGBA_SRGB_BLOCK => Some((TextureFormat::Bc1RgbaUnorm, ColorSpace::Srgb)),
+            ktx2::Format::BC3_UNORM_BLOCK => Some((TextureFormat::Bc3RgbaUnorm, ColorSpace::Linear)),
+            ktx2::Format::BC3_SRGB_BLOCK => Some((TextureFormat::Bc3RgbaUnorm, ColorSpace::Srgb)),
+            ktx2::Format::BC5_UNORM_BLOCK => Some((TextureFormat::Bc5RgUnorm, ColorSpace::Linear)),
+            ktx2::Format::BC7_UNORM_BLOCK => Some((TextureFormat::Bc7RgbaUnorm, ColorSpace::Linear)),
+            ktx2::Format::BC7_SRGB_BLOCK => Some((TextureFormat::Bc7RgbaUnorm, ColorSpace::Srgb)),
+            _ => None,
+        }
+    }
+}
+
+impl RawResourceLoader for DdsLoader {
+    type Raw = RawTexture;
+
+    fn load(path: &Path) -> Result<Self::Raw> {
+        let bytes = load_with_memory_mapping(path)?;
+        let dds = ddsfile::Dds::read(&bytes[..]).map_err(|e| anyhow!("Invalid dds file {:?}: {}", path, e))?;
+
+        if dds.get_num_array_layers() > 1 || dds.get_depth() > 1 {
+            return Err(anyhow!("Only 2D dds texture is supported: {:?}", path));
+        }
+
+        let (format, color_space) = Self::texture_format(&dds)
+            .ok_or(anyhow!("Unsupported dds format of {:?}", path))?;
+        let (width, height) = (dds.get_width(), dds.get_height());
+        let mip_level_count = dds.get_num_mipmap_levels().max(1);
+
+        let texture = TextureBuilder::default()
+            .width(width)
+            .height(height)
+            .format(format)
+            .color_space(color_space)
+            .pixels(dds.data)
+            .mip_level_count(mip_level_count)
+            .build()?;
+        validate_mip_levels(&texture, path)?;
+
+        Ok(RawTexture {
+            path: path.to_owned(),
+            texture,
+        })
+    }
+
+    fn load_async(raw_content_path: &Path) -> TaskResult<Result<Self::Raw>> {
+        let path = raw_content_path.to_owned();
+
+        submit(move || {
+            Self::load(&path)
+        })
+    }
+}
+
+impl DdsLoader {
+    fn texture_format(dds: &ddsfile::Dds) -> Option<(TextureFormat, ColorSpace)> {
+        use ddsfile::{D3DFormat, DxgiFormat};
+
+        if let Some(format) = dds.get_dxgi_format() {
+            return match format {
+                DxgiFormat::R8_UNorm => Some((TextureFormat::R8, ColorSpace::Linear)),
+                DxgiFormat::R8G8_UNorm => Some((TextureFormat::R8G8, ColorSpace::Linear)),
+                DxgiFormat::R8G8B8A8_UNorm => Some((TextureFormat::R8G8B8A8, ColorSpace::Linear)),
+                DxgiFormat::R8G8B8A8_UNorm_sRGB => Some((TextureFormat::R8G8B8A8, ColorSpace::Srgb)),
+                DxgiFormat::BC1_UNorm => Some((TextureFormat::Bc1RgbaUnorm, ColorSpace::Linear)),
+                DxgiFormat::BC1_UNorm_sRGB => Some((TextureFormat::Bc1RgbaUnorm, ColorSpace::Srgb)),
+                DxgiFormat::BC3_UNorm => Some((TextureFormat::Bc3RgbaUnorm, ColorSpace::Linear)),
+                DxgiFormat::BC3_UNorm_sRGB => Some((TextureFormat::Bc3RgbaUnorm, ColorSpace::Srgb)),
+                DxgiFormat::BC5_UNorm => Some((TextureFormat::Bc5RgUnorm, ColorSpace::Linear)),
+                DxgiFormat::BC7_UNorm => Some((TextureFormat::Bc7RgbaUnorm, ColorSpace::Linear)),
+                DxgiFormat::BC7_UNorm_sRGB => Some((TextureFormat::Bc7RgbaUnorm, ColorSpace::Srgb)),
+                _ => None,
+            };
+        }
+
+        // legacy dds without DX10 header carries no color space, treat color blocks as sRGB
+        match dds.get_d3d_format()? {
+            D3DFormat::A8B8G8R8 => Some((TextureFormat::R8G8B8A8, ColorSpace::Srgb)),
+            D3DFormat::DXT1 => Some((TextureFormat::Bc1RgbaUnorm, ColorSpace::Srgb)),
+            D3DFormat::DXT5 => Some((TextureFormat::Bc3RgbaUnorm, ColorSpace::Srgb)),
+            _ => None,
+        }
+    }
+}
+
+/// Make sure the container holds enough data for all mip levels it claims.
+fn validate_mip_levels(texture: &Texture, path: &Path) -> Result<()> {
+    let expected_size: usize = (0..texture.mip_level_count).map(|level| texture.mip_level_size(level)).sum();
+    if texture.pixels.len() < expected_size {
+        return Err(anyhow!("Texture {:?} has {} bytes, but {} bytes are expected for {} mip levels",
+            path, texture.pixels.len(), expected_size, texture.mip_level_count));
+    }
+    Ok(())
+}
+
+pub struct RawTextureProcessor;
+
+impl RawResourceBaker for RawTextureProcessor {
+    type Raw = RawTexture;
+
+    fn bake(raw: Self::Raw, registry: &'static AssetRegistry, base_directory: &PathBuf, url: &AssetUrl) -> Result<()> {
+        let RawTexture { texture, .. } = raw;
+
+        let asset_url = Self::asset_url(url);
+        serialize_asset(&texture, &base_directory.join(&asset_url))?;
+
+        info!(target: ASSET, "[{:?}] is loaded and serialized: {}x{} {:?}, {} mips.",
+            asset_url, texture.width, texture.height, texture.format, texture.mip_level_count);
+
+        registry.register(asset_url, texture);
+        Ok(())
+    }
+
+    /// Return the url of the baked texture, e.g. "texture/brick.ktx2" -> "texture/brick.tex".
+    fn asset_url(raw_url: &AssetUrl) -> AssetUrl {
+        let mut path = raw_url.path.clone();
+        path.set_extension(Texture::extension());
+        path.into()
+    }
+}
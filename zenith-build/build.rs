@@ -6,6 +6,13 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         .workspace_root("shader")
         .add_entry_point("shader/triangle.wgsl")
         .add_entry_point("shader/mesh.wgsl")
+        .add_entry_point("shader/mesh_bindless.wgsl")
+        .add_entry_point("shader/instanced_mesh.wgsl")
+        .add_entry_point("shader/instance_cull.wgsl")
+        .add_entry_point("shader/skybox.wgsl")
+        .add_entry_point("shader/shadow.wgsl")
+        .add_entry_point("shader/blit.wgsl")
+        .add_entry_point("shader/text.wgsl")
         .serialization_strategy(WgslTypeSerializeStrategy::Bytemuck)
         .type_map(GlamWgslTypeMap)
         .shader_source_type(WgslShaderSourceType::ComposerWithRelativePath)
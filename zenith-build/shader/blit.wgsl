@@ -0,0 +1,30 @@
+@group(0) @binding(0)
+var source_texture: texture_2d<f32>;
+
+@group(0) @binding(1)
+var source_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) tex_coord: vec2<f32>,
+}
+
+// Draw a single triangle covering the whole screen, no vertex buffer needed.
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    var output: VertexOutput;
+
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+
+    output.position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    // texture space is y-down
+    output.tex_coord = vec2<f32>(uv.x, 1.0 - uv.y);
+
+    return output;
+}
+
+// Passthrough, copy the source color as is.
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(source_texture, source_sampler, input.tex_coord);
+}
@@ -0,0 +1,72 @@
+// Frustum culling of instances, one invocation per instance.
+// Must match zenith_renderer::InstanceCuller and zenith_renderer::cull_instances.
+
+struct CullUniforms {
+    // normal in xyz and distance to origin in w, normals point inside, see zenith_core::math::Frustum
+    planes: array<vec4<f32>, 6>,
+    num_instances: u32,
+}
+
+// Same layout as instanced_mesh.wgsl, only the model matrix is read.
+struct InstanceData {
+    model: mat4x4<f32>,
+    color: vec4<f32>,
+}
+
+// Bounds of the mesh of a batch in mesh space, w is unused.
+struct BatchBounds {
+    center: vec4<f32>,
+    extents: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> cull: CullUniforms;
+
+@group(0) @binding(1)
+var<storage, read> instances: array<InstanceData>;
+
+// index of the batch of each instance
+@group(0) @binding(2)
+var<storage, read> instance_batches: array<u32>;
+
+@group(0) @binding(3)
+var<storage, read> batch_bounds: array<BatchBounds>;
+
+// DrawIndexedIndirectArgs of each batch, 5 words each: index count, instance count, first index, base vertex, first instance.
+// The instance count must be cleared before the dispatch.
+@group(0) @binding(4)
+var<storage, read_write> draw_args: array<atomic<u32>>;
+
+// indices of the visible instances, packed from the first instance of their batch
+@group(0) @binding(5)
+var<storage, read_write> visible: array<u32>;
+
+const DRAW_ARGS_WORDS: u32 = 5u;
+
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    if (index >= cull.num_instances) {
+        return;
+    }
+
+    let batch = instance_batches[index];
+    let bounds = batch_bounds[batch];
+    let model = instances[index].model;
+
+    // world space box of the transformed mesh box, same as Aabb::transformed
+    let center = (model * vec4<f32>(bounds.center.xyz, 1.0)).xyz;
+    let local_extents = bounds.extents.xyz;
+    let extents = abs(model[0].xyz) * local_extents.x + abs(model[1].xyz) * local_extents.y + abs(model[2].xyz) * local_extents.z;
+
+    for (var plane_index = 0u; plane_index < 6u; plane_index++) {
+        let plane = cull.planes[plane_index];
+        if (dot(plane.xyz, center) + plane.w + dot(abs(plane.xyz), extents) < 0.0) {
+            return;
+        }
+    }
+
+    let slot = atomicAdd(&draw_args[batch * DRAW_ARGS_WORDS + 1u], 1u);
+    let first_instance = atomicLoad(&draw_args[batch * DRAW_ARGS_WORDS + 4u]);
+    visible[first_instance + slot] = index;
+}
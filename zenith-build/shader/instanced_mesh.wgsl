@@ -0,0 +1,77 @@
+// Per-frame data shared by all renderers, must match zenith_renderer::FrameUniforms
+struct FrameUniforms {
+    view: mat4x4<f32>,
+    projection: mat4x4<f32>,
+    view_projection: mat4x4<f32>,
+    camera_position: vec3<f32>,
+    // seconds the engine has run, stops while paused
+    time_seconds: f32,
+    // time of the last tick in seconds
+    delta_seconds: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> frame: FrameUniforms;
+
+struct InstanceData {
+    model: mat4x4<f32>,
+    // base color factor of the material, alpha is ignored
+    color: vec4<f32>,
+}
+
+struct LightUniforms {
+    direction: vec3<f32>,
+    intensity: f32,
+    color: vec3<f32>,
+    // added to the diffuse term, so faces turned away from the light stay visible
+    ambient: f32,
+}
+
+// instances of all meshes, grouped by mesh
+@group(1) @binding(0)
+var<storage, read> instances: array<InstanceData>;
+
+@group(1) @binding(1)
+var<uniform> light: LightUniforms;
+
+// indices of the visible instances, each draw covers the range of its mesh, culled on the CPU or by instance_cull.wgsl
+@group(1) @binding(2)
+var<storage, read> visible: array<u32>;
+
+// Keep the same layout as mesh.wgsl, so the mesh vertex buffer can be reused.
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) tex_coord: vec2<f32>,
+    @location(3) tangent: vec4<f32>,
+    @location(4) tex_coord1: vec2<f32>,
+    // RGBA8 packed, R in the lowest byte
+    @location(5) color: u32,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) world_normal: vec3<f32>,
+    @location(1) color: vec3<f32>,
+}
+
+@vertex
+fn vs_main(input: VertexInput, @builtin(instance_index) instance_index: u32) -> VertexOutput {
+    let instance = instances[visible[instance_index]];
+
+    var output: VertexOutput;
+    output.position = frame.view_projection * instance.model * vec4<f32>(input.position, 1.0);
+    // exact for uniform scale only, normals are renormalized per fragment
+    output.world_normal = (instance.model * vec4<f32>(input.normal, 0.0)).xyz;
+    output.color = instance.color.rgb * unpack4x8unorm(input.color).rgb;
+    return output;
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    let normal = normalize(input.world_normal);
+    let to_light = normalize(-light.direction);
+    let diffuse = max(dot(normal, to_light), 0.0) * light.intensity * light.color;
+
+    return vec4<f32>(input.color * (diffuse / 3.14159265 + light.ambient), 1.0);
+}
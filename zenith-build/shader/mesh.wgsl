@@ -1,34 +1,84 @@
-struct ViewUniforms {
-    view_proj: mat4x4<f32>,
+// Per-frame data shared by all renderers, must match zenith_renderer::FrameUniforms
+struct FrameUniforms {
+    view: mat4x4<f32>,
+    projection: mat4x4<f32>,
+    view_projection: mat4x4<f32>,
+    camera_position: vec3<f32>,
+    // seconds the engine has run, stops while paused
+    time_seconds: f32,
+    // time of the last tick in seconds
+    delta_seconds: f32,
 }
 
+@group(0) @binding(0)
+var<uniform> frame: FrameUniforms;
+
 struct ModelUniforms {
     model: mat4x4<f32>,
     base_color: vec3<f32>,
+    metallic: f32,
+    roughness: f32,
+    occlusion_strength: f32,
+    // added to the lit color, multiplied with emissive_texture
+    emissive: vec3<f32>,
+    // fragments with alpha below it are discarded, 0 unless the material is alpha masked
+    alpha_cutoff: f32,
+    // alpha of the material base color
+    opacity: f32,
+    // 1 if the material is alpha blended, otherwise the output is opaque
+    alpha_blend: u32,
 }
 
-@group(0) @binding(0)
-var<uniform> view: ViewUniforms;
+struct DirectionalLightUniforms {
+    view_proj: mat4x4<f32>,
+    direction: vec3<f32>,
+    intensity: f32,
+    color: vec3<f32>,
+}
 
-@group(0) @binding(1)
+@group(1) @binding(0)
 var<uniform> model: ModelUniforms;
 
-@group(0) @binding(2)
+@group(1) @binding(1)
 var base_color_texture: texture_2d<f32>;
 
-@group(0) @binding(3)
+@group(1) @binding(2)
 var base_color_sampler: sampler;
 
+@group(1) @binding(3)
+var<uniform> light: DirectionalLightUniforms;
+
+@group(1) @binding(4)
+var shadow_map: texture_depth_2d;
+
+@group(1) @binding(5)
+var shadow_sampler: sampler_comparison;
+
+// ambient occlusion in the R channel, shares base_color_sampler
+@group(1) @binding(6)
+var occlusion_texture: texture_2d<f32>;
+
+// shares base_color_sampler
+@group(1) @binding(7)
+var emissive_texture: texture_2d<f32>;
+
 struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) normal: vec3<f32>,
     @location(2) tex_coord: vec2<f32>,
+    @location(3) tangent: vec4<f32>,
+    @location(4) tex_coord1: vec2<f32>,
+    // RGBA8 packed, R in the lowest byte
+    @location(5) color: u32,
 }
 
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
     @location(0) world_normal: vec3<f32>,
     @location(1) tex_coord: vec2<f32>,
+    @location(2) world_tangent: vec4<f32>,
+    @location(3) world_position: vec3<f32>,
+    @location(4) color: vec4<f32>,
 }
 
 @vertex
@@ -36,22 +86,121 @@ fn vs_main(input: VertexInput) -> VertexOutput {
     var output: VertexOutput;
 
     let world_pos = model.model * vec4<f32>(input.position, 1.0);
-    output.position = view.view_proj * world_pos;
+    output.position = frame.view_projection * world_pos;
+    output.world_position = world_pos.xyz;
 
     output.world_normal = (model.model * vec4<f32>(input.normal, 0.0)).xyz;
     output.tex_coord = input.tex_coord;
+    output.world_tangent = vec4<f32>((model.model * vec4<f32>(input.tangent.xyz, 0.0)).xyz, input.tangent.w);
+    output.color = unpack4x8unorm(input.color);
 
     return output;
 }
 
-@fragment
-fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+const AMBIENT_INTENSITY: f32 = 0.1;
+const DIELECTRIC_F0: vec3<f32> = vec3<f32>(0.04, 0.04, 0.04);
+const SHADOW_DEPTH_BIAS: f32 = 0.002;
+
+// 3x3 PCF, every tap is bilinear filtered by the comparison sampler in hardware.
+fn sample_shadow(world_position: vec3<f32>) -> f32 {
+    let light_clip = light.view_proj * vec4<f32>(world_position, 1.0);
+    let light_ndc = light_clip.xyz / light_clip.w;
+    let uv = light_ndc.xy * vec2<f32>(0.5, -0.5) + vec2<f32>(0.5);
+
+    // outside of the shadow map is always lit
+    if (any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) || light_ndc.z > 1.0) {
+        return 1.0;
+    }
+
+    let texel_size = 1.0 / vec2<f32>(textureDimensions(shadow_map));
+    var visibility = 0.0;
+    for (var x = -1; x <= 1; x++) {
+        for (var y = -1; y <= 1; y++) {
+            let offset = vec2<f32>(f32(x), f32(y)) * texel_size;
+            visibility += textureSampleCompareLevel(shadow_map, shadow_sampler, uv + offset, light_ndc.z - SHADOW_DEPTH_BIAS);
+        }
+    }
+
+    return visibility / 9.0;
+}
+
+fn shade(input: VertexOutput) -> vec3<f32> {
     let normal = normalize(input.world_normal);
-    let light_intensity = max(dot(normal, vec3<f32>(0.0, 0.0, 1.0)), 0.3);
+    let to_light = normalize(-light.direction);
+    let to_camera = normalize(frame.camera_position - input.world_position);
+    let half_vector = normalize(to_light + to_camera);
 
     let texture_color = textureSample(base_color_texture, base_color_sampler, input.tex_coord);
-    let base_color = model.base_color * texture_color.rgb;
-    let final_color = base_color * light_intensity;
-    
-    return vec4<f32>(final_color, 1.0);
-} 
\ No newline at end of file
+    let base_color = model.base_color * texture_color.rgb * input.color.rgb;
+
+    let metallic = clamp(model.metallic, 0.0, 1.0);
+    let roughness = clamp(model.roughness, 0.04, 1.0);
+
+    let n_dot_l = max(dot(normal, to_light), 0.0);
+    let n_dot_h = max(dot(normal, half_vector), 0.0);
+    let v_dot_h = max(dot(to_camera, half_vector), 0.0);
+
+    // Lambert diffuse, metals have no diffuse reflection
+    let diffuse = base_color * (1.0 - metallic);
+
+    // Blinn-Phong lobe with shininess derived from roughness, schlick fresnel
+    let shininess = 2.0 / (roughness * roughness * roughness * roughness) - 2.0;
+    let f0 = mix(DIELECTRIC_F0, base_color, metallic);
+    let fresnel = f0 + (vec3<f32>(1.0) - f0) * pow(1.0 - v_dot_h, 5.0);
+    let specular = fresnel * pow(n_dot_h, shininess) * (shininess + 8.0) / 8.0;
+
+    let radiance = light.color * light.intensity;
+    let shadow = sample_shadow(input.world_position);
+    let occlusion = mix(1.0, textureSample(occlusion_texture, base_color_sampler, input.tex_coord).r, model.occlusion_strength);
+    let ambient = base_color * AMBIENT_INTENSITY * occlusion;
+    let lit_color = ambient + (diffuse + specular) * radiance * n_dot_l * shadow;
+
+    return lit_color + emissive_color(input);
+}
+
+// Discard masked out fragments, return the output alpha.
+fn surface_alpha(input: VertexOutput) -> f32 {
+    let alpha = model.opacity * textureSample(base_color_texture, base_color_sampler, input.tex_coord).a * input.color.a;
+    if (alpha < model.alpha_cutoff) {
+        discard;
+    }
+
+    return select(1.0, alpha, model.alpha_blend != 0u);
+}
+
+fn emissive_color(input: VertexOutput) -> vec3<f32> {
+    return model.emissive * textureSample(emissive_texture, base_color_sampler, input.tex_coord).rgb;
+}
+
+// Back faces are only drawn for double-sided materials, light them as seen from their side.
+fn facing_camera(input: VertexOutput, front_facing: bool) -> VertexOutput {
+    var output = input;
+    if (!front_facing) {
+        output.world_normal = -output.world_normal;
+    }
+    return output;
+}
+
+@fragment
+fn fs_main(vertex: VertexOutput, @builtin(front_facing) front_facing: bool) -> @location(0) vec4<f32> {
+    let input = facing_camera(vertex, front_facing);
+    let alpha = surface_alpha(input);
+    return vec4<f32>(shade(input), alpha);
+}
+
+struct EmissiveOutput {
+    @location(0) color: vec4<f32>,
+    // emissive only, e.g. the input of a bloom pass
+    @location(1) emissive: vec4<f32>,
+}
+
+@fragment
+fn fs_main_emissive(vertex: VertexOutput, @builtin(front_facing) front_facing: bool) -> EmissiveOutput {
+    let input = facing_camera(vertex, front_facing);
+    let alpha = surface_alpha(input);
+
+    var output: EmissiveOutput;
+    output.color = vec4<f32>(shade(input), alpha);
+    output.emissive = vec4<f32>(emissive_color(input), 1.0);
+    return output;
+}
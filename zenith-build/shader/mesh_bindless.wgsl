@@ -0,0 +1,164 @@
+// Variant of mesh.wgsl drawing every mesh of a model with one bind group.
+// Materials are read from a storage buffer, their textures from a binding array.
+// Lighting must be kept in sync with mesh.wgsl.
+
+// Per-frame data shared by all renderers, must match zenith_renderer::FrameUniforms
+struct FrameUniforms {
+    view: mat4x4<f32>,
+    projection: mat4x4<f32>,
+    view_projection: mat4x4<f32>,
+    camera_position: vec3<f32>,
+    // seconds the engine has run, stops while paused
+    time_seconds: f32,
+    // time of the last tick in seconds
+    delta_seconds: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> frame: FrameUniforms;
+
+// Per-draw data, bound with a dynamic offset
+struct DrawUniforms {
+    model: mat4x4<f32>,
+    material_index: u32,
+}
+
+struct MaterialData {
+    base_color: vec3<f32>,
+    metallic: f32,
+    roughness: f32,
+    occlusion_strength: f32,
+    // indices into material_textures, 0 is the default white texture
+    base_color_texture: u32,
+    occlusion_texture: u32,
+}
+
+struct DirectionalLightUniforms {
+    view_proj: mat4x4<f32>,
+    direction: vec3<f32>,
+    intensity: f32,
+    color: vec3<f32>,
+}
+
+@group(1) @binding(0)
+var<uniform> draw: DrawUniforms;
+
+@group(1) @binding(1)
+var<storage, read> materials: array<MaterialData>;
+
+// must match zenith_renderer::MAX_MATERIAL_TEXTURES
+@group(1) @binding(2)
+var material_textures: binding_array<texture_2d<f32>, 64>;
+
+@group(1) @binding(3)
+var material_sampler: sampler;
+
+@group(1) @binding(4)
+var<uniform> light: DirectionalLightUniforms;
+
+@group(1) @binding(5)
+var shadow_map: texture_depth_2d;
+
+@group(1) @binding(6)
+var shadow_sampler: sampler_comparison;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) tex_coord: vec2<f32>,
+    @location(3) tangent: vec4<f32>,
+    @location(4) tex_coord1: vec2<f32>,
+    // RGBA8 packed, R in the lowest byte
+    @location(5) color: u32,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) world_normal: vec3<f32>,
+    @location(1) tex_coord: vec2<f32>,
+    @location(2) world_tangent: vec4<f32>,
+    @location(3) world_position: vec3<f32>,
+    @location(4) color: vec4<f32>,
+}
+
+@vertex
+fn vs_main(input: VertexInput) -> VertexOutput {
+    var output: VertexOutput;
+
+    let world_pos = draw.model * vec4<f32>(input.position, 1.0);
+    output.position = frame.view_projection * world_pos;
+    output.world_position = world_pos.xyz;
+
+    output.world_normal = (draw.model * vec4<f32>(input.normal, 0.0)).xyz;
+    output.tex_coord = input.tex_coord;
+    output.world_tangent = vec4<f32>((draw.model * vec4<f32>(input.tangent.xyz, 0.0)).xyz, input.tangent.w);
+    output.color = unpack4x8unorm(input.color);
+
+    return output;
+}
+
+const AMBIENT_INTENSITY: f32 = 0.1;
+const DIELECTRIC_F0: vec3<f32> = vec3<f32>(0.04, 0.04, 0.04);
+const SHADOW_DEPTH_BIAS: f32 = 0.002;
+
+// 3x3 PCF, every tap is bilinear filtered by the comparison sampler in hardware.
+fn sample_shadow(world_position: vec3<f32>) -> f32 {
+    let light_clip = light.view_proj * vec4<f32>(world_position, 1.0);
+    let light_ndc = light_clip.xyz / light_clip.w;
+    let uv = light_ndc.xy * vec2<f32>(0.5, -0.5) + vec2<f32>(0.5);
+
+    // outside of the shadow map is always lit
+    if (any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) || light_ndc.z > 1.0) {
+        return 1.0;
+    }
+
+    let texel_size = 1.0 / vec2<f32>(textureDimensions(shadow_map));
+    var visibility = 0.0;
+    for (var x = -1; x <= 1; x++) {
+        for (var y = -1; y <= 1; y++) {
+            let offset = vec2<f32>(f32(x), f32(y)) * texel_size;
+            visibility += textureSampleCompareLevel(shadow_map, shadow_sampler, uv + offset, light_ndc.z - SHADOW_DEPTH_BIAS);
+        }
+    }
+
+    return visibility / 9.0;
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    // the material index is the same for the whole draw, no non-uniform indexing is needed
+    let material = materials[draw.material_index];
+
+    let normal = normalize(input.world_normal);
+    let to_light = normalize(-light.direction);
+    let to_camera = normalize(frame.camera_position - input.world_position);
+    let half_vector = normalize(to_light + to_camera);
+
+    let texture_color = textureSample(material_textures[material.base_color_texture], material_sampler, input.tex_coord);
+    let base_color = material.base_color * texture_color.rgb * input.color.rgb;
+
+    let metallic = clamp(material.metallic, 0.0, 1.0);
+    let roughness = clamp(material.roughness, 0.04, 1.0);
+
+    let n_dot_l = max(dot(normal, to_light), 0.0);
+    let n_dot_h = max(dot(normal, half_vector), 0.0);
+    let v_dot_h = max(dot(to_camera, half_vector), 0.0);
+
+    // Lambert diffuse, metals have no diffuse reflection
+    let diffuse = base_color * (1.0 - metallic);
+
+    // Blinn-Phong lobe with shininess derived from roughness, schlick fresnel
+    let shininess = 2.0 / (roughness * roughness * roughness * roughness) - 2.0;
+    let f0 = mix(DIELECTRIC_F0, base_color, metallic);
+    let fresnel = f0 + (vec3<f32>(1.0) - f0) * pow(1.0 - v_dot_h, 5.0);
+    let specular = fresnel * pow(n_dot_h, shininess) * (shininess + 8.0) / 8.0;
+
+    let radiance = light.color * light.intensity;
+    let shadow = sample_shadow(input.world_position);
+    let occlusion_sample = textureSample(material_textures[material.occlusion_texture], material_sampler, input.tex_coord).r;
+    let occlusion = mix(1.0, occlusion_sample, material.occlusion_strength);
+    let ambient = base_color * AMBIENT_INTENSITY * occlusion;
+    let final_color = ambient + (diffuse + specular) * radiance * n_dot_l * shadow;
+
+    return vec4<f32>(final_color, 1.0);
+}
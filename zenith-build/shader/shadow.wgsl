@@ -0,0 +1,24 @@
+struct ShadowUniforms {
+    light_view_proj: mat4x4<f32>,
+    model: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> uniforms: ShadowUniforms;
+
+// Keep the same layout as mesh.wgsl, so the mesh vertex buffer can be reused.
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) tex_coord: vec2<f32>,
+    @location(3) tangent: vec4<f32>,
+    @location(4) tex_coord1: vec2<f32>,
+    // RGBA8 packed, R in the lowest byte
+    @location(5) color: u32,
+}
+
+// Depth-only pass, no fragment stage is needed.
+@vertex
+fn vs_main(input: VertexInput) -> @builtin(position) vec4<f32> {
+    return uniforms.light_view_proj * uniforms.model * vec4<f32>(input.position, 1.0);
+}
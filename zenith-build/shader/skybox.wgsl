@@ -0,0 +1,45 @@
+struct SkyboxUniforms {
+    inv_view_proj: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> uniforms: SkyboxUniforms;
+
+@group(0) @binding(1)
+var environment_texture: texture_cube<f32>;
+
+@group(0) @binding(2)
+var environment_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) clip_position: vec2<f32>,
+}
+
+// Draw a single triangle covering the whole screen, no vertex buffer needed.
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    var output: VertexOutput;
+
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    let clip_position = uv * 2.0 - 1.0;
+
+    // reversed-z, place the skybox at the far plane so that any geometry draws over it
+    output.position = vec4<f32>(clip_position, 0.0, 1.0);
+    output.clip_position = clip_position;
+
+    return output;
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    // unproject a point on the near plane (z = 1 in reversed-z) to get the view direction
+    let world_position = uniforms.inv_view_proj * vec4<f32>(input.clip_position, 1.0, 1.0);
+    let view_direction = normalize(world_position.xyz / world_position.w);
+
+    // cubemap faces are authored y-up, convert from zenith world space (z-up)
+    let cube_direction = vec3<f32>(view_direction.x, view_direction.z, -view_direction.y);
+
+    let color = textureSample(environment_texture, environment_sampler, cube_direction);
+    return vec4<f32>(color.rgb, 1.0);
+}
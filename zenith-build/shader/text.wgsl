@@ -0,0 +1,45 @@
+struct TextUniforms {
+    // render target size in pixels
+    screen_size: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> uniforms: TextUniforms;
+
+@group(0) @binding(1)
+var glyph_atlas: texture_2d<f32>;
+
+@group(0) @binding(2)
+var glyph_sampler: sampler;
+
+struct VertexInput {
+    // screen space position in pixels, origin at top-left
+    @location(0) position: vec2<f32>,
+    @location(1) tex_coord: vec2<f32>,
+    @location(2) color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) tex_coord: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+@vertex
+fn vs_main(input: VertexInput) -> VertexOutput {
+    var output: VertexOutput;
+
+    let ndc = input.position / uniforms.screen_size * 2.0 - 1.0;
+    output.position = vec4<f32>(ndc.x, -ndc.y, 0.0, 1.0);
+    output.tex_coord = input.tex_coord;
+    output.color = input.color;
+
+    return output;
+}
+
+// The atlas only stores glyph coverage in the red channel.
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    let coverage = textureSample(glyph_atlas, glyph_sampler, input.tex_coord).r;
+    return vec4<f32>(input.color.rgb, input.color.a * coverage);
+}
@@ -1,10 +1,29 @@
+// Per-frame data shared by all renderers, must match zenith_renderer::FrameUniforms
+struct FrameUniforms {
+    view: mat4x4<f32>,
+    projection: mat4x4<f32>,
+    view_projection: mat4x4<f32>,
+    camera_position: vec3<f32>,
+    // seconds the engine has run, stops while paused
+    time_seconds: f32,
+    // time of the last tick in seconds
+    delta_seconds: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> frame: FrameUniforms;
+
+// placement of each triangle in clip space, selected by dynamic offset
 struct Uniforms {
     transform: mat4x4<f32>,
 }
 
-@group(0) @binding(0)
+@group(1) @binding(0)
 var<uniform> uniforms: Uniforms;
 
+// quarter turn per second
+const ROTATION_SPEED: f32 = 1.5707964;
+
 struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) color: vec3<f32>,
@@ -18,7 +37,12 @@ struct VertexOutput {
 @vertex
 fn vs_main(input: VertexInput) -> VertexOutput {
     var output: VertexOutput;
-    output.position = uniforms.transform * vec4<f32>(input.position, 1.0);
+    let angle = frame.time_seconds * ROTATION_SPEED;
+    let rotated = vec2<f32>(
+        cos(angle) * input.position.x - sin(angle) * input.position.y,
+        sin(angle) * input.position.x + cos(angle) * input.position.y,
+    );
+    output.position = uniforms.transform * vec4<f32>(rotated, input.position.z, 1.0);
     output.color = input.color;
     return output;
 }
@@ -10,9 +10,10 @@
 
 use glam::{EulerRot, Mat4, Quat, Vec3};
 use log::{warn};
+use serde::{Deserialize, Serialize};
 use winit::event::{DeviceEvent, ElementState, MouseButton, WindowEvent};
 use winit::window::{CursorGrabMode, Window};
-use crate::math::{Degree, Radians};
+use crate::math::{Degree, Frustum, Radians};
 
 pub const NEAR_PLANE: f32 = 0.1;
 pub const WORLD_SPACE_UP: Vec3 = Vec3::new(0., 0., 1.);
@@ -26,6 +27,13 @@ pub struct Camera {
     rotation: Quat,
     pitch: Radians,
     yaw: Radians,
+    /// Angles the smoothed pitch and yaw are approaching, yaw is kept in (-π, π].
+    target_pitch: Radians,
+    target_yaw: Radians,
+
+    fov_y: Radians,
+    aspect_ratio: f32,
+    z_near: f32,
 
     forward: Vec3,
     right: Vec3,
@@ -34,6 +42,18 @@ pub struct Camera {
     proj: Mat4,
 }
 
+/// Serializable viewpoint of a [`Camera`], e.g. for camera bookmarks or resuming a session.
+/// The cached basis and matrices are NOT saved, they are rebuilt by [`Camera::from_saved`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SavedCamera {
+    pub position: [f32; 3],
+    pub pitch: Radians,
+    pub yaw: Radians,
+    pub fov_y: Radians,
+    pub aspect_ratio: f32,
+    pub z_near: f32,
+}
+
 impl Default for Camera {
     fn default() -> Self {
         let mut cam = Self {
@@ -41,14 +61,21 @@ impl Default for Camera {
             rotation: Quat::IDENTITY,
             pitch: Default::default(),
             yaw: Default::default(),
+            target_pitch: Default::default(),
+            target_yaw: Default::default(),
+
+            fov_y: std::f32::consts::FRAC_PI_6.into(),
+            aspect_ratio: 1.77777,
+            z_near: NEAR_PLANE,
 
             forward: WORLD_SPACE_FORWARD,
             right: WORLD_SPACE_RIGHT,
             up: WORLD_SPACE_UP,
 
             view: Default::default(),
-            proj: Mat4::perspective_infinite_reverse_rh(std::f32::consts::FRAC_PI_6, 1.77777, NEAR_PLANE),
+            proj: Default::default(),
         };
+        cam.update_projection();
         cam.update_view();
         cam
     }
@@ -57,13 +84,48 @@ impl Default for Camera {
 impl Camera {
     pub fn new(fov_y: Radians, aspect_ratio: f32, z_near: f32) -> Self {
         let mut cam = Self {
-            proj: Mat4::perspective_infinite_reverse_rh(fov_y.into(), aspect_ratio, z_near.max(0.0001)),
+            fov_y,
+            aspect_ratio,
+            z_near: z_near.max(0.0001),
             ..Default::default()
         };
+        cam.update_projection();
         cam.update_view();
         cam
     }
 
+    /// Restore a camera saved by [`Camera::to_saved`], the smoothed rotation starts settled at the saved angles.
+    pub fn from_saved(saved: &SavedCamera) -> Self {
+        let mut cam = Self {
+            position: Vec3::from_array(saved.position),
+            rotation: Self::rotation_from_angles(saved.yaw, saved.pitch),
+            pitch: saved.pitch,
+            yaw: saved.yaw,
+            target_pitch: saved.pitch,
+            target_yaw: saved.yaw,
+
+            fov_y: saved.fov_y,
+            aspect_ratio: saved.aspect_ratio,
+            z_near: saved.z_near.max(0.0001),
+            ..Default::default()
+        };
+        cam.update_local_basis();
+        cam.update_projection();
+        cam.update_view();
+        cam
+    }
+
+    pub fn to_saved(&self) -> SavedCamera {
+        SavedCamera {
+            position: self.position.to_array(),
+            pitch: self.pitch,
+            yaw: self.yaw,
+            fov_y: self.fov_y,
+            aspect_ratio: self.aspect_ratio,
+            z_near: self.z_near,
+        }
+    }
+
     /// Return the location of camera.
     #[inline]
     pub fn location(&self) -> Vec3 {
@@ -86,6 +148,12 @@ impl Camera {
         self.proj * self.view
     }
 
+    /// Return the volume seen by this camera, to cull what is outside of it.
+    #[inline]
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_view_projection(self.view_projection())
+    }
+
     /// Return the forward vector of this camera.
     #[inline]
     pub fn forward(&self) -> Vec3 {
@@ -112,12 +180,24 @@ impl Camera {
         self.position += r * delta_position.x + f * delta_position.y + u * delta_position.z;
     }
 
-    fn rotate(&mut self, delta_yaw: Radians, delta_pitch: Radians, max_pitch: Radians) {
-        self.yaw += delta_yaw;
-        self.pitch += delta_pitch;
-        self.pitch = self.pitch.clamp(-max_pitch, max_pitch);
+    /// Move the target angles by the deltas, then blend the current angles towards them by `blend_factor`.
+    fn rotate(&mut self, delta_yaw: Radians, delta_pitch: Radians, blend_factor: f32, max_pitch: Radians) {
+        self.target_yaw = (self.target_yaw + delta_yaw).normalize();
+        self.target_pitch = (self.target_pitch + delta_pitch).clamp(-max_pitch, max_pitch);
+
+        // take the shortest way, otherwise crossing ±π spins the camera the long way around
+        self.yaw = self.yaw.lerp_angle(self.target_yaw, blend_factor).normalize();
+        self.pitch = self.pitch.lerp(self.target_pitch, blend_factor);
+        self.rotation = Self::rotation_from_angles(self.yaw, self.pitch);
+    }
+
+    fn rotation_from_angles(yaw: Radians, pitch: Radians) -> Quat {
         // eliminate roll and avoid gimbal lock
-        self.rotation = Quat::from_euler(EulerRot::ZXY, self.yaw.into(), self.pitch.into(), 0.);
+        Quat::from_euler(EulerRot::ZXY, yaw.into(), pitch.into(), 0.)
+    }
+
+    fn update_projection(&mut self) {
+        self.proj = Mat4::perspective_infinite_reverse_rh(self.fov_y.into(), self.aspect_ratio, self.z_near);
     }
 
     fn update_view(&mut self) {
@@ -132,16 +212,42 @@ impl Camera {
     }
 }
 
+pub const MIN_MOUSE_SENSITIVITY: f32 = 0.01;
+pub const MAX_MOUSE_SENSITIVITY: f32 = 100.;
+/// Smoothing of 1 would never move the camera.
+pub const MAX_SMOOTHING_FACTOR: f32 = 0.99;
+/// Looking straight up or down flips the view.
+pub const MAX_PITCH_DEGREE: f32 = 89.99;
+
+/// Tunables of a [`CameraController`], e.g. loaded from the settings of a game.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CameraControllerConfig {
+    pub move_speed: f32,
+    pub mouse_sensitivity: f32,
+    /// The higher the value, the higher the lagging. Zero results in abrupt changes.
+    pub smoothing_factor: f32,
+    pub max_pitch: Radians,
+}
+
+impl Default for CameraControllerConfig {
+    fn default() -> Self {
+        Self {
+            move_speed: 70.,
+            mouse_sensitivity: 1.,
+            smoothing_factor: 0.5,
+            max_pitch: Degree::from(MAX_PITCH_DEGREE).into(),
+        }
+    }
+}
+
 /// Controller to modify specific camera data.
 pub struct CameraController {
-    accum_local_pitch: Radians,
     max_pitch_angle: Radians,
-    accum_local_yaw: Radians,
 
     move_speed: f32,
     mouse_sensitivity: f32,
     /// The higher the value, the higher the lagging. Zero results in abrupt changes.
-    rotation_smoothing_factor: f32,
+    smoothing_factor: f32,
 
     accum_dx: f32,
     accum_dy: f32,
@@ -150,45 +256,86 @@ pub struct CameraController {
 
 impl Default for CameraController {
     fn default() -> Self {
-        Self {
-            accum_local_pitch: Default::default(),
-            max_pitch_angle: Degree::from(89.99).into(),
-            accum_local_yaw: Default::default(),
-
-            move_speed: 70.,
-            mouse_sensitivity: 1.,
-            rotation_smoothing_factor: 0.5,
-
-            accum_dx: 0.0,
-            accum_dy: 0.0,
-            is_grabbed: false,
-        }
+        Self::from_config(CameraControllerConfig::default())
     }
 }
 
 impl CameraController {
     pub fn new(mouse_sensitivity: f32) -> Self {
-        Self {
+        Self::from_config(CameraControllerConfig {
             mouse_sensitivity,
             ..Default::default()
+        })
+    }
+
+    /// Create a controller from the config, out of range values are clamped like the setters do.
+    pub fn from_config(config: CameraControllerConfig) -> Self {
+        let mut controller = Self {
+            max_pitch_angle: Default::default(),
+
+            move_speed: 0.,
+            mouse_sensitivity: 0.,
+            smoothing_factor: 0.,
+
+            accum_dx: 0.0,
+            accum_dy: 0.0,
+            is_grabbed: false,
+        };
+        controller.apply_config(config);
+        controller
+    }
+
+    /// Change all tunables at once, accumulated mouse motion and cursor grab are kept.
+    pub fn apply_config(&mut self, config: CameraControllerConfig) {
+        self.set_move_speed(config.move_speed);
+        self.set_mouse_sensitivity(config.mouse_sensitivity);
+        self.set_smoothing_factor(config.smoothing_factor);
+        self.set_max_pitch(config.max_pitch);
+    }
+
+    /// Return the current tunables, e.g. to save them in settings.
+    pub fn config(&self) -> CameraControllerConfig {
+        CameraControllerConfig {
+            move_speed: self.move_speed,
+            mouse_sensitivity: self.mouse_sensitivity,
+            smoothing_factor: self.smoothing_factor,
+            max_pitch: self.max_pitch_angle,
         }
     }
 
-    /// The higher the value, the smoother the rotation.
-    pub fn set_rotation_smoothing_factor(&mut self, rotation_smoothing_factor: f32) {
-        self.rotation_smoothing_factor = rotation_smoothing_factor;
+    /// The higher the value, the smoother the rotation. Clamped to [0, [`MAX_SMOOTHING_FACTOR`]].
+    pub fn set_smoothing_factor(&mut self, smoothing_factor: f32) {
+        self.smoothing_factor = smoothing_factor.clamp(0., MAX_SMOOTHING_FACTOR);
     }
 
-    /// Determine how fast camera location changes.
+    #[inline]
+    pub fn smoothing_factor(&self) -> f32 { self.smoothing_factor }
+
+    /// Determine how fast camera location changes, negative speed is treated as zero.
     pub fn set_move_speed(&mut self, move_speed: f32) {
-        self.move_speed = move_speed;
+        self.move_speed = move_speed.max(0.);
     }
 
+    #[inline]
+    pub fn move_speed(&self) -> f32 { self.move_speed }
+
     /// Determine how fast camera rotation changes.
+    /// Clamped to [[`MIN_MOUSE_SENSITIVITY`], [`MAX_MOUSE_SENSITIVITY`]].
     pub fn set_mouse_sensitivity(&mut self, mouse_sensitivity: f32) {
-        self.mouse_sensitivity = mouse_sensitivity;
+        self.mouse_sensitivity = mouse_sensitivity.clamp(MIN_MOUSE_SENSITIVITY, MAX_MOUSE_SENSITIVITY);
     }
 
+    #[inline]
+    pub fn mouse_sensitivity(&self) -> f32 { self.mouse_sensitivity }
+
+    /// Limit how far the camera looks up or down. Clamped to [0, [`MAX_PITCH_DEGREE`]°].
+    pub fn set_max_pitch(&mut self, max_pitch: Radians) {
+        self.max_pitch_angle = max_pitch.clamp(Radians::from(0.), Degree::from(MAX_PITCH_DEGREE).into());
+    }
+
+    #[inline]
+    pub fn max_pitch(&self) -> Radians { self.max_pitch_angle }
+
     /// Receive and process window events.
     pub fn on_window_event(&mut self, event: &WindowEvent, window: &Window) {
         match event {
@@ -233,19 +380,10 @@ impl CameraController {
                               up_axis_speed: f32,
                               to_update_cameras: impl IntoIterator<Item = &'a mut Camera>,
     ) {
-        let d_local_yaw = Radians::from(-self.accum_dx * self.mouse_sensitivity * delta_time);
-        let d_local_pitch = Radians::from(-self.accum_dy * self.mouse_sensitivity * delta_time);
-
-        let blend_factor = 1.0 - self.rotation_smoothing_factor.powf(delta_time * 60.0);
-
-        self.accum_local_yaw += d_local_yaw;
-        self.accum_local_pitch += d_local_pitch;
+        let delta_yaw = Radians::from(-self.accum_dx * self.mouse_sensitivity * delta_time);
+        let delta_pitch = Radians::from(-self.accum_dy * self.mouse_sensitivity * delta_time);
 
-        let delta_yaw = self.accum_local_yaw * blend_factor;
-        let delta_pitch = self.accum_local_pitch * blend_factor;
-
-        self.accum_local_yaw -= delta_yaw;
-        self.accum_local_pitch -= delta_pitch;
+        let blend_factor = 1.0 - self.smoothing_factor.powf(delta_time * 60.0);
 
         let axis_dir = Vec3::new(
             right_axis_speed,
@@ -255,7 +393,7 @@ impl CameraController {
         let delta_pos = axis_dir * self.move_speed * delta_time;
 
         for camera in to_update_cameras {
-            camera.rotate(delta_yaw, delta_pitch, self.max_pitch_angle);
+            camera.rotate(delta_yaw, delta_pitch, blend_factor, self.max_pitch_angle);
             camera.translate(delta_pos);
             camera.update_local_basis();
             camera.update_view();
@@ -286,3 +424,41 @@ impl CameraController {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_camera_round_trip() {
+        let mut camera = Camera::new(Degree::from(60.).into(), 16. / 9., 0.5);
+        let mut controller = CameraController::default();
+        controller.accum_dx = 30.;
+        controller.accum_dy = -10.;
+        controller.update_cameras(1. / 60., 1., 0.5, 0.2, [&mut camera]);
+
+        let config = bincode::config::standard();
+        let bytes = bincode::serde::encode_to_vec(camera.to_saved(), config).unwrap();
+        let (saved, _): (SavedCamera, _) = bincode::serde::decode_from_slice(&bytes, config).unwrap();
+        let restored = Camera::from_saved(&saved);
+
+        assert_eq!(saved, camera.to_saved());
+        assert!(restored.view_projection().abs_diff_eq(camera.view_projection(), 1e-5));
+    }
+
+    #[test]
+    fn controller_config_is_clamped() {
+        let controller = CameraController::from_config(CameraControllerConfig {
+            move_speed: -1.,
+            mouse_sensitivity: 1000.,
+            smoothing_factor: 1.5,
+            max_pitch: Degree::from(120.).into(),
+        });
+
+        assert_eq!(controller.move_speed(), 0.);
+        assert_eq!(controller.mouse_sensitivity(), MAX_MOUSE_SENSITIVITY);
+        assert_eq!(controller.smoothing_factor(), MAX_SMOOTHING_FACTOR);
+        assert_eq!(controller.max_pitch(), Radians::from(Degree::from(MAX_PITCH_DEGREE)));
+        assert_eq!(CameraController::from_config(controller.config()).config(), controller.config());
+    }
+}
@@ -0,0 +1,41 @@
+use anyhow::anyhow;
+use log::warn;
+
+/// Access to the system clipboard text.
+///
+/// Keep it alive as long as copied text should stay available: on X11 and Wayland the clipboard content is
+/// served by the process which set it. Without a clipboard (e.g. headless or CI) reads return None and writes fail.
+pub struct Clipboard {
+    inner: Option<arboard::Clipboard>,
+}
+
+impl Default for Clipboard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clipboard {
+    pub fn new() -> Self {
+        let inner = arboard::Clipboard::new()
+            .inspect_err(|err| warn!("Clipboard is NOT available: {}", err))
+            .ok();
+
+        Self { inner }
+    }
+
+    #[inline]
+    pub fn is_available(&self) -> bool { self.inner.is_some() }
+
+    /// Return the text in the clipboard, None if it is empty, NOT text or the clipboard is unavailable.
+    pub fn get_text(&mut self) -> Option<String> {
+        self.inner.as_mut()?.get_text().ok()
+    }
+
+    /// Replace the clipboard content with `text`.
+    pub fn set_text(&mut self, text: impl Into<String>) -> Result<(), anyhow::Error> {
+        let clipboard = self.inner.as_mut().ok_or(anyhow!("Clipboard is NOT available"))?;
+        clipboard.set_text(text.into())?;
+        Ok(())
+    }
+}
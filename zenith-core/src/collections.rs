@@ -1,25 +1,71 @@
-﻿use std::hash::Hasher;
-use foldhash::fast::FoldHasher;
+﻿//! Collection aliases shared across the engine.
+//!
+//! Every [`hashmap::HashMap`] and [`hashset::HashSet`] hashes through [`DefaultHashBuilder`].
+//! By default each map gets a random seed, so iteration order changes from run to run.
+//! Enabling the `deterministic-hash` feature of `zenith-core` seeds every map with
+//! [`DETERMINISTIC_SEED`] instead, which makes iteration order reproducible for:
+//!
+//! - the asset registry and the in-flight load / raw loader maps of the asset manager,
+//! - the input state key sets and action/axis mappings,
+//! - the pipeline cache and the task executor bookkeeping maps.
+//!
+//! Render graph resources are stored in vectors indexed by their handles and are
+//! deterministic regardless of this feature.
+//!
+//! Where the order itself is part of the contract (e.g. bind group entries, which must
+//! line up with the shader layout), use [`orderedmap::OrderedMap`] or [`orderedset::OrderedSet`]
+//! instead: they iterate in key order no matter how the engine is built.
+
+use std::hash::{BuildHasher, Hasher};
+use foldhash::fast::{FixedState, FoldHasher, RandomState};
 use foldhash::SharedSeed;
 
 pub type SmallVec<A> = smallvec::SmallVec<A>;
 
+/// Seed used by [`DefaultHashBuilder`] when the `deterministic-hash` feature is enabled.
+pub const DETERMINISTIC_SEED: u64 = 0x5a5e_417c_9e37_79b9;
+
 pub struct DefaultHasher(FoldHasher);
 
+/// Builds [`DefaultHasher`]s for the engine hash maps and sets.
+#[derive(Clone)]
+pub struct DefaultHashBuilder(HashBuilderState);
+
+#[derive(Clone)]
+enum HashBuilderState {
+    Random(RandomState),
+    Fixed(FixedState),
+}
+
 pub mod hashmap {
-    pub type HashMap<K, V> = hashbrown::HashMap<K, V>;
+    pub type HashMap<K, V> = hashbrown::HashMap<K, V, super::DefaultHashBuilder>;
     pub use hashbrown::hash_map::*;
 }
 
 pub mod hashset {
-    pub type HashSet<T> = hashbrown::HashSet<T>;
+    pub type HashSet<T> = hashbrown::HashSet<T, super::DefaultHashBuilder>;
     pub use hashbrown::hash_set::*;
 }
 
+pub mod orderedmap {
+    pub type OrderedMap<K, V> = std::collections::BTreeMap<K, V>;
+    pub use std::collections::btree_map::*;
+}
+
+pub mod orderedset {
+    pub type OrderedSet<T> = std::collections::BTreeSet<T>;
+    pub use std::collections::btree_set::*;
+}
+
 impl DefaultHasher {
     pub fn new() -> Self {
         Self(FoldHasher::with_seed(0, SharedSeed::global_random()))
     }
+
+    /// Create a hasher which produces the same hashes in every run for the same `seed`.
+    pub fn with_fixed_seed(seed: u64) -> Self {
+        Self(FoldHasher::with_seed(seed, SharedSeed::global_fixed()))
+    }
 }
 
 impl Hasher for DefaultHasher {
@@ -32,4 +78,38 @@ impl Hasher for DefaultHasher {
     fn write(&mut self, bytes: &[u8]) {
         self.0.write(bytes);
     }
-}
\ No newline at end of file
+}
+
+impl DefaultHashBuilder {
+    /// Builder with a per-instance random seed.
+    pub fn random() -> Self {
+        Self(HashBuilderState::Random(RandomState::default()))
+    }
+
+    /// Builder whose hashers produce the same hashes in every run for the same `seed`.
+    pub fn with_fixed_seed(seed: u64) -> Self {
+        Self(HashBuilderState::Fixed(FixedState::with_seed(seed)))
+    }
+}
+
+impl Default for DefaultHashBuilder {
+    fn default() -> Self {
+        if cfg!(feature = "deterministic-hash") {
+            Self::with_fixed_seed(DETERMINISTIC_SEED)
+        } else {
+            Self::random()
+        }
+    }
+}
+
+impl BuildHasher for DefaultHashBuilder {
+    type Hasher = DefaultHasher;
+
+    #[inline(always)]
+    fn build_hasher(&self) -> DefaultHasher {
+        match &self.0 {
+            HashBuilderState::Random(state) => DefaultHasher(state.build_hasher()),
+            HashBuilderState::Fixed(state) => DefaultHasher(state.build_hasher()),
+        }
+    }
+}
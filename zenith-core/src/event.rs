@@ -0,0 +1,232 @@
+//! Input events normalized from the windowing system, and [`Signal`] to notify owners of state changes.
+//!
+//! A [`Signal`] calls its slots synchronously on the emitting thread, unlike [`crate::event_bus::EventBus`]
+//! which queues events for subscribers on any thread.
+
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use glam::Vec2;
+use winit::event::{DeviceEvent, ElementState, MouseScrollDelta, WindowEvent};
+use winit::keyboard::PhysicalKey;
+use crate::input::ModifiersState;
+
+pub use winit::event::MouseButton;
+pub use winit::keyboard::KeyCode;
+
+/// Pixels scrolled by one line of a mouse wheel, used to normalize touchpad scrolling.
+pub const PIXELS_PER_SCROLL_LINE: f32 = 20.;
+
+/// Input event normalized from the windowing system events.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InputEvent {
+    /// A key is pressed. `repeat` is set for OS repeat events of a key already down.
+    KeyPressed { key: KeyCode, repeat: bool },
+    /// A key is released.
+    KeyReleased { key: KeyCode },
+    /// The cursor moved to `position`, in physical pixels relative to the top-left corner of the window.
+    MouseMoved { position: Vec2 },
+    /// Raw mouse movement not bound to the window, e.g. for camera look while the cursor is grabbed.
+    MouseMotion { delta: Vec2 },
+    /// A mouse button is pressed or released.
+    MouseButton { button: MouseButton, pressed: bool },
+    /// The wheel or touchpad scrolled by `delta` lines.
+    Scroll { delta: Vec2 },
+    /// Modifier keys changed.
+    ModifiersChanged(ModifiersState),
+    /// The window gained or lost focus.
+    Focused(bool),
+    /// The window is resized, in physical pixels.
+    Resized { width: u32, height: u32 },
+    /// Ratio of physical to logical pixels of the window changed, e.g. moved to another monitor.
+    ScaleFactorChanged { scale_factor: f32 },
+    /// A file is dropped on the window. Several files dropped at once come as one event each.
+    FileDropped(PathBuf),
+    /// A file is dragged over the window, useful for drop feedback.
+    FileHovered(PathBuf),
+    /// Files dragged over the window left it or the drag was cancelled.
+    FileHoverCancelled,
+}
+
+/// Collect the system events of a frame as [`InputEvent`]s.
+/// Events having nothing to do with input are skipped.
+#[derive(Default)]
+pub struct SystemEventCollector {
+    events: VecDeque<InputEvent>,
+}
+
+impl SystemEventCollector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Translate and enqueue a window event.
+    pub fn collect_window_event(&mut self, event: &WindowEvent) {
+        let event = match event {
+            WindowEvent::KeyboardInput { event, .. } => {
+                let PhysicalKey::Code(key) = event.physical_key else {
+                    return;
+                };
+                match event.state {
+                    ElementState::Pressed => InputEvent::KeyPressed { key, repeat: event.repeat },
+                    ElementState::Released => InputEvent::KeyReleased { key },
+                }
+            }
+            WindowEvent::CursorMoved { position, .. } => InputEvent::MouseMoved {
+                position: Vec2::new(position.x as f32, position.y as f32),
+            },
+            WindowEvent::MouseInput { button, state, .. } => InputEvent::MouseButton {
+                button: *button,
+                pressed: state.is_pressed(),
+            },
+            WindowEvent::MouseWheel { delta, .. } => InputEvent::Scroll {
+                delta: match delta {
+                    MouseScrollDelta::LineDelta(x, y) => Vec2::new(*x, *y),
+                    MouseScrollDelta::PixelDelta(position) => Vec2::new(position.x as f32, position.y as f32) / PIXELS_PER_SCROLL_LINE,
+                },
+            },
+            WindowEvent::ModifiersChanged(modifiers) => InputEvent::ModifiersChanged(ModifiersState {
+                shift: modifiers.state().shift_key(),
+                ctrl: modifiers.state().control_key(),
+                alt: modifiers.state().alt_key(),
+                super_key: modifiers.state().super_key(),
+            }),
+            WindowEvent::Focused(focused) => InputEvent::Focused(*focused),
+            WindowEvent::Resized(size) => InputEvent::Resized {
+                width: size.width,
+                height: size.height,
+            },
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => InputEvent::ScaleFactorChanged {
+                scale_factor: *scale_factor as f32,
+            },
+            WindowEvent::DroppedFile(path) => InputEvent::FileDropped(path.clone()),
+            WindowEvent::HoveredFile(path) => InputEvent::FileHovered(path.clone()),
+            WindowEvent::HoveredFileCancelled => InputEvent::FileHoverCancelled,
+            _ => return,
+        };
+        self.events.push_back(event);
+    }
+
+    /// Translate and enqueue a device event.
+    pub fn collect_device_event(&mut self, event: &DeviceEvent) {
+        if let DeviceEvent::MouseMotion { delta } = event {
+            self.events.push_back(InputEvent::MouseMotion {
+                delta: Vec2::new(delta.0 as f32, delta.1 as f32),
+            });
+        }
+    }
+
+    /// Enqueue an event not coming from the system, e.g. the initial window state which has no event.
+    pub fn push(&mut self, event: InputEvent) {
+        self.events.push_back(event);
+    }
+
+    /// Take all collected events in the order they were received.
+    pub fn drain(&mut self) -> impl Iterator<Item = InputEvent> + '_ {
+        self.events.drain(..)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+/// Handle of a slot connected to a [`Signal`], used to disconnect it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SlotId(u64);
+
+type Slot<T> = Box<dyn FnMut(&T) + Send + Sync>;
+
+/// List of callbacks (slots) called in connection order with every value emitted.
+/// Slots can NOT connect or emit on the signal calling them, as it is borrowed mutably while emitting.
+pub struct Signal<T> {
+    slots: Vec<(SlotId, Slot<T>)>,
+    next_id: u64,
+}
+
+impl<T> Default for Signal<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Signal<T> {
+    pub fn new() -> Self {
+        Self {
+            slots: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    /// Call `slot` with every value emitted from now on, until disconnected.
+    pub fn connect(&mut self, slot: impl FnMut(&T) + Send + Sync + 'static) -> SlotId {
+        let id = SlotId(self.next_id);
+        self.next_id += 1;
+        self.slots.push((id, Box::new(slot)));
+        id
+    }
+
+    /// Return false if the slot was already disconnected.
+    pub fn disconnect(&mut self, id: SlotId) -> bool {
+        let len = self.slots.len();
+        self.slots.retain(|(slot_id, _)| *slot_id != id);
+        self.slots.len() != len
+    }
+
+    pub fn emit(&mut self, value: &T) {
+        for (_, slot) in &mut self.slots {
+            slot(value);
+        }
+    }
+
+    #[inline]
+    pub fn num_slots(&self) -> usize { self.slots.len() }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use winit::dpi::PhysicalSize;
+    use super::*;
+
+    #[test]
+    fn events_are_translated_in_order() {
+        let mut collector = SystemEventCollector::new();
+        collector.collect_window_event(&WindowEvent::Resized(PhysicalSize::new(800, 600)));
+        collector.collect_window_event(&WindowEvent::RedrawRequested);
+        collector.collect_device_event(&DeviceEvent::MouseMotion { delta: (1., -2.) });
+        collector.collect_window_event(&WindowEvent::Focused(false));
+
+        assert_eq!(
+            collector.drain().collect::<Vec<_>>(),
+            [
+                InputEvent::Resized { width: 800, height: 600 },
+                InputEvent::MouseMotion { delta: Vec2::new(1., -2.) },
+                InputEvent::Focused(false),
+            ]
+        );
+        assert!(collector.is_empty());
+    }
+
+    #[test]
+    fn signal_calls_connected_slots_in_order() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let mut signal = Signal::<u32>::new();
+
+        let first = {
+            let received = received.clone();
+            signal.connect(move |value| received.lock().unwrap().push(("first", *value)))
+        };
+        {
+            let received = received.clone();
+            signal.connect(move |value| received.lock().unwrap().push(("second", *value)));
+        }
+
+        signal.emit(&1);
+        assert!(signal.disconnect(first));
+        assert!(!signal.disconnect(first));
+        signal.emit(&2);
+
+        assert_eq!(signal.num_slots(), 1);
+        assert_eq!(*received.lock().unwrap(), [("first", 1), ("second", 1), ("second", 2)]);
+    }
+}
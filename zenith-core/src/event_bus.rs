@@ -0,0 +1,123 @@
+//! Typed publish/subscribe between subsystems, e.g. the asset loader notifies renderers of loaded assets.
+//!
+//! Every subscriber owns an unbounded channel receiving the events published after it subscribed,
+//! in the order they were published by each thread. Dropping the receiver unsubscribes it on the next publish.
+
+use std::any::{Any, TypeId};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use crossbeam_channel::Sender;
+use crate::collections::hashmap::HashMap;
+
+pub use crossbeam_channel::{Receiver, TryRecvError};
+
+type Subscribers<E> = Mutex<Vec<Sender<E>>>;
+
+#[derive(Default)]
+pub struct EventBus {
+    /// `Subscribers<E>` keyed by the type id of `E`.
+    subscribers: RwLock<HashMap<TypeId, Arc<dyn Any + Send + Sync>>>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Receive every event of type `E` published from now on.
+    pub fn subscribe<E: Clone + Send + 'static>(&self) -> Receiver<E> {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        self.subscribers_of::<E>(true).unwrap().lock().unwrap().push(sender);
+        receiver
+    }
+
+    /// Send `event` to all subscribers of `E`, cloned for all but the last one. Nothing is allocated without subscribers.
+    pub fn publish<E: Clone + Send + 'static>(&self, event: E) {
+        let Some(subscribers) = self.subscribers_of::<E>(false) else {
+            return;
+        };
+        let mut subscribers = subscribers.lock().unwrap();
+        let Some(last_index) = subscribers.len().checked_sub(1) else {
+            return;
+        };
+
+        let mut event = Some(event);
+        let mut index = 0;
+        subscribers.retain(|sender| {
+            let event = if index == last_index { event.take().unwrap() } else { event.clone().unwrap() };
+            index += 1;
+            // a send only fails if the receiver is dropped
+            sender.send(event).is_ok()
+        });
+    }
+
+    /// Return the number of subscribers of `E`. Dropped receivers are counted until the next publish.
+    pub fn num_subscribers<E: Clone + Send + 'static>(&self) -> usize {
+        self.subscribers_of::<E>(false)
+            .map(|subscribers| subscribers.lock().unwrap().len())
+            .unwrap_or(0)
+    }
+
+    fn subscribers_of<E: Send + 'static>(&self, create: bool) -> Option<Arc<Subscribers<E>>> {
+        let type_id = TypeId::of::<E>();
+        let existing = self.subscribers.read().unwrap().get(&type_id).cloned();
+
+        let subscribers = match existing {
+            Some(subscribers) => subscribers,
+            None if create => self.subscribers
+                .write()
+                .unwrap()
+                .entry(type_id)
+                .or_insert_with(|| Arc::new(Subscribers::<E>::default()))
+                .clone(),
+            None => return None,
+        };
+        Some(subscribers.downcast::<Subscribers<E>>().unwrap())
+    }
+}
+
+/// Bus shared by the whole engine.
+pub fn global() -> &'static EventBus {
+    static GLOBAL: OnceLock<EventBus> = OnceLock::new();
+    GLOBAL.get_or_init(EventBus::new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_subscriber_receives_events_in_order() {
+        let bus = EventBus::new();
+        bus.publish(0u32);
+
+        let first = bus.subscribe::<u32>();
+        let second = bus.subscribe::<u32>();
+        let other_type = bus.subscribe::<String>();
+
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                for event in 1..=3u32 {
+                    bus.publish(event);
+                }
+            });
+        });
+
+        assert_eq!(first.try_iter().collect::<Vec<_>>(), [1, 2, 3]);
+        assert_eq!(second.try_iter().collect::<Vec<_>>(), [1, 2, 3]);
+        assert_eq!(other_type.try_recv(), Err(TryRecvError::Empty));
+    }
+
+    #[test]
+    fn dropped_receiver_unsubscribes() {
+        let bus = EventBus::new();
+        let kept = bus.subscribe::<u32>();
+        drop(bus.subscribe::<u32>());
+
+        assert_eq!(bus.num_subscribers::<u32>(), 2);
+
+        bus.publish(7u32);
+
+        assert_eq!(bus.num_subscribers::<u32>(), 1);
+        assert_eq!(kept.recv().unwrap(), 7);
+    }
+}
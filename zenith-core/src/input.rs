@@ -1,14 +1,16 @@
-﻿use glam::FloatExt;
+﻿use glam::{FloatExt, Vec2};
 use winit::event::{ElementState, MouseButton, WindowEvent};
 use winit::keyboard::{KeyCode, PhysicalKey};
 use crate::collections::hashmap::HashMap;
 use crate::collections::hashset::HashSet;
 use crate::collections::SmallVec;
+use crate::event::InputEvent;
 
 /// Key state.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum KeyState {
-    /// KeyCode was just pressed this frame
+    /// KeyCode was just pressed this frame.
+    /// OS repeat events of a key already down never count as a fresh press.
     JustPressed,
     /// KeyCode is being held down
     Held,
@@ -32,11 +34,18 @@ pub struct InputManager {
     mouse_just_released: HashSet<MouseButton>,
     prev_mouse_pressed: HashSet<MouseButton>,
 
+    /// Cursor position in physical pixels, clamped to the window.
+    cursor_position: Option<Vec2>,
+    window_size: Option<Vec2>,
+    scale_factor: f32,
+    /// Cursor position when each held mouse button was pressed.
+    drag_origins: HashMap<MouseButton, Vec2>,
+
     modifiers: ModifiersState,
 }
 
 /// Modifier of this frame.
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub struct ModifiersState {
     pub shift: bool,
     pub ctrl: bool,
@@ -47,16 +56,21 @@ pub struct ModifiersState {
 impl InputManager {
     pub fn new() -> Self {
         Self {
-            keys_pressed: HashSet::new(),
-            keys_just_pressed: HashSet::new(),
-            keys_just_released: HashSet::new(),
-            keys_with_repeat: HashSet::new(),
-            prev_keys_pressed: HashSet::new(),
-
-            mouse_pressed: HashSet::new(),
-            mouse_just_pressed: HashSet::new(),
-            mouse_just_released: HashSet::new(),
-            prev_mouse_pressed: HashSet::new(),
+            keys_pressed: HashSet::default(),
+            keys_just_pressed: HashSet::default(),
+            keys_just_released: HashSet::default(),
+            keys_with_repeat: HashSet::default(),
+            prev_keys_pressed: HashSet::default(),
+
+            mouse_pressed: HashSet::default(),
+            mouse_just_pressed: HashSet::default(),
+            mouse_just_released: HashSet::default(),
+            prev_mouse_pressed: HashSet::default(),
+
+            cursor_position: None,
+            window_size: None,
+            scale_factor: 1.,
+            drag_origins: HashMap::default(),
 
             modifiers: ModifiersState::default(),
         }
@@ -67,33 +81,20 @@ impl InputManager {
         match event {
             WindowEvent::KeyboardInput { event, .. } => {
                 if let PhysicalKey::Code(keycode) = event.physical_key {
-                    match event.state {
-                        ElementState::Pressed => {
-                            if !event.repeat {
-                                // only register as pressed if it's not a repeat event
-                                self.keys_pressed.insert(keycode);
-                                self.keys_with_repeat.remove(&keycode);
-                            } else {
-                                // mark this key as having repeat events
-                                self.keys_with_repeat.insert(keycode);
-                            }
-                        }
-                        ElementState::Released => {
-                            self.keys_pressed.remove(&keycode);
-                            self.keys_with_repeat.remove(&keycode);
-                        }
-                    }
+                    self.on_key_event(keycode, event.state, event.repeat);
                 }
             }
             WindowEvent::MouseInput { button, state, .. } => {
-                match state {
-                    ElementState::Pressed => {
-                        self.mouse_pressed.insert(*button);
-                    }
-                    ElementState::Released => {
-                        self.mouse_pressed.remove(button);
-                    }
-                }
+                self.on_mouse_event(*button, *state);
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                self.on_cursor_moved(Vec2::new(position.x as f32, position.y as f32));
+            }
+            WindowEvent::Resized(size) => {
+                self.window_size = Some(Vec2::new(size.width as f32, size.height as f32));
+            }
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                self.scale_factor = *scale_factor as f32;
             }
             WindowEvent::ModifiersChanged(modifiers) => {
                 self.modifiers = ModifiersState {
@@ -111,6 +112,72 @@ impl InputManager {
         }
     }
 
+    /// Receive and process input events, see [`crate::event::SystemEventCollector`].
+    pub fn on_input_event(&mut self, event: &InputEvent) {
+        match *event {
+            InputEvent::KeyPressed { key, repeat } => self.on_key_event(key, ElementState::Pressed, repeat),
+            InputEvent::KeyReleased { key } => self.on_key_event(key, ElementState::Released, false),
+            InputEvent::MouseMoved { position } => self.on_cursor_moved(position),
+            InputEvent::MouseButton { button, pressed } => {
+                let state = if pressed { ElementState::Pressed } else { ElementState::Released };
+                self.on_mouse_event(button, state);
+            }
+            InputEvent::ModifiersChanged(modifiers) => self.modifiers = modifiers,
+            InputEvent::Focused(false) => self.clear(),
+            InputEvent::Resized { width, height } => self.window_size = Some(Vec2::new(width as f32, height as f32)),
+            InputEvent::ScaleFactorChanged { scale_factor } => self.scale_factor = scale_factor,
+            _ => {}
+        }
+    }
+
+    fn on_key_event(&mut self, keycode: KeyCode, state: ElementState, repeat: bool) {
+        match state {
+            ElementState::Pressed => {
+                if !repeat {
+                    // only register as pressed if it's not a repeat event
+                    self.keys_pressed.insert(keycode);
+                    self.keys_with_repeat.remove(&keycode);
+                } else {
+                    // mark this key as having repeat events
+                    self.keys_with_repeat.insert(keycode);
+                }
+            }
+            ElementState::Released => {
+                self.keys_pressed.remove(&keycode);
+                self.keys_with_repeat.remove(&keycode);
+            }
+        }
+    }
+
+    fn on_mouse_event(&mut self, button: MouseButton, state: ElementState) {
+        match state {
+            ElementState::Pressed => {
+                self.mouse_pressed.insert(button);
+                if let Some(position) = self.cursor_position {
+                    self.drag_origins.insert(button, position);
+                }
+            }
+            ElementState::Released => {
+                self.mouse_pressed.remove(&button);
+                self.drag_origins.remove(&button);
+            }
+        }
+    }
+
+    fn on_cursor_moved(&mut self, position: Vec2) {
+        // some platforms keep reporting the cursor outside of the window while a button is held
+        let position = match self.window_size {
+            Some(size) => position.clamp(Vec2::ZERO, size),
+            None => position,
+        };
+        self.cursor_position = Some(position);
+
+        // a button pressed before the cursor ever entered the window starts dragging from here
+        for button in &self.mouse_pressed {
+            self.drag_origins.entry(*button).or_insert(position);
+        }
+    }
+
     /// Update input states.
     pub fn tick(&mut self) {
         self.keys_just_pressed.clear();
@@ -164,7 +231,8 @@ impl InputManager {
         self.keys_pressed.contains(&key)
     }
 
-    /// Return true if a key is just pressed (i.e. key turns from unpress to press in this frame)
+    /// Return true if a key is just pressed (i.e. key turns from unpress to press in this frame).
+    /// OS repeat events never make a key just pressed, see [`InputManager::is_key_repeating`].
     pub fn is_key_just_pressed(&self, key: KeyCode) -> bool {
         self.keys_just_pressed.contains(&key)
     }
@@ -179,6 +247,12 @@ impl InputManager {
         self.keys_pressed.contains(&key) && !self.keys_just_pressed.contains(&key)
     }
 
+    /// Return true if the OS has sent repeat events for a key since it was last pressed.
+    /// Useful for text-entry-like behaviors, unlike [`InputManager::is_key_held`] which is true from the second frame on.
+    pub fn is_key_repeating(&self, key: KeyCode) -> bool {
+        self.keys_with_repeat.contains(&key)
+    }
+
     /// Return all pressed keys.
     pub fn pressed_keys(&self) -> &HashSet<KeyCode> {
         &self.keys_pressed
@@ -199,6 +273,29 @@ impl InputManager {
         self.mouse_just_released.contains(&button)
     }
 
+    /// Return the cursor position in physical pixels relative to the top-left corner of the window.
+    /// None if the cursor has never entered the window.
+    pub fn cursor_position(&self) -> Option<Vec2> {
+        self.cursor_position
+    }
+
+    /// Return the cursor position in logical pixels, for ui layout.
+    pub fn logical_cursor_position(&self) -> Option<Vec2> {
+        self.cursor_position.map(|position| position / self.scale_factor)
+    }
+
+    /// Return the ratio of physical to logical pixels of the window.
+    pub fn scale_factor(&self) -> f32 {
+        self.scale_factor
+    }
+
+    /// Return the cursor movement since a mouse button was pressed, clamped to the window.
+    /// None if the button is not held.
+    pub fn drag_delta(&self, button: MouseButton) -> Option<Vec2> {
+        let origin = self.drag_origins.get(&button)?;
+        Some(self.cursor_position? - *origin)
+    }
+
     /// Return the state of modifier key in this frame.
     pub fn modifiers(&self) -> &ModifiersState {
         &self.modifiers
@@ -214,6 +311,7 @@ impl InputManager {
         self.mouse_pressed.clear();
         self.mouse_just_pressed.clear();
         self.mouse_just_released.clear();
+        self.drag_origins.clear();
     }
 }
 
@@ -225,6 +323,7 @@ pub struct InputActionMapper {
     input: InputManager,
     action_mappings: HashMap<String, SmallVec<[KeyCode; 1]>>,
     axis_mappings: HashMap<String, AxisMapping>,
+    chord_mappings: HashMap<String, ChordMapping>,
 }
 
 /// Directional, non-abrupt changes mapping useful to do movement mapping.
@@ -237,12 +336,27 @@ pub struct AxisMapping {
     smoothing_factor: f32,
 }
 
+/// Keys which must be pressed together with an exact set of modifiers, e.g. Ctrl+S.
+#[derive(Debug, Clone)]
+pub struct ChordMapping {
+    keys: SmallVec<[KeyCode; 2]>,
+    modifiers: ModifiersState,
+    /// Extra non-modifier keys held break the chord.
+    exclusive: bool,
+    pressed: bool,
+    just_pressed: bool,
+    /// Set once the chord fires, cleared when one of its keys or modifiers is released.
+    /// Prevent the chord from re-triggering without being pressed again.
+    latched: bool,
+}
+
 impl InputActionMapper {
     pub fn new() -> Self {
         Self {
             input: InputManager::new(),
-            action_mappings: HashMap::new(),
-            axis_mappings: HashMap::new(),
+            action_mappings: HashMap::default(),
+            axis_mappings: HashMap::default(),
+            chord_mappings: HashMap::default(),
         }
     }
 
@@ -264,11 +378,41 @@ impl InputActionMapper {
         );
     }
 
+    /// Register a chord mapping, active when all `keys` are pressed with exactly `modifiers`.
+    /// Holding extra keys still counts.
+    pub fn register_chord(&mut self, action: &str, keys: &[KeyCode], modifiers: ModifiersState) {
+        self.insert_chord(action, keys, modifiers, false);
+    }
+
+    /// Register a chord mapping like [`InputActionMapper::register_chord`], but holding any extra non-modifier key breaks it.
+    pub fn register_exclusive_chord(&mut self, action: &str, keys: &[KeyCode], modifiers: ModifiersState) {
+        self.insert_chord(action, keys, modifiers, true);
+    }
+
+    fn insert_chord(&mut self, action: &str, keys: &[KeyCode], modifiers: ModifiersState, exclusive: bool) {
+        self.chord_mappings.insert(
+            action.to_string(),
+            ChordMapping {
+                keys: keys.iter().copied().collect(),
+                modifiers,
+                exclusive,
+                pressed: false,
+                just_pressed: false,
+                latched: false,
+            }
+        );
+    }
+
     /// Receive and process window events.
     pub fn on_window_event(&mut self, event: &WindowEvent) {
         self.input.on_window_event(event);
     }
 
+    /// Receive and process input events, see [`crate::event::SystemEventCollector`].
+    pub fn on_input_event(&mut self, event: &InputEvent) {
+        self.input.on_input_event(event);
+    }
+
     /// Update input mapping states.
     pub fn tick(&mut self, delta_time: f32) {
         self.input.tick();
@@ -297,6 +441,24 @@ impl InputActionMapper {
                 mapping.axis = mapping.axis.lerp(0.0, blend_factor);
             }
         }
+
+        for mapping in self.chord_mappings.values_mut() {
+            let keys_down = mapping.keys.iter().all(|key| self.input.is_key_pressed(*key));
+            let modifiers_match = *self.input.modifiers() == mapping.modifiers;
+            let no_extra_keys = !mapping.exclusive || self.input.pressed_keys()
+                .iter()
+                .all(|key| mapping.keys.contains(key) || is_modifier_key(*key));
+
+            if !keys_down || !modifiers_match {
+                mapping.latched = false;
+            }
+
+            mapping.pressed = keys_down && modifiers_match && no_extra_keys;
+            mapping.just_pressed = mapping.pressed && !mapping.latched;
+            if mapping.pressed {
+                mapping.latched = true;
+            }
+        }
     }
 
     /// Return true if a specific action is pressed.
@@ -317,6 +479,17 @@ impl InputActionMapper {
         }
     }
 
+    /// Return true if a specific chord is pressed.
+    pub fn is_chord_pressed(&self, action: &str) -> bool {
+        self.chord_mappings.get(action).is_some_and(|mapping| mapping.pressed)
+    }
+
+    /// Return true if a specific chord is just pressed. (i.e. the last key or modifier completing the chord is pressed in this frame)
+    /// Keys of the chord must be released and pressed again to re-trigger.
+    pub fn is_chord_just_pressed(&self, action: &str) -> bool {
+        self.chord_mappings.get(action).is_some_and(|mapping| mapping.just_pressed)
+    }
+
     /// Return a float in [-1, 1] represents the direction and strength for a specific axis mapping.
     pub fn get_axis(&self, axis: &str) -> f32 {
         if let Some(mapping) = self.axis_mappings.get(axis) {
@@ -330,4 +503,104 @@ impl InputActionMapper {
     pub fn raw_input(&self) -> &InputManager {
         &self.input
     }
-}
\ No newline at end of file
+}
+
+fn is_modifier_key(key: KeyCode) -> bool {
+    matches!(
+        key,
+        KeyCode::ShiftLeft | KeyCode::ShiftRight
+            | KeyCode::ControlLeft | KeyCode::ControlRight
+            | KeyCode::AltLeft | KeyCode::AltRight
+            | KeyCode::SuperLeft | KeyCode::SuperRight
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeat_is_not_a_fresh_press() {
+        let mut input = InputManager::new();
+
+        input.on_key_event(KeyCode::KeyA, ElementState::Pressed, false);
+        input.tick();
+        assert!(input.is_key_just_pressed(KeyCode::KeyA));
+        assert!(!input.is_key_repeating(KeyCode::KeyA));
+
+        input.on_key_event(KeyCode::KeyA, ElementState::Pressed, true);
+        input.tick();
+        assert!(!input.is_key_just_pressed(KeyCode::KeyA));
+        assert!(input.is_key_repeating(KeyCode::KeyA));
+        assert_eq!(input.key_state(KeyCode::KeyA), KeyState::Held);
+
+        input.on_key_event(KeyCode::KeyA, ElementState::Released, false);
+        input.tick();
+        assert!(!input.is_key_repeating(KeyCode::KeyA));
+        assert!(input.is_key_just_released(KeyCode::KeyA));
+    }
+
+    #[test]
+    fn chord_requires_release_to_retrigger() {
+        let ctrl = ModifiersState { ctrl: true, ..Default::default() };
+        let mut mapper = InputActionMapper::new();
+        mapper.register_chord("save", &[KeyCode::KeyS], ctrl);
+        mapper.register_exclusive_chord("save exclusive", &[KeyCode::KeyS], ctrl);
+
+        // S alone is not the chord
+        mapper.input.on_key_event(KeyCode::KeyS, ElementState::Pressed, false);
+        mapper.tick(1. / 60.);
+        assert!(!mapper.is_chord_pressed("save"));
+
+        // pressing ctrl last completes it
+        mapper.input.on_key_event(KeyCode::ControlLeft, ElementState::Pressed, false);
+        mapper.input.modifiers = ctrl;
+        mapper.tick(1. / 60.);
+        assert!(mapper.is_chord_just_pressed("save"));
+        assert!(mapper.is_chord_just_pressed("save exclusive"));
+
+        // extra key held only breaks the exclusive chord, and does not re-trigger once released
+        mapper.input.on_key_event(KeyCode::KeyA, ElementState::Pressed, false);
+        mapper.tick(1. / 60.);
+        assert!(mapper.is_chord_pressed("save"));
+        assert!(!mapper.is_chord_just_pressed("save"));
+        assert!(!mapper.is_chord_pressed("save exclusive"));
+        mapper.input.on_key_event(KeyCode::KeyA, ElementState::Released, false);
+        mapper.tick(1. / 60.);
+        assert!(mapper.is_chord_pressed("save exclusive"));
+        assert!(!mapper.is_chord_just_pressed("save exclusive"));
+
+        // release and press again
+        mapper.input.on_key_event(KeyCode::KeyS, ElementState::Released, false);
+        mapper.tick(1. / 60.);
+        assert!(!mapper.is_chord_pressed("save"));
+        mapper.input.on_key_event(KeyCode::KeyS, ElementState::Pressed, false);
+        mapper.tick(1. / 60.);
+        assert!(mapper.is_chord_just_pressed("save"));
+
+        // any other modifier set is not the chord
+        mapper.input.modifiers = ModifiersState { shift: true, ..ctrl };
+        mapper.tick(1. / 60.);
+        assert!(!mapper.is_chord_pressed("save"));
+    }
+
+    #[test]
+    fn drag_delta_is_clamped_to_window() {
+        let mut input = InputManager::new();
+        input.window_size = Some(Vec2::new(100., 100.));
+
+        input.on_cursor_moved(Vec2::new(50., 50.));
+        assert_eq!(input.drag_delta(MouseButton::Left), None);
+
+        input.on_mouse_event(MouseButton::Left, ElementState::Pressed);
+        input.on_cursor_moved(Vec2::new(60., 45.));
+        assert_eq!(input.drag_delta(MouseButton::Left), Some(Vec2::new(10., -5.)));
+        assert_eq!(input.drag_delta(MouseButton::Right), None);
+
+        input.on_cursor_moved(Vec2::new(250., -30.));
+        assert_eq!(input.drag_delta(MouseButton::Left), Some(Vec2::new(50., -50.)));
+
+        input.on_mouse_event(MouseButton::Left, ElementState::Released);
+        assert_eq!(input.drag_delta(MouseButton::Left), None);
+    }
+}
@@ -3,4 +3,8 @@ pub mod collections;
 pub mod camera;
 pub mod math;
 pub mod input;
+pub mod event;
+pub mod event_bus;
+pub mod clipboard;
+pub mod scene;
 pub mod file;
\ No newline at end of file
@@ -1,13 +1,34 @@
-﻿pub use log::{trace, debug, info, warn, error};
+﻿pub use log::{trace, debug, info, warn, error, LevelFilter};
 
+/// Log targets of the engine subsystems, e.g. `RUST_LOG=zenith::asset=debug` traces only asset loading.
+pub mod target {
+    pub const TASK: &str = "zenith::task";
+    pub const ASSET: &str = "zenith::asset";
+    pub const RENDER: &str = "zenith::render";
+}
+
+/// Log everything at info level except the noisy graphics backends.
 pub fn initialize() -> Result<(), anyhow::Error> {
-    env_logger::builder()
-        .filter_level(log::LevelFilter::Info)
-        .filter_module("wgpu_core", log::LevelFilter::Warn)
-        .filter_module("wgpu_hal", log::LevelFilter::Error)
-        .filter_module("naga", log::LevelFilter::Error)
+    initialize_with(&[])
+}
+
+/// Same as [`initialize`] with the level of some modules or targets overridden, e.g. `[(target::TASK, LevelFilter::Debug)]`.
+/// `RUST_LOG` still takes precedence over all of them.
+pub fn initialize_with(module_levels: &[(&str, LevelFilter)]) -> Result<(), anyhow::Error> {
+    let mut builder = env_logger::builder();
+    builder
+        .filter_level(LevelFilter::Info)
+        .filter_module("wgpu_core", LevelFilter::Warn)
+        .filter_module("wgpu_hal", LevelFilter::Error)
+        .filter_module("naga", LevelFilter::Error);
+
+    for (module, level) in module_levels {
+        builder.filter_module(module, *level);
+    }
+
+    builder
         .parse_default_env()
-        .init();
+        .try_init()?;
 
     Ok(())
-}
\ No newline at end of file
+}
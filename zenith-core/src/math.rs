@@ -1,9 +1,11 @@
 ﻿use std::cmp::Ordering;
-use std::f32::consts::{FRAC_1_PI, PI};
+use std::f32::consts::{FRAC_1_PI, PI, TAU};
 use derive_more::{Add, AddAssign, Deref, DerefMut, Div, DivAssign, From, Into, Mul, MulAssign, Neg, Rem, RemAssign, Sub, SubAssign};
-use glam::FloatExt;
+use glam::{FloatExt, Mat4, Quat, Vec3, Vec4, Vec4Swizzles};
+use serde::{Deserialize, Serialize};
 
-#[derive(Deref, DerefMut, From, Into, Default, Debug, Clone, Copy, PartialEq, PartialOrd, Neg, Add, Sub, Mul, Div, Rem, AddAssign, SubAssign, MulAssign, DivAssign, RemAssign)]
+#[derive(Deref, DerefMut, From, Into, Default, Debug, Clone, Copy, PartialEq, PartialOrd, Neg, Add, Sub, Mul, Div, Rem, AddAssign, SubAssign, MulAssign, DivAssign, RemAssign, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct Degree(f32);
 
 impl PartialEq<f32> for Degree {
@@ -28,9 +30,29 @@ impl Degree {
     pub fn lerp(&self, rhs: Degree, factor: f32) -> Degree {
         self.0.lerp(rhs.0, factor).into()
     }
+
+    /// Interpolate along the shortest arc, e.g. from 170° to -170° sweeps 20° instead of 340°.
+    /// The result is NOT normalized.
+    #[inline]
+    pub fn lerp_angle(&self, rhs: Degree, factor: f32) -> Degree {
+        Degree::from(Radians::from(*self).lerp_angle(rhs.into(), factor))
+    }
+
+    /// Wrap into (-180°, 180°].
+    #[inline]
+    pub fn normalize(&self) -> Degree {
+        (180.0 - (180.0 - self.0).rem_euclid(360.0)).into()
+    }
+
+    /// Return the unsigned angle of the shortest arc between two angles, in [0°, 180°].
+    #[inline]
+    pub fn angle_between(&self, other: Degree) -> Degree {
+        (other - *self).normalize().0.abs().into()
+    }
 }
 
-#[derive(Deref, DerefMut, From, Into, Default, Debug, Clone, Copy, PartialEq, PartialOrd, Neg, Add, Sub, Mul, Div, Rem, AddAssign, SubAssign, MulAssign, DivAssign, RemAssign)]
+#[derive(Deref, DerefMut, From, Into, Default, Debug, Clone, Copy, PartialEq, PartialOrd, Neg, Add, Sub, Mul, Div, Rem, AddAssign, SubAssign, MulAssign, DivAssign, RemAssign, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct Radians(f32);
 
 impl PartialEq<f32> for Radians {
@@ -55,6 +77,25 @@ impl Radians {
     pub fn lerp(&self, rhs: Radians, factor: f32) -> Radians {
         self.0.lerp(rhs.0, factor).into()
     }
+
+    /// Interpolate along the shortest arc, e.g. from 0.9π to -0.9π sweeps 0.2π instead of 1.8π.
+    /// The result is NOT normalized.
+    #[inline]
+    pub fn lerp_angle(&self, rhs: Radians, factor: f32) -> Radians {
+        *self + (rhs - *self).normalize() * factor
+    }
+
+    /// Wrap into (-π, π].
+    #[inline]
+    pub fn normalize(&self) -> Radians {
+        (PI - (PI - self.0).rem_euclid(TAU)).into()
+    }
+
+    /// Return the unsigned angle of the shortest arc between two angles, in [0, π].
+    #[inline]
+    pub fn angle_between(&self, other: Radians) -> Radians {
+        (other - *self).normalize().0.abs().into()
+    }
 }
 
 impl From<Degree> for Radians {
@@ -67,4 +108,181 @@ impl From<Radians> for Degree {
     fn from(value: Radians) -> Self {
         Self(value.0 * FRAC_1_PI * 180.0)
     }
-}
\ No newline at end of file
+}
+
+/// Translation, rotation and scale of an object relative to its parent.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Transform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+    };
+
+    #[inline]
+    pub fn from_translation(translation: Vec3) -> Self {
+        Self {
+            translation,
+            ..Self::IDENTITY
+        }
+    }
+
+    /// Decompose an affine matrix, shear is lost.
+    #[inline]
+    pub fn from_matrix(matrix: Mat4) -> Self {
+        let (scale, rotation, translation) = matrix.to_scale_rotation_translation();
+        Self {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    /// Matrix which scales first, then rotates and translates.
+    #[inline]
+    pub fn to_matrix(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+}
+
+impl From<Transform> for Mat4 {
+    fn from(value: Transform) -> Self {
+        value.to_matrix()
+    }
+}
+
+/// Axis-aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    /// Box of no point, min above max. Growing it by a point gives the box of that point.
+    pub const EMPTY: Aabb = Aabb {
+        min: Vec3::INFINITY,
+        max: Vec3::NEG_INFINITY,
+    };
+
+    #[inline]
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    /// Return the smallest box containing all points, [`Aabb::EMPTY`] without points.
+    pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Self {
+        points.into_iter().fold(Self::EMPTY, |aabb, point| Self::new(aabb.min.min(point), aabb.max.max(point)))
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.min.cmpgt(self.max).any()
+    }
+
+    #[inline]
+    pub fn center(&self) -> Vec3 { (self.min + self.max) * 0.5 }
+
+    /// Half size along each axis.
+    #[inline]
+    pub fn extents(&self) -> Vec3 { (self.max - self.min) * 0.5 }
+
+    /// Return the box containing this box transformed by an affine matrix, which is larger than the box itself if rotated.
+    pub fn transformed(&self, matrix: Mat4) -> Self {
+        let center = matrix.transform_point3(self.center());
+        let extents = self.extents();
+        // each world axis extent sums the absolute projections of the local extents
+        let extents = matrix.x_axis.xyz().abs() * extents.x + matrix.y_axis.xyz().abs() * extents.y + matrix.z_axis.xyz().abs() * extents.z;
+        Self::new(center - extents, center + extents)
+    }
+}
+
+/// Six planes bounding the volume a camera sees, normals point inside.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    /// Normal in xyz and distance to origin in w, a point p is inside if `dot(normal, p) + w >= 0`.
+    planes: [Vec4; 6],
+}
+
+impl Frustum {
+    /// Extract the planes from a view projection matrix with depth in [0, 1], reversed or NOT.
+    /// The far plane of an infinite projection never culls.
+    pub fn from_view_projection(view_projection: Mat4) -> Self {
+        let (row_0, row_1, row_2, row_3) = (view_projection.row(0), view_projection.row(1), view_projection.row(2), view_projection.row(3));
+        let planes = [row_3 + row_0, row_3 - row_0, row_3 + row_1, row_3 - row_1, row_2, row_3 - row_2].map(|plane| {
+            let length = plane.xyz().length();
+            // e.g. the far plane of an infinite projection, which has no normal
+            if length < 1e-6 { Vec4::W } else { plane / length }
+        });
+        Self { planes }
+    }
+
+    /// Return false only if the box is completely outside of a plane. Boxes near the corners may pass without intersecting.
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        let center = aabb.center();
+        let extents = aabb.extents();
+        self.planes.iter().all(|plane| {
+            let normal = plane.xyz();
+            normal.dot(center) + plane.w + normal.abs().dot(extents) >= 0.
+        })
+    }
+
+    pub fn contains_point(&self, point: Vec3) -> bool {
+        self.planes.iter().all(|plane| plane.xyz().dot(point) + plane.w >= 0.)
+    }
+
+    /// Left, right, bottom, top, near and far planes, e.g. to cull on the GPU.
+    #[inline]
+    pub fn planes(&self) -> &[Vec4; 6] { &self.planes }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transformed_aabb_contains_rotated_corners() {
+        let aabb = Aabb::new(Vec3::new(-1., -2., -3.), Vec3::new(1., 2., 3.));
+        let matrix = Mat4::from_scale_rotation_translation(Vec3::splat(2.), Quat::from_rotation_z(0.7), Vec3::new(5., 0., 0.));
+
+        let transformed = aabb.transformed(matrix);
+        let corners = (0..8).map(|index| {
+            let pick = |bit: usize, min: f32, max: f32| if index & bit == 0 { min } else { max };
+            matrix.transform_point3(Vec3::new(pick(1, aabb.min.x, aabb.max.x), pick(2, aabb.min.y, aabb.max.y), pick(4, aabb.min.z, aabb.max.z)))
+        });
+        // the tight box of the corners, an axis-aligned box of a box is exact
+        let expected = Aabb::from_points(corners);
+        assert!(transformed.min.abs_diff_eq(expected.min, 1e-4) && transformed.max.abs_diff_eq(expected.max, 1e-4), "{:?} {:?}", transformed, expected);
+    }
+
+    #[test]
+    fn frustum_culls_boxes_outside() {
+        let view = Mat4::look_to_rh(Vec3::ZERO, Vec3::NEG_Z, Vec3::Y);
+        let projection = Mat4::perspective_infinite_reverse_rh(std::f32::consts::FRAC_PI_2, 1., 0.1);
+        let frustum = Frustum::from_view_projection(projection * view);
+
+        let unit_box_at = |center: Vec3| Aabb::new(center - Vec3::splat(0.5), center + Vec3::splat(0.5));
+        assert!(frustum.intersects_aabb(&unit_box_at(Vec3::new(0., 0., -10.))));
+        // infinitely far
+        assert!(frustum.intersects_aabb(&unit_box_at(Vec3::new(0., 0., -1e6))));
+        // straddling the left plane
+        assert!(frustum.intersects_aabb(&unit_box_at(Vec3::new(-10.4, 0., -10.))));
+        assert!(!frustum.intersects_aabb(&unit_box_at(Vec3::new(0., 0., 10.))));
+        assert!(!frustum.intersects_aabb(&unit_box_at(Vec3::new(-20., 0., -10.))));
+        assert!(!frustum.intersects_aabb(&unit_box_at(Vec3::new(0., 20., -10.))));
+        assert!(!frustum.contains_point(Vec3::new(0., 0., -0.05)));
+    }
+}
@@ -0,0 +1,208 @@
+//! Minimal scene hierarchy: nodes with a local [`Transform`], an optional parent and an optional payload,
+//! e.g. the mesh and material drawn at the node.
+//!
+//! Scenes are saved as human-editable RON for authoring, unlike the baked asset format.
+
+use std::path::Path;
+use anyhow::anyhow;
+use glam::Mat4;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use crate::math::Transform;
+
+/// Index of a node in its [`Scene`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct NodeId(u32);
+
+impl NodeId {
+    #[inline]
+    pub fn index(&self) -> usize { self.0 as usize }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneNode<T> {
+    pub name: String,
+    /// Transform relative to the parent, or to the world for root nodes.
+    #[serde(default)]
+    pub transform: Transform,
+    #[serde(default = "none")]
+    pub payload: Option<T>,
+    #[serde(default)]
+    parent: Option<NodeId>,
+}
+
+/// `#[serde(default)]` would require `T: Default`.
+fn none<T>() -> Option<T> {
+    None
+}
+
+impl<T> SceneNode<T> {
+    #[inline]
+    pub fn parent(&self) -> Option<NodeId> { self.parent }
+}
+
+/// Nodes are stored in insertion order. A parent is always added before its children,
+/// so the hierarchy can NOT have cycles and a single pass in order visits parents first.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Scene<T> {
+    nodes: Vec<SceneNode<T>>,
+}
+
+impl<T> Default for Scene<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Scene<T> {
+    pub fn new() -> Self {
+        Self { nodes: Vec::new() }
+    }
+
+    /// Add a node under `parent`, or a root node if None.
+    pub fn add_node(&mut self, parent: Option<NodeId>, name: impl Into<String>, transform: Transform, payload: Option<T>) -> NodeId {
+        if let Some(parent) = parent {
+            assert!(parent.index() < self.nodes.len(), "Parent {:?} is NOT in the scene", parent);
+        }
+
+        let id = NodeId(self.nodes.len() as u32);
+        self.nodes.push(SceneNode {
+            name: name.into(),
+            transform,
+            payload,
+            parent,
+        });
+        id
+    }
+
+    #[inline]
+    pub fn node(&self, id: NodeId) -> &SceneNode<T> {
+        &self.nodes[id.index()]
+    }
+
+    #[inline]
+    pub fn node_mut(&mut self, id: NodeId) -> &mut SceneNode<T> {
+        &mut self.nodes[id.index()]
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize { self.nodes.len() }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.nodes.is_empty() }
+
+    /// Iterate all nodes in insertion order, parents before their children.
+    pub fn iter(&self) -> impl Iterator<Item = (NodeId, &SceneNode<T>)> {
+        self.nodes.iter().enumerate().map(|(index, node)| (NodeId(index as u32), node))
+    }
+
+    /// Iterate the nodes carrying a payload.
+    pub fn payloads(&self) -> impl Iterator<Item = (NodeId, &T)> {
+        self.iter().filter_map(|(id, node)| Some((id, node.payload.as_ref()?)))
+    }
+
+    /// Return the transform from the node space to the world space, composing the whole parent chain.
+    pub fn world_transform(&self, id: NodeId) -> Mat4 {
+        let mut world = Mat4::IDENTITY;
+        let mut current = Some(id);
+        while let Some(id) = current {
+            let node = self.node(id);
+            world = node.transform.to_matrix() * world;
+            current = node.parent;
+        }
+        world
+    }
+
+    /// Return the world transforms of all nodes indexed by [`NodeId::index`], cheaper than calling
+    /// [`Scene::world_transform`] for every node of a deep hierarchy.
+    pub fn world_transforms(&self) -> Vec<Mat4> {
+        let mut transforms: Vec<Mat4> = Vec::with_capacity(self.nodes.len());
+        for node in &self.nodes {
+            let local = node.transform.to_matrix();
+            let world = match node.parent {
+                Some(parent) => transforms[parent.index()] * local,
+                None => local,
+            };
+            transforms.push(world);
+        }
+        transforms
+    }
+}
+
+impl<T: Serialize> Scene<T> {
+    pub fn to_ron(&self) -> Result<String, anyhow::Error> {
+        Ok(ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?)
+    }
+
+    pub fn save_ron(&self, path: impl AsRef<Path>) -> Result<(), anyhow::Error> {
+        std::fs::write(path, self.to_ron()?)?;
+        Ok(())
+    }
+}
+
+impl<T: DeserializeOwned> Scene<T> {
+    /// Parse a scene, fail if a node refers to a parent which is NOT listed before it.
+    pub fn from_ron(ron: &str) -> Result<Self, anyhow::Error> {
+        let scene: Self = ron::from_str(ron)?;
+
+        for (index, node) in scene.nodes.iter().enumerate() {
+            if node.parent.is_some_and(|parent| parent.index() >= index) {
+                return Err(anyhow!("Parent of node {} \"{}\" must be listed before it", index, node.name));
+            }
+        }
+        Ok(scene)
+    }
+
+    pub fn load_ron(path: impl AsRef<Path>) -> Result<Self, anyhow::Error> {
+        let path = path.as_ref();
+        let ron = std::fs::read_to_string(path).map_err(|err| anyhow!("Failed to read scene {:?}: {}", path, err))?;
+        Self::from_ron(&ron).map_err(|err| anyhow!("Failed to parse scene {:?}: {}", path, err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Quat, Vec3};
+    use super::*;
+
+    #[test]
+    fn world_transform_composes_parents() {
+        let mut scene = Scene::<()>::new();
+        let root = scene.add_node(None, "root", Transform {
+            scale: Vec3::splat(2.),
+            ..Transform::from_translation(Vec3::new(10., 0., 0.))
+        }, None);
+        let arm = scene.add_node(Some(root), "arm", Transform {
+            rotation: Quat::from_rotation_z(std::f32::consts::FRAC_PI_2),
+            ..Transform::from_translation(Vec3::new(0., 1., 0.))
+        }, None);
+        let hand = scene.add_node(Some(arm), "hand", Transform::from_translation(Vec3::new(1., 0., 0.)), None);
+
+        // hand origin: rotated into +y by the arm, offset by the arm, scaled and moved by the root
+        let position = scene.world_transform(hand).transform_point3(Vec3::ZERO);
+        assert!(position.abs_diff_eq(Vec3::new(10., 4., 0.), 1e-5), "{:?}", position);
+
+        let transforms = scene.world_transforms();
+        for (id, _) in scene.iter() {
+            assert!(transforms[id.index()].abs_diff_eq(scene.world_transform(id), 1e-5));
+        }
+    }
+
+    #[test]
+    fn ron_round_trip() {
+        let mut scene = Scene::<String>::new();
+        let root = scene.add_node(None, "table", Transform::from_translation(Vec3::new(0., 0., 1.)), Some("mesh/table_0.mesh".to_string()));
+        scene.add_node(Some(root), "cup", Transform {
+            rotation: Quat::from_rotation_y(0.5),
+            scale: Vec3::splat(0.1),
+            ..Transform::from_translation(Vec3::new(0.2, 0., 0.8))
+        }, None);
+
+        let ron = scene.to_ron().unwrap();
+        assert_eq!(Scene::<String>::from_ron(&ron).unwrap(), scene);
+
+        // children must come after their parent
+        assert!(Scene::<String>::from_ron(r#"(nodes: [(name: "orphan", parent: Some(0))])"#).is_err());
+    }
+}
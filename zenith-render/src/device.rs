@@ -1,80 +1,229 @@
 ﻿use std::sync::Arc;
+use anyhow::anyhow;
+use winit::dpi::PhysicalSize;
 use winit::window::Window;
-use zenith_core::log::info;
+use zenith_core::event::Signal;
+use zenith_core::log::{info, warn, target::RENDER};
+
+/// Options used to create the render device.
+#[derive(Debug, Clone)]
+pub struct RenderDeviceOptions {
+    /// Preferred adapter by power usage, e.g. discrete over integrated GPU.
+    pub power_preference: wgpu::PowerPreference,
+    /// Backends allowed to pick the adapter from.
+    pub backends: wgpu::Backends,
+    /// Force the software (fallback) adapter, useful on CI machines without a GPU.
+    pub force_fallback_adapter: bool,
+    /// Features the app can NOT run without, device creation fails if the adapter misses any of them.
+    pub required_features: wgpu::Features,
+    /// Features enabled only if the adapter supports them, check with [`RenderDevice::supports`] before use.
+    pub optional_features: wgpu::Features,
+    /// Pick a half float swapchain for HDR displays if the surface supports it, see [`RenderDevice::set_prefer_hdr`].
+    pub prefer_hdr: bool,
+}
+
+impl Default for RenderDeviceOptions {
+    fn default() -> Self {
+        Self {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            backends: wgpu::Backends::PRIMARY,
+            force_fallback_adapter: false,
+            required_features: wgpu::Features::empty(),
+            optional_features: wgpu::Features::PUSH_CONSTANTS
+                | wgpu::Features::TEXTURE_COMPRESSION_BC
+                | wgpu::Features::TIMESTAMP_QUERY
+                | wgpu::Features::TEXTURE_BINDING_ARRAY
+                | wgpu::Features::POLYGON_MODE_LINE
+                | wgpu::Features::POLYGON_MODE_POINT
+                | wgpu::Features::INDIRECT_FIRST_INSTANCE,
+            prefer_hdr: false,
+        }
+    }
+}
+
+/// Format of the headless output, stands in for the surface format.
+const HEADLESS_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+/// Half float surface, presented in extended linear sRGB (scRGB) by backends supporting HDR output.
+const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// Surface and its configuration of the main window.
+struct Swapchain {
+    surface: wgpu::Surface<'static>,
+    config: wgpu::SurfaceConfiguration,
+}
 
 /// Render device to maintain and dispatch all rendering instructions.
 pub struct RenderDevice {
     #[allow(dead_code)]
     instance: wgpu::Instance,
-    #[allow(dead_code)]
     adapter: wgpu::Adapter,
     device: wgpu::Device,
     queue: wgpu::Queue,
-    surface: wgpu::Surface<'static>,
-    surface_config: wgpu::SurfaceConfiguration,
+    /// None if the device is headless.
+    swapchain: Option<Swapchain>,
+    resized: Signal<PhysicalSize<u32>>,
 }
 
 impl RenderDevice {
     pub fn new(window: Arc<Window>) -> Result<Self, anyhow::Error> {
-        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::METAL,
+        Self::with_options(window, RenderDeviceOptions::default())
+    }
+
+    pub fn with_options(window: Arc<Window>, options: RenderDeviceOptions) -> Result<Self, anyhow::Error> {
+        let instance = Self::create_instance(&options);
+
+        let window_size = window.inner_size();
+        let width = window_size.width.max(1);
+        let height = window_size.height.max(1);
+        let surface = instance.create_surface(window)?;
+
+        let (adapter, device, queue) = Self::create_device(&instance, &options, Some(&surface))?;
+
+        let mut surface_config = surface
+            .get_default_config(&adapter, width, height)
+            .expect("Surface isn't supported by the adapter.");
+        surface_config.usage |= wgpu::TextureUsages::COPY_DST;
+        Self::set_surface_format(&mut surface_config, Self::pick_surface_format(&surface, &adapter, options.prefer_hdr));
+
+        info!(target: RENDER, "Picked surface pixel format: {:?}, resolution({}x{})", surface_config.format, width, height);
+
+        surface.configure(&device, &surface_config);
+
+        Ok(Self {
+            instance,
+            adapter,
+            device,
+            queue,
+            swapchain: Some(Swapchain {
+                surface,
+                config: surface_config,
+            }),
+            resized: Signal::new(),
+        })
+    }
+
+    /// Create a render device without window and surface, e.g. for tests or offline rendering.
+    pub fn headless(options: RenderDeviceOptions) -> Result<Self, anyhow::Error> {
+        let instance = Self::create_instance(&options);
+        let (adapter, device, queue) = Self::create_device(&instance, &options, None)?;
+
+        Ok(Self {
+            instance,
+            adapter,
+            device,
+            queue,
+            swapchain: None,
+            resized: Signal::new(),
+        })
+    }
+
+    fn create_instance(options: &RenderDeviceOptions) -> wgpu::Instance {
+        wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: options.backends,
             flags: wgpu::InstanceFlags::VALIDATION,
             ..Default::default()
-        });
-
-        let adapter = pollster::block_on(async {
-            instance
-                .request_adapter(&wgpu::RequestAdapterOptions {
-                    power_preference: wgpu::PowerPreference::HighPerformance,
-                    ..Default::default()
-                })
-                .await
-                .unwrap()
-        });
+        })
+    }
+
+    fn create_device(
+        instance: &wgpu::Instance,
+        options: &RenderDeviceOptions,
+        compatible_surface: Option<&wgpu::Surface<'static>>,
+    ) -> Result<(wgpu::Adapter, wgpu::Device, wgpu::Queue), anyhow::Error> {
+        let request_adapter = |force_fallback_adapter: bool| {
+            pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: options.power_preference,
+                force_fallback_adapter,
+                compatible_surface,
+            }))
+        };
+
+        let adapter = match request_adapter(options.force_fallback_adapter) {
+            Ok(adapter) => adapter,
+            Err(err) if !options.force_fallback_adapter => {
+                warn!(target: RENDER, "No adapter found for {:?} ({}), try the fallback adapter.", options.backends, err);
+                request_adapter(true)
+                    .map_err(|err| anyhow!("No suitable adapter found for {:?}: {}", options.backends, err))?
+            }
+            Err(err) => return Err(anyhow!("No fallback adapter found for {:?}: {}", options.backends, err)),
+        };
         let adapter_info = adapter.get_info();
-        info!("Selected adapter: {} ({:?})\n\tDriver {}: {}",
+        info!(target: RENDER, "Selected adapter: {} ({:?})\n\tDriver {}: {}",
             adapter_info.name,
             adapter_info.backend,
             adapter_info.driver,
             adapter_info.driver_info);
 
+        let missing_features = options.required_features - adapter.features();
+        if !missing_features.is_empty() {
+            return Err(anyhow!("Adapter {} does NOT support required features: {:?}", adapter_info.name, missing_features));
+        }
+
+        let unsupported_features = options.optional_features - adapter.features();
+        if !unsupported_features.is_empty() {
+            warn!(target: RENDER, "Optional features NOT supported by the adapter: {:?}", unsupported_features);
+        }
+
+        let required_features = options.required_features | (options.optional_features & adapter.features());
+        let required_limits = wgpu::Limits {
+            max_push_constant_size: adapter.limits().max_push_constant_size,
+            // zero unless binding arrays are enabled, then take all the adapter offers
+            max_binding_array_elements_per_shader_stage: adapter.limits().max_binding_array_elements_per_shader_stage,
+            ..Default::default()
+        };
+
         let (device, queue) = pollster::block_on(async {
             adapter
                 .request_device(
                     &wgpu::DeviceDescriptor {
                         label: Some("zenith rhi device"),
+                        required_features,
+                        required_limits,
                         ..Default::default()
                     },
                 )
                 .await
-                .unwrap()
-        });
+        })?;
 
-        let window_size = window.inner_size();
-        let width = window_size.width.max(1);
-        let height = window_size.height.max(1);
-        let surface = instance.create_surface(window)?;
+        Ok((adapter, device, queue))
+    }
 
-        let mut surface_config = surface
-            .get_default_config(&adapter, width, height)
-            .expect("Surface isn't supported by the adapter.");
-        surface_config.usage |= wgpu::TextureUsages::COPY_DST;
+    /// Return the HDR format if preferred and supported by the surface, otherwise the preferred format of the surface.
+    fn pick_surface_format(surface: &wgpu::Surface<'static>, adapter: &wgpu::Adapter, prefer_hdr: bool) -> wgpu::TextureFormat {
+        let formats = surface.get_capabilities(adapter).formats;
+
+        if prefer_hdr {
+            if formats.contains(&HDR_FORMAT) {
+                return HDR_FORMAT;
+            }
+            warn!(target: RENDER, "Surface does NOT support HDR format {:?}, fallback to SDR.", HDR_FORMAT);
+        }
 
-        let view_format = surface_config.format.add_srgb_suffix();
-        surface_config.view_formats.push(view_format);
+        // surface formats are sorted by preference
+        formats
+            .into_iter()
+            .find(|format| *format != HDR_FORMAT)
+            .expect("Surface isn't supported by the adapter.")
+    }
 
-        info!("Picked surface pixel format: {:?}, resolution({}x{})", surface_config.format, width, height);
+    fn set_surface_format(config: &mut wgpu::SurfaceConfiguration, format: wgpu::TextureFormat) {
+        config.format = format;
+        config.view_formats.clear();
+        // render through an sRGB view even if the surface itself is NOT sRGB
+        if format.add_srgb_suffix() != format {
+            config.view_formats.push(format.add_srgb_suffix());
+        }
+    }
 
-        surface.configure(&device, &surface_config);
+    /// Return true if the device has no swapchain to present.
+    #[inline]
+    pub fn is_headless(&self) -> bool {
+        self.swapchain.is_none()
+    }
 
-        Ok(Self {
-            instance,
-            adapter,
-            device,
-            queue,
-            surface,
-            surface_config,
-        })
+    #[inline]
+    fn swapchain(&self) -> &Swapchain {
+        self.swapchain.as_ref().expect("Headless render device has no swapchain!")
     }
 
     /// Return the inner render device (wgpu).
@@ -82,41 +231,114 @@ impl RenderDevice {
         &self.device
     }
 
+    /// Return all features enabled on this device.
+    pub fn features(&self) -> wgpu::Features {
+        self.device.features()
+    }
+
+    /// Return the limits of this device.
+    pub fn limits(&self) -> wgpu::Limits {
+        self.device.limits()
+    }
+
+    /// Return true if all the features are enabled on this device.
+    pub fn supports(&self, features: wgpu::Features) -> bool {
+        self.device.features().contains(features)
+    }
+
     /// Return the main submit queue.
     pub fn queue(&self) -> &wgpu::Queue {
         &self.queue
     }
 
     /// Acquire next frame from swapchain.
-    /// If acquire fails, this function will panic.
-    pub fn acquire_next_frame(&self) -> wgpu::SurfaceTexture {
-        match self.surface.get_current_texture() {
-            Ok(frame) => frame,
-            // If we timed out, just try again
-            Err(wgpu::SurfaceError::Timeout) => self.surface
-                .get_current_texture()
-                .expect("Failed to acquire next surface texture!"),
-            Err(
-                // If the surface is outdated, or was lost, reconfigure it.
-                wgpu::SurfaceError::Outdated
-                | wgpu::SurfaceError::Lost
-                | wgpu::SurfaceError::Other
-                // If OutOfMemory happens, reconfiguring may not help, but we might as well try
-                | wgpu::SurfaceError::OutOfMemory,
-            ) => {
-                self.surface.configure(&self.device, &self.surface_config);
-                self.surface
-                    .get_current_texture()
-                    .expect("Failed to acquire next surface texture!")
-            }
+    /// On Outdated or Lost, call [`RenderDevice::reconfigure`] and acquire again next frame.
+    /// Panics if the device is headless.
+    pub fn acquire_next_frame(&self) -> Result<wgpu::SurfaceTexture, wgpu::SurfaceError> {
+        self.swapchain().surface.get_current_texture()
+    }
+
+    /// Reconfigure the swapchain with current configuration, e.g. after the surface is outdated or lost.
+    pub fn reconfigure(&self) {
+        if let Some(swapchain) = &self.swapchain {
+            swapchain.surface.configure(&self.device, &swapchain.config);
         }
     }
 
-    /// Resize the swapchain with specific width and height.
+    /// Return the pixel format of the swapchain, or of the headless output.
+    pub fn surface_format(&self) -> wgpu::TextureFormat {
+        self.swapchain
+            .as_ref()
+            .map_or(HEADLESS_FORMAT, |swapchain| swapchain.config.format)
+    }
+
+    /// Return the format renderers create their output targets with, so that outputs can be copied to the swapchain.
+    /// Same as [`RenderDevice::surface_format`] but sRGB encoded for SDR, shaders always write linear colors.
+    pub fn output_format(&self) -> wgpu::TextureFormat {
+        self.surface_format().add_srgb_suffix()
+    }
+
+    /// Return true if the swapchain is HDR, colors above 1 are shown brighter than SDR white instead of clipped.
+    pub fn is_hdr(&self) -> bool {
+        self.surface_format() == HDR_FORMAT
+    }
+
+    /// Reconfigure the swapchain with a HDR format if `prefer_hdr` and the surface supports it, otherwise with a SDR format.
+    /// Targets created with the previous [`RenderDevice::output_format`] must be recreated. Return the format actually used.
+    /// Panics if the device is headless.
+    pub fn set_prefer_hdr(&mut self, prefer_hdr: bool) -> wgpu::TextureFormat {
+        let swapchain = self.swapchain.as_mut().expect("Headless render device has no swapchain!");
+
+        let format = Self::pick_surface_format(&swapchain.surface, &self.adapter, prefer_hdr);
+        if format != swapchain.config.format {
+            info!(target: RENDER, "Switch surface format: {:?} -> {:?}", swapchain.config.format, format);
+            Self::set_surface_format(&mut swapchain.config, format);
+            swapchain.surface.configure(&self.device, &swapchain.config);
+        }
+        format
+    }
+
+    /// Return the present mode of the swapchain.
+    /// Panics if the device is headless.
+    pub fn present_mode(&self) -> wgpu::PresentMode {
+        self.swapchain().config.present_mode
+    }
+
+    /// Reconfigure the swapchain with specific present mode.
+    /// Fallback to FIFO (always supported) if the surface does NOT support the mode, return the mode actually used.
+    /// Panics if the device is headless.
+    pub fn set_present_mode(&mut self, present_mode: wgpu::PresentMode) -> wgpu::PresentMode {
+        let swapchain = self.swapchain.as_mut().expect("Headless render device has no swapchain!");
+
+        let is_auto = matches!(present_mode, wgpu::PresentMode::AutoVsync | wgpu::PresentMode::AutoNoVsync);
+        let present_mode = if is_auto || swapchain.surface.get_capabilities(&self.adapter).present_modes.contains(&present_mode) {
+            present_mode
+        } else {
+            warn!(target: RENDER, "Present mode {:?} is NOT supported by the surface, fallback to Fifo.", present_mode);
+            wgpu::PresentMode::Fifo
+        };
+
+        if present_mode != swapchain.config.present_mode {
+            info!(target: RENDER, "Switch present mode: {:?} -> {:?}", swapchain.config.present_mode, present_mode);
+            swapchain.config.present_mode = present_mode;
+            swapchain.surface.configure(&self.device, &swapchain.config);
+        }
+        present_mode
+    }
+
+    /// Resize the swapchain with specific width and height, then notify the slots of [`RenderDevice::on_resized`].
     pub fn resize(&mut self, width: u32, height: u32) {
-        self.surface_config.width = width.max(1);
-        self.surface_config.height = height.max(1);
-        self.surface.configure(&self.device, &self.surface_config);
+        if let Some(swapchain) = &mut self.swapchain {
+            swapchain.config.width = width.max(1);
+            swapchain.config.height = height.max(1);
+            swapchain.surface.configure(&self.device, &swapchain.config);
+        }
+        self.resized.emit(&PhysicalSize::new(width, height));
     }
-}
 
+    /// Signal emitted with the new window size in physical pixels after every resize,
+    /// e.g. for renderers to recreate their size dependent targets.
+    pub fn on_resized(&mut self) -> &mut Signal<PhysicalSize<u32>> {
+        &mut self.resized
+    }
+}
@@ -2,8 +2,8 @@ mod pipeline_cache;
 mod shader;
 mod device;
 
-pub use shader::GraphicShader;
-pub use device::RenderDevice;
+pub use shader::{GraphicShader, ComputeShader};
+pub use device::{RenderDevice, RenderDeviceOptions};
 pub use pipeline_cache::PipelineCache;
 pub use zenith_asset::gltf_loader::GltfLoader;
 
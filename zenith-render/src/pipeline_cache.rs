@@ -1,17 +1,19 @@
 ﻿use std::hash::{Hash, Hasher};
 use zenith_core::collections::{DefaultHasher};
 use zenith_core::collections::hashmap::{Entry, HashMap};
-use crate::shader::{GraphicShader};
+use crate::shader::{ComputeShader, GraphicShader};
 
 /// Cache all types of pipelines created during rendering.
 pub struct PipelineCache {
     raster_pipelines: HashMap<u64, wgpu::RenderPipeline>,
+    compute_pipelines: HashMap<u64, wgpu::ComputePipeline>,
 }
 
 impl PipelineCache {
     pub fn new() -> Self {
         Self {
-            raster_pipelines: HashMap::new(),
+            raster_pipelines: HashMap::default(),
+            compute_pipelines: HashMap::default(),
         }
     }
 
@@ -23,9 +25,14 @@ impl PipelineCache {
         shader: &GraphicShader,
         color_states: &[Option<wgpu::ColorTargetState>],
         depth_stencil_state: Option<wgpu::DepthStencilState>,
+        primitive: wgpu::PrimitiveState,
     ) -> anyhow::Result<wgpu::RenderPipeline> {
+        // the same shader is drawn with different states, e.g. opaque and alpha blended materials
         let mut hasher = DefaultHasher::new();
         shader.hash(&mut hasher);
+        color_states.hash(&mut hasher);
+        depth_stencil_state.hash(&mut hasher);
+        primitive.hash(&mut hasher);
         let hash = hasher.finish();
 
         match self.raster_pipelines.entry(hash) {
@@ -33,6 +40,10 @@ impl PipelineCache {
                 Ok(pipeline.get().clone())
             }
             Entry::Vacant(entry) => {
+                if !shader.push_constant_ranges().is_empty() && !device.features().contains(wgpu::Features::PUSH_CONSTANTS) {
+                    return Err(anyhow::anyhow!("Shader[{}] uses push constants, but PUSH_CONSTANTS is NOT supported by the device.", shader.name()));
+                }
+
                 let module = shader.create_shader_module(
                     device,
                     Default::default(),
@@ -48,7 +59,7 @@ impl PipelineCache {
                         label: Some(&shader.name()),
                         layout: Some(&layout),
                         vertex,
-                        primitive: Default::default(),
+                        primitive,
                         depth_stencil: depth_stencil_state,
                         multisample: Default::default(),
                         fragment,
@@ -62,4 +73,43 @@ impl PipelineCache {
             }
         }
     }
+
+    /// Same as [`PipelineCache::get_or_create_graphic_pipeline`], for compute pipelines.
+    pub fn get_or_create_compute_pipeline(
+        &mut self,
+        device: &wgpu::Device,
+        shader: &ComputeShader,
+    ) -> anyhow::Result<wgpu::ComputePipeline> {
+        let mut hasher = DefaultHasher::new();
+        shader.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        match self.compute_pipelines.entry(hash) {
+            Entry::Occupied(pipeline) => {
+                Ok(pipeline.get().clone())
+            }
+            Entry::Vacant(entry) => {
+                let module = shader.create_shader_module(
+                    device,
+                    Default::default(),
+                )?;
+
+                let layout = shader.create_pipeline_layout(device);
+
+                let pipeline = device.create_compute_pipeline(
+                    &wgpu::ComputePipelineDescriptor {
+                        label: Some(shader.name()),
+                        layout: Some(&layout),
+                        module: &module,
+                        entry_point: Some(shader.entry_name()),
+                        compilation_options: Default::default(),
+                        cache: None,
+                    }
+                );
+
+                entry.insert(pipeline.clone());
+                Ok(pipeline)
+            }
+        }
+    }
 }
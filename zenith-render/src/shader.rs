@@ -8,6 +8,34 @@ pub const SHADER_ASSET_ABSOLUTE_DIR: &str = include_absolute_path::include_absol
 /// Define a shader entry which located in zenith-build/shader/.
 #[macro_export]
 macro_rules! define_shader {
+    // Shader whose fragment stage is NOT `fs_main`, `$fragment` is the generated entry function (e.g. `fs_main_emissive_entry`).
+    ($(let $name:ident = Graphic($module:ident, $path:expr, $entry:expr, $step_mode:expr, fragment = $fragment:ident, $num_color_outputs:expr, $num_bindgroup:expr)),*) => {
+        $(
+            let vs_entry = zenith_build::$module::vs_main_entry($step_mode);
+            let dummy_targets: [Option<wgpu::ColorTargetState>; $num_color_outputs] = [None; $num_color_outputs];
+            let ps_entry = zenith_build::$module::$fragment(dummy_targets);
+            let mut bind_group_layouts: SmallVec<[wgpu::BindGroupLayoutDescriptor<'static>; 4]> = SmallVec::new();
+            $crate::seq!(N in 0..$num_bindgroup {
+                bind_group_layouts.push(zenith_build::$module::WgpuBindGroup~N::LAYOUT_DESCRIPTOR);
+            });
+
+            let $name = GraphicShader::new(
+                $path,
+                $entry,
+
+                vs_entry.entry_point,
+                vs_entry.buffers.to_vec(),
+                vs_entry.constants.to_vec(),
+
+                ps_entry.entry_point,
+                ps_entry.constants.to_vec(),
+                ps_entry.targets.len() as u32,
+                false,
+
+                bind_group_layouts,
+            );
+        )*
+    };
     ($(let $name:ident = Graphic($module:ident, $path:expr, $entry:expr, $step_mode:expr, $num_color_outputs:expr, $num_bindgroup:expr)),*) => {
         $(
             let vs_entry = zenith_build::$module::vs_main_entry($step_mode);
@@ -35,6 +63,82 @@ macro_rules! define_shader {
             );
         )*
     };
+    // Shader without any vertex input (e.g. fullscreen pass using vertex index).
+    ($(let $name:ident = Graphic($module:ident, $path:expr, $entry:expr, $num_color_outputs:expr, $num_bindgroup:expr)),*) => {
+        $(
+            let vs_entry = zenith_build::$module::vs_main_entry();
+            let dummy_targets: [Option<wgpu::ColorTargetState>; $num_color_outputs] = [None; $num_color_outputs];
+            let ps_entry = zenith_build::$module::fs_main_entry(dummy_targets);
+            let mut bind_group_layouts: SmallVec<[wgpu::BindGroupLayoutDescriptor<'static>; 4]> = SmallVec::new();
+            $crate::seq!(N in 0..$num_bindgroup {
+                bind_group_layouts.push(zenith_build::$module::WgpuBindGroup~N::LAYOUT_DESCRIPTOR);
+            });
+
+            let $name = GraphicShader::new(
+                $path,
+                $entry,
+
+                vs_entry.entry_point,
+                vs_entry.buffers.to_vec(),
+                vs_entry.constants.to_vec(),
+
+                ps_entry.entry_point,
+                ps_entry.constants.to_vec(),
+                ps_entry.targets.len() as u32,
+                false,
+
+                bind_group_layouts,
+            );
+        )*
+    };
+    // Shader without fragment stage (e.g. depth-only shadow pass).
+    ($(let $name:ident = DepthOnly($module:ident, $path:expr, $entry:expr, $step_mode:expr, $num_bindgroup:expr)),*) => {
+        $(
+            let vs_entry = zenith_build::$module::vs_main_entry($step_mode);
+            let mut bind_group_layouts: SmallVec<[wgpu::BindGroupLayoutDescriptor<'static>; 4]> = SmallVec::new();
+            $crate::seq!(N in 0..$num_bindgroup {
+                bind_group_layouts.push(zenith_build::$module::WgpuBindGroup~N::LAYOUT_DESCRIPTOR);
+            });
+
+            let $name = GraphicShader::new(
+                $path,
+                $entry,
+
+                vs_entry.entry_point,
+                vs_entry.buffers.to_vec(),
+                vs_entry.constants.to_vec(),
+
+                "",
+                vec![],
+                0,
+                true,
+
+                bind_group_layouts,
+            );
+        )*
+    };
+    // Compute shader, its entry must be named `cs_main`.
+    ($(let $name:ident = Compute($module:ident, $path:expr, $entry:expr, $num_bindgroup:expr)),*) => {
+        $(
+            let mut bind_group_layouts: SmallVec<[wgpu::BindGroupLayoutDescriptor<'static>; 4]> = SmallVec::new();
+            $crate::seq!(N in 0..$num_bindgroup {
+                bind_group_layouts.push(zenith_build::$module::WgpuBindGroup~N::LAYOUT_DESCRIPTOR);
+            });
+
+            let $name = ComputeShader::new(
+                $path,
+                $entry,
+                "cs_main",
+                bind_group_layouts,
+            );
+        )*
+    };
+}
+
+/// Bind group layout of a shader, entries are owned so that they can be tweaked after reflection.
+struct BindGroupLayoutEntries {
+    label: Option<&'static str>,
+    entries: Vec<wgpu::BindGroupLayoutEntry>,
 }
 
 /// A shader object corresponds to a graphic pipeline.
@@ -49,10 +153,11 @@ pub struct GraphicShader {
     fragment_entry: String,
     fragment_constants: Vec<(&'static str, f64)>,
 
-    bind_group_layouts: SmallVec<[wgpu::BindGroupLayoutDescriptor<'static>; 4]>,
+    bind_group_layouts: SmallVec<[BindGroupLayoutEntries; 4]>,
+    push_constant_ranges: Vec<wgpu::PushConstantRange>,
 
     num_color_targets: u32,
-    _has_depth_stencil: bool,
+    has_depth_stencil: bool,
 }
 
 impl GraphicShader {
@@ -67,7 +172,7 @@ impl GraphicShader {
         vertex_constants: Vec<(&'static str, f64)>,
 
         num_color_targets: u32,
-        _has_depth_stencil: bool,
+        has_depth_stencil: bool,
 
         bind_group_layouts: SmallVec<[wgpu::BindGroupLayoutDescriptor<'static>; 4]>,
     ) -> anyhow::Result<Self> {
@@ -81,11 +186,60 @@ impl GraphicShader {
             fragment_entry: fragment_entry.to_owned(),
             fragment_constants,
             num_color_targets,
-            _has_depth_stencil,
-            bind_group_layouts,
+            has_depth_stencil,
+            bind_group_layouts: bind_group_layouts
+                .into_iter()
+                .map(|desc| BindGroupLayoutEntries {
+                    label: desc.label,
+                    entries: desc.entries.to_vec(),
+                })
+                .collect(),
+            push_constant_ranges: vec![],
         })
     }
 
+    /// Declare a push constant range used by this shader.
+    ///
+    /// Requires `wgpu::Features::PUSH_CONSTANTS`, pipeline creation fails on devices without it.
+    /// As a fallback, pass the data through a (dynamic offset) uniform buffer instead.
+    pub fn with_push_constants(mut self, stages: wgpu::ShaderStages, range: std::ops::Range<u32>) -> Self {
+        self.push_constant_ranges.push(wgpu::PushConstantRange {
+            stages,
+            range,
+        });
+        self
+    }
+
+    /// Return all push constant ranges declared in this shader.
+    pub fn push_constant_ranges(&self) -> &[wgpu::PushConstantRange] {
+        &self.push_constant_ranges
+    }
+
+    /// Make a buffer binding use dynamic offset, `binding_size` bytes are visible to the shader from the offset.
+    /// Panic if the binding does NOT exist or is NOT a buffer binding.
+    pub fn with_dynamic_offset(mut self, group: u32, binding: u32, binding_size: wgpu::BufferAddress) -> Self {
+        let entry = self.bind_group_layouts
+            .get_mut(group as usize)
+            .and_then(|layout| layout.entries.iter_mut().find(|entry| entry.binding == binding))
+            .unwrap_or_else(|| panic!("Binding {} of group {} does NOT exist in shader[{}]", binding, group, self.name));
+
+        match &mut entry.ty {
+            wgpu::BindingType::Buffer { has_dynamic_offset, min_binding_size, .. } => {
+                *has_dynamic_offset = true;
+                *min_binding_size = wgpu::BufferSize::new(binding_size);
+            }
+            _ => panic!("Only buffer binding can have dynamic offset, shader[{}] group {} binding {}", self.name, group, binding),
+        }
+
+        self
+    }
+
+    /// Declare that this shader is used with a depth stencil attachment (depth only shaders always are).
+    pub fn with_depth_stencil(mut self) -> Self {
+        self.has_depth_stencil = true;
+        self
+    }
+
     /// Return the name of this shader.
     pub fn name(&self) -> &str {
         &self.name
@@ -123,7 +277,19 @@ impl GraphicShader {
 
     /// Create a graphic pipeline layout used in this shader.
     pub fn create_pipeline_layout(&self, device: &wgpu::Device) -> wgpu::PipelineLayout {
-        self.reflection_info.create_pipeline_layout(device)
+        // NOT use the reflected pipeline layout, bind group layouts may be modified (e.g. dynamic offset)
+        let bind_group_layouts = (0..self.num_bind_groups())
+            .filter_map(|group| self.create_bind_group_layout(device, group))
+            .collect::<SmallVec<[wgpu::BindGroupLayout; 4]>>();
+        let bind_group_layouts = bind_group_layouts
+            .iter()
+            .collect::<SmallVec<[&wgpu::BindGroupLayout; 4]>>();
+
+        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(&self.name),
+            bind_group_layouts: &bind_group_layouts,
+            push_constant_ranges: &self.push_constant_ranges,
+        })
     }
 
     /// Create a shader module.
@@ -132,22 +298,12 @@ impl GraphicShader {
         device: &wgpu::Device,
         shader_defs: std::collections::HashMap<String, naga_oil::compose::ShaderDefValue>,
     ) -> Result<wgpu::ShaderModule, naga_oil::compose::ComposerError> {
-        self.reflection_info.create_shader_module_relative_path(
-            device,
-            SHADER_ASSET_ABSOLUTE_DIR,
-            self.reflection_info,
-            shader_defs,
-            |path| {
-                let path = PathBuf::from(path);
-                path.canonicalize()?;
-                std::fs::read_to_string(path)
-            }
-        )
+        create_shader_module(device, self.reflection_info, shader_defs)
     }
 
     /// Create shader resources bindings for this shader.
     pub fn create_bind_group_layout(&self, device: &wgpu::Device, group: u32) -> Option<wgpu::BindGroupLayout> {
-        self.bind_group_layouts.get(group as usize).map(|binding| device.create_bind_group_layout(binding))
+        create_bind_group_layout(device, &self.bind_group_layouts, group)
     }
 
     /// Return the relative path of this shader. (Relative to zenith-build/shader/)
@@ -155,6 +311,16 @@ impl GraphicShader {
         self.reflection_info.relative_path()
     }
 
+    /// Return the number of color outputs of the fragment stage.
+    pub fn num_color_targets(&self) -> u32 {
+        self.num_color_targets
+    }
+
+    /// Return true if this shader expects a depth stencil attachment.
+    pub fn has_depth_stencil(&self) -> bool {
+        self.has_depth_stencil
+    }
+
     /// Return the number of bind group used in this shader.
     pub fn num_bind_groups(&self) -> u32 {
         self.bind_group_layouts.len() as u32
@@ -165,6 +331,11 @@ impl GraphicShader {
         self.bind_group_layouts.get(group as usize).map(|binding| binding.entries.len() as u32)
     }
 
+    /// Return the binding type declared in this shader, e.g. to tell a comparison sampler from a filtering one.
+    pub fn binding_type(&self, group: u32, binding: u32) -> Option<wgpu::BindingType> {
+        binding_type(&self.bind_group_layouts, group, binding)
+    }
+
     /// Return the vertex shader entry name.
     pub fn vertex_entry_name(&self) -> &str {
         &self.vertex_entry
@@ -178,6 +349,136 @@ impl GraphicShader {
 
 impl Hash for GraphicShader {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        self.name.hash(state)
+        // shaders of the same file may use different fragment entries
+        self.name.hash(state);
+        self.fragment_entry.hash(state);
     }
-}
\ No newline at end of file
+}
+
+/// A shader object corresponds to a compute pipeline.
+pub struct ComputeShader {
+    name: String,
+    reflection_info: ShaderEntry,
+    entry: String,
+    bind_group_layouts: SmallVec<[BindGroupLayoutEntries; 4]>,
+}
+
+impl ComputeShader {
+    pub fn new(
+        name: &str,
+        reflection_info: ShaderEntry,
+        entry: &str,
+        bind_group_layouts: SmallVec<[wgpu::BindGroupLayoutDescriptor<'static>; 4]>,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            name: name.to_owned(),
+            reflection_info,
+            entry: entry.to_owned(),
+            bind_group_layouts: bind_group_layouts
+                .into_iter()
+                .map(|desc| BindGroupLayoutEntries {
+                    label: desc.label,
+                    entries: desc.entries.to_vec(),
+                })
+                .collect(),
+        })
+    }
+
+    /// Return the name of this shader.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Return the compute shader entry name.
+    pub fn entry_name(&self) -> &str {
+        &self.entry
+    }
+
+    /// Create a compute pipeline layout used in this shader.
+    pub fn create_pipeline_layout(&self, device: &wgpu::Device) -> wgpu::PipelineLayout {
+        let bind_group_layouts = (0..self.num_bind_groups())
+            .filter_map(|group| self.create_bind_group_layout(device, group))
+            .collect::<SmallVec<[wgpu::BindGroupLayout; 4]>>();
+        let bind_group_layouts = bind_group_layouts
+            .iter()
+            .collect::<SmallVec<[&wgpu::BindGroupLayout; 4]>>();
+
+        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(&self.name),
+            bind_group_layouts: &bind_group_layouts,
+            push_constant_ranges: &[],
+        })
+    }
+
+    /// Create a shader module.
+    pub fn create_shader_module(
+        &self,
+        device: &wgpu::Device,
+        shader_defs: std::collections::HashMap<String, naga_oil::compose::ShaderDefValue>,
+    ) -> Result<wgpu::ShaderModule, naga_oil::compose::ComposerError> {
+        create_shader_module(device, self.reflection_info, shader_defs)
+    }
+
+    /// Create shader resources bindings for this shader.
+    pub fn create_bind_group_layout(&self, device: &wgpu::Device, group: u32) -> Option<wgpu::BindGroupLayout> {
+        create_bind_group_layout(device, &self.bind_group_layouts, group)
+    }
+
+    /// Return the number of bind group used in this shader.
+    pub fn num_bind_groups(&self) -> u32 {
+        self.bind_group_layouts.len() as u32
+    }
+
+    /// Return the number of bindings (all bind groups) used in this shader.
+    pub fn num_bindings(&self, group: u32) -> Option<u32> {
+        self.bind_group_layouts.get(group as usize).map(|binding| binding.entries.len() as u32)
+    }
+
+    /// Return the binding type declared in this shader.
+    pub fn binding_type(&self, group: u32, binding: u32) -> Option<wgpu::BindingType> {
+        binding_type(&self.bind_group_layouts, group, binding)
+    }
+}
+
+impl Hash for ComputeShader {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+        self.entry.hash(state);
+    }
+}
+
+fn create_shader_module(
+    device: &wgpu::Device,
+    reflection_info: ShaderEntry,
+    shader_defs: std::collections::HashMap<String, naga_oil::compose::ShaderDefValue>,
+) -> Result<wgpu::ShaderModule, naga_oil::compose::ComposerError> {
+    reflection_info.create_shader_module_relative_path(
+        device,
+        SHADER_ASSET_ABSOLUTE_DIR,
+        reflection_info,
+        shader_defs,
+        |path| {
+            let path = PathBuf::from(path);
+            path.canonicalize()?;
+            std::fs::read_to_string(path)
+        }
+    )
+}
+
+fn create_bind_group_layout(device: &wgpu::Device, layouts: &[BindGroupLayoutEntries], group: u32) -> Option<wgpu::BindGroupLayout> {
+    layouts.get(group as usize).map(|layout| {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: layout.label,
+            entries: &layout.entries,
+        })
+    })
+}
+
+fn binding_type(layouts: &[BindGroupLayoutEntries], group: u32, binding: u32) -> Option<wgpu::BindingType> {
+    layouts
+        .get(group as usize)?
+        .entries
+        .iter()
+        .find(|entry| entry.binding == binding)
+        .map(|entry| entry.ty)
+}
@@ -0,0 +1,63 @@
+use std::sync::Arc;
+use zenith_build::{ShaderEntry};
+use zenith_core::collections::SmallVec;
+use zenith_render::{define_shader, GraphicShader, RenderDevice};
+use zenith_rendergraph::{RenderGraphBuilder, RenderGraphResource, RenderResource, Sampler, SamplerDesc, Texture, Viewport};
+
+/// Copy a texture into another one by a fullscreen pass.
+/// Unlike a texture copy, source and destination can differ in size and format.
+pub struct BlitRenderer {
+    shader: Arc<GraphicShader>,
+    sampler: RenderResource<Sampler>,
+}
+
+impl BlitRenderer {
+    /// Create a passthrough blit.
+    pub fn new(device: &RenderDevice) -> Self {
+        define_shader! {
+            let shader = Graphic(blit, "blit.wgsl", ShaderEntry::Blit, 1, 1)
+        }
+
+        Self::with_shader(device, Arc::new(shader.unwrap()))
+    }
+
+    /// Create a blit with custom fragment logic (e.g. tonemapping), see [`RenderGraphBuilder::add_blit_node`] for the shader interface.
+    pub fn with_shader(device: &RenderDevice, shader: Arc<GraphicShader>) -> Self {
+        let sampler = device.device().create_sampler(&SamplerDesc {
+            label: Some("blit sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            shader,
+            sampler: RenderResource::new(sampler),
+        }
+    }
+
+    pub fn build_render_graph(
+        &self,
+        builder: &mut RenderGraphBuilder,
+        src: &RenderGraphResource<Texture>,
+        dst: &mut RenderGraphResource<Texture>,
+    ) {
+        builder.add_blit_node("blit", src, dst, self.shader.clone(), &self.sampler);
+    }
+
+    /// Blit into the `viewport` region of `dst`, see [`RenderGraphBuilder::add_viewport_blit_node`].
+    pub fn build_viewport_render_graph(
+        &self,
+        builder: &mut RenderGraphBuilder,
+        name: &str,
+        src: &RenderGraphResource<Texture>,
+        dst: &mut RenderGraphResource<Texture>,
+        viewport: Viewport,
+        load_op: wgpu::LoadOp<wgpu::Color>,
+    ) {
+        builder.add_viewport_blit_node(name, src, dst, self.shader.clone(), &self.sampler, viewport, load_op);
+    }
+}
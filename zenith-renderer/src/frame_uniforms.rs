@@ -0,0 +1,106 @@
+use glam::{Mat4, Vec3};
+use zenith_core::camera::Camera;
+use zenith_render::RenderDevice;
+use zenith_rendergraph::{Buffer, BufferState, RenderGraphBuilder, RenderGraphResource, RenderResource};
+
+/// Per-frame data shared by all renderers, bound at group 0 binding 0 of their shaders.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameUniforms {
+    pub view: Mat4,
+    pub projection: Mat4,
+    pub view_projection: Mat4,
+    pub camera_position: Vec3,
+    /// Seconds the engine has run, stops while paused.
+    pub time_seconds: f32,
+    /// Time of the last tick in seconds.
+    pub delta_seconds: f32,
+    /// The struct is 16 bytes aligned in WGSL, make the tail padding explicit so it is Pod.
+    _padding: [f32; 3],
+}
+
+// same layout as FrameUniforms declared in the shaders, no implicit padding bytes
+const _: () = assert!(size_of::<FrameUniforms>() == 224);
+unsafe impl bytemuck::Zeroable for FrameUniforms {}
+unsafe impl bytemuck::Pod for FrameUniforms {}
+
+impl Default for FrameUniforms {
+    fn default() -> Self {
+        Self {
+            view: Mat4::IDENTITY,
+            projection: Mat4::IDENTITY,
+            view_projection: Mat4::IDENTITY,
+            camera_position: Vec3::ZERO,
+            time_seconds: 0.,
+            delta_seconds: 0.,
+            _padding: [0.; 3],
+        }
+    }
+}
+
+impl FrameUniforms {
+    pub fn from_camera(camera: &Camera, time_seconds: f32, delta_seconds: f32) -> Self {
+        Self {
+            view: camera.view(),
+            projection: camera.projection(),
+            view_projection: camera.view_projection(),
+            camera_position: camera.location(),
+            time_seconds,
+            delta_seconds,
+            _padding: [0.; 3],
+        }
+    }
+}
+
+/// Uniform buffer holding [`FrameUniforms`], created once and uploaded every frame.
+pub struct FrameUniformBuffer {
+    buffer: RenderResource<Buffer>,
+    uniforms: FrameUniforms,
+}
+
+impl FrameUniformBuffer {
+    pub fn new(device: &RenderDevice) -> Self {
+        let buffer = device.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("frame uniforms"),
+            size: size_of::<FrameUniforms>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            buffer: RenderResource::new(buffer),
+            uniforms: FrameUniforms::default(),
+        }
+    }
+
+    /// View and projection of the following frames, frames without a camera use identity matrices.
+    pub fn set_camera(&mut self, camera: &Camera) {
+        self.uniforms = FrameUniforms::from_camera(camera, self.uniforms.time_seconds, self.uniforms.delta_seconds);
+    }
+
+    pub fn set_time(&mut self, time_seconds: f32, delta_seconds: f32) {
+        self.uniforms.time_seconds = time_seconds;
+        self.uniforms.delta_seconds = delta_seconds;
+    }
+
+    #[inline]
+    pub fn uniforms(&self) -> &FrameUniforms {
+        &self.uniforms
+    }
+
+    /// Import the buffer into the graph with a node uploading the current uniforms, readable by any later node.
+    /// Apps can own more than one, e.g. one per camera of a split-screen.
+    pub fn import(&self, builder: &mut RenderGraphBuilder) -> RenderGraphResource<Buffer> {
+        let mut buffer = builder.import("frame.uniforms", self.buffer.clone(), BufferState::UNIFORM);
+
+        let mut node = builder.add_lambda_node("upload_frame_uniforms");
+        let upload = node.write(&mut buffer, BufferState::COPY_DST);
+
+        let uniforms = self.uniforms;
+        node.execute(move |ctx, _| {
+            ctx.write_buffer(&upload, 0, uniforms);
+        });
+
+        buffer
+    }
+}
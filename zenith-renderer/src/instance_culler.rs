@@ -0,0 +1,255 @@
+use std::ops::Range;
+use std::sync::Arc;
+use glam::{Mat4, Vec4};
+use zenith_build::ShaderEntry;
+use zenith_build::instance_cull::{BatchBounds, CullUniforms};
+use zenith_core::collections::SmallVec;
+use zenith_core::math::{Aabb, Frustum};
+use zenith_render::{define_shader, ComputeShader};
+use zenith_rendergraph::{Buffer, BufferDesc, RenderGraphBuilder, RenderGraphResource};
+
+/// Invocations per workgroup of instance_cull.wgsl.
+const WORKGROUP_SIZE: u32 = 64;
+
+/// Size of the indirect arguments of a batch, laid out as `wgpu::util::DrawIndexedIndirectArgs`.
+pub const DRAW_INDEXED_INDIRECT_ARGS_SIZE: wgpu::BufferAddress = size_of::<[u32; 5]>() as wgpu::BufferAddress;
+
+/// Instances of a mesh drawn together, consecutive in the instance buffer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CullBatch {
+    /// Bounds of the mesh in mesh space.
+    pub bounds: Aabb,
+    pub index_count: u32,
+    /// Range of the batch in the instance buffer. Its visible instances are packed from the start of the same range in the visible buffer.
+    pub instances: Range<u32>,
+}
+
+/// Buffers written by [`InstanceCuller::add_cull_node`].
+pub struct CulledInstances {
+    /// Indirect arguments of each batch at `batch * DRAW_INDEXED_INDIRECT_ARGS_SIZE`, the first instance is the start of the batch.
+    pub draw_args: RenderGraphResource<Buffer>,
+    /// Indices of the visible instances, read by instanced_mesh.wgsl.
+    pub visible: RenderGraphResource<Buffer>,
+}
+
+/// Cull the instances of each batch against the frustum on the CPU, with the same test as [`InstanceCuller`].
+/// Return the indices of the visible instances packed from the start of their batch, and the visible count of each batch.
+/// Slots past the count of a batch are zero.
+pub fn cull_instances(frustum: &Frustum, batches: &[CullBatch], transforms: &[Mat4]) -> (Vec<u32>, Vec<u32>) {
+    let mut visible = vec![0; transforms.len()];
+    let counts = batches
+        .iter()
+        .map(|batch| {
+            let mut count = 0;
+            for index in batch.instances.clone() {
+                if frustum.intersects_aabb(&batch.bounds.transformed(transforms[index as usize])) {
+                    visible[(batch.instances.start + count) as usize] = index;
+                    count += 1;
+                }
+            }
+            count
+        })
+        .collect();
+
+    (visible, counts)
+}
+
+/// Frustum culling of instances in a compute node, whose results are drawn with `draw_indexed_indirect` without any readback.
+/// Cheaper than [`cull_instances`] for very large instance counts.
+pub struct InstanceCuller {
+    shader: Arc<ComputeShader>,
+}
+
+impl Default for InstanceCuller {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InstanceCuller {
+    pub fn new() -> Self {
+        define_shader! {
+            let shader = Compute(instance_cull, "instance_cull.wgsl", ShaderEntry::InstanceCull, 1)
+        }
+
+        Self {
+            shader: Arc::new(shader.unwrap()),
+        }
+    }
+
+    /// Add a compute node culling the instances of `batches` against `frustum`.
+    /// `instances` holds the `InstanceData` of instanced_mesh.wgsl of all batches, and must be filled before the graph is executed.
+    pub fn add_cull_node(
+        &self,
+        builder: &mut RenderGraphBuilder,
+        frustum: &Frustum,
+        instances: &RenderGraphResource<Buffer>,
+        batches: &[CullBatch],
+    ) -> CulledInstances {
+        let num_instances = batches.iter().map(|batch| batch.instances.end).max().unwrap_or(0);
+
+        let mut instance_batches = vec![0u32; num_instances as usize];
+        for (index, batch) in batches.iter().enumerate() {
+            instance_batches[batch.instances.start as usize..batch.instances.end as usize].fill(index as u32);
+        }
+        let bounds = batches
+            .iter()
+            .map(|batch| BatchBounds::new(Vec4::from((batch.bounds.center(), 0.)), Vec4::from((batch.bounds.extents(), 0.))))
+            .collect::<Vec<_>>();
+        // index count, instance count, first index, base vertex and first instance, the instance count is accumulated by the shader
+        let draw_args = batches
+            .iter()
+            .map(|batch| [batch.index_count, 0, 0, 0, batch.instances.start])
+            .collect::<Vec<_>>();
+
+        // a storage binding can NOT be empty, results are copyable to be read back
+        let storage_desc = |label: &'static str, size: usize, usage: wgpu::BufferUsages| BufferDesc {
+            label: Some(label),
+            size: size.max(1) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE | usage,
+            mapped_at_creation: false,
+        };
+        let uniforms = builder.create_uniform::<CullUniforms>("instance_cull.uniforms");
+        let instance_batch_buffer = builder.create("instance_cull.instance_batches", storage_desc(
+            "instance cull batch of instances", size_of_val(instance_batches.as_slice()), wgpu::BufferUsages::COPY_DST));
+        let bounds_buffer = builder.create("instance_cull.bounds", storage_desc(
+            "instance cull batch bounds", size_of_val(bounds.as_slice()), wgpu::BufferUsages::COPY_DST));
+        let mut draw_args_buffer = builder.create("instance_cull.draw_args", storage_desc(
+            "instance cull draw args", size_of_val(draw_args.as_slice()), wgpu::BufferUsages::INDIRECT | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC));
+        let mut visible_buffer = builder.create("instance_cull.visible", storage_desc(
+            "instance cull visible instances", num_instances as usize * size_of::<u32>(), wgpu::BufferUsages::COPY_SRC));
+
+        let mut node = builder.add_compute_node("instance_cull");
+
+        let uniforms = node.read(&uniforms, wgpu::BufferUses::UNIFORM);
+        let instances = node.read(instances, wgpu::BufferUses::STORAGE_READ_ONLY);
+        let instance_batch_read = node.read(&instance_batch_buffer, wgpu::BufferUses::STORAGE_READ_ONLY);
+        let bounds_read = node.read(&bounds_buffer, wgpu::BufferUses::STORAGE_READ_ONLY);
+        let draw_args_write = node.write(&mut draw_args_buffer, wgpu::BufferUses::STORAGE_READ_WRITE);
+        let visible_write = node.write(&mut visible_buffer, wgpu::BufferUses::STORAGE_READ_WRITE);
+
+        node.setup_pipeline().with_shader(self.shader.clone());
+
+        let planes = *frustum.planes();
+        node.execute(move |ctx, encoder| {
+            ctx.write_uniform(&uniforms, &CullUniforms::new(planes, num_instances));
+            if !draw_args.is_empty() {
+                ctx.write_buffer_slice(&instance_batch_read, 0, &instance_batches);
+                ctx.write_buffer_slice(&bounds_read, 0, &bounds);
+                ctx.write_buffer_slice(&draw_args_write, 0, &draw_args);
+            }
+
+            let uniform_buffer = ctx.get_buffer(&uniforms);
+            let instance_buffer = ctx.get_buffer(&instances);
+            let instance_batch_buffer = ctx.get_buffer(&instance_batch_read);
+            let bounds_buffer = ctx.get_buffer(&bounds_read);
+            let draw_args_buffer = ctx.get_buffer(&draw_args_write);
+            let visible_buffer = ctx.get_buffer(&visible_write);
+
+            let mut compute_pass = ctx.begin_compute_pass(encoder);
+
+            ctx.bind_pipeline(&mut compute_pass)
+                .with_binding(0, 0, uniform_buffer.as_entire_binding())
+                .with_binding(0, 1, instance_buffer.as_entire_binding())
+                .with_binding(0, 2, instance_batch_buffer.as_entire_binding())
+                .with_binding(0, 3, bounds_buffer.as_entire_binding())
+                .with_binding(0, 4, draw_args_buffer.as_entire_binding())
+                .with_binding(0, 5, visible_buffer.as_entire_binding())
+                .bind();
+
+            compute_pass.dispatch_workgroups(num_instances.div_ceil(WORKGROUP_SIZE), 1, 1);
+        });
+
+        CulledInstances {
+            draw_args: draw_args_buffer,
+            visible: visible_buffer,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Quat, Vec3};
+    use wgpu::util::DeviceExt;
+    use zenith_build::instanced_mesh::InstanceData;
+    use zenith_render::RenderDevice;
+    use zenith_rendergraph::RenderResource;
+    use super::*;
+
+    #[test]
+    #[ignore = "needs a GPU adapter, run with --ignored"]
+    fn gpu_culling_matches_cpu() {
+        let render_device = RenderDevice::headless(Default::default()).expect("No adapter to run GPU tests on");
+        let (device, queue) = (render_device.device(), render_device.queue());
+
+        // two meshes interleaved on a grid around the camera, many of them straddling the frustum planes
+        let batches = [
+            CullBatch { bounds: Aabb::new(Vec3::splat(-0.5), Vec3::splat(0.5)), index_count: 36, instances: 0..200 },
+            CullBatch { bounds: Aabb::new(Vec3::new(-2., 0., -1.), Vec3::new(2., 3., 1.)), index_count: 12, instances: 200..400 },
+        ];
+        let transforms = (0..400)
+            .map(|index| {
+                let cell = (index % 200) * 2 + index / 200;
+                let (x, z) = ((cell % 20) as f32 * 3. - 30., (cell / 20) as f32 * 3. - 30.);
+                Mat4::from_rotation_translation(Quat::from_rotation_y(index as f32 * 0.1), Vec3::new(x, 0., z))
+            })
+            .collect::<Vec<_>>();
+        let view = Mat4::look_to_rh(Vec3::new(0., 1., 0.), Vec3::new(0.3, -0.1, -1.), Vec3::Y);
+        let projection = Mat4::perspective_infinite_reverse_rh(std::f32::consts::FRAC_PI_3, 1.5, 0.1);
+        let frustum = Frustum::from_view_projection(projection * view);
+
+        let (cpu_visible, cpu_counts) = cull_instances(&frustum, &batches, &transforms);
+        assert!(cpu_counts.iter().all(|count| *count > 0 && *count < 200), "{:?}", cpu_counts);
+
+        let instances = transforms.iter().map(|transform| InstanceData::new(*transform, Vec4::ONE)).collect::<Vec<_>>();
+        let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("instances"),
+            contents: bytemuck::cast_slice(&instances),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let readback = |size: usize| device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("readback"),
+            size: size as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let draw_args_readback = readback(batches.len() * DRAW_INDEXED_INDIRECT_ARGS_SIZE as usize);
+        let visible_readback = readback(transforms.len() * size_of::<u32>());
+
+        let mut builder = RenderGraphBuilder::new();
+        let instances = builder.import("instances", RenderResource::new(instance_buffer), wgpu::BufferUses::STORAGE_READ_ONLY);
+        let culled = InstanceCuller::new().add_cull_node(&mut builder, &frustum, &instances, &batches);
+
+        let mut node = builder.add_lambda_node("readback");
+        let draw_args = node.read(&culled.draw_args, wgpu::BufferUses::COPY_SRC);
+        let visible = node.read(&culled.visible, wgpu::BufferUses::COPY_SRC);
+        let (draw_args_dst, visible_dst) = (draw_args_readback.clone(), visible_readback.clone());
+        node.execute(move |ctx, encoder| {
+            encoder.copy_buffer_to_buffer(&ctx.get_buffer(&draw_args), 0, &draw_args_dst, 0, draw_args_dst.size());
+            encoder.copy_buffer_to_buffer(&ctx.get_buffer(&visible), 0, &visible_dst, 0, visible_dst.size());
+        });
+
+        let mut pipeline_cache = zenith_render::PipelineCache::new();
+        builder.build(device).compile(device, &mut pipeline_cache).unwrap().execute(device, queue).unwrap();
+
+        draw_args_readback.slice(..).map_async(wgpu::MapMode::Read, |_| {});
+        visible_readback.slice(..).map_async(wgpu::MapMode::Read, |_| {});
+        device.poll(wgpu::PollType::Wait).unwrap();
+        let gpu_draw_args: Vec<u32> = bytemuck::cast_slice(&draw_args_readback.slice(..).get_mapped_range()).to_vec();
+        let gpu_visible: Vec<u32> = bytemuck::cast_slice(&visible_readback.slice(..).get_mapped_range()).to_vec();
+
+        for (index, batch) in batches.iter().enumerate() {
+            let args = &gpu_draw_args[index * 5..index * 5 + 5];
+            assert_eq!(args, [batch.index_count, cpu_counts[index], 0, 0, batch.instances.start]);
+
+            // the GPU appends visible instances in any order
+            let packed = |visible: &[u32]| {
+                let start = batch.instances.start as usize;
+                let mut packed = visible[start..start + cpu_counts[index] as usize].to_vec();
+                packed.sort_unstable();
+                packed
+            };
+            assert_eq!(packed(&gpu_visible), packed(&cpu_visible), "batch {}", index);
+        }
+    }
+}
@@ -0,0 +1,330 @@
+use std::sync::Arc;
+use glam::{Mat4, Vec3, Vec4};
+use wgpu::util::DeviceExt;
+use zenith_asset::AssetUrl;
+use zenith_build::ShaderEntry;
+use zenith_build::instanced_mesh::{InstanceData, LightUniforms};
+use zenith_core::camera::Camera;
+use zenith_core::collections::SmallVec;
+use zenith_core::collections::hashmap::HashMap;
+use zenith_core::log::{warn, target::RENDER};
+use zenith_core::math::Aabb;
+use zenith_render::{define_shader, GraphicShader, RenderDevice};
+use zenith_rendergraph::{Buffer, BufferDesc, ColorInfoBuilder, RenderGraphBuilder, RenderGraphResource, RenderResource, Texture, TextureDesc};
+use crate::{cull_instances, CullBatch, DirectionalLight, InstanceCuller, MeshScene, SimpleMeshRenderer, DRAW_INDEXED_INDIRECT_ARGS_SIZE};
+
+/// Light added to the diffuse term of every instance.
+const AMBIENT: f32 = 0.05;
+
+/// Buffers of a mesh shared by all of its instances.
+struct InstancedMesh {
+    vertex_buffer: RenderResource<Buffer>,
+    index_buffer: RenderResource<Buffer>,
+    index_format: wgpu::IndexFormat,
+    index_count: u32,
+    /// Bounds in mesh space.
+    bounds: Aabb,
+}
+
+/// Instance counts of the last graph built.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InstanceStats {
+    /// Mesh nodes of the scene whose mesh is loaded.
+    pub total: usize,
+    /// Instances inside the camera frustum. Equal to `total` when culled on the GPU, whose result is NOT read back.
+    pub visible: usize,
+    /// Instanced draws, one per mesh with visible instances, or one indirect draw per mesh when culled on the GPU.
+    pub draws: usize,
+}
+
+/// Draw the mesh nodes of a [`MeshScene`] with one instanced draw per mesh, skipping nodes whose bounds are outside of the camera frustum.
+/// Meant for many nodes sharing few meshes. Materials only tint the instances by their base color factor, textures are NOT sampled
+/// and there is no shadow, use [`crate::SceneRenderer`] for fully shaded meshes.
+///
+/// Nodes are culled on the CPU by default, see [`InstancedSceneRenderer::set_gpu_culling`] to cull them in a compute node instead.
+/// [`crate::SceneRenderer`] does NOT cull at all.
+pub struct InstancedSceneRenderer {
+    shader: Arc<GraphicShader>,
+    culler: InstanceCuller,
+    meshes: HashMap<AssetUrl, InstancedMesh>,
+    output_format: wgpu::TextureFormat,
+    light: DirectionalLight,
+    gpu_culling: bool,
+    supports_gpu_culling: bool,
+    stats: InstanceStats,
+}
+
+impl InstancedSceneRenderer {
+    pub fn new(device: &RenderDevice, scene: &MeshScene) -> Self {
+        define_shader! {
+            let shader = Graphic(instanced_mesh, "instanced_mesh.wgsl", ShaderEntry::InstancedMesh, wgpu::VertexStepMode::Vertex, 1, 2)
+        }
+
+        let mut renderer = Self {
+            shader: Arc::new(shader.unwrap().with_depth_stencil()),
+            culler: InstanceCuller::new(),
+            meshes: HashMap::default(),
+            output_format: device.output_format(),
+            light: DirectionalLight::default(),
+            gpu_culling: false,
+            // indirect draws start at the first instance of their mesh
+            supports_gpu_culling: device.supports(wgpu::Features::INDIRECT_FIRST_INSTANCE),
+            stats: InstanceStats::default(),
+        };
+        renderer.update(device, scene);
+        renderer
+    }
+
+    /// Upload the meshes of the scene loaded since the last update, nodes of meshes NOT uploaded yet are skipped.
+    pub fn update(&mut self, device: &RenderDevice, scene: &MeshScene) {
+        for (_, node) in scene.payloads() {
+            if self.meshes.contains_key(node.mesh.url()) {
+                continue;
+            }
+            let Some(mesh) = node.mesh.get() else {
+                continue;
+            };
+
+            let vertex_buffer = device.device().create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("instanced_vertex_buffer"),
+                contents: mesh.vertices_bytes(),
+                usage: wgpu::BufferUsages::VERTEX,
+            });
+            let index_buffer = device.device().create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("instanced_index_buffer"),
+                contents: mesh.indices_bytes(),
+                usage: wgpu::BufferUsages::INDEX,
+            });
+
+            self.meshes.insert(node.mesh.url().clone(), InstancedMesh {
+                vertex_buffer: RenderResource::new(vertex_buffer),
+                index_buffer: RenderResource::new(index_buffer),
+                index_format: mesh.index_format().to_wgpu_format(),
+                index_count: mesh.indices.len() as u32,
+                bounds: Aabb::from_points(mesh.vertices.iter().map(|vertex| Vec3::from_array(vertex.position))),
+            });
+        }
+    }
+
+    pub fn set_light(&mut self, light: DirectionalLight) {
+        self.light = light;
+    }
+
+    /// Cull the nodes in a compute node and draw them with indirect draws, instead of culling them on the CPU.
+    /// Requires `wgpu::Features::INDIRECT_FIRST_INSTANCE`, culling stays on the CPU without it. Return whether the GPU culls.
+    pub fn set_gpu_culling(&mut self, enabled: bool) -> bool {
+        if enabled && !self.supports_gpu_culling {
+            warn!(target: RENDER, "GPU culling requires INDIRECT_FIRST_INSTANCE, which is NOT supported by the device, cull on the CPU instead.");
+        }
+        self.gpu_culling = enabled && self.supports_gpu_culling;
+        self.gpu_culling
+    }
+
+    #[inline]
+    pub fn gpu_culling(&self) -> bool { self.gpu_culling }
+
+    #[inline]
+    pub fn stats(&self) -> InstanceStats { self.stats }
+
+    /// Draw the visible instances of the scene seen by `camera` into new color and depth targets.
+    /// `frame_uniforms` must be filled from the same camera.
+    pub fn build_render_graph(
+        &mut self,
+        builder: &mut RenderGraphBuilder,
+        frame_uniforms: &RenderGraphResource<Buffer>,
+        scene: &MeshScene,
+        camera: &Camera,
+        width: u32,
+        height: u32,
+    ) -> RenderGraphResource<Texture> {
+        let (instances, transforms, batches) = self.collect_instances(scene);
+        let frustum = camera.frustum();
+
+        let (mut output, mut depth_buffer) = self.create_targets(builder, width, height);
+        // a storage binding can NOT be empty
+        let instance_buffer = builder.create("instanced_scene.instances", BufferDesc {
+            label: Some("instanced scene instances"),
+            size: (instances.len().max(1) * size_of::<InstanceData>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let light_uniform = builder.create_uniform::<LightUniforms>("instanced_scene.light");
+
+        let cull_batches = batches.iter().map(|(_, batch)| batch.clone()).collect::<Vec<_>>();
+        let (visible_buffer, draw_args, cpu_culled) = if self.gpu_culling {
+            let culled = self.culler.add_cull_node(builder, &frustum, &instance_buffer, &cull_batches);
+            self.stats = InstanceStats {
+                total: instances.len(),
+                visible: instances.len(),
+                draws: batches.len(),
+            };
+            (culled.visible, Some(culled.draw_args), None)
+        } else {
+            let (visible, counts) = cull_instances(&frustum, &cull_batches, &transforms);
+            let visible_buffer = builder.create("instanced_scene.visible", BufferDesc {
+                label: Some("instanced scene visible instances"),
+                size: (visible.len().max(1) * size_of::<u32>()) as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            self.stats = InstanceStats {
+                total: instances.len(),
+                visible: counts.iter().sum::<u32>() as usize,
+                draws: counts.iter().filter(|count| **count > 0).count(),
+            };
+            (visible_buffer, None, Some((visible, counts)))
+        };
+
+        let mesh_buffers = batches
+            .into_iter()
+            .map(|(url, batch)| {
+                let mesh = &self.meshes[&url];
+                let vb = builder.import("instanced_scene.vertex", mesh.vertex_buffer.clone(), wgpu::BufferUses::empty());
+                let ib = builder.import("instanced_scene.index", mesh.index_buffer.clone(), wgpu::BufferUses::empty());
+                (vb, ib, mesh.index_format, batch)
+            })
+            .collect::<Vec<_>>();
+
+        let mut node = builder.add_graphic_node("instanced_scene");
+
+        let frame_uniforms = node.read(frame_uniforms, wgpu::BufferUses::UNIFORM);
+        let instance_buffer = node.read(&instance_buffer, wgpu::BufferUses::STORAGE_READ_ONLY);
+        let light_uniform = node.read(&light_uniform, wgpu::BufferUses::UNIFORM);
+        let visible_buffer = node.read(&visible_buffer, wgpu::BufferUses::STORAGE_READ_ONLY);
+        let draw_args = draw_args.map(|draw_args| node.read(&draw_args, wgpu::BufferUses::INDIRECT));
+        let mesh_buffers = mesh_buffers
+            .into_iter()
+            .map(|(vb, ib, index_format, batch)| {
+                (node.read(&vb, wgpu::BufferUses::VERTEX), node.read(&ib, wgpu::BufferUses::INDEX), index_format, batch)
+            })
+            .collect::<Vec<_>>();
+        let output_write = node.write(&mut output, wgpu::TextureUses::COLOR_TARGET);
+        let depth_write = node.write(&mut depth_buffer, wgpu::TextureUses::DEPTH_STENCIL_WRITE);
+
+        node.setup_pipeline()
+            .with_shader(self.shader.clone())
+            .with_color(output_write, ColorInfoBuilder::default()
+                .load_op(wgpu::LoadOp::Clear(Default::default()))
+                .store_op(wgpu::StoreOp::Store)
+                .build()
+                .unwrap())
+            .with_depth_stencil(depth_write, SimpleMeshRenderer::depth_stencil_info(wgpu::LoadOp::Clear(0.0)));
+
+        let light = self.light;
+        node.execute(move |ctx, encoder| {
+            // queue writes land before the commands of the graph, so the instances are also filled for the cull node
+            if !instances.is_empty() {
+                ctx.write_buffer_slice(&instance_buffer, 0, &instances);
+            }
+            if let Some((visible, _)) = cpu_culled.as_ref().filter(|(visible, _)| !visible.is_empty()) {
+                ctx.write_buffer_slice(&visible_buffer, 0, visible);
+            }
+            ctx.write_uniform(&light_uniform, &LightUniforms::new(light.direction.normalize(), light.intensity, light.color, AMBIENT));
+
+            let frame_buffer = ctx.get_buffer(&frame_uniforms);
+            let instance_buffer = ctx.get_buffer(&instance_buffer);
+            let light_buffer = ctx.get_buffer(&light_uniform);
+            let visible_buffer = ctx.get_buffer(&visible_buffer);
+            let mesh_buffers = mesh_buffers
+                .into_iter()
+                .map(|(vb, ib, index_format, batch)| {
+                    (ctx.get_buffer(&vb), ctx.get_buffer(&ib), index_format, batch)
+                })
+                .collect::<Vec<_>>();
+
+            let mut render_pass = ctx.begin_render_pass(encoder);
+
+            let mut bound = ctx.bind_pipeline(&mut render_pass)
+                .with_binding(0, 0, frame_buffer.as_entire_binding())
+                .with_binding(1, 0, instance_buffer.as_entire_binding())
+                .with_binding(1, 1, light_buffer.as_entire_binding())
+                .with_binding(1, 2, visible_buffer.as_entire_binding())
+                .bind();
+
+            for (index, (vertex_buffer, index_buffer, index_format, batch)) in mesh_buffers.into_iter().enumerate() {
+                bound.render_pass().set_vertex_buffer(0, vertex_buffer.slice(..));
+                bound.render_pass().set_index_buffer(index_buffer.slice(..), index_format);
+
+                match (&draw_args, &cpu_culled) {
+                    (Some(draw_args), _) => {
+                        bound.draw_indexed_indirect(draw_args, index as wgpu::BufferAddress * DRAW_INDEXED_INDIRECT_ARGS_SIZE);
+                    }
+                    (None, Some((_, counts))) if counts[index] > 0 => {
+                        let first_instance = batch.instances.start;
+                        bound.render_pass().draw_indexed(0..batch.index_count, 0, first_instance..first_instance + counts[index]);
+                    }
+                    _ => {}
+                }
+            }
+        });
+
+        output
+    }
+
+    /// Return the instances of all nodes whose mesh is uploaded grouped by mesh, their world transforms, and the batch of each mesh.
+    fn collect_instances(&self, scene: &MeshScene) -> (Vec<InstanceData>, Vec<Mat4>, Vec<(AssetUrl, CullBatch)>) {
+        let world_transforms = scene.world_transforms();
+
+        let mut groups: HashMap<&AssetUrl, Vec<(Mat4, Vec4)>> = HashMap::default();
+        for (id, node) in scene.payloads() {
+            if !self.meshes.contains_key(node.mesh.url()) {
+                continue;
+            }
+
+            let color = node.material.get().map_or(Vec4::ONE, |material| Vec4::from_array(material.base_color));
+            groups.entry(node.mesh.url()).or_default().push((world_transforms[id.index()], color));
+        }
+
+        let num_instances = groups.values().map(Vec::len).sum();
+        let mut instances = Vec::with_capacity(num_instances);
+        let mut transforms = Vec::with_capacity(num_instances);
+        let mut batches = Vec::with_capacity(groups.len());
+        for (url, group) in groups {
+            let mesh = &self.meshes[url];
+            let first_instance = instances.len() as u32;
+            for (transform, color) in group {
+                instances.push(InstanceData::new(transform, color));
+                transforms.push(transform);
+            }
+
+            batches.push((url.clone(), CullBatch {
+                bounds: mesh.bounds,
+                index_count: mesh.index_count,
+                instances: first_instance..instances.len() as u32,
+            }));
+        }
+
+        (instances, transforms, batches)
+    }
+
+    fn create_targets(&self, builder: &mut RenderGraphBuilder, width: u32, height: u32) -> (RenderGraphResource<Texture>, RenderGraphResource<Texture>) {
+        let size = wgpu::Extent3d {
+            width: width.max(1),
+            height: height.max(1),
+            depth_or_array_layers: 1,
+        };
+
+        let output = builder.create("instanced_scene.output", TextureDesc {
+            label: Some("instanced scene output render target"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.output_format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let depth_buffer = builder.create("instanced_scene.depth", TextureDesc {
+            label: Some("instanced scene depth buffer"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Depth32Float,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+
+        (output, depth_buffer)
+    }
+}
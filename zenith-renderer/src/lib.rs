@@ -1,5 +1,23 @@
 mod triangle_renderer;
 mod simple_mesh_renderer;
+mod skybox_renderer;
+mod blit_renderer;
+mod text_renderer;
+mod frame_uniforms;
+mod model_renderer;
+mod mip_generator;
+mod scene_renderer;
+mod instanced_scene_renderer;
+mod instance_culler;
 
 pub use triangle_renderer::TriangleRenderer;
-pub use simple_mesh_renderer::{SimpleMeshRenderer, MeshRenderData};
\ No newline at end of file
+pub use simple_mesh_renderer::{SimpleMeshRenderer, MeshRenderData, DirectionalLight, PackedMeshBuffers};
+pub use skybox_renderer::SkyboxRenderer;
+pub use blit_renderer::BlitRenderer;
+pub use text_renderer::TextRenderer;
+pub use frame_uniforms::{FrameUniforms, FrameUniformBuffer};
+pub use model_renderer::{ModelRenderer, MAX_MATERIAL_TEXTURES};
+pub use mip_generator::MipGenerator;
+pub use scene_renderer::{MeshNode, MeshScene, SceneRenderer, scene_from_collection, load_mesh_scene};
+pub use instanced_scene_renderer::{InstancedSceneRenderer, InstanceStats};
+pub use instance_culler::{InstanceCuller, CullBatch, CulledInstances, cull_instances, DRAW_INDEXED_INDIRECT_ARGS_SIZE};
\ No newline at end of file
@@ -0,0 +1,246 @@
+use std::sync::Arc;
+use parking_lot::Mutex;
+use zenith_build::{ShaderEntry};
+use zenith_core::collections::hashmap::HashMap;
+use zenith_render::{define_shader, GraphicShader};
+use zenith_rendergraph::{RenderGraphBuilder, RenderGraphResource, Texture, TextureState};
+
+/// Generate the mip chain of a texture on GPU, each level is downsampled from the previous one.
+/// The texture must be usable as both render attachment and texture binding, with a filterable color format.
+pub struct MipGenerator {
+    pipelines: Arc<MipPipelines>,
+}
+
+/// Pipelines are created on first use of a format, shared by all graphs built from the same generator.
+struct MipPipelines {
+    shader: GraphicShader,
+    pipelines: Mutex<HashMap<wgpu::TextureFormat, wgpu::RenderPipeline>>,
+    sampler: Mutex<Option<wgpu::Sampler>>,
+}
+
+impl Default for MipGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MipGenerator {
+    pub fn new() -> Self {
+        // a half size bilinear sample lands on the corner of 4 texels, which is their box filter
+        define_shader! {
+            let shader = Graphic(blit, "blit.wgsl", ShaderEntry::Blit, 1, 1)
+        }
+
+        Self {
+            pipelines: Arc::new(MipPipelines {
+                shader: shader.unwrap(),
+                pipelines: Mutex::new(HashMap::default()),
+                sampler: Mutex::new(None),
+            }),
+        }
+    }
+
+    /// Fill mip levels 1.. of `texture` from its level 0, one render pass per level.
+    pub fn generate(&self, builder: &mut RenderGraphBuilder, texture: &mut RenderGraphResource<Texture>) {
+        let pipelines = self.pipelines.clone();
+
+        let mut node = builder.add_lambda_node("generate mips");
+        let texture = node.write(texture, TextureState::COLOR_TARGET);
+
+        node.execute(move |ctx, encoder| {
+            let texture = ctx.get_texture(&texture);
+            let device = ctx.device();
+
+            let pipeline = pipelines.get_or_create_pipeline(device, texture.format());
+            let sampler = pipelines.get_or_create_sampler(device);
+
+            for level in 1..texture.mip_level_count() {
+                let src_view = Self::create_level_view(&texture, level - 1);
+                let dst_view = Self::create_level_view(&texture, level);
+
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("mip bind group"),
+                    layout: &pipeline.get_bind_group_layout(0),
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&src_view),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(&sampler),
+                        },
+                    ],
+                });
+
+                let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("generate mip"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: &dst_view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    depth_stencil_attachment: None,
+                    timestamp_writes: None,
+                    occlusion_query_set: None,
+                });
+                pass.set_pipeline(&pipeline);
+                pass.set_bind_group(0, &bind_group, &[]);
+                pass.draw(0..3, 0..1);
+            }
+        });
+    }
+
+    fn create_level_view(texture: &wgpu::Texture, level: u32) -> wgpu::TextureView {
+        texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("mip level"),
+            dimension: Some(wgpu::TextureViewDimension::D2),
+            base_mip_level: level,
+            mip_level_count: Some(1),
+            base_array_layer: 0,
+            array_layer_count: Some(1),
+            ..Default::default()
+        })
+    }
+}
+
+impl MipPipelines {
+    fn get_or_create_pipeline(&self, device: &wgpu::Device, format: wgpu::TextureFormat) -> wgpu::RenderPipeline {
+        let mut pipelines = self.pipelines.lock();
+        pipelines
+            .entry(format)
+            .or_insert_with(|| {
+                let module = self.shader
+                    .create_shader_module(device, Default::default())
+                    .expect("Failed to compile mip generation shader");
+                let layout = self.shader.create_pipeline_layout(device);
+                let targets = [Some(wgpu::ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })];
+
+                device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some("generate mip"),
+                    layout: Some(&layout),
+                    vertex: self.shader.create_vertex_state(&module),
+                    primitive: Default::default(),
+                    depth_stencil: None,
+                    multisample: Default::default(),
+                    fragment: self.shader.create_fragment_state(&module, &targets),
+                    multiview: None,
+                    cache: None,
+                })
+            })
+            .clone()
+    }
+
+    fn get_or_create_sampler(&self, device: &wgpu::Device) -> wgpu::Sampler {
+        self.sampler
+            .lock()
+            .get_or_insert_with(|| device.create_sampler(&wgpu::SamplerDescriptor {
+                label: Some("mip sampler"),
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                ..Default::default()
+            }))
+            .clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zenith_render::RenderDevice;
+    use zenith_rendergraph::{RenderGraphBuilder, RenderResource, TextureState};
+    use super::MipGenerator;
+
+    #[test]
+    #[ignore = "needs a GPU adapter, run with --ignored"]
+    fn level_1_of_checkerboard_is_average() {
+        let render_device = RenderDevice::headless(Default::default()).expect("No adapter to run GPU tests on");
+        let (device, queue) = (render_device.device(), render_device.queue());
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("checkerboard"),
+            size: wgpu::Extent3d {
+                width: 2,
+                height: 2,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 2,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        #[rustfmt::skip]
+        let checkerboard: [u8; 16] = [
+            255, 255, 255, 255,   0,   0,   0, 255,
+              0,   0,   0, 255, 255, 255, 255, 255,
+        ];
+        queue.write_texture(
+            texture.as_image_copy(),
+            &checkerboard,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(8),
+                rows_per_image: Some(2),
+            },
+            wgpu::Extent3d {
+                width: 2,
+                height: 2,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        let mut builder = RenderGraphBuilder::new();
+        let mut graph_texture = builder.import("checkerboard", RenderResource::new(texture.clone()), TextureState::COPY_DST);
+        MipGenerator::new().generate(&mut builder, &mut graph_texture);
+
+        let mut pipeline_cache = zenith_render::PipelineCache::new();
+        builder.build(device).compile(device, &mut pipeline_cache).unwrap().execute(device, queue).unwrap();
+
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("readback"),
+            size: wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&Default::default());
+        encoder.copy_texture_to_buffer(
+            wgpu::TexelCopyTextureInfo {
+                texture: &texture,
+                mip_level: 1,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::TexelCopyBufferInfo {
+                buffer: &readback,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT),
+                    rows_per_image: Some(1),
+                },
+            },
+            wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+        queue.submit([encoder.finish()]);
+
+        readback.slice(..).map_async(wgpu::MapMode::Read, |_| {});
+        device.poll(wgpu::PollType::Wait).unwrap();
+        let texel = readback.slice(..4).get_mapped_range().to_vec();
+
+        for channel in &texel[..3] {
+            assert!((*channel as i32 - 128).abs() <= 1, "Expected gray, got {:?}", texel);
+        }
+        assert_eq!(texel[3], 255);
+    }
+}
@@ -0,0 +1,471 @@
+use std::ops::Range;
+use std::sync::Arc;
+use glam::{Mat4, Vec3};
+use wgpu::util::DeviceExt;
+use zenith_asset::{AssetHandle, AssetUrl};
+use zenith_asset::render::{Material, MeshCollection, Texture as TextureAsset};
+use zenith_build::ShaderEntry;
+use zenith_build::mesh_bindless::{DirectionalLightUniforms, DrawUniforms, MaterialData};
+use zenith_build::shadow::ShadowUniforms;
+use zenith_core::collections::{hashmap::HashMap, SmallVec};
+use zenith_core::log::{warn, target::RENDER};
+use zenith_render::{define_shader, GraphicShader, RenderDevice};
+use zenith_rendergraph::{Buffer, BufferDesc, ColorInfoBuilder, DepthStencilInfo, RenderGraphBuilder, RenderGraphResource, RenderResource, Sampler, Texture, TextureDesc};
+use crate::simple_mesh_renderer::{PackedMeshBuffers, SimpleMeshRenderer};
+use crate::DirectionalLight;
+
+/// Number of material textures bound at once, must match the binding array in mesh_bindless.wgsl.
+pub const MAX_MATERIAL_TEXTURES: usize = 64;
+
+const SHADOW_MAP_SIZE: u32 = 2048;
+
+/// Draw all meshes of a model in one pass with a single bind group.
+///
+/// Meshes share packed vertex and index buffers (see [`PackedMeshBuffers`]), materials live in a storage buffer
+/// and their textures in a binding array, each draw only moves the dynamic offset of its transform and material index.
+/// Requires `wgpu::Features::TEXTURE_BINDING_ARRAY`, draw the meshes with [`SimpleMeshRenderer`] on other devices.
+pub struct ModelRenderer {
+    meshes: PackedMeshBuffers,
+    draws: Vec<ModelDraw>,
+    material_buffer: RenderResource<Buffer>,
+    /// The first one is the default white texture, unused slots of the binding array are bound to it.
+    textures: Vec<RenderResource<Texture>>,
+    /// All materials of the model share one sampler, the one of the first base color texture.
+    sampler: RenderResource<Sampler>,
+    shadow_sampler: RenderResource<Sampler>,
+    shader: Arc<GraphicShader>,
+    shadow_shader: Arc<GraphicShader>,
+    /// Byte strides between the per-draw uniforms of two meshes.
+    draw_stride: wgpu::BufferAddress,
+    shadow_stride: wgpu::BufferAddress,
+    light: DirectionalLight,
+    /// Format of the output target, matches the swapchain so that the output can be copied to it.
+    output_format: wgpu::TextureFormat,
+}
+
+struct ModelDraw {
+    mesh_index: usize,
+    material_index: u32,
+    /// Placement of the mesh inside the model.
+    transform: Mat4,
+}
+
+impl ModelRenderer {
+    /// Upload all meshes and materials of a loaded collection.
+    /// Return None if the device can NOT bind texture arrays.
+    pub fn new(device: &RenderDevice, collection: &MeshCollection) -> Option<Self> {
+        let supported = device.supports(wgpu::Features::TEXTURE_BINDING_ARRAY)
+            && device.limits().max_binding_array_elements_per_shader_stage >= MAX_MATERIAL_TEXTURES as u32;
+        if !supported {
+            warn!(target: RENDER, "Device does NOT support texture binding arrays, draw the model with SimpleMeshRenderer instead.");
+            return None;
+        }
+
+        let meshes = PackedMeshBuffers::from_collection(device, collection);
+        let (default_texture, default_sampler) = SimpleMeshRenderer::create_default_texture(device);
+
+        let mut materials = MaterialTable {
+            indices: HashMap::default(),
+            materials: vec![],
+            textures: vec![default_texture],
+            sampler: None,
+        };
+
+        let draws = (0..collection.meshes.len())
+            .map(|index| ModelDraw {
+                mesh_index: index,
+                material_index: materials.add(device, &collection.materials[index]),
+                transform: collection.transform(index),
+            })
+            .collect::<Vec<_>>();
+
+        // storage buffer can NOT be empty
+        if materials.materials.is_empty() {
+            materials.materials.push(MaterialData::new(Vec3::ONE, 0.0, 1.0, 0.0, 0, 0));
+        }
+
+        let material_buffer = RenderResource::new(device.device().create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("model materials"),
+            contents: bytemuck::cast_slice(&materials.materials),
+            usage: wgpu::BufferUsages::STORAGE,
+        }));
+
+        let sampler = materials.sampler
+            .map(|desc| RenderResource::new(device.device().create_sampler(&desc)))
+            .unwrap_or(default_sampler);
+
+        let alignment = device.limits().min_uniform_buffer_offset_alignment as wgpu::BufferAddress;
+        let draw_size = size_of::<DrawUniforms>() as wgpu::BufferAddress;
+        let shadow_size = size_of::<ShadowUniforms>() as wgpu::BufferAddress;
+
+        define_shader! {
+            let shader = Graphic(mesh_bindless, "mesh_bindless.wgsl", ShaderEntry::MeshBindless, wgpu::VertexStepMode::Vertex, 1, 2)
+        }
+        let shader = shader.unwrap().with_depth_stencil().with_dynamic_offset(1, 0, draw_size);
+        let shadow_shader = SimpleMeshRenderer::create_shadow_shader().with_dynamic_offset(0, 0, shadow_size);
+
+        Some(Self {
+            meshes,
+            draws,
+            material_buffer,
+            textures: materials.textures,
+            sampler,
+            shadow_sampler: SimpleMeshRenderer::create_shadow_sampler(device),
+            shader: Arc::new(shader),
+            shadow_shader: Arc::new(shadow_shader),
+            draw_stride: draw_size.next_multiple_of(alignment),
+            shadow_stride: shadow_size.next_multiple_of(alignment),
+            light: DirectionalLight::default(),
+            output_format: device.output_format(),
+        })
+    }
+
+    pub fn set_light(&mut self, light: DirectionalLight) {
+        self.light = light;
+    }
+
+    /// Return the bounding sphere of the whole model in world space.
+    fn world_bounding_sphere(&self, model_matrix: Mat4) -> (Vec3, f32) {
+        let spheres = self.draws
+            .iter()
+            .map(|draw| {
+                let mesh = &self.meshes.meshes[draw.mesh_index];
+                let matrix = model_matrix * draw.transform;
+                let (scale, _, _) = matrix.to_scale_rotation_translation();
+                let center = matrix.transform_point3((mesh.bounds_min + mesh.bounds_max) * 0.5);
+                let radius = (mesh.bounds_max - mesh.bounds_min).length() * 0.5 * scale.abs().max_element();
+                (center, radius)
+            })
+            .collect::<SmallVec<[(Vec3, f32); 16]>>();
+        if spheres.is_empty() {
+            return (model_matrix.transform_point3(Vec3::ZERO), 1e-3);
+        }
+
+        let (min, max) = spheres
+            .iter()
+            .fold((Vec3::INFINITY, Vec3::NEG_INFINITY), |(min, max), &(center, radius)| (min.min(center - radius), max.max(center + radius)));
+
+        let center = (min + max) * 0.5;
+        let radius = spheres
+            .iter()
+            .map(|&(sphere_center, sphere_radius)| sphere_center.distance(center) + sphere_radius)
+            .fold(0.0f32, f32::max);
+
+        (center, radius.max(1e-3))
+    }
+
+    /// Draw the model into new color and depth targets, seen from the camera of `frame_uniforms` (see `FrameUniformBuffer`).
+    pub fn build_render_graph(
+        &self,
+        builder: &mut RenderGraphBuilder,
+        frame_uniforms: &RenderGraphResource<Buffer>,
+        model_matrix: Mat4,
+        width: u32,
+        height: u32,
+    ) -> RenderGraphResource<Texture> {
+        let mut output = builder.create("model.output", TextureDesc {
+            label: Some("model output render target"),
+            size: wgpu::Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.output_format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+
+        let mut depth_buffer = builder.create("model.depth", TextureDesc {
+            label: Some("model depth buffer"),
+            size: wgpu::Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Depth32Float,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+
+        let vb = builder.import("model.vertex", self.meshes.vertex_buffer.clone(), wgpu::BufferUses::empty());
+        let ib = builder.import("model.index", self.meshes.index_buffer.clone(), wgpu::BufferUses::empty());
+        let material_buffer = builder.import("model.materials", self.material_buffer.clone(), wgpu::BufferUses::empty());
+        let textures = self.textures
+            .iter()
+            .enumerate()
+            .map(|(index, texture)| builder.import(&format!("model.texture{}", index), texture.clone(), wgpu::TextureUses::empty()))
+            .collect::<Vec<_>>();
+        let sampler = builder.import("model.sampler", self.sampler.clone(), ());
+
+        let num_draws = self.draws.len().max(1) as wgpu::BufferAddress;
+        let draw_uniforms = builder.create("model.draw_uniforms", BufferDesc {
+            label: None,
+            size: num_draws * self.draw_stride,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let shadow_uniforms = builder.create("model.shadow_uniforms", BufferDesc {
+            label: None,
+            size: num_draws * self.shadow_stride,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let light_uniform = builder.create_uniform::<DirectionalLightUniforms>("model.light_uniform");
+
+        let mut shadow_map = builder.create("model.shadow_map", TextureDesc {
+            label: Some("model shadow map"),
+            size: wgpu::Extent3d {
+                width: SHADOW_MAP_SIZE,
+                height: SHADOW_MAP_SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Depth32Float,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let shadow_sampler = builder.import("model.shadow_sampler", self.shadow_sampler.clone(), ());
+
+        let (bounds_center, bounds_radius) = self.world_bounding_sphere(model_matrix);
+        let light_view_proj = self.light.shadow_view_proj(bounds_center, bounds_radius);
+
+        // per-draw data of all meshes, written once and selected by dynamic offsets
+        let mut draw_data = vec![0u8; (num_draws * self.draw_stride) as usize];
+        let mut shadow_data = vec![0u8; (num_draws * self.shadow_stride) as usize];
+        for (index, draw) in self.draws.iter().enumerate() {
+            let matrix = model_matrix * draw.transform;
+
+            let offset = index * self.draw_stride as usize;
+            let uniforms = DrawUniforms::new(matrix, draw.material_index);
+            draw_data[offset..offset + size_of::<DrawUniforms>()].copy_from_slice(bytemuck::bytes_of(&uniforms));
+
+            let offset = index * self.shadow_stride as usize;
+            let uniforms = ShadowUniforms::new(light_view_proj, matrix);
+            shadow_data[offset..offset + size_of::<ShadowUniforms>()].copy_from_slice(bytemuck::bytes_of(&uniforms));
+        }
+
+        let draw_ranges = self.draws
+            .iter()
+            .map(|draw| {
+                let range = self.meshes.meshes[draw.mesh_index].range;
+                (range.first_index..range.first_index + range.index_count, range.base_vertex)
+            })
+            .collect::<Vec<(Range<u32>, i32)>>();
+        let index_format = self.meshes.index_format;
+
+        {
+            let mut node = builder.add_graphic_node("model_shadow");
+
+            let shadow_uniforms = node.read(&shadow_uniforms, wgpu::BufferUses::UNIFORM);
+            let vb_read = node.read(&vb, wgpu::BufferUses::VERTEX);
+            let ib_read = node.read(&ib, wgpu::BufferUses::INDEX);
+            let shadow_map = node.write(&mut shadow_map, wgpu::TextureUses::DEPTH_STENCIL_WRITE);
+
+            // shadow map uses conventional depth, cleared to the far plane
+            node.setup_pipeline()
+                .with_shader(self.shadow_shader.clone())
+                .with_depth_only(shadow_map, DepthStencilInfo {
+                    depth_write: true,
+                    compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                    depth_load_op: wgpu::LoadOp::Clear(1.0),
+                    depth_store_op: wgpu::StoreOp::Store,
+                    stencil_load_op: wgpu::LoadOp::Clear(0),
+                    stencil_store_op: wgpu::StoreOp::Discard,
+                });
+
+            let draw_ranges = draw_ranges.clone();
+            let shadow_stride = self.shadow_stride;
+
+            // many draws, recorded into a bundle in parallel with the main pass
+            node.execute_bundle(move |ctx, bundle| {
+                ctx.write_buffer_slice(&shadow_uniforms, 0, &shadow_data);
+
+                let shadow_buffer = ctx.get_buffer(&shadow_uniforms);
+                let vertex_buffer = ctx.get_buffer(&vb_read);
+                let index_buffer = ctx.get_buffer(&ib_read);
+
+                let shadow_group = ctx.create_bind_group(0, &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                            buffer: &shadow_buffer,
+                            offset: 0,
+                            size: wgpu::BufferSize::new(size_of::<ShadowUniforms>() as wgpu::BufferAddress),
+                        }),
+                    },
+                ]);
+
+                bundle.set_vertex_buffer(0, vertex_buffer.slice(..));
+                bundle.set_index_buffer(index_buffer.slice(..), index_format);
+
+                for (index, (indices, base_vertex)) in draw_ranges.into_iter().enumerate() {
+                    bundle.set_bind_group(0, &shadow_group, &[(index as wgpu::BufferAddress * shadow_stride) as wgpu::DynamicOffset]);
+                    bundle.draw_indexed(indices, base_vertex, 0..1);
+                }
+            });
+        }
+
+        {
+            let mut node = builder.add_graphic_node("model_render");
+
+            let frame_uniforms = node.read(frame_uniforms, wgpu::BufferUses::UNIFORM);
+            let draw_uniforms = node.read(&draw_uniforms, wgpu::BufferUses::UNIFORM);
+            let light_uniform = node.read(&light_uniform, wgpu::BufferUses::UNIFORM);
+            let material_buffer = node.read(&material_buffer, wgpu::BufferUses::STORAGE_READ_ONLY);
+            let output = node.write(&mut output, wgpu::TextureUses::COLOR_TARGET);
+            let depth_buffer = node.write(&mut depth_buffer, wgpu::TextureUses::DEPTH_STENCIL_WRITE);
+
+            let vb_read = node.read(&vb, wgpu::BufferUses::VERTEX);
+            let ib_read = node.read(&ib, wgpu::BufferUses::INDEX);
+
+            let textures = textures
+                .iter()
+                .map(|texture| node.read(texture, wgpu::TextureUses::RESOURCE))
+                .collect::<Vec<_>>();
+            let shadow_map_read = node.read(&shadow_map, wgpu::TextureUses::RESOURCE);
+            let sampler = node.read(&sampler, ());
+            let shadow_sampler = node.read(&shadow_sampler, ());
+
+            node.setup_pipeline()
+                .with_shader(self.shader.clone())
+                .with_color(output, ColorInfoBuilder::default()
+                    .load_op(wgpu::LoadOp::Clear(Default::default()))
+                    .store_op(wgpu::StoreOp::Store)
+                    .build()
+                    .unwrap())
+                .with_depth_stencil(depth_buffer, SimpleMeshRenderer::depth_stencil_info(wgpu::LoadOp::Clear(0.0)));
+
+            let light = self.light;
+            let draw_stride = self.draw_stride;
+
+            node.execute_bundle(move |ctx, bundle| {
+                ctx.write_buffer_slice(&draw_uniforms, 0, &draw_data);
+                let light_uniform_data = DirectionalLightUniforms::new(light_view_proj, light.direction.normalize(), light.intensity, light.color);
+                ctx.write_uniform(&light_uniform, &light_uniform_data);
+
+                let frame_buffer = ctx.get_buffer(&frame_uniforms);
+                let draw_buffer = ctx.get_buffer(&draw_uniforms);
+                let light_buffer = ctx.get_buffer(&light_uniform);
+                let material_buffer = ctx.get_buffer(&material_buffer);
+                let vertex_buffer = ctx.get_buffer(&vb_read);
+                let index_buffer = ctx.get_buffer(&ib_read);
+
+                let texture_views = textures
+                    .iter()
+                    .map(|texture| ctx.get_texture(texture).create_view(&wgpu::TextureViewDescriptor::default()))
+                    .collect::<Vec<_>>();
+                // every slot of the binding array must be bound
+                let texture_view_refs = (0..MAX_MATERIAL_TEXTURES)
+                    .map(|slot| texture_views.get(slot).unwrap_or(&texture_views[0]))
+                    .collect::<Vec<_>>();
+                let shadow_map_view = ctx.get_texture(&shadow_map_read).create_view(&wgpu::TextureViewDescriptor::default());
+                let sampler = ctx.get_sampler(&sampler);
+                let shadow_sampler = ctx.get_sampler(&shadow_sampler);
+
+                let frame_group = ctx.create_bind_group(0, &[
+                    wgpu::BindGroupEntry { binding: 0, resource: frame_buffer.as_entire_binding() },
+                ]);
+                // one bind group for all meshes of the model
+                let draw_group = ctx.create_bind_group(1, &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                            buffer: &draw_buffer,
+                            offset: 0,
+                            size: wgpu::BufferSize::new(size_of::<DrawUniforms>() as wgpu::BufferAddress),
+                        }),
+                    },
+                    wgpu::BindGroupEntry { binding: 1, resource: material_buffer.as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::TextureViewArray(&texture_view_refs) },
+                    wgpu::BindGroupEntry { binding: 3, resource: wgpu::BindingResource::Sampler(&sampler) },
+                    wgpu::BindGroupEntry { binding: 4, resource: light_buffer.as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 5, resource: wgpu::BindingResource::TextureView(&shadow_map_view) },
+                    wgpu::BindGroupEntry { binding: 6, resource: wgpu::BindingResource::Sampler(&shadow_sampler) },
+                ]);
+
+                bundle.set_vertex_buffer(0, vertex_buffer.slice(..));
+                bundle.set_index_buffer(index_buffer.slice(..), index_format);
+                bundle.set_bind_group(0, &frame_group, &[]);
+
+                for (index, (indices, base_vertex)) in draw_ranges.into_iter().enumerate() {
+                    bundle.set_bind_group(1, &draw_group, &[(index as wgpu::BufferAddress * draw_stride) as wgpu::DynamicOffset]);
+                    bundle.draw_indexed(indices, base_vertex, 0..1);
+                }
+            });
+        }
+
+        output
+    }
+}
+
+/// Materials of a model, deduplicated by url.
+struct MaterialTable {
+    indices: HashMap<AssetUrl, u32>,
+    materials: Vec<MaterialData>,
+    textures: Vec<RenderResource<Texture>>,
+    sampler: Option<wgpu::SamplerDescriptor<'static>>,
+}
+
+impl MaterialTable {
+    /// Return the index of the material, uploading its textures on the first use.
+    fn add(&mut self, device: &RenderDevice, url: &AssetUrl) -> u32 {
+        if let Some(index) = self.indices.get(url) {
+            return *index;
+        }
+
+        let material = AssetHandle::<Material>::new(url.clone()).get().unwrap();
+
+        let base_color_texture = self.add_texture(device, "base_color", material.base_color_tex.as_ref());
+        let occlusion_texture = self.add_texture(device, "occlusion", material.occlusion_texture());
+        if self.sampler.is_none() {
+            self.sampler = material.base_color_tex
+                .as_ref()
+                .map(|texture| texture.sampler_descriptor(Some("model_material_sampler")));
+        }
+
+        let [r, g, b, _] = material.base_color;
+        self.materials.push(MaterialData::new(
+            Vec3::new(r, g, b),
+            material.metallic,
+            material.roughness,
+            material.occlusion_strength,
+            base_color_texture,
+            occlusion_texture,
+        ));
+
+        let index = self.materials.len() as u32 - 1;
+        self.indices.insert(url.clone(), index);
+        index
+    }
+
+    /// Return the slot of the texture in the binding array, 0 (default white texture) if it is absent or does NOT fit.
+    fn add_texture(&mut self, device: &RenderDevice, label: &str, texture: Option<&TextureAsset>) -> u32 {
+        let Some(texture) = texture else {
+            return 0;
+        };
+
+        if self.textures.len() >= MAX_MATERIAL_TEXTURES {
+            warn!(target: RENDER, "Model has more than {} material textures, fallback to the default texture.", MAX_MATERIAL_TEXTURES);
+            return 0;
+        }
+
+        match SimpleMeshRenderer::create_texture(device, label, texture) {
+            Some(texture) => {
+                self.textures.push(texture);
+                self.textures.len() as u32 - 1
+            }
+            None => 0,
+        }
+    }
+}
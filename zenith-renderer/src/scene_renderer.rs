@@ -0,0 +1,160 @@
+use std::path::Path;
+use glam::{Mat4, Vec3};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use zenith_asset::{AssetHandle, AssetLoaded, AssetUrl};
+use zenith_asset::manager::{AssetLoadTask, AssetManager};
+use zenith_asset::render::{Material, Mesh, MeshCollection};
+use zenith_core::collections::hashset::HashSet;
+use zenith_core::event_bus::{self, Receiver};
+use zenith_core::math::Transform;
+use zenith_core::scene::{NodeId, Scene};
+use zenith_render::RenderDevice;
+use zenith_rendergraph::{Buffer, RenderGraphBuilder, RenderGraphResource, Texture};
+use crate::{DirectionalLight, MeshRenderData, SimpleMeshRenderer};
+
+/// Mesh drawn with a material at a scene node.
+pub struct MeshNode {
+    pub mesh: AssetHandle<Mesh>,
+    pub material: AssetHandle<Material>,
+}
+
+/// Serialized form of [`MeshNode`], the urls of its baked assets.
+#[derive(Serialize, Deserialize)]
+struct MeshNodeUrls {
+    mesh: AssetUrl,
+    material: AssetUrl,
+}
+
+impl Serialize for MeshNode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        MeshNodeUrls {
+            mesh: self.mesh.url().clone(),
+            material: self.material.url().clone(),
+        }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for MeshNode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let urls = MeshNodeUrls::deserialize(deserializer)?;
+        Ok(Self {
+            mesh: AssetHandle::new(urls.mesh),
+            material: AssetHandle::new(urls.material),
+        })
+    }
+}
+
+pub type MeshScene = Scene<MeshNode>;
+
+/// Load a scene saved by [`Scene::save_ron`] and start loading the meshes and materials it refers to.
+/// A [`SceneRenderer`] can be created right away, its nodes show up as their assets finish loading.
+pub fn load_mesh_scene(path: impl AsRef<Path>, manager: &AssetManager) -> Result<(MeshScene, Vec<AssetLoadTask>), anyhow::Error> {
+    let scene = MeshScene::load_ron(path)?;
+    let tasks = scene
+        .payloads()
+        .flat_map(|(_, node)| [node.mesh.get_or_load(manager), node.material.get_or_load(manager)])
+        .collect();
+
+    Ok((scene, tasks))
+}
+
+/// Build a flat scene with a root node per mesh of a loaded collection, placed by its transform.
+pub fn scene_from_collection(collection: &MeshCollection) -> MeshScene {
+    let mut scene = MeshScene::new();
+    for (index, (mesh, material)) in collection.meshes.iter().zip(&collection.materials).enumerate() {
+        let name = AsRef::<Path>::as_ref(mesh).with_extension("").display().to_string();
+        scene.add_node(None, name, Transform::from_matrix(collection.transform(index)), Some(MeshNode {
+            mesh: AssetHandle::new(mesh.clone()),
+            material: AssetHandle::new(material.clone()),
+        }));
+    }
+    scene
+}
+
+/// Draw every mesh node of a [`MeshScene`] with a [`SimpleMeshRenderer`] each, at its world transform.
+/// Transforms are read every frame, so nodes may move.
+///
+/// Nodes whose mesh or material is NOT loaded yet are skipped, [`SceneRenderer::update`] picks them up
+/// once the asset loader publishes [`AssetLoaded`] for them.
+///
+/// Nodes are NOT culled, every node records its own draw. Use [`crate::InstancedSceneRenderer`] to cull them,
+/// on the GPU as well with [`crate::InstancedSceneRenderer::set_gpu_culling`].
+pub struct SceneRenderer {
+    renderers: Vec<(NodeId, SimpleMeshRenderer)>,
+    light: DirectionalLight,
+    asset_loaded: Receiver<AssetLoaded>,
+}
+
+impl SceneRenderer {
+    pub fn new(device: &RenderDevice, scene: &MeshScene) -> Self {
+        // subscribe first, so no asset loaded in between is missed
+        let asset_loaded = event_bus::global().subscribe::<AssetLoaded>();
+
+        let mut renderer = Self {
+            renderers: Vec::new(),
+            light: DirectionalLight::default(),
+            asset_loaded,
+        };
+        renderer.create_missing_renderers(device, scene);
+        renderer
+    }
+
+    /// Create the renderers of nodes whose assets finished loading since the last update.
+    pub fn update(&mut self, device: &RenderDevice, scene: &MeshScene) {
+        if self.asset_loaded.try_iter().count() > 0 {
+            self.create_missing_renderers(device, scene);
+        }
+    }
+
+    fn create_missing_renderers(&mut self, device: &RenderDevice, scene: &MeshScene) {
+        let existing: HashSet<NodeId> = self.renderers.iter().map(|(id, _)| *id).collect();
+
+        for (id, node) in scene.payloads() {
+            if existing.contains(&id) || node.mesh.get().is_none() || node.material.get().is_none() {
+                continue;
+            }
+
+            let data = MeshRenderData::from_handles(
+                AssetHandle::new(node.mesh.url().clone()),
+                AssetHandle::new(node.material.url().clone()),
+                Mat4::IDENTITY,
+            );
+            let mut renderer = SimpleMeshRenderer::from_model(device, data);
+            renderer.set_light(self.light);
+            self.renderers.push((id, renderer));
+        }
+    }
+
+    pub fn set_light(&mut self, light: DirectionalLight) {
+        self.light = light;
+        for (_, renderer) in &mut self.renderers {
+            renderer.set_light(light);
+        }
+    }
+
+    /// Mutable access to the renderer of each mesh node, e.g. to set its base color.
+    pub fn renderers_mut(&mut self) -> impl Iterator<Item = (NodeId, &mut SimpleMeshRenderer)> {
+        self.renderers.iter_mut().map(|(id, renderer)| (*id, renderer))
+    }
+
+    /// Draw the scene into new color and depth targets, blended meshes back-to-front from `camera_position`.
+    /// Return None if the scene has no mesh.
+    pub fn build_render_graph(
+        &self,
+        builder: &mut RenderGraphBuilder,
+        frame_uniforms: &RenderGraphResource<Buffer>,
+        scene: &MeshScene,
+        camera_position: Vec3,
+        width: u32,
+        height: u32,
+    ) -> Option<RenderGraphResource<Texture>> {
+        let world_transforms = scene.world_transforms();
+        let mut meshes: Vec<_> = self.renderers
+            .iter()
+            .map(|(id, renderer)| (renderer, world_transforms[id.index()]))
+            .collect();
+        SimpleMeshRenderer::sort_draw_order(&mut meshes, camera_position);
+
+        SimpleMeshRenderer::build_meshes_render_graph(builder, frame_uniforms, &meshes, width, height)
+    }
+}
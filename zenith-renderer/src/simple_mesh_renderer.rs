@@ -1,39 +1,243 @@
+use std::ops::Range;
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use glam::{Mat4, Vec3};
 use wgpu::util::DeviceExt;
 use zenith_asset::AssetHandle;
-use zenith_asset::render::{Material, Mesh};
+use zenith_asset::render::{AlphaMode, Material, Mesh, MeshCollection, MeshIndices, PackedMeshRange, PackedMeshes, Texture as TextureAsset, Vertex};
 use zenith_build::{ShaderEntry};
+use zenith_core::camera::Camera;
 use zenith_core::collections::SmallVec;
+use zenith_core::log::{warn, target::RENDER};
 use zenith_render::{define_shader, GraphicShader, RenderDevice};
-use zenith_rendergraph::{Buffer, DepthStencilInfo, RenderGraphBuilder, RenderGraphResource, RenderResource, Texture, TextureDesc};
+use zenith_rendergraph::{Buffer, ColorInfo, ColorInfoBuilder, DepthStencilInfo, RenderGraphBuilder, RenderGraphResource, RenderResource, Sampler, SamplerDesc, Texture, TextureDesc, Viewport, WeakRenderResource};
+
+const SHADOW_MAP_SIZE: u32 = 2048;
+/// HDR so that emissive stronger than 1 survives for bloom.
+const EMISSIVE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+/// Default max error of the level of detail on screen, in pixels.
+const DEFAULT_LOD_ERROR_PIXELS: f32 = 1.0;
 
 pub struct SimpleMeshRenderer {
+    /// Keep mesh buffers and material textures alive, None once released.
+    gpu_resources: Option<OwnedGpuResources>,
+    mesh: AssetHandle<Mesh>,
     mesh_buffers: MeshBuffers,
     material: MaterialResources,
     default_texture: RenderResource<Texture>,
-    default_sampler: Arc<wgpu::Sampler>,
+    default_sampler: RenderResource<Sampler>,
+    shadow_sampler: RenderResource<Sampler>,
     shader: Arc<GraphicShader>,
+    /// Same as `shader`, with the emissive color also written to a second target.
+    emissive_shader: Arc<GraphicShader>,
+    shadow_shader: Arc<GraphicShader>,
+    /// Placement of the mesh inside its model, applied before the model matrix.
+    mesh_transform: Mat4,
     base_color: [f32; 3],
+    light: DirectionalLight,
+    polygon_mode: wgpu::PolygonMode,
+    /// False if the device can NOT rasterize with `polygon_mode`, the topology is changed instead.
+    polygon_mode_supported: bool,
+    /// Line list of the triangle edges, draws the wireframe on devices without `POLYGON_MODE_LINE`.
+    line_indices: Option<LineIndices>,
+    /// Format of the output target, matches the swapchain so that the output can be copied to it.
+    output_format: wgpu::TextureFormat,
+    /// Set by [`SimpleMeshRenderer::set_bundle_caching`].
+    static_draw: Option<StaticDrawResources>,
+    /// Level of detail drawn, picked by [`SimpleMeshRenderer::select_lod`].
+    lod: usize,
+    lod_error_pixels: f32,
+}
+
+/// Resources of the mesh pass kept across frames, so that its draw can be recorded once into a render bundle and replayed.
+/// Uniforms are written every frame, which is why a renderer is drawn at most once per view of a graph.
+struct StaticDrawResources {
+    model_uniform: RenderResource<Buffer>,
+    light_uniform: RenderResource<Buffer>,
+    shadow_map: RenderResource<Texture>,
+    /// Bundle of each view, recorded again once anything it binds changes, e.g. the mesh is reloaded.
+    bundles: Arc<Mutex<Vec<Option<CachedMeshBundle>>>>,
+}
+
+struct CachedMeshBundle {
+    key: MeshBundleKey,
+    bundle: wgpu::RenderBundle,
+}
+
+/// Everything recorded into a mesh bundle, compared by identity of the GPU objects.
+#[derive(PartialEq)]
+struct MeshBundleKey {
+    pipeline: wgpu::RenderPipeline,
+    /// Frame, model, light, vertex and index buffers.
+    buffers: [Buffer; 5],
+    /// Base color, shadow map, occlusion and emissive textures.
+    textures: [Texture; 4],
+    /// Base color and shadow samplers.
+    samplers: [Sampler; 2],
+    indices: Range<u32>,
+    base_vertex: i32,
+    index_format: wgpu::IndexFormat,
+}
+
+struct LodRange {
+    first_index: u32,
+    index_count: u32,
+    /// Max deviation from the full mesh, in mesh space units.
+    error: f32,
+}
+
+struct LineIndices {
+    index_buffer: WeakRenderResource<Buffer>,
+    index_count: u32,
+}
+
+/// Light which comes from infinitely far away, all light rays are parallel.
+#[derive(Debug, Clone, Copy)]
+pub struct DirectionalLight {
+    /// The direction light rays travel, in world space.
+    pub direction: Vec3,
+    pub color: Vec3,
+    pub intensity: f32,
+}
+
+impl Default for DirectionalLight {
+    fn default() -> Self {
+        Self {
+            direction: Vec3::new(-0.3, 0.5, -1.0).normalize(),
+            color: Vec3::ONE,
+            intensity: 3.0,
+        }
+    }
+}
+
+impl DirectionalLight {
+    /// Orthographic view projection of this light which covers a bounding sphere in world space.
+    pub(crate) fn shadow_view_proj(&self, center: Vec3, radius: f32) -> Mat4 {
+        let direction = self.direction.normalize();
+        let up = if direction.dot(Vec3::Z).abs() > 0.99 { Vec3::Y } else { Vec3::Z };
+
+        let view = Mat4::look_to_rh(center - direction * radius * 2.0, direction, up);
+        let proj = Mat4::orthographic_rh(-radius, radius, -radius, radius, radius, radius * 3.0);
+        proj * view
+    }
+}
+
+/// Strong handles of the large GPU resources of the mesh and its material.
+#[derive(Default)]
+struct OwnedGpuResources {
+    buffers: SmallVec<[RenderResource<Buffer>; 2]>,
+    textures: SmallVec<[RenderResource<Texture>; 2]>,
 }
 
 struct MeshBuffers {
-    vertex_buffer: RenderResource<Buffer>,
-    index_buffer: RenderResource<Buffer>,
+    vertex_buffer: WeakRenderResource<Buffer>,
+    index_buffer: WeakRenderResource<Buffer>,
+    /// Non-zero if the buffers are shared with other meshes, see [`PackedMeshBuffers`].
+    base_vertex: i32,
+    first_index: u32,
     index_count: u32,
+    index_format: wgpu::IndexFormat,
+    /// Axis-aligned bounds in model space.
+    bounds_min: Vec3,
+    bounds_max: Vec3,
+    /// Coarser levels of detail, after the level 0 in the index buffer. Empty for packed meshes.
+    lods: Vec<LodRange>,
     // material_index: Option<usize>,
     // _name: Option<String>,
 }
 
+/// Vertices and indices of all meshes of a model in one vertex and one index buffer, shared by their renderers.
+/// Only for static meshes, a mesh changing at runtime keeps its own buffers (see [`SimpleMeshRenderer::from_model`]).
+pub struct PackedMeshBuffers {
+    pub(crate) vertex_buffer: RenderResource<Buffer>,
+    pub(crate) index_buffer: RenderResource<Buffer>,
+    pub(crate) index_format: wgpu::IndexFormat,
+    pub(crate) meshes: Vec<PackedMeshInfo>,
+}
+
+pub(crate) struct PackedMeshInfo {
+    pub(crate) range: PackedMeshRange,
+    /// Axis-aligned bounds in mesh space.
+    pub(crate) bounds_min: Vec3,
+    pub(crate) bounds_max: Vec3,
+}
+
+impl PackedMeshBuffers {
+    pub fn new(device: &RenderDevice, packed: &PackedMeshes) -> Self {
+        let device = device.device();
+
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("packed_vertex_buffer"),
+            contents: packed.vertices_bytes(),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("packed_index_buffer"),
+            contents: packed.indices_bytes(),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        let meshes = packed.ranges
+            .iter()
+            .map(|range| {
+                let first_vertex = range.base_vertex as usize;
+                let vertices = &packed.vertices[first_vertex..first_vertex + range.vertex_count as usize];
+                let (bounds_min, bounds_max) = vertex_bounds(vertices);
+
+                PackedMeshInfo {
+                    range: *range,
+                    bounds_min,
+                    bounds_max,
+                }
+            })
+            .collect();
+
+        Self {
+            vertex_buffer: RenderResource::new(vertex_buffer),
+            index_buffer: RenderResource::new(index_buffer),
+            index_format: packed.index_format().to_wgpu_format(),
+            meshes,
+        }
+    }
+
+    /// Pack all meshes of a loaded collection, in the order of the collection.
+    pub fn from_collection(device: &RenderDevice, collection: &MeshCollection) -> Self {
+        let meshes = collection.meshes
+            .iter()
+            .map(|url| AssetHandle::<Mesh>::new(url.clone()).get().unwrap())
+            .collect::<Vec<_>>();
+
+        Self::new(device, &PackedMeshes::pack(meshes.iter().map(|mesh| &**mesh)))
+    }
+
+    #[inline]
+    pub fn num_meshes(&self) -> usize {
+        self.meshes.len()
+    }
+}
+
+/// Return the axis-aligned bounds of the vertices.
+fn vertex_bounds(vertices: &[Vertex]) -> (Vec3, Vec3) {
+    vertices
+        .iter()
+        .map(|vertex| Vec3::from_array(vertex.position))
+        .fold((Vec3::INFINITY, Vec3::NEG_INFINITY), |(min, max), position| (min.min(position), max.max(position)))
+}
+
 struct MaterialResources {
-    base_color_texture: Option<RenderResource<Texture>>,
-    base_color_sampler: Arc<wgpu::Sampler>,
-    _material: Material,
+    base_color_texture: Option<WeakRenderResource<Texture>>,
+    /// Uploaded once even if it is shared with the metallic-roughness texture.
+    occlusion_texture: Option<WeakRenderResource<Texture>>,
+    emissive_texture: Option<WeakRenderResource<Texture>>,
+    base_color_sampler: RenderResource<Sampler>,
+    material: Material,
 }
 
 pub struct MeshRenderData {
     mesh: AssetHandle<Mesh>,
     material: AssetHandle<Material>,
+    transform: Mat4,
 }
 
 // "/mesh/cerberus/scene.mesh"
@@ -42,6 +246,7 @@ impl MeshRenderData {
         Self {
             mesh: AssetHandle::null(),
             material: AssetHandle::null(),
+            transform: Mat4::IDENTITY,
         }
     }
 
@@ -54,37 +259,277 @@ impl MeshRenderData {
         Self {
             mesh: AssetHandle::new(mesh_path.into()),
             material: AssetHandle::new(material_path.into()),
+            transform: Mat4::IDENTITY,
+        }
+    }
+
+    /// Render data of a loaded mesh drawn with a loaded material, placed by `transform` in the model space.
+    pub fn from_handles(mesh: AssetHandle<Mesh>, material: AssetHandle<Material>, transform: Mat4) -> Self {
+        Self {
+            mesh,
+            material,
+            transform,
+        }
+    }
+
+    /// Render data of the mesh at `index` of a collection, placed by its transform in the scene hierarchy.
+    pub fn from_collection(collection: &MeshCollection, index: usize) -> Self {
+        Self {
+            mesh: AssetHandle::new(collection.meshes[index].clone()),
+            material: AssetHandle::new(collection.materials[index].clone()),
+            transform: collection.transform(index),
         }
     }
 }
 
 impl SimpleMeshRenderer {
     pub fn from_model(device: &RenderDevice, data: MeshRenderData) -> Self {
+        let mut gpu_resources = OwnedGpuResources::default();
+
         let mat = data.material.get().unwrap();
-        let material = Self::create_material_resources(device, &mat);
+        let material = Self::create_material_resources(device, &mat, &mut gpu_resources);
 
-        let mesh = data.mesh.get().unwrap();
-        let mesh_buffers = Self::create_mesh_buffers(device, &mesh);
+        let mesh_buffers = Self::create_mesh_buffers(device, &data.mesh.get().unwrap(), &mut gpu_resources);
+
+        Self::new(device, data.mesh, gpu_resources, mesh_buffers, material, data.transform)
+    }
 
+    /// Renderer of the mesh at `index` of a collection, drawn from the buffers packed by [`PackedMeshBuffers::from_collection`].
+    pub fn from_packed(device: &RenderDevice, packed: &PackedMeshBuffers, collection: &MeshCollection, index: usize) -> Self {
+        let data = MeshRenderData::from_collection(collection, index);
+        let mut gpu_resources = OwnedGpuResources::default();
+
+        let mat = data.material.get().unwrap();
+        let material = Self::create_material_resources(device, &mat, &mut gpu_resources);
+
+        let mesh = &packed.meshes[index];
+        let mesh_buffers = MeshBuffers {
+            vertex_buffer: packed.vertex_buffer.downgrade(),
+            index_buffer: packed.index_buffer.downgrade(),
+            base_vertex: mesh.range.base_vertex,
+            first_index: mesh.range.first_index,
+            index_count: mesh.range.index_count,
+            index_format: packed.index_format,
+            bounds_min: mesh.bounds_min,
+            bounds_max: mesh.bounds_max,
+            lods: Vec::new(),
+        };
+        gpu_resources.buffers.extend([packed.vertex_buffer.clone(), packed.index_buffer.clone()]);
+
+        Self::new(device, data.mesh, gpu_resources, mesh_buffers, material, data.transform)
+    }
+
+    fn new(
+        device: &RenderDevice,
+        mesh: AssetHandle<Mesh>,
+        gpu_resources: OwnedGpuResources,
+        mesh_buffers: MeshBuffers,
+        material: MaterialResources,
+        mesh_transform: Mat4,
+    ) -> Self {
         let (default_texture, default_sampler) = Self::create_default_texture(device);
+        let shadow_sampler = Self::create_shadow_sampler(device);
 
         let shader = Self::create_shader();
+        let emissive_shader = Self::create_emissive_shader();
+        let shadow_shader = Self::create_shadow_shader();
 
         Self {
+            gpu_resources: Some(gpu_resources),
+            mesh,
             mesh_buffers,
             material,
             default_texture,
             default_sampler,
+            shadow_sampler,
             shader: Arc::new(shader),
+            emissive_shader: Arc::new(emissive_shader),
+            shadow_shader: Arc::new(shadow_shader),
+            mesh_transform,
             base_color: [0.8, 0.8, 0.8],
+            light: DirectionalLight::default(),
+            polygon_mode: wgpu::PolygonMode::Fill,
+            polygon_mode_supported: true,
+            line_indices: None,
+            output_format: device.output_format(),
+            static_draw: None,
+            lod: 0,
+            lod_error_pixels: DEFAULT_LOD_ERROR_PIXELS,
         }
     }
 
     pub fn set_base_color(&mut self, color: [f32; 3]) {
         self.base_color = color;
     }
+
+    pub fn set_light(&mut self, light: DirectionalLight) {
+        self.light = light;
+    }
+
+    /// Record the draw of the mesh pass once into a render bundle and replay it every frame, which saves CPU time for static meshes.
+    /// The bundle is recorded again only when the mesh, its material or the pipeline changes.
+    /// The renderer then keeps its own shadow map instead of a transient one, and must be drawn at most once per view of a graph.
+    pub fn set_bundle_caching(&mut self, device: &RenderDevice, enabled: bool) {
+        if !enabled {
+            self.static_draw = None;
+            return;
+        }
+        if self.static_draw.is_some() {
+            return;
+        }
+
+        let device = device.device();
+        let create_uniform = |label, size: usize| RenderResource::new(device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: size as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        }));
+
+        self.static_draw = Some(StaticDrawResources {
+            model_uniform: create_uniform("mesh model uniform", size_of::<zenith_build::mesh::ModelUniforms>()),
+            light_uniform: create_uniform("mesh light uniform", size_of::<zenith_build::mesh::DirectionalLightUniforms>()),
+            shadow_map: RenderResource::new(device.create_texture(&Self::shadow_map_desc())),
+            bundles: Arc::new(Mutex::new(vec![])),
+        });
+    }
+
+    /// Draw the mesh filled (default), as wireframe or as points, to debug its topology.
+    /// Without `POLYGON_MODE_LINE` the wireframe is drawn from a line list built on CPU,
+    /// without `POLYGON_MODE_POINT` the vertices are drawn as a point list.
+    /// The line list needs the mesh loaded, else the mesh is drawn filled until this is called again once it is.
+    pub fn set_polygon_mode(&mut self, device: &RenderDevice, polygon_mode: wgpu::PolygonMode) {
+        self.polygon_mode = polygon_mode;
+        self.polygon_mode_supported = match polygon_mode {
+            wgpu::PolygonMode::Fill => true,
+            wgpu::PolygonMode::Line => device.supports(wgpu::Features::POLYGON_MODE_LINE),
+            wgpu::PolygonMode::Point => device.supports(wgpu::Features::POLYGON_MODE_POINT),
+        };
+
+        let needs_line_indices = polygon_mode == wgpu::PolygonMode::Line && !self.polygon_mode_supported;
+        if !needs_line_indices || self.line_indices.is_some() {
+            return;
+        }
+        let (Some(gpu_resources), Some(mesh)) = (self.gpu_resources.as_mut(), self.mesh.get()) else {
+            warn!(target: RENDER, "Mesh {:?} is NOT loaded, can NOT build its wireframe, fallback to fill.", self.mesh.url());
+            return;
+        };
+
+        let indices = mesh.indices.triangle_edges();
+        let index_buffer = RenderResource::new(device.device().create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("line_index_buffer"),
+            contents: bytemuck::cast_slice(&indices),
+            usage: wgpu::BufferUsages::INDEX,
+        }));
+
+        self.line_indices = Some(LineIndices {
+            index_buffer: index_buffer.downgrade(),
+            index_count: indices.len() as u32,
+        });
+        gpu_resources.buffers.push(index_buffer);
+    }
+
+    /// Return the number of levels of detail, including the full mesh. Meshes drawn from packed buffers only have the full mesh.
+    #[inline]
+    pub fn num_lods(&self) -> usize { 1 + self.mesh_buffers.lods.len() }
+
+    #[inline]
+    pub fn lod(&self) -> usize { self.lod }
+
+    /// Set the max error in pixels a level of detail may show on screen, see [`Self::select_lod`].
+    pub fn set_lod_error_pixels(&mut self, error_pixels: f32) {
+        self.lod_error_pixels = error_pixels.max(0.);
+    }
+
+    /// Pick the coarsest level of detail whose error projects under [`Self::set_lod_error_pixels`] on screen,
+    /// for the mesh placed by `model_matrix` seen by `camera` in a viewport `viewport_height` pixels high.
+    /// The distance is measured to the bounding sphere of the mesh. Return the level picked.
+    pub fn select_lod(&mut self, camera: &Camera, model_matrix: Mat4, viewport_height: u32) -> usize {
+        let world_matrix = model_matrix * self.mesh_transform;
+        let (center, radius) = self.world_bounding_sphere(world_matrix);
+        let distance = center.distance(camera.location()) - radius;
+        if distance <= 0. {
+            // camera inside the bounds
+            self.lod = 0;
+            return self.lod;
+        }
+
+        // y scale of the projection is 1 / tan(fov_y / 2)
+        let pixels_per_unit = camera.projection().y_axis.y * viewport_height as f32 * 0.5 / distance;
+        let (scale, _, _) = world_matrix.to_scale_rotation_translation();
+        let pixels_per_mesh_unit = pixels_per_unit * scale.abs().max_element();
+
+        self.lod = self.mesh_buffers.lods
+            .iter()
+            .rposition(|lod| lod.error * pixels_per_mesh_unit <= self.lod_error_pixels)
+            .map_or(0, |index| index + 1);
+        self.lod
+    }
+
+    /// Index range of the level of detail drawn.
+    fn lod_indices(&self) -> Range<u32> {
+        let (first_index, index_count) = match self.lod.checked_sub(1).and_then(|index| self.mesh_buffers.lods.get(index)) {
+            Some(lod) => (lod.first_index, lod.index_count),
+            None => (self.mesh_buffers.first_index, self.mesh_buffers.index_count),
+        };
+        first_index..first_index + index_count
+    }
+
+    /// Index buffer, index range, topology and polygon mode of the main pass, with the fallbacks of [`Self::set_polygon_mode`].
+    fn draw_primitive(&self) -> (&WeakRenderResource<Buffer>, Range<u32>, wgpu::IndexFormat, wgpu::PrimitiveTopology, wgpu::PolygonMode) {
+        let mesh_indices = self.lod_indices();
+
+        match (self.polygon_mode, &self.line_indices) {
+            (wgpu::PolygonMode::Line, Some(lines)) if !self.polygon_mode_supported => {
+                (&lines.index_buffer, 0..lines.index_count, wgpu::IndexFormat::Uint32, wgpu::PrimitiveTopology::LineList, wgpu::PolygonMode::Fill)
+            }
+            (wgpu::PolygonMode::Line, None) if !self.polygon_mode_supported => {
+                (&self.mesh_buffers.index_buffer, mesh_indices, self.mesh_buffers.index_format, wgpu::PrimitiveTopology::TriangleList, wgpu::PolygonMode::Fill)
+            }
+            (wgpu::PolygonMode::Point, _) if !self.polygon_mode_supported => {
+                (&self.mesh_buffers.index_buffer, mesh_indices, self.mesh_buffers.index_format, wgpu::PrimitiveTopology::PointList, wgpu::PolygonMode::Fill)
+            }
+            (polygon_mode, _) => {
+                (&self.mesh_buffers.index_buffer, mesh_indices, self.mesh_buffers.index_format, wgpu::PrimitiveTopology::TriangleList, polygon_mode)
+            }
+        }
+    }
+
+    /// Drop the mesh buffers and material textures, e.g. when the model is unloaded.
+    /// The GPU memory is freed once no graph holds them, afterwards the mesh is NOT drawn but the targets are still cleared.
+    pub fn release(&mut self) {
+        self.gpu_resources = None;
+    }
+
+    #[inline]
+    pub fn is_released(&self) -> bool {
+        self.gpu_resources.is_none()
+    }
+
+    #[inline]
+    pub fn alpha_mode(&self) -> AlphaMode {
+        self.material.material.alpha_mode
+    }
+
+    /// Sort meshes into draw order: opaque and masked ones first in their current order, then blended ones back-to-front from `camera_position`.
+    /// Blending is only correct over what is already drawn, add the meshes with [`Self::build_render_graph_onto`] in this order.
+    pub fn sort_draw_order(meshes: &mut [(&SimpleMeshRenderer, Mat4)], camera_position: Vec3) {
+        let blended_distance = |(renderer, model_matrix): &(&SimpleMeshRenderer, Mat4)| {
+            (renderer.alpha_mode() == AlphaMode::Blend).then(|| {
+                let (center, _) = renderer.world_bounding_sphere(*model_matrix * renderer.mesh_transform);
+                center.distance_squared(camera_position)
+            })
+        };
+
+        meshes.sort_by(|a, b| match (blended_distance(a), blended_distance(b)) {
+            (None, None) => std::cmp::Ordering::Equal,
+            (None, Some(_)) => std::cmp::Ordering::Less,
+            (Some(_), None) => std::cmp::Ordering::Greater,
+            (Some(a), Some(b)) => b.total_cmp(&a),
+        });
+    }
     
-    fn create_mesh_buffers(device: &RenderDevice, mesh: &Mesh) -> MeshBuffers {
+    fn create_mesh_buffers(device: &RenderDevice, mesh: &Mesh, gpu_resources: &mut OwnedGpuResources) -> MeshBuffers {
         let device = device.device();
 
         let vertex_buffer = RenderResource::new(device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -93,86 +538,146 @@ impl SimpleMeshRenderer {
             usage: wgpu::BufferUsages::VERTEX,
         }));
 
+        // levels of detail follow the level 0 in the same buffer
+        let mut indices = mesh.indices.clone();
+        let mut lods = Vec::with_capacity(mesh.lods.len());
+        for lod in &mesh.lods {
+            lods.push(LodRange {
+                first_index: indices.len() as u32,
+                index_count: lod.indices.len() as u32,
+                error: lod.error,
+            });
+            match (&mut indices, &lod.indices) {
+                (MeshIndices::U16(all), MeshIndices::U16(lod_indices)) => all.extend_from_slice(lod_indices),
+                (MeshIndices::U32(all), lod_indices) => all.extend(lod_indices.to_u32()),
+                (MeshIndices::U16(_), MeshIndices::U32(_)) => unreachable!("Levels of detail only reference vertices of the level 0"),
+            }
+        }
+
         let index_buffer = RenderResource::new(device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("index_buffer"),
-            contents: mesh.indices_bytes(),
+            contents: indices.as_bytes(),
             usage: wgpu::BufferUsages::INDEX,
         }));
 
+        let (bounds_min, bounds_max) = vertex_bounds(&mesh.vertices);
+
+        let vertex_buffer_handle = vertex_buffer.downgrade();
+        let index_buffer_handle = index_buffer.downgrade();
+        gpu_resources.buffers.extend([vertex_buffer, index_buffer]);
+
         MeshBuffers {
-            vertex_buffer,
-            index_buffer,
+            vertex_buffer: vertex_buffer_handle,
+            index_buffer: index_buffer_handle,
+            base_vertex: 0,
+            first_index: 0,
             index_count: mesh.indices.len() as u32,
+            index_format: mesh.index_format().to_wgpu_format(),
+            bounds_min,
+            bounds_max,
+            lods,
             // _name: mesh.name.clone(),
         }
     }
     
-    fn create_material_resources(render_device: &RenderDevice, material: &Material) -> MaterialResources {
+    fn create_material_resources(render_device: &RenderDevice, material: &Material, gpu_resources: &mut OwnedGpuResources) -> MaterialResources {
         let device = render_device.device();
+
+        let base_color_texture = material.base_color_tex
+            .as_ref()
+            .and_then(|texture_data| Self::create_texture(render_device, "base_color", texture_data));
+        let occlusion_texture = material
+            .occlusion_texture()
+            .and_then(|texture_data| Self::create_texture(render_device, "occlusion", texture_data));
+        let emissive_texture = material.emissive_tex
+            .as_ref()
+            .and_then(|texture_data| Self::create_texture(render_device, "emissive", texture_data));
         
-        let base_color_texture = if let Some(texture_data) = &material.base_color_tex {
-            let format = texture_data.format.to_wgpu_format();
-            let pixels = &texture_data.pixels;
-
-            let texture = device.create_texture(&wgpu::TextureDescriptor {
-                label: Some("base_color"),
-                size: wgpu::Extent3d {
-                    width: texture_data.width,
-                    height: texture_data.height,
-                    depth_or_array_layers: 1,
-                },
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: wgpu::TextureDimension::D2,
-                format,
-                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-                view_formats: &[],
+        // all textures of the material share the sampler the base color texture is baked with
+        let base_color_sampler = material.base_color_tex
+            .as_ref()
+            .map(|texture_data| texture_data.sampler_descriptor(Some("base_color_sampler")))
+            .unwrap_or(wgpu::SamplerDescriptor {
+                label: Some("base_color_sampler"),
+                address_mode_u: wgpu::AddressMode::Repeat,
+                address_mode_v: wgpu::AddressMode::Repeat,
+                address_mode_w: wgpu::AddressMode::Repeat,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Linear,
+                ..Default::default()
             });
+        let base_color_sampler = RenderResource::new(device.create_sampler(&base_color_sampler));
+        
+        let mut own_texture = |texture: Option<RenderResource<Texture>>| {
+            texture.map(|texture| {
+                let handle = texture.downgrade();
+                gpu_resources.textures.push(texture);
+                handle
+            })
+        };
+
+        MaterialResources {
+            base_color_texture: own_texture(base_color_texture),
+            occlusion_texture: own_texture(occlusion_texture),
+            emissive_texture: own_texture(emissive_texture),
+            base_color_sampler,
+            material: material.clone(),
+        }
+    }
+
+    /// Upload all mip levels of the texture, return None if the device can NOT sample its format.
+    pub(crate) fn create_texture(render_device: &RenderDevice, label: &str, texture_data: &TextureAsset) -> Option<RenderResource<Texture>> {
+        let supported = !texture_data.format.is_compressed() || render_device.supports(wgpu::Features::TEXTURE_COMPRESSION_BC);
+        if !supported {
+            warn!(target: RENDER, "Device does NOT support {:?}, fallback to the default texture.", texture_data.format);
+            return None;
+        }
 
+        let format = texture_data.wgpu_format();
+
+        let texture = render_device.device().create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: texture_data.width,
+                height: texture_data.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: texture_data.mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        for (mip_level, (width, height, pixels)) in texture_data.mip_levels().enumerate() {
             render_device.queue().write_texture(
                 wgpu::TexelCopyTextureInfo {
                     texture: &texture,
-                    mip_level: 0,
+                    mip_level: mip_level as u32,
                     origin: wgpu::Origin3d::ZERO,
                     aspect: wgpu::TextureAspect::All,
                 },
-                &pixels,
+                pixels,
                 wgpu::TexelCopyBufferLayout {
                     offset: 0,
-                    bytes_per_row: Some(texture_data.width * texture_data.format.bytes_per_pixel()),
-                    rows_per_image: Some(texture_data.height),
+                    bytes_per_row: Some(texture_data.format.bytes_per_row(width)),
+                    rows_per_image: Some(texture_data.format.rows_per_image(height)),
                 },
+                // compressed mips smaller than a block still copy a whole block
                 wgpu::Extent3d {
-                    width: texture_data.width,
-                    height: texture_data.height,
+                    width,
+                    height,
                     depth_or_array_layers: 1,
-                },
+                }.physical_size(format),
             );
-            
-            Some(RenderResource::new(texture))
-        } else {
-            None
-        };
-        
-        let base_color_sampler = Arc::new(device.create_sampler(&wgpu::SamplerDescriptor {
-            label: Some("lll_r_sampler"),
-            address_mode_u: wgpu::AddressMode::Repeat,
-            address_mode_v: wgpu::AddressMode::Repeat,
-            address_mode_w: wgpu::AddressMode::Repeat,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Linear,
-            mipmap_filter: wgpu::FilterMode::Linear,
-            ..Default::default()
-        }));
-        
-        MaterialResources {
-            base_color_texture,
-            base_color_sampler,
-            _material: material.clone(),
         }
+
+        Some(RenderResource::new(texture))
     }
     
-    fn create_default_texture(render_device: &RenderDevice) -> (RenderResource<wgpu::Texture>, Arc<wgpu::Sampler>) {
+    pub(crate) fn create_default_texture(render_device: &RenderDevice) -> (RenderResource<wgpu::Texture>, RenderResource<Sampler>) {
         let device = render_device.device();
         
         let texture = device.create_texture(&wgpu::TextureDescriptor {
@@ -222,26 +727,169 @@ impl SimpleMeshRenderer {
             ..Default::default()
         });
         
-        (RenderResource::new(texture), Arc::new(sampler))
+        (RenderResource::new(texture), RenderResource::new(sampler))
     }
     
     fn create_shader() -> GraphicShader {
         define_shader! {
-            let shader = Graphic(mesh, "mesh.wgsl", ShaderEntry::Mesh, wgpu::VertexStepMode::Vertex, 1, 1)
+            let shader = Graphic(mesh, "mesh.wgsl", ShaderEntry::Mesh, wgpu::VertexStepMode::Vertex, 1, 2)
+        }
+        shader.unwrap().with_depth_stencil()
+    }
+
+    fn create_emissive_shader() -> GraphicShader {
+        define_shader! {
+            let shader = Graphic(mesh, "mesh.wgsl", ShaderEntry::Mesh, wgpu::VertexStepMode::Vertex, fragment = fs_main_emissive_entry, 2, 2)
+        }
+        shader.unwrap().with_depth_stencil()
+    }
+
+    pub(crate) fn create_shadow_shader() -> GraphicShader {
+        define_shader! {
+            let shader = DepthOnly(shadow, "shadow.wgsl", ShaderEntry::Shadow, wgpu::VertexStepMode::Vertex, 1)
         }
         shader.unwrap()
     }
 
+    /// Return the bounding sphere of the mesh in world space.
+    fn world_bounding_sphere(&self, model_matrix: Mat4) -> (Vec3, f32) {
+        let local_center = (self.mesh_buffers.bounds_min + self.mesh_buffers.bounds_max) * 0.5;
+        let local_radius = (self.mesh_buffers.bounds_max - self.mesh_buffers.bounds_min).length() * 0.5;
+        let (scale, _, _) = model_matrix.to_scale_rotation_translation();
+
+        (model_matrix.transform_point3(local_center), (local_radius * scale.abs().max_element()).max(1e-3))
+    }
+
+    /// Draw the mesh into new color and depth targets, seen from the camera of `frame_uniforms` (see `FrameUniformBuffer`).
     pub fn build_render_graph(
         &self, 
         builder: &mut RenderGraphBuilder, 
-        view_matrix: glam::Mat4,
-        proj_matrix: glam::Mat4,
+        frame_uniforms: &RenderGraphResource<Buffer>,
+        model_matrix: glam::Mat4,
+        width: u32,
+        height: u32,
+    ) -> RenderGraphResource<Texture>  {
+        self.build_viewports_render_graph(builder, &[(frame_uniforms, Viewport::FULL)], model_matrix, width, height)
+    }
+
+    /// Draw the mesh once per view into its region of the same new targets, e.g. split-screen with a camera per player.
+    /// Each view has its own frame uniforms, whose projection should match the aspect ratio of its viewport.
+    pub fn build_viewports_render_graph(
+        &self,
+        builder: &mut RenderGraphBuilder,
+        views: &[(&RenderGraphResource<Buffer>, Viewport)],
         model_matrix: glam::Mat4,
         width: u32,
         height: u32,
     ) -> RenderGraphResource<Texture>  {
-        let mut output = builder.create("triangle.output", TextureDesc {
+        self.build_targets_render_graph(builder, views, model_matrix, width, height, false).0
+    }
+
+    /// Same as [`Self::build_render_graph`], also return a target with only the emissive color (e.g. for bloom).
+    pub fn build_emissive_render_graph(
+        &self,
+        builder: &mut RenderGraphBuilder,
+        frame_uniforms: &RenderGraphResource<Buffer>,
+        model_matrix: glam::Mat4,
+        width: u32,
+        height: u32,
+    ) -> (RenderGraphResource<Texture>, RenderGraphResource<Texture>) {
+        let (output, emissive) = self.build_targets_render_graph(builder, &[(frame_uniforms, Viewport::FULL)], model_matrix, width, height, true);
+        (output, emissive.unwrap())
+    }
+
+    fn build_targets_render_graph(
+        &self,
+        builder: &mut RenderGraphBuilder,
+        views: &[(&RenderGraphResource<Buffer>, Viewport)],
+        model_matrix: glam::Mat4,
+        width: u32,
+        height: u32,
+        emissive_output: bool,
+    ) -> (RenderGraphResource<Texture>, Option<RenderGraphResource<Texture>>) {
+        let (mut output, mut depth_buffer) = self.create_targets(builder, width, height);
+
+        let mut emissive = emissive_output.then(|| builder.create("mesh.emissive", TextureDesc {
+            label: Some("mesh emissive render target"),
+            size: wgpu::Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: EMISSIVE_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        }));
+
+        for (index, (frame_uniforms, viewport)) in views.iter().enumerate() {
+            // load ops apply to the whole targets, only the first view clears them
+            let (color_load_op, depth_load_op) = if index == 0 {
+                (wgpu::LoadOp::Clear(Default::default()), wgpu::LoadOp::Clear(0.0))
+            } else {
+                (wgpu::LoadOp::Load, wgpu::LoadOp::Load)
+            };
+
+            self.add_mesh_node(
+                builder,
+                index,
+                frame_uniforms,
+                &mut output,
+                &mut depth_buffer,
+                emissive.as_mut(),
+                model_matrix,
+                *viewport,
+                color_load_op,
+                depth_load_op,
+            );
+        }
+
+        (output, emissive)
+    }
+
+    /// Draw several meshes, each with its own model matrix, into the same new color and depth targets.
+    /// Meshes are drawn in order, see [`Self::sort_draw_order`] if some of them are blended.
+    /// Return None if there is no mesh.
+    pub fn build_meshes_render_graph(
+        builder: &mut RenderGraphBuilder,
+        frame_uniforms: &RenderGraphResource<Buffer>,
+        meshes: &[(&SimpleMeshRenderer, Mat4)],
+        width: u32,
+        height: u32,
+    ) -> Option<RenderGraphResource<Texture>> {
+        let (first, _) = meshes.first()?;
+        let (mut output, mut depth_buffer) = first.create_targets(builder, width, height);
+
+        for (index, (renderer, model_matrix)) in meshes.iter().enumerate() {
+            // only the first mesh clears the targets
+            let (color_load_op, depth_load_op) = if index == 0 {
+                (wgpu::LoadOp::Clear(Default::default()), wgpu::LoadOp::Clear(0.0))
+            } else {
+                (wgpu::LoadOp::Load, wgpu::LoadOp::Load)
+            };
+
+            renderer.add_mesh_node(
+                builder,
+                0,
+                frame_uniforms,
+                &mut output,
+                &mut depth_buffer,
+                None,
+                *model_matrix,
+                Viewport::FULL,
+                color_load_op,
+                depth_load_op,
+            );
+        }
+
+        Some(output)
+    }
+
+    /// Create the color and depth targets the mesh is drawn into.
+    fn create_targets(&self, builder: &mut RenderGraphBuilder, width: u32, height: u32) -> (RenderGraphResource<Texture>, RenderGraphResource<Texture>) {
+        let output = builder.create("triangle.output", TextureDesc {
             label: Some("mesh output render target"),
             size: wgpu::Extent3d {
                 width: width.max(1),
@@ -251,12 +899,12 @@ impl SimpleMeshRenderer {
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Bgra8UnormSrgb,
+            format: self.output_format,
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
-            view_formats: &[wgpu::TextureFormat::Bgra8UnormSrgb],
+            view_formats: &[],
         });
 
-        let mut depth_buffer = builder.create("mesh.depth", TextureDesc {
+        let depth_buffer = builder.create("mesh.depth", TextureDesc {
             label: Some("mesh depth buffer"),
             size: wgpu::Extent3d {
                 width: width.max(1),
@@ -270,31 +918,170 @@ impl SimpleMeshRenderer {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             view_formats: &[],
         });
-        
-        let view_uniform = builder.create("mesh.camera_uniform", wgpu::BufferDescriptor {
-            label: Some("Camera Uniform Buffer"),
-            size: size_of::<[[f32; 4]; 4]>() as wgpu::BufferAddress,
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
 
-        let model_uniform = builder.create("mesh.model_uniform", wgpu::BufferDescriptor {
-            label: Some("Model Uniform Buffer"),
-            size: (size_of::<[[f32; 4]; 4]>() + size_of::<[f32; 3]>() + 4) as wgpu::BufferAddress,
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
+        (output, depth_buffer)
+    }
 
-        let vb = builder.import(
-            "mesh.vertex",
-            self.mesh_buffers.vertex_buffer.clone(),
-            wgpu::BufferUses::empty()
-        );
-        let ib = builder.import(
-            "mesh.index",
-            self.mesh_buffers.index_buffer.clone(),
-            wgpu::BufferUses::empty()
+    /// Draw the mesh on top of the existing content of color and depth targets inside `viewport`, e.g. after a skybox pass.
+    pub fn build_render_graph_onto(
+        &self,
+        builder: &mut RenderGraphBuilder,
+        frame_uniforms: &RenderGraphResource<Buffer>,
+        output: &mut RenderGraphResource<Texture>,
+        depth_buffer: &mut RenderGraphResource<Texture>,
+        model_matrix: glam::Mat4,
+        viewport: Viewport,
+    ) {
+        self.add_mesh_node(
+            builder,
+            0,
+            frame_uniforms,
+            output,
+            depth_buffer,
+            None,
+            model_matrix,
+            viewport,
+            wgpu::LoadOp::Load,
+            wgpu::LoadOp::Load,
         );
+    }
+
+    /// Shader of the mesh pass and the color info of the emissive target if it is written.
+    fn emissive_target_info(&self, has_emissive: bool, color_load_op: wgpu::LoadOp<wgpu::Color>) -> (Arc<GraphicShader>, Option<ColorInfo>) {
+        if has_emissive {
+            let color_info = ColorInfoBuilder::default()
+                .load_op(color_load_op)
+                .store_op(wgpu::StoreOp::Store)
+                .build()
+                .unwrap();
+            (self.emissive_shader.clone(), Some(color_info))
+        } else {
+            (self.shader.clone(), None)
+        }
+    }
+
+    fn shadow_map_desc() -> TextureDesc {
+        TextureDesc {
+            label: Some("mesh shadow map"),
+            size: wgpu::Extent3d {
+                width: SHADOW_MAP_SIZE,
+                height: SHADOW_MAP_SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Depth32Float,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        }
+    }
+
+    /// Comparison sampler of the shadow map, created once and imported into every graph.
+    pub(crate) fn create_shadow_sampler(render_device: &RenderDevice) -> RenderResource<Sampler> {
+        RenderResource::new(render_device.device().create_sampler(&SamplerDesc {
+            label: Some("Shadow Comparison Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        }))
+    }
+
+    /// Reverse-Z depth of the mesh pass.
+    pub(crate) fn depth_stencil_info(depth_load_op: wgpu::LoadOp<f32>) -> DepthStencilInfo {
+        DepthStencilInfo {
+            depth_write: true,
+            compare: wgpu::CompareFunction::Greater,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+            depth_load_op,
+            depth_store_op: wgpu::StoreOp::Store,
+            stencil_load_op: wgpu::LoadOp::Clear(0),
+            stencil_store_op: wgpu::StoreOp::Discard,
+        }
+    }
+
+    /// Apply the load ops of the mesh pass without drawing, for a released mesh.
+    fn add_clear_node(
+        &self,
+        builder: &mut RenderGraphBuilder,
+        output: &mut RenderGraphResource<Texture>,
+        depth_buffer: &mut RenderGraphResource<Texture>,
+        emissive: Option<&mut RenderGraphResource<Texture>>,
+        viewport: Viewport,
+        color_load_op: wgpu::LoadOp<wgpu::Color>,
+        depth_load_op: wgpu::LoadOp<f32>,
+    ) {
+        let mut node = builder.add_graphic_node("mesh_clear");
+
+        let output = node.write(output, wgpu::TextureUses::COLOR_TARGET);
+        let depth_buffer = node.write(depth_buffer, wgpu::TextureUses::DEPTH_STENCIL_WRITE);
+        let emissive = emissive.map(|emissive| node.write(emissive, wgpu::TextureUses::COLOR_TARGET));
+        let (shader, emissive_info) = self.emissive_target_info(emissive.is_some(), color_load_op);
+
+        let mut pipeline = node.setup_pipeline()
+            .with_shader(shader)
+            .with_color(output, ColorInfoBuilder::default()
+                .load_op(color_load_op)
+                .store_op(wgpu::StoreOp::Store)
+                .build()
+                .unwrap());
+        if let (Some(emissive), Some(emissive_info)) = (emissive, emissive_info) {
+            pipeline = pipeline.with_color(emissive, emissive_info);
+        }
+        pipeline
+            .with_depth_stencil(depth_buffer, Self::depth_stencil_info(depth_load_op))
+            .with_viewport(viewport);
+
+        node.execute(|ctx, encoder| {
+            let _render_pass = ctx.begin_render_pass(encoder);
+        });
+    }
+
+    /// `view_index` selects the cached bundle if bundle caching is enabled.
+    fn add_mesh_node(
+        &self,
+        builder: &mut RenderGraphBuilder,
+        view_index: usize,
+        frame_uniforms: &RenderGraphResource<Buffer>,
+        output: &mut RenderGraphResource<Texture>,
+        depth_buffer: &mut RenderGraphResource<Texture>,
+        emissive: Option<&mut RenderGraphResource<Texture>>,
+        model_matrix: glam::Mat4,
+        viewport: Viewport,
+        color_load_op: wgpu::LoadOp<wgpu::Color>,
+        depth_load_op: wgpu::LoadOp<f32>,
+    ) {
+        let model_matrix = model_matrix * self.mesh_transform;
+
+        let vb = builder.import_weak("mesh.vertex", &self.mesh_buffers.vertex_buffer, wgpu::BufferUses::empty());
+        let ib = builder.import_weak("mesh.index", &self.mesh_buffers.index_buffer, wgpu::BufferUses::empty());
+        let (Some(vb), Some(ib)) = (vb, ib) else {
+            self.add_clear_node(builder, output, depth_buffer, emissive, viewport, color_load_op, depth_load_op);
+            return;
+        };
+
+        let (draw_index_buffer, draw_indices, draw_index_format, topology, polygon_mode) = self.draw_primitive();
+        let line_ib = if topology == wgpu::PrimitiveTopology::LineList {
+            builder.import_weak("mesh.line_index", draw_index_buffer, wgpu::BufferUses::empty())
+        } else {
+            None
+        };
+
+        let (model_uniform, light_uniform) = match &self.static_draw {
+            Some(static_draw) => (
+                builder.import("mesh.model_uniform", static_draw.model_uniform.clone(), wgpu::BufferUses::empty()),
+                builder.import("mesh.light_uniform", static_draw.light_uniform.clone(), wgpu::BufferUses::empty()),
+            ),
+            None => (
+                builder.create_uniform::<zenith_build::mesh::ModelUniforms>("mesh.model_uniform"),
+                builder.create_uniform::<zenith_build::mesh::DirectionalLightUniforms>("mesh.light_uniform"),
+            ),
+        };
 
         // Import default texture
         let default_texture = builder.import(
@@ -303,91 +1090,269 @@ impl SimpleMeshRenderer {
             wgpu::TextureUses::empty()
         );
 
-        let base_color = if let Some(texture) = &self.material.base_color_texture {
-            Some(builder.import(
-                "base_color",
-                texture.clone(),
-                wgpu::TextureUses::empty()
-            ))
-        } else {
-            None
+        let base_color = self.material.base_color_texture
+            .as_ref()
+            .and_then(|texture| builder.import_weak("base_color", texture, wgpu::TextureUses::empty()));
+
+        let occlusion = self.material.occlusion_texture
+            .as_ref()
+            .and_then(|texture| builder.import_weak("occlusion", texture, wgpu::TextureUses::empty()));
+
+        let emissive_texture = self.material.emissive_texture
+            .as_ref()
+            .and_then(|texture| builder.import_weak("emissive", texture, wgpu::TextureUses::empty()));
+
+        let (bounds_center, bounds_radius) = self.world_bounding_sphere(model_matrix);
+        let light_view_proj = self.light.shadow_view_proj(bounds_center, bounds_radius);
+
+        let mut shadow_map = match &self.static_draw {
+            Some(static_draw) => builder.import("mesh.shadow_map", static_draw.shadow_map.clone(), wgpu::TextureUses::empty()),
+            None => builder.create("mesh.shadow_map", Self::shadow_map_desc()),
         };
 
+        let shadow_sampler = builder.import("mesh.shadow_sampler", self.shadow_sampler.clone(), ());
+
+        let base_color_sampler = builder.import(
+            "base_color_sampler",
+            if base_color.is_some() { self.material.base_color_sampler.clone() } else { self.default_sampler.clone() },
+            ()
+        );
+
         {
-            let mut node = builder.add_graphic_node("mesh_render");
+            let shadow_uniform = builder.create_uniform::<zenith_build::shadow::ShadowUniforms>("mesh.shadow_uniform");
 
-            let view_uniform = node.read(&view_uniform, wgpu::BufferUses::UNIFORM);
-            let model_uniform = node.read(&model_uniform, wgpu::BufferUses::UNIFORM);
-            let output = node.write(&mut output, wgpu::TextureUses::COLOR_TARGET);
-            let depth_buffer = node.write(&mut depth_buffer, wgpu::TextureUses::DEPTH_STENCIL_WRITE);
+            let mut node = builder.add_graphic_node("mesh_shadow");
 
+            let shadow_uniform = node.read(&shadow_uniform, wgpu::BufferUses::UNIFORM);
             let vb_read = node.read(&vb, wgpu::BufferUses::VERTEX);
             let ib_read = node.read(&ib, wgpu::BufferUses::INDEX);
+            let shadow_map = node.write(&mut shadow_map, wgpu::TextureUses::DEPTH_STENCIL_WRITE);
 
-            let default_texture_read = node.read(&default_texture, wgpu::TextureUses::RESOURCE);
-
-            let tex_read = if let Some(texture) = &base_color {
-                Some(node.read(texture, wgpu::TextureUses::RESOURCE))
-            } else {
-                None
-            };
-
+            // shadow map uses conventional depth, cleared to the far plane
             node.setup_pipeline()
-                .with_shader(self.shader.clone())
-                .with_color(output, Default::default())
-                .with_depth_stencil(depth_buffer, DepthStencilInfo {
+                .with_shader(self.shadow_shader.clone())
+                .with_depth_only(shadow_map, DepthStencilInfo {
                     depth_write: true,
-                    compare: wgpu::CompareFunction::Greater,
+                    compare: wgpu::CompareFunction::Less,
                     stencil: wgpu::StencilState::default(),
                     bias: wgpu::DepthBiasState::default(),
-                    depth_load_op: wgpu::LoadOp::Clear(0.0),
+                    depth_load_op: wgpu::LoadOp::Clear(1.0),
                     depth_store_op: wgpu::StoreOp::Store,
                     stencil_load_op: wgpu::LoadOp::Clear(0),
                     stencil_store_op: wgpu::StoreOp::Discard,
                 });
 
-            let view_proj = proj_matrix * view_matrix;
-            let base_color = self.base_color.into();
-            let default_sampler_clone = self.default_sampler.clone();
-            let index_count = self.mesh_buffers.index_count;
-            let base_color_sampler = self.material.base_color_sampler.clone();
+            let indices = self.lod_indices();
+            let base_vertex = self.mesh_buffers.base_vertex;
+            let index_format = self.mesh_buffers.index_format;
 
             node.execute(move |ctx, encoder| {
-                let view_uniform_data = zenith_build::mesh::ViewUniforms::new(view_proj);
-                ctx.write_buffer(&view_uniform, 0, view_uniform_data);
-                let model_uniform_data = zenith_build::mesh::ModelUniforms::new(model_matrix, base_color);
-                ctx.write_buffer(&model_uniform, 0, model_uniform_data);
+                ctx.write_uniform(&shadow_uniform, &zenith_build::shadow::ShadowUniforms::new(light_view_proj, model_matrix));
 
-                let view_buffer = ctx.get_buffer(&view_uniform);
-                let model_buffer = ctx.get_buffer(&model_uniform);
+                let shadow_buffer = ctx.get_buffer(&shadow_uniform);
+                let vertex_buffer = ctx.get_buffer(&vb_read);
+                let index_buffer = ctx.get_buffer(&ib_read);
 
                 let mut render_pass = ctx.begin_render_pass(encoder);
 
+                ctx.bind_pipeline(&mut render_pass)
+                    .with_binding(0, 0, shadow_buffer.as_entire_binding())
+                    .bind();
+
+                render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+                render_pass.set_index_buffer(index_buffer.slice(..), index_format);
+                render_pass.draw_indexed(indices, base_vertex, 0..1);
+            });
+        }
+
+        {
+            let mut node = builder.add_graphic_node("mesh_render");
+
+            let frame_uniforms = node.read(frame_uniforms, wgpu::BufferUses::UNIFORM);
+            let model_uniform = node.read(&model_uniform, wgpu::BufferUses::UNIFORM);
+            let light_uniform = node.read(&light_uniform, wgpu::BufferUses::UNIFORM);
+            let output = node.write(output, wgpu::TextureUses::COLOR_TARGET);
+            let depth_buffer = node.write(depth_buffer, wgpu::TextureUses::DEPTH_STENCIL_WRITE);
+
+            let vb_read = node.read(&vb, wgpu::BufferUses::VERTEX);
+            let ib_read = node.read(line_ib.as_ref().unwrap_or(&ib), wgpu::BufferUses::INDEX);
+
+            let default_texture_read = node.read(&default_texture, wgpu::TextureUses::RESOURCE);
+            let shadow_map_read = node.read(&shadow_map, wgpu::TextureUses::RESOURCE);
+            let base_color_sampler = node.read(&base_color_sampler, ());
+            let shadow_sampler = node.read(&shadow_sampler, ());
+
+            let tex_read = if let Some(texture) = &base_color {
+                Some(node.read(texture, wgpu::TextureUses::RESOURCE))
+            } else {
+                None
+            };
+            let occlusion_read = occlusion.as_ref().map(|texture| node.read(texture, wgpu::TextureUses::RESOURCE));
+            let emissive_texture_read = emissive_texture.as_ref().map(|texture| node.read(texture, wgpu::TextureUses::RESOURCE));
+
+            let emissive = emissive.map(|emissive| node.write(emissive, wgpu::TextureUses::COLOR_TARGET));
+            let (shader, emissive_info) = self.emissive_target_info(emissive.is_some(), color_load_op);
+
+            let alpha_mode = self.alpha_mode();
+            let blend = (alpha_mode == AlphaMode::Blend).then_some(wgpu::BlendState::ALPHA_BLENDING);
+            // blended surfaces are tested against, but do NOT occlude what is drawn after them
+            let mut depth_stencil_info = Self::depth_stencil_info(depth_load_op);
+            depth_stencil_info.depth_write = alpha_mode != AlphaMode::Blend;
+
+            let mut pipeline = node.setup_pipeline()
+                .with_shader(shader)
+                .with_color(output, ColorInfoBuilder::default()
+                    .blend(blend)
+                    .load_op(color_load_op)
+                    .store_op(wgpu::StoreOp::Store)
+                    .build()
+                    .unwrap());
+            if let (Some(emissive), Some(emissive_info)) = (emissive, emissive_info) {
+                pipeline = pipeline.with_color(emissive, emissive_info);
+            }
+            // mirroring transforms flip the winding
+            let cull_mode = self.material.material.cull_mode().map(|face| match (face, model_matrix.determinant() < 0.0) {
+                (wgpu::Face::Back, true) => wgpu::Face::Front,
+                (wgpu::Face::Front, true) => wgpu::Face::Back,
+                (face, false) => face,
+            });
+
+            pipeline
+                .with_depth_stencil(depth_buffer, depth_stencil_info)
+                .with_viewport(viewport)
+                .with_cull_mode(cull_mode)
+                .with_topology(topology)
+                .with_polygon_mode(polygon_mode);
+
+            let base_color = self.base_color.into();
+            let metallic = self.material.material.metallic;
+            let roughness = self.material.material.roughness;
+            // without an occlusion map the default white texture is sampled, strength does NOT matter
+            let occlusion_strength = self.material.material.occlusion_strength;
+            let emissive_factor = Vec3::from_array(self.material.material.emissive);
+            let alpha_cutoff = if alpha_mode == AlphaMode::Mask { self.material.material.alpha_cutoff } else { 0.0 };
+            let opacity = self.material.material.base_color[3];
+            let alpha_blend = (alpha_mode == AlphaMode::Blend) as u32;
+            let light = self.light;
+            let indices = draw_indices;
+            let base_vertex = self.mesh_buffers.base_vertex;
+            let index_format = draw_index_format;
+            let bundles = self.static_draw.as_ref().map(|static_draw| static_draw.bundles.clone());
+
+            node.execute(move |ctx, encoder| {
+                let model_uniform_data = zenith_build::mesh::ModelUniforms::new(
+                    model_matrix,
+                    base_color,
+                    metallic,
+                    roughness,
+                    occlusion_strength,
+                    emissive_factor,
+                    alpha_cutoff,
+                    opacity,
+                    alpha_blend,
+                );
+                ctx.write_uniform(&model_uniform, &model_uniform_data);
+                let light_uniform_data = zenith_build::mesh::DirectionalLightUniforms::new(light_view_proj, light.direction.normalize(), light.intensity, light.color);
+                ctx.write_uniform(&light_uniform, &light_uniform_data);
+
+                let frame_buffer = ctx.get_buffer(&frame_uniforms);
+                let model_buffer = ctx.get_buffer(&model_uniform);
+                let light_buffer = ctx.get_buffer(&light_uniform);
                 let vertex_buffer = ctx.get_buffer(&vb_read);
                 let index_buffer = ctx.get_buffer(&ib_read);
 
-                let (tex, sampler) = if let Some(tex) = tex_read {
-                    (ctx.get_texture(&tex), base_color_sampler.clone())
+                let tex = if let Some(tex) = tex_read {
+                    ctx.get_texture(&tex)
+                } else {
+                    ctx.get_texture(&default_texture_read)
+                };
+                let occlusion = if let Some(occlusion) = occlusion_read {
+                    ctx.get_texture(&occlusion)
                 } else {
-                    (ctx.get_texture(&default_texture_read), default_sampler_clone.clone())
+                    ctx.get_texture(&default_texture_read)
                 };
+                let emissive = if let Some(emissive) = emissive_texture_read {
+                    ctx.get_texture(&emissive)
+                } else {
+                    ctx.get_texture(&default_texture_read)
+                };
+                let shadow_map = ctx.get_texture(&shadow_map_read);
+                let sampler = ctx.get_sampler(&base_color_sampler);
+                let shadow_sampler = ctx.get_sampler(&shadow_sampler);
 
                 let texture_view = tex.create_view(&wgpu::TextureViewDescriptor::default());
+                let occlusion_view = occlusion.create_view(&wgpu::TextureViewDescriptor::default());
+                let emissive_view = emissive.create_view(&wgpu::TextureViewDescriptor::default());
+                let shadow_map_view = shadow_map.create_view(&wgpu::TextureViewDescriptor::default());
+
+                if let Some(bundles) = bundles {
+                    let key = MeshBundleKey {
+                        pipeline: ctx.pipeline().clone(),
+                        buffers: [frame_buffer.clone(), model_buffer.clone(), light_buffer.clone(), vertex_buffer.clone(), index_buffer.clone()],
+                        textures: [tex, shadow_map, occlusion, emissive],
+                        samplers: [sampler.clone(), shadow_sampler.clone()],
+                        indices: indices.clone(),
+                        base_vertex,
+                        index_format,
+                    };
+
+                    let mut bundles = bundles.lock().unwrap();
+                    if bundles.len() <= view_index {
+                        bundles.resize_with(view_index + 1, || None);
+                    }
+                    let cached = &mut bundles[view_index];
+                    if cached.as_ref().is_none_or(|cached| cached.key != key) {
+                        let frame_group = ctx.create_bind_group(0, &[
+                            wgpu::BindGroupEntry { binding: 0, resource: frame_buffer.as_entire_binding() },
+                        ]);
+                        let mesh_group = ctx.create_bind_group(1, &[
+                            wgpu::BindGroupEntry { binding: 0, resource: model_buffer.as_entire_binding() },
+                            wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(&texture_view) },
+                            wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::Sampler(&sampler) },
+                            wgpu::BindGroupEntry { binding: 3, resource: light_buffer.as_entire_binding() },
+                            wgpu::BindGroupEntry { binding: 4, resource: wgpu::BindingResource::TextureView(&shadow_map_view) },
+                            wgpu::BindGroupEntry { binding: 5, resource: wgpu::BindingResource::Sampler(&shadow_sampler) },
+                            wgpu::BindGroupEntry { binding: 6, resource: wgpu::BindingResource::TextureView(&occlusion_view) },
+                            wgpu::BindGroupEntry { binding: 7, resource: wgpu::BindingResource::TextureView(&emissive_view) },
+                        ]);
+
+                        let mut bundle_encoder = ctx.create_render_bundle_encoder();
+                        bundle_encoder.set_bind_group(0, &frame_group, &[]);
+                        bundle_encoder.set_bind_group(1, &mesh_group, &[]);
+                        bundle_encoder.set_vertex_buffer(0, vertex_buffer.slice(..));
+                        bundle_encoder.set_index_buffer(index_buffer.slice(..), index_format);
+                        bundle_encoder.draw_indexed(indices, base_vertex, 0..1);
+
+                        let bundle = bundle_encoder.finish(&wgpu::RenderBundleDescriptor {
+                            label: Some("mesh bundle"),
+                        });
+                        *cached = Some(CachedMeshBundle { key, bundle });
+                    }
+
+                    let mut render_pass = ctx.begin_render_pass(encoder);
+                    render_pass.execute_bundles(cached.as_ref().map(|cached| &cached.bundle));
+                    return;
+                }
+
+                let mut render_pass = ctx.begin_render_pass(encoder);
 
                 // Bind all resources for this mesh
                 ctx.bind_pipeline(&mut render_pass)
-                    .with_binding(0, 0, view_buffer.as_entire_binding())
-                    .with_binding(0, 1, model_buffer.as_entire_binding())
-                    .with_binding(0, 2, wgpu::BindingResource::TextureView(&texture_view))
-                    .with_binding(0, 3, wgpu::BindingResource::Sampler(&sampler))
+                    .with_binding(0, 0, frame_buffer.as_entire_binding())
+                    .with_binding(1, 0, model_buffer.as_entire_binding())
+                    .with_binding(1, 1, wgpu::BindingResource::TextureView(&texture_view))
+                    .with_binding(1, 2, wgpu::BindingResource::Sampler(&sampler))
+                    .with_binding(1, 3, light_buffer.as_entire_binding())
+                    .with_binding(1, 4, wgpu::BindingResource::TextureView(&shadow_map_view))
+                    .with_binding(1, 5, wgpu::BindingResource::Sampler(&shadow_sampler))
+                    .with_binding(1, 6, wgpu::BindingResource::TextureView(&occlusion_view))
+                    .with_binding(1, 7, wgpu::BindingResource::TextureView(&emissive_view))
                     .bind();
 
                 render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
-                render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-                render_pass.draw_indexed(0..index_count, 0, 0..1);
+                render_pass.set_index_buffer(index_buffer.slice(..), index_format);
+                render_pass.draw_indexed(indices, base_vertex, 0..1);
             });
         }
-
-        output
     }
 } 
\ No newline at end of file
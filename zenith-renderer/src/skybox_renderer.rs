@@ -0,0 +1,170 @@
+use std::sync::Arc;
+use glam::{Mat3, Mat4};
+use zenith_asset::render::Texture as TextureAsset;
+use zenith_build::{ShaderEntry};
+use zenith_core::collections::SmallVec;
+use zenith_render::{define_shader, GraphicShader, RenderDevice};
+use zenith_rendergraph::{ColorInfoBuilder, DepthStencilInfo, RenderGraphBuilder, RenderGraphResource, RenderResource, Sampler, Texture};
+
+/// Draw an environment cubemap as background.
+/// Skybox is drawn at the far plane, so any geometry rendered afterward will draw over it.
+pub struct SkyboxRenderer {
+    cubemap: RenderResource<Texture>,
+    sampler: RenderResource<Sampler>,
+    shader: Arc<GraphicShader>,
+}
+
+impl SkyboxRenderer {
+    /// Create a skybox from six faces in the order of +X, -X, +Y, -Y, +Z, -Z.
+    /// All faces must share the same size and format.
+    pub fn from_faces(render_device: &RenderDevice, faces: [&TextureAsset; 6]) -> Self {
+        let device = render_device.device();
+
+        let width = faces[0].width;
+        let height = faces[0].height;
+        let format = faces[0].format;
+        let mip_level_count = faces[0].mip_level_count;
+        debug_assert!(faces.iter().all(|face| face.width == width && face.height == height && face.mip_level_count == mip_level_count),
+            "Skybox faces must share the same size!");
+
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 6,
+        };
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("skybox cubemap"),
+            size,
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: faces[0].wgpu_format(),
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        for (layer, face) in faces.iter().enumerate() {
+            for (mip_level, (width, height, pixels)) in face.mip_levels().enumerate() {
+                render_device.queue().write_texture(
+                    wgpu::TexelCopyTextureInfo {
+                        texture: &texture,
+                        mip_level: mip_level as u32,
+                        origin: wgpu::Origin3d {
+                            x: 0,
+                            y: 0,
+                            z: layer as u32,
+                        },
+                        aspect: wgpu::TextureAspect::All,
+                    },
+                    pixels,
+                    wgpu::TexelCopyBufferLayout {
+                        offset: 0,
+                        bytes_per_row: Some(format.bytes_per_row(width)),
+                        rows_per_image: Some(format.rows_per_image(height)),
+                    },
+                    wgpu::Extent3d {
+                        width,
+                        height,
+                        depth_or_array_layers: 1,
+                    }.physical_size(face.wgpu_format()),
+                );
+            }
+        }
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("skybox sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        define_shader! {
+            let shader = Graphic(skybox, "skybox.wgsl", ShaderEntry::Skybox, 1, 1)
+        }
+
+        Self {
+            cubemap: RenderResource::new(texture),
+            sampler: RenderResource::new(sampler),
+            shader: Arc::new(shader.unwrap().with_depth_stencil()),
+        }
+    }
+
+    /// Clear the color and depth targets and draw the skybox into them.
+    /// Should be added before any other geometry pass which loads (NOT clears) these targets.
+    pub fn build_render_graph(
+        &self,
+        builder: &mut RenderGraphBuilder,
+        output: &mut RenderGraphResource<Texture>,
+        depth_buffer: &mut RenderGraphResource<Texture>,
+        view_matrix: Mat4,
+        proj_matrix: Mat4,
+    ) {
+        let uniform = builder.create_uniform::<zenith_build::skybox::SkyboxUniforms>("skybox.uniform");
+
+        let cubemap = builder.import(
+            "skybox.cubemap",
+            self.cubemap.clone(),
+            wgpu::TextureUses::empty()
+        );
+        let sampler = builder.import("skybox.sampler", self.sampler.clone(), ());
+
+        {
+            let mut node = builder.add_graphic_node("skybox");
+
+            let uniform = node.read(&uniform, wgpu::BufferUses::UNIFORM);
+            let cubemap = node.read(&cubemap, wgpu::TextureUses::RESOURCE);
+            let sampler = node.read(&sampler, ());
+            let output = node.write(output, wgpu::TextureUses::COLOR_TARGET);
+            let depth_buffer = node.write(depth_buffer, wgpu::TextureUses::DEPTH_STENCIL_WRITE);
+
+            node.setup_pipeline()
+                .with_shader(self.shader.clone())
+                .with_color(output, ColorInfoBuilder::default()
+                    .load_op(wgpu::LoadOp::Clear(wgpu::Color::BLACK))
+                    .store_op(wgpu::StoreOp::Store)
+                    .build()
+                    .unwrap())
+                .with_depth_stencil(depth_buffer, DepthStencilInfo {
+                    depth_write: true,
+                    compare: wgpu::CompareFunction::Always,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                    depth_load_op: wgpu::LoadOp::Clear(0.0),
+                    depth_store_op: wgpu::StoreOp::Store,
+                    stencil_load_op: wgpu::LoadOp::Clear(0),
+                    stencil_store_op: wgpu::StoreOp::Discard,
+                });
+
+            // only rotation matters when sampling the environment
+            let view_rotation = Mat4::from_mat3(Mat3::from_mat4(view_matrix));
+            let inv_view_proj = (proj_matrix * view_rotation).inverse();
+
+            node.execute(move |ctx, encoder| {
+                ctx.write_uniform(&uniform, &zenith_build::skybox::SkyboxUniforms::new(inv_view_proj));
+
+                let uniform_buffer = ctx.get_buffer(&uniform);
+                let sampler = ctx.get_sampler(&sampler);
+                let cubemap = ctx.get_texture(&cubemap);
+                let cubemap_view = cubemap.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("skybox cubemap view"),
+                    dimension: Some(wgpu::TextureViewDimension::Cube),
+                    ..Default::default()
+                });
+
+                let mut render_pass = ctx.begin_render_pass(encoder);
+                ctx.bind_pipeline(&mut render_pass)
+                    .with_binding(0, 0, uniform_buffer.as_entire_binding())
+                    .with_binding(0, 1, wgpu::BindingResource::TextureView(&cubemap_view))
+                    .with_binding(0, 2, wgpu::BindingResource::Sampler(&sampler))
+                    .bind();
+
+                render_pass.draw(0..3, 0..1);
+            });
+        }
+    }
+}
@@ -0,0 +1,274 @@
+use std::sync::Arc;
+use anyhow::anyhow;
+use glam::{Vec2, Vec4};
+use zenith_build::text::{self, VertexInput as TextVertex};
+use zenith_build::{ShaderEntry};
+use zenith_core::collections::hashmap::HashMap;
+use zenith_core::collections::SmallVec;
+use zenith_render::{define_shader, GraphicShader, RenderDevice};
+use zenith_rendergraph::{BufferDesc, ColorInfoBuilder, RenderGraphBuilder, RenderGraphResource, RenderResource, Sampler, Texture};
+
+const ATLAS_WIDTH: u32 = 512;
+/// Empty pixels around each glyph, avoid bleeding of neighbours on linear filtering.
+const GLYPH_PADDING: u32 = 1;
+
+/// Placement of a rasterized glyph in the atlas.
+#[derive(Debug, Clone, Copy)]
+struct Glyph {
+    uv_min: Vec2,
+    uv_max: Vec2,
+    size: Vec2,
+    /// Offset from the pen position on the baseline to the top-left corner, y-down.
+    offset: Vec2,
+    advance: f32,
+}
+
+/// Draw text in screen space with a glyph atlas rasterized once on creation.
+///
+/// Positions are in logical pixels of the render target, origin at top-left and y-down.
+/// Glyphs are rasterized at the physical size for the scale factor given on creation, so HiDPI text stays sharp.
+/// Texts queued by [`TextRenderer::draw_text`] are flushed in the next [`TextRenderer::build_render_graph`].
+pub struct TextRenderer {
+    glyphs: HashMap<char, Glyph>,
+    atlas: RenderResource<Texture>,
+    sampler: RenderResource<Sampler>,
+    shader: Arc<GraphicShader>,
+    ascent: f32,
+    line_height: f32,
+    scale_factor: f32,
+    vertices: Vec<TextVertex>,
+}
+
+impl TextRenderer {
+    /// Rasterize printable ASCII characters of the font (ttf/otf bytes) at `font_size` pixels, with a scale factor of 1.
+    pub fn new(render_device: &RenderDevice, font_bytes: &[u8], font_size: f32) -> Result<Self, anyhow::Error> {
+        Self::with_scale_factor(render_device, font_bytes, font_size, 1.)
+    }
+
+    /// Rasterize printable ASCII characters of the font (ttf/otf bytes) at `font_size` logical pixels.
+    /// `scale_factor` is the ratio of physical to logical pixels of the window, see `Engine::scale_factor`.
+    /// Create a new renderer when it changes.
+    pub fn with_scale_factor(render_device: &RenderDevice, font_bytes: &[u8], font_size: f32, scale_factor: f32) -> Result<Self, anyhow::Error> {
+        let font_size = font_size * scale_factor;
+        let font = fontdue::Font::from_bytes(font_bytes, fontdue::FontSettings {
+            scale: font_size,
+            ..Default::default()
+        }).map_err(|err| anyhow!("Failed to parse font: {}", err))?;
+
+        let line_metrics = font
+            .horizontal_line_metrics(font_size)
+            .ok_or(anyhow!("Font has no horizontal line metrics"))?;
+
+        // shelf packing, glyphs are placed in rows from left to right
+        let rasterized: Vec<_> = (b' '..=b'~')
+            .map(char::from)
+            .map(|ch| (ch, font.rasterize(ch, font_size)))
+            .collect();
+
+        let mut placements = Vec::with_capacity(rasterized.len());
+        let (mut cursor_x, mut cursor_y, mut row_height) = (GLYPH_PADDING, GLYPH_PADDING, 0);
+        for (_, (metrics, _)) in &rasterized {
+            let (width, height) = (metrics.width as u32, metrics.height as u32);
+            if cursor_x + width + GLYPH_PADDING > ATLAS_WIDTH {
+                cursor_x = GLYPH_PADDING;
+                cursor_y += row_height + GLYPH_PADDING;
+                row_height = 0;
+            }
+
+            placements.push((cursor_x, cursor_y));
+            cursor_x += width + GLYPH_PADDING;
+            row_height = row_height.max(height);
+        }
+        let atlas_height = (cursor_y + row_height + GLYPH_PADDING).next_power_of_two();
+
+        let mut pixels = vec![0u8; (ATLAS_WIDTH * atlas_height) as usize];
+        let mut glyphs = HashMap::default();
+        let atlas_size = Vec2::new(ATLAS_WIDTH as f32, atlas_height as f32);
+
+        for ((ch, (metrics, bitmap)), (x, y)) in rasterized.iter().zip(placements) {
+            for row in 0..metrics.height {
+                let dst = ((y as usize + row) * ATLAS_WIDTH as usize) + x as usize;
+                pixels[dst..dst + metrics.width].copy_from_slice(&bitmap[row * metrics.width..(row + 1) * metrics.width]);
+            }
+
+            let size = Vec2::new(metrics.width as f32, metrics.height as f32);
+            let uv_min = Vec2::new(x as f32, y as f32) / atlas_size;
+            glyphs.insert(*ch, Glyph {
+                uv_min,
+                uv_max: uv_min + size / atlas_size,
+                size,
+                offset: Vec2::new(metrics.xmin as f32, -(metrics.ymin as f32 + size.y)),
+                advance: metrics.advance_width,
+            });
+        }
+
+        let (atlas, sampler) = Self::create_atlas(render_device, &pixels, atlas_height);
+
+        define_shader! {
+            let shader = Graphic(text, "text.wgsl", ShaderEntry::Text, wgpu::VertexStepMode::Vertex, 1, 1)
+        }
+
+        Ok(Self {
+            glyphs,
+            atlas,
+            sampler,
+            shader: Arc::new(shader.unwrap()),
+            ascent: line_metrics.ascent,
+            line_height: line_metrics.new_line_size,
+            scale_factor,
+            vertices: Vec::new(),
+        })
+    }
+
+    fn create_atlas(render_device: &RenderDevice, pixels: &[u8], height: u32) -> (RenderResource<Texture>, RenderResource<Sampler>) {
+        let device = render_device.device();
+
+        let size = wgpu::Extent3d {
+            width: ATLAS_WIDTH,
+            height,
+            depth_or_array_layers: 1,
+        };
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("glyph atlas"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        render_device.queue().write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            pixels,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(ATLAS_WIDTH),
+                rows_per_image: Some(height),
+            },
+            size,
+        );
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("glyph atlas sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        (RenderResource::new(texture), RenderResource::new(sampler))
+    }
+
+    /// Return the ratio of physical to logical pixels the glyphs are rasterized for.
+    #[inline]
+    pub fn scale_factor(&self) -> f32 { self.scale_factor }
+
+    /// Queue a text whose top-left corner is at `position`, in logical pixels. '\n' starts a new line.
+    /// Characters NOT in the atlas are skipped.
+    pub fn draw_text(&mut self, position: Vec2, text: &str, color: [f32; 4]) {
+        let color = Vec4::from_array(color);
+        // glyph metrics are already in physical pixels
+        let position = position * self.scale_factor;
+        let mut pen = Vec2::new(position.x, position.y + self.ascent);
+
+        for ch in text.chars() {
+            if ch == '\n' {
+                pen = Vec2::new(position.x, pen.y + self.line_height);
+                continue;
+            }
+
+            let Some(glyph) = self.glyphs.get(&ch) else {
+                continue;
+            };
+
+            if glyph.size.x > 0. && glyph.size.y > 0. {
+                let min = (pen + glyph.offset).round();
+                let max = min + glyph.size;
+
+                let vertex = |x: f32, y: f32, u: f32, v: f32| TextVertex {
+                    position: Vec2::new(x, y),
+                    tex_coord: Vec2::new(u, v),
+                    color,
+                };
+                let (uv_min, uv_max) = (glyph.uv_min, glyph.uv_max);
+
+                self.vertices.extend_from_slice(&[
+                    vertex(min.x, min.y, uv_min.x, uv_min.y),
+                    vertex(min.x, max.y, uv_min.x, uv_max.y),
+                    vertex(max.x, max.y, uv_max.x, uv_max.y),
+                    vertex(min.x, min.y, uv_min.x, uv_min.y),
+                    vertex(max.x, max.y, uv_max.x, uv_max.y),
+                    vertex(max.x, min.y, uv_max.x, uv_min.y),
+                ]);
+            }
+
+            pen.x += glyph.advance;
+        }
+    }
+
+    /// Draw all queued texts over `output` with alpha blending, then clear the queue.
+    pub fn build_render_graph(&mut self, builder: &mut RenderGraphBuilder, output: &mut RenderGraphResource<Texture>) {
+        if self.vertices.is_empty() {
+            return;
+        }
+        let vertices = std::mem::take(&mut self.vertices);
+
+        let uniform = builder.create_uniform::<text::TextUniforms>("text.uniform");
+        let vb = builder.create("text.vertex", BufferDesc {
+            label: Some("text vertices"),
+            size: size_of_val(vertices.as_slice()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let atlas = builder.import("text.atlas", self.atlas.clone(), wgpu::TextureUses::empty());
+        let sampler = builder.import("text.sampler", self.sampler.clone(), ());
+
+        let mut node = builder.add_graphic_node("text");
+
+        let uniform = node.read(&uniform, wgpu::BufferUses::UNIFORM);
+        let vb = node.read(&vb, wgpu::BufferUses::VERTEX);
+        let atlas = node.read(&atlas, wgpu::TextureUses::RESOURCE);
+        let sampler = node.read(&sampler, ());
+        let output = node.write(output, wgpu::TextureUses::COLOR_TARGET);
+
+        node.setup_pipeline()
+            .with_shader(self.shader.clone())
+            .with_color(output.clone(), ColorInfoBuilder::default()
+                .blend(wgpu::BlendState::ALPHA_BLENDING)
+                .load_op(wgpu::LoadOp::Load)
+                .store_op(wgpu::StoreOp::Store)
+                .build()
+                .unwrap());
+
+        node.execute(move |ctx, encoder| {
+            let output_size = ctx.get_texture(&output).size();
+            ctx.write_uniform(&uniform, &text::TextUniforms::new(Vec2::new(output_size.width as f32, output_size.height as f32)));
+            ctx.write_buffer_slice(&vb, 0, &vertices);
+
+            let uniform_buffer = ctx.get_buffer(&uniform);
+            let vertex_buffer = ctx.get_buffer(&vb);
+            let atlas_view = ctx.get_texture(&atlas).create_view(&wgpu::TextureViewDescriptor::default());
+            let sampler = ctx.get_sampler(&sampler);
+
+            let mut render_pass = ctx.begin_render_pass(encoder);
+            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+
+            ctx.bind_pipeline(&mut render_pass)
+                .with_binding(0, 0, uniform_buffer.as_entire_binding())
+                .with_binding(0, 1, wgpu::BindingResource::TextureView(&atlas_view))
+                .with_binding(0, 2, wgpu::BindingResource::Sampler(&sampler))
+                .bind();
+
+            render_pass.draw(0..vertices.len() as u32, 0..1);
+        });
+    }
+}
@@ -4,17 +4,28 @@ use zenith_build::triangle::{self, VertexInput as Vertex};
 use zenith_build::{ShaderEntry};
 use zenith_core::collections::SmallVec;
 use zenith_render::{define_shader, GraphicShader, RenderDevice};
-use zenith_rendergraph::{Buffer, BufferDesc, ColorInfoBuilder, RenderGraphBuilder, RenderGraphResource, RenderResource, Texture, TextureDesc};
+use zenith_rendergraph::{Buffer, ColorInfoBuilder, RenderGraphBuilder, RenderGraphResource, RenderResource, Texture, TextureDesc};
 
 pub struct TriangleRenderer {
     vertex_buffer: RenderResource<Buffer>,
     index_buffer: RenderResource<Buffer>,
+    /// Placements of all triangles, rotation is animated in the shader by the frame time.
+    transform_buffer: RenderResource<Buffer>,
     shader: Arc<GraphicShader>,
-    start_time: std::time::Instant,
+    count: u32,
+    /// Byte stride between transforms of two triangles in the uniform buffer.
+    uniform_stride: wgpu::BufferAddress,
+    /// Format of the output target, matches the swapchain so that the output can be copied to it.
+    output_format: wgpu::TextureFormat,
 }
 
 impl TriangleRenderer {
     pub fn new(device: &RenderDevice) -> Self {
+        Self::with_count(device, 1)
+    }
+
+    /// Draw `count` spinning triangles in a row, transforms of all triangles share one uniform buffer.
+    pub fn with_count(device: &RenderDevice, count: u32) -> Self {
         let vertices = [
             Vertex { position: [0.0, 0.5, 0.0].into(), color: [1.0, 0.0, 0.0].into() },
             Vertex { position: [-0.5, -0.5, 0.0].into(), color: [0.0, 1.0, 0.0].into() },
@@ -22,7 +33,12 @@ impl TriangleRenderer {
         ];
         let indices = [0u16, 1, 2];
 
+        let count = count.max(1);
+        let output_format = device.output_format();
         let device = device.device();
+        let uniform_size = size_of::<triangle::Uniforms>() as wgpu::BufferAddress;
+        let uniform_stride = uniform_size.next_multiple_of(device.limits().min_uniform_buffer_offset_alignment as wgpu::BufferAddress);
+
         let vertex_buffer = RenderResource::new(device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("triangle vertex buffer"),
             contents: bytemuck::cast_slice(&vertices),
@@ -35,22 +51,51 @@ impl TriangleRenderer {
             usage: wgpu::BufferUsages::INDEX,
         }));
 
+        // lay out triangles evenly in a row across the screen
+        let scale = 1.0 / count as f32;
+        let mut transforms = vec![0u8; (uniform_stride * count as wgpu::BufferAddress) as usize];
+        for index in 0..count {
+            let x = -1.0 + (2 * index + 1) as f32 * scale;
+            let transform = glam::Mat4::from_translation(glam::Vec3::new(x, 0.0, 0.0))
+                * glam::Mat4::from_scale(glam::Vec3::splat(scale));
+
+            let offset = (index as wgpu::BufferAddress * uniform_stride) as usize;
+            transforms[offset..offset + uniform_size as usize].copy_from_slice(bytemuck::bytes_of(&triangle::Uniforms::new(transform)));
+        }
+
+        let transform_buffer = RenderResource::new(device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("triangle transforms"),
+            contents: &transforms,
+            usage: wgpu::BufferUsages::UNIFORM,
+        }));
+
         define_shader! {
-            let shader = Graphic(triangle, "triangle.wgsl", ShaderEntry::Triangle, wgpu::VertexStepMode::Vertex, 1, 1)
+            let shader = Graphic(triangle, "triangle.wgsl", ShaderEntry::Triangle, wgpu::VertexStepMode::Vertex, 1, 2)
         }
-        let shader = Arc::new(shader.unwrap());
+        let shader = Arc::new(shader.unwrap().with_dynamic_offset(1, 0, uniform_size));
 
         Self {
             vertex_buffer,
             index_buffer,
+            transform_buffer,
             shader,
-            start_time: std::time::Instant::now()
+            count,
+            uniform_stride,
+            output_format,
         }
     }
 
-    pub fn build_render_graph(&self, builder: &mut RenderGraphBuilder, width: u32, height: u32) -> RenderGraphResource<Texture> {
+    /// Draw the triangles into a new render target, `frame_uniforms` comes from the engine (see `FrameUniformBuffer`).
+    pub fn build_render_graph(
+        &self,
+        builder: &mut RenderGraphBuilder,
+        frame_uniforms: &RenderGraphResource<Buffer>,
+        width: u32,
+        height: u32,
+    ) -> RenderGraphResource<Texture> {
         let vb = builder.import("triangle.vertex", self.vertex_buffer.clone(), wgpu::BufferUses::VERTEX);
         let ib = builder.import("triangle.index", self.index_buffer.clone(), wgpu::BufferUses::INDEX);
+        let transforms = builder.import("triangle.transform", self.transform_buffer.clone(), wgpu::BufferUses::UNIFORM);
 
         let mut output = builder.create("triangle.output", TextureDesc {
             label: Some("triangle output render target"),
@@ -62,16 +107,9 @@ impl TriangleRenderer {
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Bgra8UnormSrgb,
+            format: self.output_format,
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
-            view_formats: &[wgpu::TextureFormat::Bgra8UnormSrgb],
-        });
-
-        let uniform = builder.create("triangle.transform", BufferDesc {
-            label: Some("triangle uniform buffer"),
-            size: size_of::<[[f32; 4]; 4]>() as wgpu::BufferAddress,
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
+            view_formats: &[],
         });
 
         {
@@ -79,35 +117,36 @@ impl TriangleRenderer {
 
             let vb = node.read(&vb, wgpu::BufferUses::VERTEX);
             let ib = node.read(&ib, wgpu::BufferUses::INDEX);
-            let uniform = node.read(&uniform, wgpu::BufferUses::UNIFORM);
+            let frame_uniforms = node.read(frame_uniforms, wgpu::BufferUses::UNIFORM);
+            let transforms = node.read(&transforms, wgpu::BufferUses::UNIFORM);
             let output = node.write(&mut output, wgpu::TextureUses::COLOR_TARGET);
 
             node.setup_pipeline()
                 .with_shader(self.shader.clone())
                 .with_color(output, ColorInfoBuilder::default().build().unwrap());
 
-            let start_time = self.start_time;
+            let count = self.count;
+            let uniform_stride = self.uniform_stride;
 
             node.execute(move |ctx, encoder| {
-                let elapsed = start_time.elapsed().as_secs_f32();
-                let rotation_angle = elapsed * std::f32::consts::PI / 2.0;
-                let rotation_mat = glam::Mat4::from_rotation_z(rotation_angle);
-
-                let uniform_data = triangle::Uniforms::new(rotation_mat);
-                ctx.write_buffer(&uniform, 0, uniform_data);
-                
-                let uniform_buffer = ctx.get_buffer(&uniform);
+                let frame_buffer = ctx.get_buffer(&frame_uniforms);
+                let transform_buffer = ctx.get_buffer(&transforms);
                 let vertex_buffer = ctx.get_buffer(&vb);
                 let index_buffer = ctx.get_buffer(&ib);
 
                 let mut render_pass = ctx.begin_render_pass(encoder);
-                ctx.bind_pipeline(&mut render_pass)
-                    .with_binding(0, 0, uniform_buffer.as_entire_binding())
-                    .bind();
-
                 render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
                 render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-                render_pass.draw_indexed(0..3, 0, 0..1);
+
+                let mut bound = ctx.bind_pipeline(&mut render_pass)
+                    .with_binding(0, 0, frame_buffer.as_entire_binding())
+                    .with_dynamic_binding(1, 0, &transform_buffer, 0)
+                    .bind();
+
+                for index in 0..count {
+                    bound.set_dynamic_offsets(1, &[(index as wgpu::BufferAddress * uniform_stride) as wgpu::DynamicOffset]);
+                    bound.render_pass().draw_indexed(0..3, 0, 0..1);
+                }
             });
         }
 
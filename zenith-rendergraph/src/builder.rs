@@ -1,18 +1,20 @@
 use std::cell::Cell;
 use std::marker::PhantomData;
+use std::path::PathBuf;
 use std::sync::Arc;
-use log::warn;
-use crate::node::{NodePipelineState, RenderGraphNode};
-use crate::graph::{GraphicNodeExecutionContext, LambdaNodeExecutionContext, RenderGraph, ResourceStorage};
-use crate::node::{DepthStencilInfo};
-use crate::interface::{GraphResourceAccess, ResourceDescriptor, RenderResource, Texture};
+use bytemuck::NoUninit;
+use zenith_core::log::{warn, target::RENDER};
+use crate::node::{GraphicNodeJob, NodePipelineState, RenderGraphNode};
+use crate::graph::{BundleNodeExecutionContext, ComputeNodeExecutionContext, GraphMemoryStats, GraphicNodeExecutionContext, LambdaNodeExecutionContext, RenderGraph, ResourceStorage};
+use crate::node::{ComputePipelineDescriptor, DepthStencilInfo};
+use crate::interface::{Buffer, BufferDesc, GraphResourceAccess, ResourceDescriptor, RenderResource, Sampler, SamplerDesc, Texture, TextureDesc, WeakRenderResource};
 use crate::resource::{
     ExportResourceStorage, ExportedRenderGraphResource, GraphImportExportResource,
     GraphResource, GraphResourceDescriptor, GraphResourceView,
     GraphResourceId, InitialResourceStorage,
     RenderGraphResource, RenderGraphResourceAccess, Rt, Srv, Uav};
-use zenith_render::GraphicShader;
-use crate::{ColorInfo, GraphicPipelineDescriptor};
+use zenith_render::{ComputeShader, GraphicShader};
+use crate::{ColorInfo, ColorInfoBuilder, GraphicPipelineDescriptor, Viewport};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub(crate) struct ResourceAccessStorage {
@@ -26,6 +28,7 @@ pub struct RenderGraphBuilder {
     pub(crate) initial_resources: Vec<InitialResourceStorage>,
     #[allow(dead_code)]
     pub(crate) export_resources: Vec<ExportResourceStorage>,
+    dot_dump_path: Option<PathBuf>,
 }
 
 impl RenderGraphBuilder {
@@ -51,6 +54,9 @@ impl RenderGraphBuilder {
             ResourceDescriptor::Texture(desc) => {
                 self.initial_resources.push((name.to_owned(), desc).into());
             }
+            ResourceDescriptor::Sampler(desc) => {
+                self.initial_resources.push((name.to_owned(), desc).into());
+            }
         }
 
         RenderGraphResource {
@@ -59,6 +65,24 @@ impl RenderGraphBuilder {
         }
     }
 
+    /// Create a uniform buffer which is able to hold a `T`, size is rounded up to 16-byte alignment.
+    #[must_use]
+    pub fn create_uniform<T: NoUninit>(&mut self, name: &str) -> RenderGraphResource<Buffer> {
+        const UNIFORM_ALIGNMENT: wgpu::BufferAddress = 16;
+
+        self.create(name, BufferDesc {
+            label: None,
+            size: (size_of::<T>() as wgpu::BufferAddress).next_multiple_of(UNIFORM_ALIGNMENT),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    #[must_use]
+    pub fn create_sampler(&mut self, name: &str, desc: &SamplerDesc) -> RenderGraphResource<Sampler> {
+        self.create(name, desc.clone())
+    }
+
     #[must_use]
     pub fn import<R: GraphImportExportResource>(
         &mut self,
@@ -69,6 +93,20 @@ impl RenderGraphBuilder {
         GraphImportExportResource::import(import_resource, name, self, access)
     }
 
+    /// Import a resource which may have been released, return None if it is so that nodes using it can be skipped.
+    /// The graph keeps the resource alive until it is executed.
+    #[must_use]
+    pub fn import_weak<R: GraphImportExportResource>(
+        &mut self,
+        name: &str,
+        import_resource: &WeakRenderResource<R>,
+        access: impl Into<GraphResourceAccess>,
+    ) -> Option<RenderGraphResource<R>> {
+        import_resource
+            .upgrade()
+            .map(|resource| self.import(name, resource, access))
+    }
+
     #[must_use]
     pub fn export<R: GraphImportExportResource>(
         &mut self,
@@ -121,23 +159,105 @@ impl RenderGraphBuilder {
         }
     }
 
-    // #[must_use]
-    // pub fn add_compute_node(&mut self, name: &str) -> GraphComputeNodeBuilder {
-    //     let index = self.nodes.len();
-    //     self.nodes.push(RenderGraphNode {
-    //         node_name: name.to_string(),
-    //         ..Default::default()
-    //     });
-    //
-    //     GraphComputeNodeBuilder {
-    //         node: &mut self.nodes[index]
-    //     }
-    // }
+    /// Add a fullscreen pass which samples `src` and draws into `dst`, e.g. for tonemapping or format conversion.
+    ///
+    /// The shader should draw a fullscreen triangle from vertex index (3 vertices without any vertex buffer),
+    /// and read the source texture at binding 0 and the filtering `sampler` at binding 1 of bind group 0.
+    /// The sampler is imported, create it once and keep it across frames.
+    pub fn add_blit_node(
+        &mut self,
+        name: &str,
+        src: &RenderGraphResource<Texture>,
+        dst: &mut RenderGraphResource<Texture>,
+        shader: Arc<GraphicShader>,
+        sampler: &RenderResource<Sampler>,
+    ) {
+        self.add_viewport_blit_node(name, src, dst, shader, sampler, Viewport::FULL, wgpu::LoadOp::Clear(wgpu::Color::BLACK));
+    }
+
+    /// Like [`RenderGraphBuilder::add_blit_node`], but only draws into the `viewport` region of `dst`.
+    /// Use `LoadOp::Load` to keep what had been drawn outside the region, e.g. to composite several outputs.
+    pub fn add_viewport_blit_node(
+        &mut self,
+        name: &str,
+        src: &RenderGraphResource<Texture>,
+        dst: &mut RenderGraphResource<Texture>,
+        shader: Arc<GraphicShader>,
+        sampler: &RenderResource<Sampler>,
+        viewport: Viewport,
+        load_op: wgpu::LoadOp<wgpu::Color>,
+    ) {
+        let sampler = self.import(&format!("{name}.sampler"), sampler.clone(), ());
+
+        let mut node = self.add_graphic_node(name);
+
+        let src = node.read(src, wgpu::TextureUses::RESOURCE);
+        let sampler = node.read(&sampler, ());
+        let dst = node.write(dst, wgpu::TextureUses::COLOR_TARGET);
+
+        node.setup_pipeline()
+            .with_shader(shader)
+            .with_color(dst, ColorInfoBuilder::default()
+                .load_op(load_op)
+                .store_op(wgpu::StoreOp::Store)
+                .build()
+                .unwrap())
+            .with_viewport(viewport);
+
+        node.execute(move |ctx, encoder| {
+            let src_view = ctx.get_texture(&src).create_view(&wgpu::TextureViewDescriptor::default());
+            let sampler = ctx.get_sampler(&sampler);
+
+            let mut render_pass = ctx.begin_render_pass(encoder);
+
+            ctx.bind_pipeline(&mut render_pass)
+                .with_binding(0, 0, wgpu::BindingResource::TextureView(&src_view))
+                .with_binding(0, 1, wgpu::BindingResource::Sampler(&sampler))
+                .bind();
+
+            render_pass.draw(0..3, 0..1);
+        });
+    }
+
+    #[must_use]
+    pub fn add_compute_node(&mut self, name: &str) -> ComputeNodeBuilder<'_, '_> {
+        let index = self.nodes.len();
+
+        self.nodes.push(RenderGraphNode {
+            name: name.to_string(),
+            inputs: vec![],
+            outputs: vec![],
+            pipeline_state: NodePipelineState::Compute {
+                pipeline_desc: Default::default(),
+                job_functor: None,
+            },
+        });
 
+        ComputeNodeBuilder {
+            common: CommonNodeBuilder {
+                node: &mut self.nodes[index],
+                resources: &self.initial_resources,
+            }
+        }
+    }
+
+    /// Write the graph as Graphviz DOT to `path` every time it is built. See [`RenderGraph::to_dot`].
+    pub fn dump_on_build(&mut self, path: impl Into<PathBuf>) {
+        self.dot_dump_path = Some(path.into());
+    }
+
+    /// Create all resources of the graph.
+    /// Managed textures whose lifetimes in the graph do NOT overlap share an allocation if their descriptors are the same.
     pub fn build(self, device: &wgpu::Device) -> RenderGraph {
+        let mut memory_stats = GraphMemoryStats::default();
+        let texture_owners = Self::alias_transient_textures(&self.nodes, &self.initial_resources);
+        // textures created so far, indexed by the id of the resource owning the allocation
+        let mut allocations: Vec<Option<Texture>> = vec![None; texture_owners.len()];
+
         let resources = self.initial_resources
             .into_iter()
-            .map(|res| {
+            .enumerate()
+            .map(|(id, res)| {
                 match res {
                     InitialResourceStorage::ManagedBuffer(name, desc) => {
                         let buffer = device.create_buffer(&desc);
@@ -148,31 +268,127 @@ impl RenderGraphBuilder {
                         }
                     }
                     InitialResourceStorage::ManagedTexture(name, desc) => {
-                        let tex = device.create_texture(&desc);
+                        let size_bytes = texture_size_bytes(&desc);
+                        memory_stats.logical_bytes += size_bytes;
+
+                        // whichever user of an allocation comes first creates the texture, the others reuse it
+                        let owner = texture_owners[id].unwrap_or(id);
+                        let tex = allocations[owner]
+                            .get_or_insert_with(|| {
+                                memory_stats.allocated_bytes += size_bytes;
+                                device.create_texture(&desc)
+                            })
+                            .clone();
+
                         ResourceStorage::ManagedTexture {
                             name,
                             resource: tex,
                             state_tracker: Cell::new(wgpu::TextureUses::UNINITIALIZED).into()
                         }
                     }
+                    InitialResourceStorage::ManagedSampler(name, desc) => {
+                        let sampler = device.create_sampler(&desc);
+                        ResourceStorage::Sampler {
+                            name,
+                            resource: RenderResource::new(sampler),
+                        }
+                    }
+                    // trackers of imported resources start from the declared import state in every graph,
+                    // which holds since the previous graph returned them to it
                     InitialResourceStorage::ImportedBuffer(name, buffer, init_access) => ResourceStorage::ImportedBuffer {
                         name,
                         resource: buffer.into(),
                         state_tracker: Cell::new(init_access).into(),
+                        import_state: init_access,
                     },
                     InitialResourceStorage::ImportedTexture(name, tex, init_access) => ResourceStorage::ImportedTexture {
                         name,
                         resource: tex.into(),
                         state_tracker: Cell::new(init_access).into(),
+                        import_state: init_access,
+                    },
+                    InitialResourceStorage::ImportedSampler(name, sampler, _) => ResourceStorage::Sampler {
+                        name,
+                        resource: sampler,
                     },
                 }
             })
             .collect();
 
-        RenderGraph {
+        let alias_owners = texture_owners
+            .iter()
+            .enumerate()
+            .map(|(id, owner)| owner.unwrap_or(id) as GraphResourceId)
+            .collect();
+
+        let graph = RenderGraph {
             nodes: self.nodes,
-            resources
+            resources,
+            alias_owners,
+            memory_stats,
+        };
+
+        if let Some(path) = self.dot_dump_path {
+            if let Err(err) = std::fs::write(&path, graph.to_dot()) {
+                warn!(target: RENDER, "Failed to dump render graph to {}: {}", path.display(), err);
+            }
         }
+
+        graph
+    }
+
+    /// Return the id of the managed texture whose allocation each resource reuses, itself if it owns the allocation.
+    /// None for non-texture resources. Conservatively, only textures with identical descriptors are aliased.
+    fn alias_transient_textures(nodes: &[RenderGraphNode], resources: &[InitialResourceStorage]) -> Vec<Option<usize>> {
+        // (first, last) node index using each resource
+        let mut lifetimes = vec![None::<(usize, usize)>; resources.len()];
+        for (node_index, node) in nodes.iter().enumerate() {
+            for access in node.inputs.iter().chain(&node.outputs) {
+                let lifetime = lifetimes[access.id as usize].get_or_insert((node_index, node_index));
+                lifetime.1 = node_index;
+            }
+        }
+
+        let mut textures: Vec<_> = resources
+            .iter()
+            .enumerate()
+            .filter_map(|(id, res)| match res {
+                InitialResourceStorage::ManagedTexture(_, desc) => Some((id, desc, lifetimes[id])),
+                _ => None,
+            })
+            .collect();
+        // unused textures sort first and never alias
+        textures.sort_by_key(|(_, _, lifetime)| lifetime.map(|(first, _)| first));
+
+        let mut aliases = vec![None; resources.len()];
+        // (owner id, descriptor, last use) of each allocation
+        let mut allocations: Vec<(usize, &TextureDesc, usize)> = vec![];
+
+        for (id, desc, lifetime) in textures {
+            let Some((first_use, last_use)) = lifetime else {
+                aliases[id] = Some(id);
+                continue;
+            };
+
+            let reusable = allocations
+                .iter_mut()
+                .find(|(_, allocation_desc, allocation_last_use)| {
+                    *allocation_last_use < first_use && allocation_desc.map_label(|_| ()) == desc.map_label(|_| ())
+                });
+
+            match reusable {
+                Some((owner, _, allocation_last_use)) => {
+                    *allocation_last_use = last_use;
+                    aliases[id] = Some(*owner);
+                }
+                None => {
+                    allocations.push((id, desc, last_use));
+                    aliases[id] = Some(id);
+                }
+            }
+        }
+
+        aliases
     }
 }
 
@@ -202,7 +418,7 @@ impl CommonNodeBuilder<'_, '_> {
                 .expect("Graph resource id out of bound!")
                 .name();
 
-            warn!("Try to read resource[{name}] multiple time!")
+            warn!(target: RENDER, "Try to read resource[{name}] multiple time!")
         }
 
         access
@@ -228,7 +444,7 @@ impl CommonNodeBuilder<'_, '_> {
                 .expect("Graph resource id out of bound!")
                 .name();
 
-            warn!("Try to write to resource[{name}] multiple time!")
+            warn!(target: RENDER, "Try to write to resource[{name}] multiple time!")
         }
 
         access
@@ -278,7 +494,22 @@ impl<'node, 'res> GraphicNodeBuilder<'node, 'res> {
         F: FnOnce(&mut GraphicNodeExecutionContext, &mut wgpu::CommandEncoder) + 'static
     {
         if let NodePipelineState::Graphic { job_functor, .. } = &mut self.common.node.pipeline_state {
-            job_functor.replace(Box::new(node_job));
+            job_functor.replace(GraphicNodeJob::Pass(Box::new(node_job)));
+        } else {
+            unreachable!("Use other node execution context in graphic node: {}", self.common.node.name());
+        }
+    }
+
+    /// Record the draws of this node into a render bundle instead of a render pass, with the pipeline already set.
+    /// Bundles of all nodes are recorded in parallel on the workers of zenith-task when the graph is executed,
+    /// then each one is executed in the render pass of its node. Prefer it for nodes with many draws.
+    #[inline]
+    pub fn execute_bundle<F>(&mut self, node_job: F)
+    where
+        F: FnOnce(&mut BundleNodeExecutionContext, &mut wgpu::RenderBundleEncoder) + Send + 'static
+    {
+        if let NodePipelineState::Graphic { job_functor, .. } = &mut self.common.node.pipeline_state {
+            job_functor.replace(GraphicNodeJob::Bundle(Box::new(node_job)));
         } else {
             unreachable!("Use other node execution context in graphic node: {}", self.common.node.name());
         }
@@ -299,6 +530,46 @@ impl<'node, 'res> GraphicNodeBuilder<'node, 'res> {
     }
 }
 
+pub struct ComputeNodeBuilder<'node, 'res> {
+    common: CommonNodeBuilder<'node, 'res>,
+}
+
+impl<'node, 'res> Drop for ComputeNodeBuilder<'node, 'res> {
+    fn drop(&mut self) {
+        debug_assert!(self.common.node.pipeline_state.valid());
+    }
+}
+
+impl<'node, 'res> ComputeNodeBuilder<'node, 'res> {
+    inject_common_node_builder_methods!(Srv, Uav);
+
+    #[inline]
+    pub fn execute<F>(&mut self, node_job: F)
+    where
+        F: FnOnce(&mut ComputeNodeExecutionContext, &mut wgpu::CommandEncoder) + 'static
+    {
+        if let NodePipelineState::Compute { job_functor, .. } = &mut self.common.node.pipeline_state {
+            job_functor.replace(Box::new(node_job));
+        } else {
+            unreachable!("Use other node execution context in compute node: {}", self.common.node.name());
+        }
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn setup_pipeline(&mut self) -> ComputePipelineBuilder<'_> {
+        let pipeline_desc = if let NodePipelineState::Compute { pipeline_desc, .. } = &mut self.common.node.pipeline_state {
+            pipeline_desc
+        } else {
+            unreachable!();
+        };
+
+        ComputePipelineBuilder {
+            pipeline_desc,
+        }
+    }
+}
+
 pub struct LambdaNodeBuilder<'node, 'res> {
     common: CommonNodeBuilder<'node, 'res>,
 }
@@ -342,9 +613,76 @@ impl<'a> GraphicPipelineBuilder<'a> {
         self
     }
 
+    /// Render into the depth attachment only, without any color attachment (e.g. shadow map).
+    #[inline]
+    pub fn with_depth_only(self, depth_stencil: RenderGraphResourceAccess<Texture, Rt>, depth_stencil_info: DepthStencilInfo) -> Self {
+        debug_assert!(self.pipeline_desc.color_attachments.is_empty(), "Depth-only pipeline can NOT have any color attachment!");
+        self.with_depth_stencil(depth_stencil, depth_stencil_info)
+    }
+
+    /// Draw into a region of the attachments only, e.g. one player of a split-screen.
+    /// Load ops still apply to the whole attachments, so do NOT clear them if another region is already drawn.
+    #[inline]
+    pub fn with_viewport(self, viewport: Viewport) -> Self {
+        self.pipeline_desc.viewport = viewport;
+        self
+    }
+
+    /// Cull back faces of single-sided geometry, both sides are drawn by default (e.g. double-sided materials).
+    #[inline]
+    pub fn with_cull_mode(self, cull_mode: Option<wgpu::Face>) -> Self {
+        self.pipeline_desc.cull_mode = cull_mode;
+        self
+    }
+
+    /// How vertices are assembled into primitives, triangle list by default.
+    #[inline]
+    pub fn with_topology(self, topology: wgpu::PrimitiveTopology) -> Self {
+        self.pipeline_desc.topology = topology;
+        self
+    }
+
+    /// Rasterize triangles as filled (default), edges or vertices.
+    /// Line and point modes require `wgpu::Features::POLYGON_MODE_LINE` and `POLYGON_MODE_POINT`, pipeline creation fails without them.
+    #[inline]
+    pub fn with_polygon_mode(self, polygon_mode: wgpu::PolygonMode) -> Self {
+        self.pipeline_desc.polygon_mode = polygon_mode;
+        self
+    }
+
     // #[inline]
     // pub fn with_binding<R: GraphResource, V: GraphResourceView>(self, binding: u32, color: &RenderGraphResourceAccess<R, V>) -> Self {
     //     self.pipeline_desc.bindings.push((binding, color.id));
     //     self
     // }
 }
+
+pub struct ComputePipelineBuilder<'a> {
+    pipeline_desc: &'a mut ComputePipelineDescriptor,
+}
+
+impl<'a> ComputePipelineBuilder<'a> {
+    #[inline]
+    pub fn with_shader(self, shader: Arc<ComputeShader>) -> Self {
+        self.pipeline_desc.shader = Some(shader);
+        self
+    }
+}
+
+/// Approximate size in bytes of a texture with all its mips, layers and samples.
+fn texture_size_bytes(desc: &TextureDesc) -> u64 {
+    let (block_width, block_height) = desc.format.block_dimensions();
+    // combined depth stencil formats have no copyable size, assume 4 bytes
+    let block_size = desc.format.block_copy_size(None).unwrap_or(4) as u64;
+
+    (0..desc.mip_level_count)
+        .map(|level| desc.size.mip_level_size(level, desc.dimension))
+        .map(|size| {
+            size.width.div_ceil(block_width) as u64
+                * size.height.div_ceil(block_height) as u64
+                * size.depth_or_array_layers as u64
+                * block_size
+        })
+        .sum::<u64>()
+        * desc.sample_count as u64
+}
@@ -1,13 +1,16 @@
 use crate::interface::RenderResource;
 use std::cell::{Cell};
-use bytemuck::NoUninit;
+use std::fmt::{Display, Formatter, Write};
+use bytemuck::{NoUninit, Pod};
 use derive_more::From;
-use log::{warn};
+use zenith_core::log::{debug, warn, target::RENDER};
 use zenith_core::collections::SmallVec;
-use zenith_render::PipelineCache;
-use crate::node::{NodePipelineState, RenderGraphNode};
-use crate::interface::{Buffer, BufferState, GraphResourceAccess, Texture, TextureState};
-use crate::GraphicPipelineDescriptor;
+use zenith_core::collections::orderedmap::OrderedMap;
+use zenith_render::{GraphicShader, PipelineCache};
+use zenith_task::TaskResult;
+use crate::node::{GraphicNodeJob, NodePipelineState, RenderGraphNode};
+use crate::interface::{Buffer, BufferState, GraphResourceAccess, Sampler, Texture, TextureState};
+use crate::{ComputePipelineDescriptor, GraphicPipelineDescriptor, Viewport};
 use crate::resource::{GraphResourceId, GraphResourceView, GraphResourceState, RenderGraphResourceAccess};
 
 pub(crate) enum ResourceStorage {
@@ -21,15 +24,23 @@ pub(crate) enum ResourceStorage {
         resource: Texture,
         state_tracker: ResourceStateTracker<TextureState>
     },
+    /// Imported resources are returned to `import_state` at the end of the graph,
+    /// so the next graph importing them with the same state sees the real one.
     ImportedBuffer {
         name: String,
         resource: RenderResource<Buffer>,
-        state_tracker: ResourceStateTracker<BufferState>
+        state_tracker: ResourceStateTracker<BufferState>,
+        import_state: BufferState,
     },
     ImportedTexture {
         name: String,
         resource: RenderResource<Texture>,
-        state_tracker: ResourceStateTracker<TextureState>
+        state_tracker: ResourceStateTracker<TextureState>,
+        import_state: TextureState,
+    },
+    Sampler {
+        name: String,
+        resource: RenderResource<Sampler>,
     },
 }
 
@@ -40,6 +51,18 @@ impl ResourceStorage {
             ResourceStorage::ManagedTexture { name, .. } => &name,
             ResourceStorage::ImportedBuffer { name, .. } => &name,
             ResourceStorage::ImportedTexture { name, .. } => &name,
+            ResourceStorage::Sampler { name, .. } => &name,
+        }
+    }
+
+    /// Kind of the resource shown in debug dumps.
+    fn kind(&self) -> &'static str {
+        match self {
+            ResourceStorage::ManagedBuffer { .. } => "buffer",
+            ResourceStorage::ManagedTexture { .. } => "texture",
+            ResourceStorage::ImportedBuffer { .. } => "imported buffer",
+            ResourceStorage::ImportedTexture { .. } => "imported texture",
+            ResourceStorage::Sampler { .. } => "sampler",
         }
     }
 
@@ -47,8 +70,8 @@ impl ResourceStorage {
         match self {
             ResourceStorage::ManagedBuffer { resource, .. } => { &resource }
             ResourceStorage::ImportedBuffer { resource, .. } => { &resource }
-            ResourceStorage::ManagedTexture { .. } | ResourceStorage::ImportedTexture { .. } => {
-                unreachable!("Expect buffer, but resource is a texture!");
+            _ => {
+                unreachable!("Expect buffer, but resource[{}] is NOT a buffer!", self.name());
             }
         }
     }
@@ -57,8 +80,17 @@ impl ResourceStorage {
         match self {
             ResourceStorage::ManagedTexture { resource, .. } => { &resource }
             ResourceStorage::ImportedTexture { resource, .. } => { &resource }
-            ResourceStorage::ManagedBuffer { .. } | ResourceStorage::ImportedBuffer { .. } => {
-                unreachable!("Expect texture, but resource is a buffer!");
+            _ => {
+                unreachable!("Expect texture, but resource[{}] is NOT a texture!", self.name());
+            }
+        }
+    }
+
+    pub(crate) fn as_sampler(&self) -> &Sampler {
+        match self {
+            ResourceStorage::Sampler { resource, .. } => { &resource }
+            _ => {
+                unreachable!("Expect sampler, but resource[{}] is NOT a sampler!", self.name());
             }
         }
     }
@@ -88,9 +120,31 @@ impl<T: GraphResourceState> ResourceStateTracker<T> {
     }
 }
 
+/// Memory of managed textures of a graph, see [`CompiledRenderGraph::memory_stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GraphMemoryStats {
+    /// Bytes if every managed texture had its own allocation.
+    pub logical_bytes: u64,
+    /// Bytes actually allocated after aliasing transient textures.
+    pub allocated_bytes: u64,
+}
+
+/// Options of [`RenderGraph::compile_with_options`], the default keeps every node in insertion order.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompileOptions {
+    /// Skip nodes whose outputs never reach an imported resource, e.g. a debug pass whose target is discarded.
+    /// Nodes without any output are always kept, since their side effects are unknown.
+    pub cull_unused: bool,
+    /// Reorder independent nodes so that consecutive nodes share resource states and need fewer transitions.
+    pub reorder: bool,
+}
+
 pub struct RenderGraph {
     pub(crate) nodes: Vec<RenderGraphNode>,
     pub(crate) resources: Vec<ResourceStorage>,
+    /// Id of the resource owning the allocation of each resource, itself unless it is an aliased transient texture.
+    pub(crate) alias_owners: Vec<GraphResourceId>,
+    pub(crate) memory_stats: GraphMemoryStats,
 }
 
 impl RenderGraph {
@@ -98,31 +152,186 @@ impl RenderGraph {
 
     }
 
+    /// Graphviz digraph of all nodes and resources, edges are labeled by the access state.
+    /// A resource without incoming edges is never written in this graph, e.g. the cause of a black screen.
+    /// Render it with `dot -Tsvg graph.dot -o graph.svg`.
+    pub fn to_dot(&self) -> String {
+        fn escape(label: &str) -> String {
+            label.replace('\\', "\\\\").replace('"', "\\\"")
+        }
+
+        fn access_label(access: &GraphResourceAccess) -> String {
+            match access {
+                GraphResourceAccess::Buffer(state) => state.iter_names().map(|(name, _)| name).collect::<Vec<_>>().join(" | "),
+                GraphResourceAccess::Texture(state) => state.iter_names().map(|(name, _)| name).collect::<Vec<_>>().join(" | "),
+                GraphResourceAccess::Sampler(_) => "sample".to_owned(),
+            }
+        }
+
+        let mut dot = String::from("digraph RenderGraph {\n    rankdir=LR;\n");
+
+        for (id, resource) in self.resources.iter().enumerate() {
+            writeln!(dot, "    r{} [label=\"{}\\n({})\", shape=ellipse];", id, escape(resource.name()), resource.kind()).unwrap();
+        }
+
+        for (index, node) in self.nodes.iter().enumerate() {
+            writeln!(dot, "    n{} [label=\"{}\", shape=box, style=filled, fillcolor=lightgrey];", index, escape(&node.name)).unwrap();
+
+            for input in &node.inputs {
+                writeln!(dot, "    r{} -> n{} [label=\"read {}\"];", input.id, index, escape(&access_label(&input.access))).unwrap();
+            }
+            for output in &node.outputs {
+                writeln!(dot, "    n{} -> r{} [label=\"write {}\", color=red];", index, output.id, escape(&access_label(&output.access))).unwrap();
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Create pipelines of all graphic nodes.
+    /// Fail if a shader does NOT match the attachments of its node, or if the pipeline can NOT be created.
     pub fn compile(
         self,
         device: &wgpu::Device,
         pipeline_cache: &mut PipelineCache,
-    ) -> CompiledRenderGraph {
+    ) -> anyhow::Result<CompiledRenderGraph> {
+        self.compile_with_options(device, pipeline_cache, CompileOptions::default())
+    }
+
+    /// Same as [`RenderGraph::compile`], culling and reordering nodes as the options ask before creating pipelines.
+    pub fn compile_with_options(
+        mut self,
+        device: &wgpu::Device,
+        pipeline_cache: &mut PipelineCache,
+        options: CompileOptions,
+    ) -> anyhow::Result<CompiledRenderGraph> {
+        if options.cull_unused {
+            self.cull_unused_nodes();
+        }
+        if options.reorder {
+            self.reorder_nodes();
+        }
+
         let mut graphic_pipelines = vec![];
-        let _compute_pipelines = vec![];
+        let mut compute_pipelines = vec![];
 
         for node in &self.nodes {
             match &node.pipeline_state {
                 NodePipelineState::Graphic { pipeline_desc, .. } => {
-                    let pipeline = self.create_graphic_pipeline(node.name(), device, pipeline_cache, pipeline_desc);
+                    let pipeline = self.create_graphic_pipeline(node.name(), device, pipeline_cache, pipeline_desc)?;
                     graphic_pipelines.push(pipeline);
                 }
-                NodePipelineState::Compute { .. } => { unimplemented!() }
+                NodePipelineState::Compute { pipeline_desc, .. } => {
+                    let shader = pipeline_desc
+                        .shader
+                        .as_ref()
+                        .ok_or_else(|| anyhow::anyhow!("Missing compute shader for node {}", node.name()))?;
+                    compute_pipelines.push(pipeline_cache.get_or_create_compute_pipeline(device, shader)?);
+                }
                 NodePipelineState::Lambda { .. } => {}
             }
         }
 
-        CompiledRenderGraph {
+        Ok(CompiledRenderGraph {
             nodes: self.nodes,
             resources: self.resources,
             graphic_pipelines,
-            _compute_pipelines,
+            compute_pipelines,
+            gpu_profiling: false,
+            parallel_bundles: true,
+            memory_stats: self.memory_stats,
+        })
+    }
+
+    /// Remove nodes whose outputs are neither imported nor used by a node kept after them.
+    fn cull_unused_nodes(&mut self) {
+        // content of imported resources outlives the graph, so their writers are the roots
+        let mut used = vec![false; self.resources.len()];
+        for (id, _) in CompiledRenderGraph::import_states(&self.resources) {
+            used[id as usize] = true;
+        }
+
+        let mut keep = vec![false; self.nodes.len()];
+        for (index, node) in self.nodes.iter().enumerate().rev() {
+            keep[index] = node.outputs.is_empty() || node.outputs.iter().any(|access| used[access.id as usize]);
+            if keep[index] {
+                // earlier writes of an output are kept as well, e.g. the clear before a pass loading the target
+                for access in node.inputs.iter().chain(&node.outputs) {
+                    used[access.id as usize] = true;
+                }
+            }
+        }
+
+        let mut keep = keep.into_iter();
+        self.nodes.retain(|node| {
+            let keep = keep.next().unwrap();
+            if !keep {
+                debug!(target: RENDER, "Cull node {}, none of its outputs is used", node.name());
+            }
+            keep
+        });
+    }
+
+    /// Topologically sort the nodes, picking the ready node needing the fewest transitions first.
+    /// A node depends on an earlier one if either writes a resource the other accesses, ties keep insertion order.
+    fn reorder_nodes(&mut self) {
+        let num_nodes = self.nodes.len();
+        // textures sharing an allocation were aliased for the current order, so they must keep it
+        let owner = |id: GraphResourceId| self.alias_owners[id as usize];
+        let writes = |node: &RenderGraphNode, id: GraphResourceId| node.outputs.iter().any(|access| owner(access.id) == owner(id));
+
+        let mut num_dependencies = vec![0usize; num_nodes];
+        let mut dependents = vec![vec![]; num_nodes];
+        for later in 0..num_nodes {
+            for earlier in 0..later {
+                let (earlier_node, later_node) = (&self.nodes[earlier], &self.nodes[later]);
+                let depends = later_node.inputs.iter().chain(&later_node.outputs).any(|access| writes(earlier_node, access.id))
+                    || earlier_node.inputs.iter().any(|access| writes(later_node, access.id));
+                if depends {
+                    num_dependencies[later] += 1;
+                    dependents[earlier].push(later);
+                }
+            }
+        }
+
+        let mut states: Vec<Option<GraphResourceAccess>> = vec![None; self.resources.len()];
+        for (id, access) in CompiledRenderGraph::import_states(&self.resources) {
+            states[id as usize] = Some(access);
+        }
+
+        let mut scheduled = vec![false; num_nodes];
+        let mut order = Vec::with_capacity(num_nodes);
+        while order.len() < num_nodes {
+            let next = (0..num_nodes)
+                .filter(|&index| !scheduled[index] && num_dependencies[index] == 0)
+                .min_by_key(|&index| {
+                    let node = &self.nodes[index];
+                    let transitions = node.inputs
+                        .iter()
+                        .chain(&node.outputs)
+                        .filter(|access| states[access.id as usize] != Some(access.access))
+                        .count();
+                    (transitions, index)
+                })
+                .expect("Dependencies of render graph nodes are always acyclic");
+
+            let node = &self.nodes[next];
+            for access in node.inputs.iter().chain(&node.outputs) {
+                states[access.id as usize] = Some(access.access);
+            }
+            for &dependent in &dependents[next] {
+                num_dependencies[dependent] -= 1;
+            }
+            scheduled[next] = true;
+            order.push(next);
         }
+
+        let mut nodes: Vec<Option<RenderGraphNode>> = self.nodes.drain(..).map(Some).collect();
+        self.nodes = order
+            .into_iter()
+            .map(|index| nodes[index].take().unwrap())
+            .collect();
     }
 
     fn create_graphic_pipeline(
@@ -131,7 +340,14 @@ impl RenderGraph {
         device: &wgpu::Device,
         pipeline_cache: &mut PipelineCache,
         desc: &GraphicPipelineDescriptor,
-    ) -> wgpu::RenderPipeline {
+    ) -> anyhow::Result<wgpu::RenderPipeline> {
+        let shader = desc
+            .shader
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Missing raster shader for node {}", node_name))?;
+
+        self.validate_attachments(node_name, shader, desc)?;
+
         let color_attachments = desc.color_attachments
             .iter()
             .map(|(resource, color_info)| {
@@ -186,18 +402,122 @@ impl RenderGraph {
                 }
             });
 
-        let shader = desc
-            .shader
-            .as_ref()
-            .expect(&format!("Missing raster shader for node {}", node_name));
-
         pipeline_cache
             .get_or_create_graphic_pipeline(
                 device,
                 shader,
                 &color_attachments,
-                depth_stencil_attachment)
-            .expect(&format!("Failed to compile graphic pipeline: {}", shader.name()))
+                depth_stencil_attachment,
+                wgpu::PrimitiveState {
+                    topology: desc.topology,
+                    cull_mode: desc.cull_mode,
+                    polygon_mode: desc.polygon_mode,
+                    ..Default::default()
+                })
+            .map_err(|err| err.context(format!("Failed to compile graphic pipeline: {}", shader.name())))
+    }
+
+    /// Check that the attachments of a node match what its shader declares,
+    /// so that mismatches are reported with the shader name instead of failing inside wgpu.
+    fn validate_attachments(
+        &self,
+        node_name: &str,
+        shader: &GraphicShader,
+        desc: &GraphicPipelineDescriptor,
+    ) -> anyhow::Result<()> {
+        let texture_of = |id: GraphResourceId| match utility::resource_storage_ref(&self.resources, id) {
+            ResourceStorage::ManagedTexture { resource, .. } => resource,
+            ResourceStorage::ImportedTexture { resource, .. } => resource,
+            _ => unreachable!("Attachment had bound to a non-texture resource!")
+        };
+
+        let num_color_attachments = desc.color_attachments.len() as u32;
+        if num_color_attachments != shader.num_color_targets() {
+            anyhow::bail!(
+                "Shader[{}] of node {} outputs {} color target(s), but {} color attachment(s) are bound",
+                shader.name(), node_name, shader.num_color_targets(), num_color_attachments
+            );
+        }
+
+        let has_depth_stencil = desc.depth_stencil_attachment.is_some();
+        if has_depth_stencil != shader.has_depth_stencil() {
+            anyhow::bail!(
+                "Shader[{}] of node {} {} a depth stencil attachment, but {} is bound",
+                shader.name(),
+                node_name,
+                if shader.has_depth_stencil() { "expects" } else { "does NOT expect" },
+                if has_depth_stencil { "one" } else { "none" }
+            );
+        }
+
+        for (resource, _) in &desc.color_attachments {
+            let texture = texture_of(resource.id);
+            if texture.format().is_depth_stencil_format() {
+                anyhow::bail!(
+                    "Color attachment of node {} (shader[{}]) has depth stencil format {:?}",
+                    node_name, shader.name(), texture.format()
+                );
+            }
+        }
+
+        if let Some((resource, _)) = &desc.depth_stencil_attachment {
+            let texture = texture_of(resource.id);
+            if !texture.format().is_depth_stencil_format() {
+                anyhow::bail!(
+                    "Depth stencil attachment of node {} (shader[{}]) has non depth format {:?}",
+                    node_name, shader.name(), texture.format()
+                );
+            }
+        }
+
+        // pipelines are created without multisampling
+        let attachments = desc.color_attachments
+            .iter()
+            .map(|(resource, _)| resource.id)
+            .chain(desc.depth_stencil_attachment.as_ref().map(|(resource, _)| resource.id));
+        for id in attachments {
+            let sample_count = texture_of(id).sample_count();
+            if sample_count != 1 {
+                anyhow::bail!(
+                    "Attachment {} of node {} (shader[{}]) has sample count {}, but only single sampled attachments are supported",
+                    utility::resource_storage_ref(&self.resources, id).name(), node_name, shader.name(), sample_count
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Error reported by wgpu while executing a render graph.
+#[derive(Debug)]
+pub enum GraphExecutionError {
+    /// Error raised while recording commands of a node.
+    Node {
+        name: String,
+        error: wgpu::Error,
+    },
+    /// Error raised while finishing and submitting the recorded commands.
+    Submit {
+        error: wgpu::Error,
+    },
+}
+
+impl Display for GraphExecutionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GraphExecutionError::Node { name, error } => write!(f, "Render graph node[{}] failed: {}", name, error),
+            GraphExecutionError::Submit { error } => write!(f, "Render graph submission failed: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for GraphExecutionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GraphExecutionError::Node { error, .. } => Some(error),
+            GraphExecutionError::Submit { error } => Some(error),
+        }
     }
 }
 
@@ -205,19 +525,70 @@ pub struct CompiledRenderGraph {
     nodes: Vec<RenderGraphNode>,
     resources: Vec<ResourceStorage>,
     graphic_pipelines: Vec<wgpu::RenderPipeline>,
-    _compute_pipelines: Vec<wgpu::ComputePipeline>,
+    compute_pipelines: Vec<wgpu::ComputePipeline>,
+    gpu_profiling: bool,
+    parallel_bundles: bool,
+    memory_stats: GraphMemoryStats,
 }
 
 impl CompiledRenderGraph {
-    pub fn execute(self, device: &wgpu::Device, queue: &wgpu::Queue) -> PresentableRenderGraph {
+    /// Return logical and allocated bytes of managed textures, the difference is saved by aliasing.
+    pub fn memory_stats(&self) -> GraphMemoryStats {
+        self.memory_stats
+    }
+
+    /// Measure the GPU time of the render pass of each graphic node, see [`PresentableRenderGraph::gpu_timings`].
+    /// Ignored if the device does NOT support `TIMESTAMP_QUERY`.
+    pub fn with_gpu_profiling(mut self, enabled: bool) -> Self {
+        self.gpu_profiling = enabled;
+        self
+    }
+
+    /// Record the render bundles of nodes on the workers of zenith-task (default), or on the calling thread if disabled.
+    /// Serial recording is mostly useful to compare frame times, see the `bundle_benchmark` example of the sandbox.
+    pub fn with_parallel_bundles(mut self, enabled: bool) -> Self {
+        self.parallel_bundles = enabled;
+        self
+    }
+
+    /// Record and submit all nodes.
+    /// Any wgpu validation error is captured per node, and the graph is NOT submitted if any node fails.
+    pub fn execute(self, device: &wgpu::Device, queue: &wgpu::Queue) -> Result<PresentableRenderGraph, GraphExecutionError> {
+        self.execute_impl(device, queue, None)
+    }
+
+    /// Same as [`CompiledRenderGraph::execute`], but `upload_buffer_staged` of nodes goes through the staging belt.
+    /// The belt is finished before submission, call `recall` on it after this returns to reuse its chunks next frame.
+    pub fn execute_with_staging_belt(
+        self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        staging_belt: &mut wgpu::util::StagingBelt,
+    ) -> Result<PresentableRenderGraph, GraphExecutionError> {
+        self.execute_impl(device, queue, Some(staging_belt))
+    }
+
+    fn execute_impl(
+        mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        mut staging_belt: Option<&mut wgpu::util::StagingBelt>,
+    ) -> Result<PresentableRenderGraph, GraphExecutionError> {
         let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("render graph main command encoder"),
         });
 
         let mut graphic_pipe_index = 0u32;
-        // let mut compute_pipe_index = 0u32;
+        let mut compute_pipe_index = 0usize;
+
+        let profiler = self.gpu_profiling
+            .then(|| GpuProfiler::new(device, queue, &self.nodes))
+            .flatten();
 
-        for node in self.nodes.into_iter() {
+        // bundles are recorded on workers while the nodes before them are recorded here
+        let mut pending_bundles = self.start_bundle_recording(device, queue);
+
+        for (node_index, node) in self.nodes.into_iter().enumerate() {
             Self::transition_resources(
                 &mut encoder,
                 &self.resources,
@@ -228,50 +599,142 @@ impl CompiledRenderGraph {
                     .chain(node.outputs.iter().map(|access| (access.id, access.access)))
             );
 
+            let name = node.name;
+            let mut bundle_error = None;
+            device.push_error_scope(wgpu::ErrorFilter::Validation);
+
             match node.pipeline_state {
                 NodePipelineState::Graphic { pipeline_desc, mut job_functor } => {
-                    let name = node.name;
                     let pipeline = self.graphic_pipelines.get(graphic_pipe_index as usize).unwrap();
                     graphic_pipe_index += 1;
 
+                    let mut ctx = GraphicNodeExecutionContext {
+                        name: name.as_str(),
+                        pipeline_desc: &pipeline_desc,
+                        device,
+                        queue,
+                        resources: &self.resources,
+                        staging_belt: staging_belt.as_deref_mut(),
+                        pipeline: pipeline.clone(),
+                        attachment_views: AttachmentViews::new(&pipeline_desc, &self.resources),
+                        timestamp_writes: profiler.as_ref().map(|profiler| profiler.timestamp_writes(graphic_pipe_index - 1)),
+                    };
+
+                    match (job_functor.take(), pending_bundles[node_index].take()) {
+                        (Some(GraphicNodeJob::Pass(record)), _) => record(&mut ctx, &mut encoder),
+                        (_, Some(bundle)) => match bundle.wait() {
+                            Ok(bundle) => {
+                                let mut render_pass = ctx.begin_render_pass(&mut encoder);
+                                render_pass.execute_bundles([&bundle]);
+                            }
+                            Err(error) => bundle_error = Some(error),
+                        },
+                        _ => warn!(target: RENDER, "Missing job of graphic node {}!", name),
+                    }
+                }
+                NodePipelineState::Compute{ pipeline_desc, mut job_functor } => {
+                    let pipeline = self.compute_pipelines[compute_pipe_index].clone();
+                    compute_pipe_index += 1;
+
                     if let Some(record) = job_functor.take() {
-                        let mut ctx = GraphicNodeExecutionContext {
+                        let mut ctx = ComputeNodeExecutionContext {
                             name: name.as_str(),
                             pipeline_desc: &pipeline_desc,
                             device,
                             queue,
+                            staging_belt: staging_belt.as_deref_mut(),
                             resources: &self.resources,
-                            pipeline: pipeline.clone(),
+                            pipeline,
                         };
                         record(&mut ctx, &mut encoder);
                     } else {
-                        warn!("Missing job of graphic node {}!", name);
+                        warn!(target: RENDER, "Missing job of compute node {}!", name);
                     }
                 }
-                NodePipelineState::Compute{ .. } => {
-                    // compute_pipe_index += 1;
-                    unimplemented!()
-                }
                 NodePipelineState::Lambda{ mut job_functor } => {
-                    let name = node.name;
-
                     if let Some(record) = job_functor.take() {
                         let mut ctx = LambdaNodeExecutionContext {
+                            device,
                             queue,
+                            staging_belt: staging_belt.as_deref_mut(),
                             resources: &self.resources,
                         };
                         record(&mut ctx, &mut encoder);
                     } else {
-                        warn!("Missing job of lambda node {}!", name);
+                        warn!(target: RENDER, "Missing job of lambda node {}!", name);
                     }
                 }
             }
+
+            if let Some(error) = pollster::block_on(device.pop_error_scope()).or(bundle_error) {
+                pending_bundles.into_iter().flatten().for_each(PendingBundle::discard);
+                return Err(GraphExecutionError::Node { name, error });
+            }
         }
 
-        queue.submit(Some(encoder.finish()));
+        Self::transition_resources(&mut encoder, &self.resources, Self::import_states(&self.resources));
 
-        PresentableRenderGraph {
+        if let Some(profiler) = &profiler {
+            profiler.resolve(&mut encoder);
         }
+        if let Some(staging_belt) = staging_belt {
+            staging_belt.finish();
+        }
+
+        // errors of recorded commands are reported when the encoder is finished
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        queue.submit(Some(encoder.finish()));
+        if let Some(error) = pollster::block_on(device.pop_error_scope()) {
+            return Err(GraphExecutionError::Submit { error });
+        }
+
+        Ok(PresentableRenderGraph {
+            device: device.clone(),
+            profiler,
+        })
+    }
+
+    /// Take the bundle jobs of all graphic nodes and start recording them, indexed by node.
+    /// Bundles are recorded on the calling thread if parallel recording is disabled or zenith-task is NOT initialized, e.g. in tests.
+    fn start_bundle_recording(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) -> Vec<Option<PendingBundle>> {
+        let mut graphic_pipe_index = 0;
+
+        self.nodes
+            .iter_mut()
+            .map(|node| {
+                let NodePipelineState::Graphic { pipeline_desc, job_functor } = &mut node.pipeline_state else {
+                    return None;
+                };
+                let pipeline = &self.graphic_pipelines[graphic_pipe_index];
+                graphic_pipe_index += 1;
+
+                let Some(GraphicNodeJob::Bundle(record)) = job_functor.take_if(|job| matches!(job, GraphicNodeJob::Bundle(_))) else {
+                    return None;
+                };
+
+                let job = BundleJob {
+                    name: node.name.clone(),
+                    formats: RenderBundleFormats::new(pipeline_desc, &self.resources),
+                    ctx: BundleNodeExecutionContext {
+                        device: device.clone(),
+                        queue: queue.clone(),
+                        pipeline: pipeline.clone(),
+                        resources: node.inputs
+                            .iter()
+                            .chain(&node.outputs)
+                            .map(|access| (access.id, BundleResource::new(&self.resources[access.id as usize])))
+                            .collect(),
+                    },
+                    record,
+                };
+
+                Some(if self.parallel_bundles && zenith_task::is_initialized() {
+                    PendingBundle::Recording(zenith_task::submit(move || job.record()))
+                } else {
+                    PendingBundle::Recorded(job.record())
+                })
+            })
+            .collect()
     }
 
     fn transition_resources(
@@ -282,39 +745,50 @@ impl CompiledRenderGraph {
         let mut buffer_transitions: SmallVec<[wgpu::BufferTransition<&Buffer>; 8]> = SmallVec::new();
         let mut texture_transitions: SmallVec<[wgpu::TextureTransition<&Texture>; 8]> = SmallVec::new();
 
-        let mut add_buffer_transition = |next_state, buffer, state_tracker: &ResourceStateTracker<BufferState>| {
-            if state_tracker.should_transition_to(next_state, true) {
-                buffer_transitions.push(wgpu::BufferTransition {
-                    buffer,
-                    state: next_state,
-                });
-                state_tracker.transition_to(next_state);
-            }
-        };
+        for (id, access) in Self::plan_transitions(resources, resources_to_transition) {
+            let storage = utility::resource_storage_ref(resources, id);
 
-        let mut add_texture_transition = |next_state, texture, state_tracker: &ResourceStateTracker<TextureState>| {
-            if state_tracker.should_transition_to(next_state, true) {
-                texture_transitions.push(wgpu::TextureTransition {
-                    texture,
+            match access {
+                GraphResourceAccess::Buffer(state) => buffer_transitions.push(wgpu::BufferTransition {
+                    buffer: storage.as_buffer(),
+                    state,
+                }),
+                GraphResourceAccess::Texture(state) => texture_transitions.push(wgpu::TextureTransition {
+                    texture: storage.as_texture(),
                     selector: None,
-                    state: next_state,
-                });
-                state_tracker.transition_to(next_state);
+                    state,
+                }),
+                GraphResourceAccess::Sampler(_) => unreachable!(),
             }
-        };
+        }
+
+        encoder.transition_resources(
+            buffer_transitions.into_iter(),
+            texture_transitions.into_iter()
+        );
+    }
+
+    /// Advance state trackers to the accesses, return the accesses which need a barrier.
+    fn plan_transitions(
+        resources: &Vec<ResourceStorage>,
+        resources_to_transition: impl Iterator<Item = (GraphResourceId, GraphResourceAccess)>,
+    ) -> SmallVec<[(GraphResourceId, GraphResourceAccess); 8]> {
+        fn transition<T: GraphResourceState>(state_tracker: &ResourceStateTracker<T>, next_state: T) -> bool {
+            let should_transition = state_tracker.should_transition_to(next_state, true);
+            state_tracker.transition_to(next_state);
+            should_transition
+        }
+
+        let mut transitions = SmallVec::new();
 
         for (id, access) in resources_to_transition {
             let storage = utility::resource_storage_ref(resources, id);
 
-            match access {
+            let should_transition = match access {
                 GraphResourceAccess::Buffer(next_state) => {
                     match storage {
-                        ResourceStorage::ManagedBuffer { resource, state_tracker, .. } => {
-                            add_buffer_transition(next_state, &*resource, state_tracker);
-                        }
-                        ResourceStorage::ImportedBuffer { resource, state_tracker, .. } => {
-                            add_buffer_transition(next_state, &*resource, state_tracker);
-                        }
+                        ResourceStorage::ManagedBuffer { state_tracker, .. } |
+                        ResourceStorage::ImportedBuffer { state_tracker, .. } => transition(state_tracker, next_state),
                         _ =>  {
                             unreachable!("Resource[{}] is a texture, but a non-texture state[{:?}] is provided when read/write!", storage.name(), next_state)
                         }
@@ -322,24 +796,126 @@ impl CompiledRenderGraph {
                 }
                 GraphResourceAccess::Texture(next_state) => {
                     match storage {
-                        ResourceStorage::ManagedTexture { resource, state_tracker, .. } => {
-                            add_texture_transition(next_state, &*resource, state_tracker);
-                        }
-                        ResourceStorage::ImportedTexture { resource, state_tracker, .. } => {
-                            add_texture_transition(next_state, &*resource, state_tracker);
-                        }
+                        ResourceStorage::ManagedTexture { state_tracker, .. } |
+                        ResourceStorage::ImportedTexture { state_tracker, .. } => transition(state_tracker, next_state),
                         _ => {
                             unreachable!("Resource[{}] is a buffer, but a non-buffer state[{:?}] is provided when read/write!", storage.name(), next_state)
                         }
                     }
                 }
+                GraphResourceAccess::Sampler(_) => {
+                    debug_assert!(matches!(storage, ResourceStorage::Sampler { .. }), "Resource[{}] is NOT a sampler, but accessed as a sampler!", storage.name());
+                    false
+                }
+            };
+
+            if should_transition {
+                transitions.push((id, access));
             }
         }
 
-        encoder.transition_resources(
-            buffer_transitions.into_iter(),
-            texture_transitions.into_iter()
-        );
+        transitions
+    }
+
+    /// Return the states all imported resources were declared with on import.
+    fn import_states(resources: &Vec<ResourceStorage>) -> impl Iterator<Item = (GraphResourceId, GraphResourceAccess)> + '_ {
+        resources
+            .iter()
+            .enumerate()
+            .filter_map(|(id, storage)| {
+                let import_state = match storage {
+                    ResourceStorage::ImportedBuffer { import_state, .. } => GraphResourceAccess::Buffer(*import_state),
+                    ResourceStorage::ImportedTexture { import_state, .. } => GraphResourceAccess::Texture(*import_state),
+                    _ => return None,
+                };
+                Some((id as GraphResourceId, import_state))
+            })
+    }
+}
+
+/// Attachment formats of a graphic node, which render bundles executed in its render pass must be recorded with.
+struct RenderBundleFormats {
+    color_formats: SmallVec<[Option<wgpu::TextureFormat>; 8]>,
+    depth_stencil: Option<wgpu::RenderBundleDepthStencil>,
+}
+
+impl RenderBundleFormats {
+    fn new(pipeline_desc: &GraphicPipelineDescriptor, resources: &[ResourceStorage]) -> Self {
+        Self {
+            color_formats: pipeline_desc.color_attachments
+                .iter()
+                .map(|(resource, _)| Some(resources[resource.id as usize].as_texture().format()))
+                .collect(),
+            depth_stencil: pipeline_desc.depth_stencil_attachment
+                .as_ref()
+                .map(|(resource, depth)| wgpu::RenderBundleDepthStencil {
+                    format: resources[resource.id as usize].as_texture().format(),
+                    depth_read_only: !depth.depth_write,
+                    stencil_read_only: depth.stencil.write_mask == 0,
+                }),
+        }
+    }
+
+    /// Create a render bundle encoder with the pipeline already set.
+    fn create_encoder<'a>(&self, device: &wgpu::Device, label: &str, pipeline: &wgpu::RenderPipeline) -> wgpu::RenderBundleEncoder<'a> {
+        let mut bundle_encoder = device.create_render_bundle_encoder(&wgpu::RenderBundleEncoderDescriptor {
+            label: Some(label),
+            color_formats: &self.color_formats,
+            depth_stencil: self.depth_stencil,
+            sample_count: 1,
+            multiview: None,
+        });
+        bundle_encoder.set_pipeline(pipeline);
+        bundle_encoder
+    }
+}
+
+/// Views of the attachments of a graphic node, created once per node and owned by its execution context,
+/// so render pass descriptors can borrow them for as long as the context lives.
+struct AttachmentViews {
+    /// Indexed by color slot, same order as `GraphicPipelineDescriptor::color_attachments`.
+    colors: SmallVec<[wgpu::TextureView; 8]>,
+    depth_stencil: Option<wgpu::TextureView>,
+    /// Width and height shared by all attachments of the render pass.
+    size: (u32, u32),
+}
+
+impl AttachmentViews {
+    fn new(pipeline_desc: &GraphicPipelineDescriptor, resources: &Vec<ResourceStorage>) -> Self {
+        let create_texture_view = |id| {
+            let storage = utility::resource_storage_ref(resources, id);
+
+            match storage {
+                ResourceStorage::ManagedTexture { resource, .. } => {
+                    resource.create_view(&wgpu::TextureViewDescriptor::default())
+                }
+                ResourceStorage::ImportedTexture { resource, .. } => {
+                    resource.create_view(&wgpu::TextureViewDescriptor::default())
+                }
+                _ => unreachable!("Resource[{}] is NOT a texture, but used as an attachment!", storage.name())
+            }
+        };
+
+        let size = pipeline_desc.color_attachments
+            .first()
+            .map(|(res, _)| res.id)
+            .or(pipeline_desc.depth_stencil_attachment.as_ref().map(|(res, _)| res.id))
+            .map(|id| {
+                let size = utility::resource_storage_ref(resources, id).as_texture().size();
+                (size.width, size.height)
+            })
+            .unwrap_or_default();
+
+        Self {
+            colors: pipeline_desc.color_attachments
+                .iter()
+                .map(|(res, _)| create_texture_view(res.id))
+                .collect(),
+            depth_stencil: pipeline_desc.depth_stencil_attachment
+                .as_ref()
+                .map(|(res, _)| create_texture_view(res.id)),
+            size,
+        }
     }
 }
 
@@ -348,8 +924,11 @@ pub struct GraphicNodeExecutionContext<'node> {
     pipeline_desc: &'node GraphicPipelineDescriptor,
     device: &'node wgpu::Device,
     queue: &'node wgpu::Queue,
+    staging_belt: Option<&'node mut wgpu::util::StagingBelt>,
     resources: &'node Vec<ResourceStorage>,
     pipeline: wgpu::RenderPipeline,
+    attachment_views: AttachmentViews,
+    timestamp_writes: Option<wgpu::RenderPassTimestampWrites<'node>>,
 }
 
 impl<'node> GraphicNodeExecutionContext<'node> {
@@ -363,72 +942,129 @@ impl<'node> GraphicNodeExecutionContext<'node> {
         self.resources.get(resource.id as usize).expect("Graph resource index out of bound!").as_texture().clone()
     }
 
+    #[inline]
+    pub fn get_sampler<V: GraphResourceView>(&mut self, resource: &RenderGraphResourceAccess<Sampler, V>) -> Sampler {
+        self.resources.get(resource.id as usize).expect("Graph resource index out of bound!").as_sampler().clone()
+    }
+
     #[inline]
     pub fn write_buffer<V: GraphResourceView, T: NoUninit>(&mut self, resource: &RenderGraphResourceAccess<Buffer, V>, offset: wgpu::BufferAddress, data: T) {
         let buffer = self.resources.get(resource.id as usize).expect("Graph resource index out of bound!").as_buffer();
-        debug_assert_eq!(buffer.size() as usize - offset as usize, size_of::<T>());
+        debug_assert!(offset as usize + size_of::<T>() <= buffer.size() as usize, "Write out of buffer bound!");
         self.queue.write_buffer(buffer, offset, bytemuck::cast_slice(&[data]));
     }
 
+    /// Write a slice of values at the offset of the buffer, e.g. per-frame vertices.
+    #[inline]
+    pub fn write_buffer_slice<V: GraphResourceView, T: NoUninit>(&mut self, resource: &RenderGraphResourceAccess<Buffer, V>, offset: wgpu::BufferAddress, data: &[T]) {
+        let buffer = self.resources.get(resource.id as usize).expect("Graph resource index out of bound!").as_buffer();
+        debug_assert!(offset as usize + size_of_val(data) <= buffer.size() as usize, "Write out of buffer bound!");
+        self.queue.write_buffer(buffer, offset, bytemuck::cast_slice(data));
+    }
+
+    /// Write a whole uniform value to the start of the buffer.
+    #[inline]
+    pub fn write_uniform<V: GraphResourceView, T: Pod>(&mut self, resource: &RenderGraphResourceAccess<Buffer, V>, value: &T) {
+        let buffer = self.resources.get(resource.id as usize).expect("Graph resource index out of bound!").as_buffer();
+        utility::write_uniform(self.queue, buffer, value);
+    }
+
+    /// Write a slice of values to the start of the buffer, the tail is zero padded to `COPY_BUFFER_ALIGNMENT`.
+    /// Panics if the padded data does NOT fit in the buffer.
+    #[inline]
+    pub fn upload_buffer<V: GraphResourceView, T: Pod>(&mut self, resource: &RenderGraphResourceAccess<Buffer, V>, data: &[T]) {
+        let buffer = self.resources.get(resource.id as usize).expect("Graph resource index out of bound!").as_buffer();
+        utility::upload_buffer(self.queue, buffer, data);
+    }
+
+    /// Same as `upload_buffer`, but copied from the staging belt in the command order of `encoder`, which is cheaper for large and frequent uploads.
+    /// Record it before beginning a pass on the encoder. Falls back to `upload_buffer` if the graph is executed without a staging belt.
+    pub fn upload_buffer_staged<V: GraphResourceView, T: Pod>(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        resource: &RenderGraphResourceAccess<Buffer, V>,
+        data: &[T],
+    ) {
+        let buffer = self.resources.get(resource.id as usize).expect("Graph resource index out of bound!").as_buffer();
+        match self.staging_belt.as_deref_mut() {
+            Some(staging_belt) => utility::upload_buffer_staged(self.device, encoder, staging_belt, buffer, data),
+            None => utility::upload_buffer(self.queue, buffer, data),
+        }
+    }
+
+    /// Set push constants declared by the shader of this node (see `GraphicShader::with_push_constants`).
+    pub fn set_push_constants<T: NoUninit>(&self, render_pass: &mut wgpu::RenderPass, stages: wgpu::ShaderStages, offset: u32, data: &T) {
+        debug_assert!(self.device.features().contains(wgpu::Features::PUSH_CONSTANTS), "PUSH_CONSTANTS is NOT supported by the device!");
+        debug_assert!(
+            self.pipeline_desc.shader.as_ref().unwrap().push_constant_ranges().iter().any(|range| {
+                range.stages.contains(stages) && range.range.start <= offset && offset + size_of::<T>() as u32 <= range.range.end
+            }),
+            "Push constants [{}, {}) of {:?} are NOT declared in shader[{}]", offset, offset + size_of::<T>() as u32, stages, self.pipeline_desc.name()
+        );
+
+        render_pass.set_push_constants(stages, offset, bytemuck::bytes_of(data));
+    }
+
     #[inline]
     pub fn bind_pipeline<'ctx, 'rp>(&'ctx mut self, render_pass: &'ctx mut wgpu::RenderPass<'rp>) -> PipelineBinder<'ctx, 'rp> {
         render_pass.set_pipeline(&self.pipeline);
         PipelineBinder {
             device: &self.device,
+            resources: self.resources,
             render_pass,
             pipeline: &self.pipeline,
             pipeline_desc: &self.pipeline_desc,
             bind_group_entries: vec![],
+            dynamic_offsets: vec![],
         }
     }
 
-    pub fn begin_render_pass<'encoder>(
-        &mut self,
-        encoder: &'encoder mut wgpu::CommandEncoder
-    ) -> wgpu::RenderPass<'encoder> {
-        let create_texture_view = |id| {
-            let storage = utility::resource_storage_ref(self.resources, id);
+    #[inline]
+    pub fn pipeline(&self) -> &wgpu::RenderPipeline {
+        &self.pipeline
+    }
 
-            match storage {
-                ResourceStorage::ManagedTexture { resource, .. } => {
-                    resource.create_view(&wgpu::TextureViewDescriptor::default())
-                }
-                ResourceStorage::ImportedTexture { resource, .. } => {
-                    resource.create_view(&wgpu::TextureViewDescriptor::default())
-                }
-                _ => unreachable!()
-            }
-        };
+    /// Create a bind group of the pipeline of this node, entries must follow the layout declared by its shader.
+    /// Unlike [`Self::bind_pipeline`], the bind group can be kept, e.g. in a render bundle replayed every frame.
+    pub fn create_bind_group(&self, group: u32, entries: &[wgpu::BindGroupEntry]) -> wgpu::BindGroup {
+        self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(&format!("{} BindGroup{}", self.pipeline_desc.name(), group)),
+            layout: &self.pipeline.get_bind_group_layout(group),
+            entries,
+        })
+    }
 
-        // TODO: use iterator-valid container
-        let color_views = self.pipeline_desc.color_attachments
-            .iter()
-            .map(|(res, _)| res.id)
-            .map(create_texture_view)
-            .collect::<SmallVec<[wgpu::TextureView; 8]>>();
-        let depth_view = self.pipeline_desc.depth_stencil_attachment
-            .as_ref()
-            .map(|(res, _)| res.id)
-            .map(create_texture_view);
+    /// Create a render bundle encoder with the pipeline of this node set, recording for the attachments of this node.
+    /// The finished bundle can be cached and replayed with `execute_bundles` as long as the pipeline and the bound resources stay the same.
+    pub fn create_render_bundle_encoder(&self) -> wgpu::RenderBundleEncoder<'static> {
+        RenderBundleFormats::new(self.pipeline_desc, self.resources).create_encoder(self.device, self.name, &self.pipeline)
+    }
 
-        let (color_attachments, depth_stencil_attachment) = (
-            self.pipeline_desc.color_attachments
-                .iter()
-                .zip(color_views.iter())
-                .map(|((_, info), view)| {
-                    Some(wgpu::RenderPassColorAttachment {
-                        view,
-                        resolve_target: None,
-                        ops: wgpu::Operations {
-                            load: info.load_op,
-                            store: info.store_op,
-                        }
-                    })
+    pub fn begin_render_pass<'encoder>(
+        &mut self,
+        encoder: &'encoder mut wgpu::CommandEncoder
+    ) -> wgpu::RenderPass<'encoder> {
+        let color_attachments = self.pipeline_desc.color_attachments
+            .iter()
+            .enumerate()
+            .map(|(slot, (_, info))| {
+                Some(wgpu::RenderPassColorAttachment {
+                    view: &self.attachment_views.colors[slot],
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: info.load_op,
+                        store: info.store_op,
+                    }
                 })
-                .collect::<SmallVec<[Option<wgpu::RenderPassColorAttachment>; 8]>>(),
-            depth_view.as_ref().zip(self.pipeline_desc.depth_stencil_attachment.as_ref()).map(|(view, (_, depth_info))| {
+            })
+            .collect::<SmallVec<[Option<wgpu::RenderPassColorAttachment>; 8]>>();
+
+        let depth_stencil_attachment = self.attachment_views.depth_stencil
+            .as_ref()
+            .zip(self.pipeline_desc.depth_stencil_attachment.as_ref())
+            .map(|(view, (_, depth_info))| {
                 wgpu::RenderPassDepthStencilAttachment {
-                    view: &view,
+                    view,
                     depth_ops: if depth_info.depth_write {
                         Some(wgpu::Operations {
                             load: depth_info.depth_load_op,
@@ -442,27 +1078,43 @@ impl<'node> GraphicNodeExecutionContext<'node> {
                         store: depth_info.stencil_store_op,
                     })
                 }
-            })
-        );
+            });
 
-        encoder.begin_render_pass(
+        let mut render_pass = encoder.begin_render_pass(
             &wgpu::RenderPassDescriptor {
                 label: Some(self.name),
                 color_attachments: &color_attachments,
                 depth_stencil_attachment,
-                timestamp_writes: None,
+                timestamp_writes: self.timestamp_writes.clone(),
                 occlusion_query_set: None,
             }
-        )
+        );
+
+        let viewport = self.pipeline_desc.viewport;
+        if viewport != Viewport::FULL {
+            let (width, height) = self.attachment_views.size;
+            let (x, y, viewport_width, viewport_height) = viewport.to_pixels(width, height);
+            render_pass.set_viewport(x, y, viewport_width, viewport_height, 0., 1.);
+
+            // viewport only maps the clip space, primitives crossing its border are NOT clipped without a scissor
+            let (x, y, scissor_width, scissor_height) = viewport.to_pixel_rect(width, height);
+            render_pass.set_scissor_rect(x, y, scissor_width, scissor_height);
+        }
+
+        render_pass
     }
 }
 
 pub struct PipelineBinder<'ctx, 'rp> {
     device: &'ctx wgpu::Device,
+    resources: &'ctx Vec<ResourceStorage>,
     render_pass: &'ctx mut wgpu::RenderPass<'rp>,
     pipeline_desc: &'ctx GraphicPipelineDescriptor,
     pipeline: &'ctx wgpu::RenderPipeline,
-    bind_group_entries: Vec<Vec<wgpu::BindGroupEntry<'ctx>>>,
+    /// Resources of each bind group keyed by binding, so entries always follow the shader declaration order.
+    bind_group_entries: Vec<OrderedMap<u32, wgpu::BindingResource<'ctx>>>,
+    /// Offsets of dynamic offset bindings of each bind group, keyed by binding.
+    dynamic_offsets: Vec<OrderedMap<u32, wgpu::DynamicOffset>>,
 }
 
 impl<'ctx, 'rp> PipelineBinder<'ctx, 'rp> {
@@ -470,72 +1122,579 @@ impl<'ctx, 'rp> PipelineBinder<'ctx, 'rp> {
         let shader = self.pipeline_desc.shader.as_ref().unwrap();
         debug_assert!(group < shader.num_bind_groups() as u32, "Invalid group index: {}, shader[{}] only have {} bind group(s)", group, shader.name(), shader.num_bind_groups());
         debug_assert!(binding < shader.num_bindings(group).unwrap() as u32, "Invalid binding index: {}, shader[{}] only have {} bind entry(s)", group, shader.name(), shader.num_bindings(group).unwrap());
+        debug_assert!(
+            utility::is_binding_compatible(&resource, shader.binding_type(group, binding)),
+            "Binding resource mismatch at group {} binding {}, shader[{}] expects {:?}", group, binding, shader.name(), shader.binding_type(group, binding)
+        );
 
         let non_allocated_groups = group as i32 - self.bind_group_entries.len() as i32 + 1;
         for _ in 0..non_allocated_groups {
-            self.bind_group_entries.push(vec![]);
+            self.bind_group_entries.push(OrderedMap::new());
         }
 
         let bindings = self.bind_group_entries.get_mut(group as usize).unwrap();
-        bindings.push(wgpu::BindGroupEntry {
-            binding,
-            resource,
-        });
+        if bindings.insert(binding, resource).is_some() {
+            warn!(target: RENDER, "Binding {} of group {} in shader[{}] is bound multiple times, the last one is used!", binding, group, shader.name());
+        }
 
         self
     }
 
-    pub fn bind(self) {
+    /// Bind a buffer to a binding declared with dynamic offset (see `GraphicShader::with_dynamic_offset`).
+    /// The offset must be aligned to `min_uniform_buffer_offset_alignment` (or the storage one).
+    pub fn with_dynamic_binding(mut self, group: u32, binding: u32, buffer: &'ctx wgpu::Buffer, offset: wgpu::DynamicOffset) -> Self {
+        let shader = self.pipeline_desc.shader.as_ref().unwrap();
+        let binding_size = match shader.binding_type(group, binding) {
+            Some(wgpu::BindingType::Buffer { has_dynamic_offset: true, min_binding_size, .. }) => min_binding_size,
+            ty => panic!("Binding {} of group {} in shader[{}] is NOT a dynamic offset buffer: {:?}", binding, group, shader.name(), ty),
+        };
+
+        let non_allocated_groups = group as i32 - self.dynamic_offsets.len() as i32 + 1;
+        for _ in 0..non_allocated_groups {
+            self.dynamic_offsets.push(OrderedMap::new());
+        }
+        self.dynamic_offsets[group as usize].insert(binding, offset);
+
+        self.with_binding(group, binding, wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+            buffer,
+            offset: 0,
+            size: binding_size,
+        }))
+    }
+
+    pub fn bind(mut self) -> BoundPipeline<'ctx, 'rp> {
         let shader = self.pipeline_desc.shader.as_ref().unwrap();
 
         let bind_groups = self.bind_group_entries
             .into_iter()
             .enumerate()
             .map(|(group, group_entries)| {
-                Some(self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                let group_entries = group_entries
+                    .into_iter()
+                    .map(|(binding, resource)| wgpu::BindGroupEntry { binding, resource })
+                    .collect::<SmallVec<[wgpu::BindGroupEntry; 8]>>();
+
+                self.device.create_bind_group(&wgpu::BindGroupDescriptor {
                     label: Some(&format!("{} BindGroup{}", shader.name(), group)),
                     layout: &shader.create_bind_group_layout(self.device, group as u32).unwrap(),
                     entries: &group_entries,
-                }))
+                })
             })
-            .collect::<SmallVec<[Option<wgpu::BindGroup>; 4]>>();
+            .collect::<SmallVec<[wgpu::BindGroup; 4]>>();
 
         self.render_pass.set_pipeline(self.pipeline);
-        for (group, bind_group) in bind_groups.into_iter().enumerate() {
-            self.render_pass.set_bind_group(group as u32, &bind_group, &[]);
+
+        let mut bound = BoundPipeline {
+            render_pass: self.render_pass,
+            resources: self.resources,
+            bind_groups,
+        };
+
+        for group in 0..bound.bind_groups.len() {
+            // dynamic offsets are ordered by binding number
+            let offsets = self.dynamic_offsets
+                .get(group)
+                .map(|offsets| offsets.values().copied().collect::<SmallVec<[wgpu::DynamicOffset; 4]>>())
+                .unwrap_or_default();
+
+            bound.set_dynamic_offsets(group as u32, &offsets);
         }
+
+        bound
+    }
+}
+
+/// Pipeline with all bind groups bound.
+/// Dynamic offsets can be changed between draws without creating new bind groups.
+pub struct BoundPipeline<'ctx, 'rp> {
+    render_pass: &'ctx mut wgpu::RenderPass<'rp>,
+    resources: &'ctx Vec<ResourceStorage>,
+    bind_groups: SmallVec<[wgpu::BindGroup; 4]>,
+}
+
+impl<'ctx, 'rp> BoundPipeline<'ctx, 'rp> {
+    /// Rebind a bind group with new dynamic offsets, ordered by binding number.
+    pub fn set_dynamic_offsets(&mut self, group: u32, offsets: &[wgpu::DynamicOffset]) {
+        let bind_group = self.bind_groups.get(group as usize).expect("Bind group index out of bound!");
+        self.render_pass.set_bind_group(group, bind_group, offsets);
+    }
+
+    pub fn render_pass(&mut self) -> &mut wgpu::RenderPass<'rp> {
+        self.render_pass
+    }
+
+    /// Draw with the arguments at `offset` of `args`, laid out as `wgpu::util::DrawIndexedIndirectArgs`, e.g. written by a compute node.
+    /// The node must read `args` with `BufferState::INDIRECT`. A non-zero first instance requires `wgpu::Features::INDIRECT_FIRST_INSTANCE`.
+    pub fn draw_indexed_indirect<V: GraphResourceView>(&mut self, args: &RenderGraphResourceAccess<Buffer, V>, offset: wgpu::BufferAddress) {
+        let buffer = self.indirect_args(args);
+        self.render_pass.draw_indexed_indirect(buffer, offset);
     }
+
+    /// Issue `count` draws whose arguments are packed from `offset` of `args`, see [`BoundPipeline::draw_indexed_indirect`].
+    /// Recorded as a single command on backends with native multi-draw, as `count` draws elsewhere.
+    pub fn multi_draw_indexed_indirect<V: GraphResourceView>(&mut self, args: &RenderGraphResourceAccess<Buffer, V>, offset: wgpu::BufferAddress, count: u32) {
+        let buffer = self.indirect_args(args);
+        self.render_pass.multi_draw_indexed_indirect(buffer, offset, count);
+    }
+
+    fn indirect_args<V: GraphResourceView>(&self, args: &RenderGraphResourceAccess<Buffer, V>) -> &'ctx Buffer {
+        let storage = utility::resource_storage_ref(self.resources, args.id);
+        debug_assert!(
+            matches!(args.access, GraphResourceAccess::Buffer(state) if state.contains(BufferState::INDIRECT)),
+            "Indirect arguments[{}] are NOT read as INDIRECT by the node", storage.name()
+        );
+        storage.as_buffer()
+    }
+}
+
+/// Execution context of a compute node, dispatches are recorded in a compute pass begun by the node.
+pub struct ComputeNodeExecutionContext<'node> {
+    name: &'node str,
+    pipeline_desc: &'node ComputePipelineDescriptor,
+    device: &'node wgpu::Device,
+    queue: &'node wgpu::Queue,
+    staging_belt: Option<&'node mut wgpu::util::StagingBelt>,
+    resources: &'node Vec<ResourceStorage>,
+    pipeline: wgpu::ComputePipeline,
 }
 
+impl<'node> ComputeNodeExecutionContext<'node> {
+    #[inline]
+    pub fn get_buffer<V: GraphResourceView>(&mut self, resource: &RenderGraphResourceAccess<Buffer, V>) -> Buffer {
+        self.resources.get(resource.id as usize).expect("Graph resource index out of bound!").as_buffer().clone()
+    }
+
+    #[inline]
+    pub fn get_texture<V: GraphResourceView>(&mut self, resource: &RenderGraphResourceAccess<Texture, V>) -> Texture {
+        self.resources.get(resource.id as usize).expect("Graph resource index out of bound!").as_texture().clone()
+    }
+
+    /// Write a slice of values at the offset of the buffer.
+    #[inline]
+    pub fn write_buffer_slice<V: GraphResourceView, T: NoUninit>(&mut self, resource: &RenderGraphResourceAccess<Buffer, V>, offset: wgpu::BufferAddress, data: &[T]) {
+        let buffer = self.resources.get(resource.id as usize).expect("Graph resource index out of bound!").as_buffer();
+        debug_assert!(offset as usize + size_of_val(data) <= buffer.size() as usize, "Write out of buffer bound!");
+        self.queue.write_buffer(buffer, offset, bytemuck::cast_slice(data));
+    }
+
+    /// Write a whole uniform value to the start of the buffer.
+    #[inline]
+    pub fn write_uniform<V: GraphResourceView, T: Pod>(&mut self, resource: &RenderGraphResourceAccess<Buffer, V>, value: &T) {
+        let buffer = self.resources.get(resource.id as usize).expect("Graph resource index out of bound!").as_buffer();
+        utility::write_uniform(self.queue, buffer, value);
+    }
+
+    /// Same as `GraphicNodeExecutionContext::upload_buffer_staged`, record it before beginning the compute pass.
+    pub fn upload_buffer_staged<V: GraphResourceView, T: Pod>(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        resource: &RenderGraphResourceAccess<Buffer, V>,
+        data: &[T],
+    ) {
+        let buffer = self.resources.get(resource.id as usize).expect("Graph resource index out of bound!").as_buffer();
+        match self.staging_belt.as_deref_mut() {
+            Some(staging_belt) => utility::upload_buffer_staged(self.device, encoder, staging_belt, buffer, data),
+            None => utility::upload_buffer(self.queue, buffer, data),
+        }
+    }
+
+    #[inline]
+    pub fn pipeline(&self) -> &wgpu::ComputePipeline {
+        &self.pipeline
+    }
+
+    pub fn begin_compute_pass<'encoder>(&mut self, encoder: &'encoder mut wgpu::CommandEncoder) -> wgpu::ComputePass<'encoder> {
+        encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some(self.name),
+            timestamp_writes: None,
+        })
+    }
+
+    #[inline]
+    pub fn bind_pipeline<'ctx, 'cp>(&'ctx mut self, compute_pass: &'ctx mut wgpu::ComputePass<'cp>) -> ComputePipelineBinder<'ctx, 'cp> {
+        ComputePipelineBinder {
+            device: self.device,
+            compute_pass,
+            pipeline: &self.pipeline,
+            pipeline_desc: self.pipeline_desc,
+            bind_group_entries: vec![],
+        }
+    }
+}
+
+/// Same as [`PipelineBinder`] for the compute pipeline of a compute node, bound groups stay set for all following dispatches.
+pub struct ComputePipelineBinder<'ctx, 'cp> {
+    device: &'ctx wgpu::Device,
+    compute_pass: &'ctx mut wgpu::ComputePass<'cp>,
+    pipeline_desc: &'ctx ComputePipelineDescriptor,
+    pipeline: &'ctx wgpu::ComputePipeline,
+    bind_group_entries: Vec<OrderedMap<u32, wgpu::BindingResource<'ctx>>>,
+}
+
+impl<'ctx, 'cp> ComputePipelineBinder<'ctx, 'cp> {
+    pub fn with_binding(mut self, group: u32, binding: u32, resource: wgpu::BindingResource<'ctx>) -> Self {
+        let shader = self.pipeline_desc.shader.as_ref().unwrap();
+        debug_assert!(group < shader.num_bind_groups(), "Invalid group index: {}, shader[{}] only have {} bind group(s)", group, shader.name(), shader.num_bind_groups());
+        debug_assert!(
+            utility::is_binding_compatible(&resource, shader.binding_type(group, binding)),
+            "Binding resource mismatch at group {} binding {}, shader[{}] expects {:?}", group, binding, shader.name(), shader.binding_type(group, binding)
+        );
+
+        let non_allocated_groups = group as i32 - self.bind_group_entries.len() as i32 + 1;
+        for _ in 0..non_allocated_groups {
+            self.bind_group_entries.push(OrderedMap::new());
+        }
+
+        if self.bind_group_entries[group as usize].insert(binding, resource).is_some() {
+            warn!(target: RENDER, "Binding {} of group {} in shader[{}] is bound multiple times, the last one is used!", binding, group, shader.name());
+        }
+
+        self
+    }
+
+    pub fn bind(self) {
+        let shader = self.pipeline_desc.shader.as_ref().unwrap();
+
+        self.compute_pass.set_pipeline(self.pipeline);
+        for (group, group_entries) in self.bind_group_entries.into_iter().enumerate() {
+            let group_entries = group_entries
+                .into_iter()
+                .map(|(binding, resource)| wgpu::BindGroupEntry { binding, resource })
+                .collect::<SmallVec<[wgpu::BindGroupEntry; 8]>>();
+
+            let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(&format!("{} BindGroup{}", shader.name(), group)),
+                layout: &shader.create_bind_group_layout(self.device, group as u32).unwrap(),
+                entries: &group_entries,
+            });
+            self.compute_pass.set_bind_group(group as u32, &bind_group, &[]);
+        }
+    }
+}
+
+/// Execution context of a lambda node, which records arbitrary commands without a pipeline, e.g. copies.
+/// Only resources declared by `read`/`write` of the node are transitioned before its execution.
 pub struct LambdaNodeExecutionContext<'node> {
+    device: &'node wgpu::Device,
     queue: &'node wgpu::Queue,
+    staging_belt: Option<&'node mut wgpu::util::StagingBelt>,
     resources: &'node Vec<ResourceStorage>,
 }
 
 impl<'node> LambdaNodeExecutionContext<'node> {
     #[inline]
-    #[allow(dead_code)]
+    pub fn device(&self) -> &wgpu::Device {
+        self.device
+    }
+
+    #[inline]
     pub fn get_buffer<V: GraphResourceView>(&mut self, resource: &RenderGraphResourceAccess<Buffer, V>) -> Buffer {
         self.resources.get(resource.id as usize).expect("Graph resource index out of bound!").as_buffer().clone()
     }
 
     #[inline]
-    #[allow(dead_code)]
     pub fn get_texture<V: GraphResourceView>(&mut self, resource: &RenderGraphResourceAccess<Texture, V>) -> Texture {
         self.resources.get(resource.id as usize).expect("Graph resource index out of bound!").as_texture().clone()
     }
 
     #[inline]
-    #[allow(dead_code)]
-    pub fn write_buffer<V: GraphResourceView>(&mut self, resource: &RenderGraphResourceAccess<Buffer, V>, offset: wgpu::BufferAddress, data: &[u8]) {
+    pub fn get_sampler<V: GraphResourceView>(&mut self, resource: &RenderGraphResourceAccess<Sampler, V>) -> Sampler {
+        self.resources.get(resource.id as usize).expect("Graph resource index out of bound!").as_sampler().clone()
+    }
+
+    #[inline]
+    pub fn write_buffer<V: GraphResourceView, T: NoUninit>(&mut self, resource: &RenderGraphResourceAccess<Buffer, V>, offset: wgpu::BufferAddress, data: T) {
+        let buffer = self.resources.get(resource.id as usize).expect("Graph resource index out of bound!").as_buffer();
+        debug_assert!(offset as usize + size_of::<T>() <= buffer.size() as usize, "Write out of buffer bound!");
+        self.queue.write_buffer(buffer, offset, bytemuck::cast_slice(&[data]));
+    }
+
+    /// Write a slice of values at the offset of the buffer.
+    #[inline]
+    pub fn write_buffer_slice<V: GraphResourceView, T: NoUninit>(&mut self, resource: &RenderGraphResourceAccess<Buffer, V>, offset: wgpu::BufferAddress, data: &[T]) {
+        let buffer = self.resources.get(resource.id as usize).expect("Graph resource index out of bound!").as_buffer();
+        debug_assert!(offset as usize + size_of_val(data) <= buffer.size() as usize, "Write out of buffer bound!");
+        self.queue.write_buffer(buffer, offset, bytemuck::cast_slice(data));
+    }
+
+    /// Write a whole uniform value to the start of the buffer.
+    #[inline]
+    pub fn write_uniform<V: GraphResourceView, T: Pod>(&mut self, resource: &RenderGraphResourceAccess<Buffer, V>, value: &T) {
+        let buffer = self.resources.get(resource.id as usize).expect("Graph resource index out of bound!").as_buffer();
+        utility::write_uniform(self.queue, buffer, value);
+    }
+
+    /// Write a slice of values to the start of the buffer, the tail is zero padded to `COPY_BUFFER_ALIGNMENT`.
+    /// Panics if the padded data does NOT fit in the buffer.
+    #[inline]
+    pub fn upload_buffer<V: GraphResourceView, T: Pod>(&mut self, resource: &RenderGraphResourceAccess<Buffer, V>, data: &[T]) {
+        let buffer = self.resources.get(resource.id as usize).expect("Graph resource index out of bound!").as_buffer();
+        utility::upload_buffer(self.queue, buffer, data);
+    }
+
+    /// Same as `upload_buffer`, but copied from the staging belt in the command order of `encoder`, which is cheaper for large and frequent uploads.
+    /// Record it before beginning a pass on the encoder. Falls back to `upload_buffer` if the graph is executed without a staging belt.
+    pub fn upload_buffer_staged<V: GraphResourceView, T: Pod>(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        resource: &RenderGraphResourceAccess<Buffer, V>,
+        data: &[T],
+    ) {
         let buffer = self.resources.get(resource.id as usize).expect("Graph resource index out of bound!").as_buffer();
-        self.queue.write_buffer(buffer, offset, data);
+        match self.staging_belt.as_deref_mut() {
+            Some(staging_belt) => utility::upload_buffer_staged(self.device, encoder, staging_belt, buffer, data),
+            None => utility::upload_buffer(self.queue, buffer, data),
+        }
+    }
+}
+
+/// Execution context of a graphic node recorded into a render bundle (see `GraphicNodeBuilder::execute_bundle`).
+/// It owns handles of the resources accessed by the node, so that it can be sent to a worker thread.
+pub struct BundleNodeExecutionContext {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::RenderPipeline,
+    resources: Vec<(GraphResourceId, BundleResource)>,
+}
+
+enum BundleResource {
+    Buffer(Buffer),
+    Texture(Texture),
+    Sampler(Sampler),
+}
+
+impl BundleResource {
+    fn new(storage: &ResourceStorage) -> Self {
+        match storage {
+            ResourceStorage::ManagedBuffer { .. } | ResourceStorage::ImportedBuffer { .. } => BundleResource::Buffer(storage.as_buffer().clone()),
+            ResourceStorage::ManagedTexture { .. } | ResourceStorage::ImportedTexture { .. } => BundleResource::Texture(storage.as_texture().clone()),
+            ResourceStorage::Sampler { .. } => BundleResource::Sampler(storage.as_sampler().clone()),
+        }
+    }
+}
+
+impl BundleNodeExecutionContext {
+    #[inline]
+    pub fn device(&self) -> &wgpu::Device {
+        &self.device
+    }
+
+    #[inline]
+    pub fn pipeline(&self) -> &wgpu::RenderPipeline {
+        &self.pipeline
+    }
+
+    fn get_resource(&self, id: GraphResourceId) -> &BundleResource {
+        self.resources
+            .iter()
+            .find_map(|(resource_id, resource)| (*resource_id == id).then_some(resource))
+            .expect("Resource is NOT accessed by the node!")
+    }
+
+    #[inline]
+    pub fn get_buffer<V: GraphResourceView>(&mut self, resource: &RenderGraphResourceAccess<Buffer, V>) -> Buffer {
+        match self.get_resource(resource.id) {
+            BundleResource::Buffer(buffer) => buffer.clone(),
+            _ => unreachable!("Expect buffer, but resource[{}] is NOT a buffer!", resource.id),
+        }
+    }
+
+    #[inline]
+    pub fn get_texture<V: GraphResourceView>(&mut self, resource: &RenderGraphResourceAccess<Texture, V>) -> Texture {
+        match self.get_resource(resource.id) {
+            BundleResource::Texture(texture) => texture.clone(),
+            _ => unreachable!("Expect texture, but resource[{}] is NOT a texture!", resource.id),
+        }
+    }
+
+    #[inline]
+    pub fn get_sampler<V: GraphResourceView>(&mut self, resource: &RenderGraphResourceAccess<Sampler, V>) -> Sampler {
+        match self.get_resource(resource.id) {
+            BundleResource::Sampler(sampler) => sampler.clone(),
+            _ => unreachable!("Expect sampler, but resource[{}] is NOT a sampler!", resource.id),
+        }
+    }
+
+    /// Write a slice of values at the offset of the buffer, the write lands before the graph is submitted.
+    #[inline]
+    pub fn write_buffer_slice<V: GraphResourceView, T: NoUninit>(&mut self, resource: &RenderGraphResourceAccess<Buffer, V>, offset: wgpu::BufferAddress, data: &[T]) {
+        let buffer = self.get_buffer(resource);
+        debug_assert!(offset as usize + size_of_val(data) <= buffer.size() as usize, "Write out of buffer bound!");
+        self.queue.write_buffer(&buffer, offset, bytemuck::cast_slice(data));
+    }
+
+    /// Write a whole uniform value to the start of the buffer.
+    #[inline]
+    pub fn write_uniform<V: GraphResourceView, T: Pod>(&mut self, resource: &RenderGraphResourceAccess<Buffer, V>, value: &T) {
+        let buffer = self.get_buffer(resource);
+        utility::write_uniform(&self.queue, &buffer, value);
+    }
+
+    /// Create a bind group of the pipeline of this node, entries must follow the layout declared by its shader.
+    pub fn create_bind_group(&self, group: u32, entries: &[wgpu::BindGroupEntry]) -> wgpu::BindGroup {
+        self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.pipeline.get_bind_group_layout(group),
+            entries,
+        })
+    }
+}
+
+/// Render bundle job of a node, moved to the thread recording it.
+struct BundleJob {
+    name: String,
+    formats: RenderBundleFormats,
+    ctx: BundleNodeExecutionContext,
+    record: Box<dyn FnOnce(&mut BundleNodeExecutionContext, &mut wgpu::RenderBundleEncoder) + Send>,
+}
+
+impl BundleJob {
+    fn record(mut self) -> Result<wgpu::RenderBundle, wgpu::Error> {
+        let device = self.ctx.device.clone();
+        // error scopes are per thread, errors of a worker are NOT captured by the scopes of the graph
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+        let mut bundle_encoder = self.formats.create_encoder(&device, &self.name, &self.ctx.pipeline);
+        (self.record)(&mut self.ctx, &mut bundle_encoder);
+        let bundle = bundle_encoder.finish(&wgpu::RenderBundleDescriptor {
+            label: Some(&self.name),
+        });
+
+        match pollster::block_on(device.pop_error_scope()) {
+            Some(error) => Err(error),
+            None => Ok(bundle),
+        }
+    }
+}
+
+enum PendingBundle {
+    Recording(TaskResult<Result<wgpu::RenderBundle, wgpu::Error>>),
+    Recorded(Result<wgpu::RenderBundle, wgpu::Error>),
+}
+
+impl PendingBundle {
+    fn wait(self) -> Result<wgpu::RenderBundle, wgpu::Error> {
+        match self {
+            PendingBundle::Recording(task) => task.get_result(),
+            PendingBundle::Recorded(result) => result,
+        }
+    }
+
+    /// Drop a bundle which will never be executed, e.g. after an earlier node failed.
+    fn discard(self) {
+        if let PendingBundle::Recording(task) = self {
+            task.detach();
+        }
+    }
+}
+
+/// Begin and end timestamps of the render pass of each graphic node.
+struct GpuProfiler {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    /// Names of graphic nodes, in execution order.
+    node_names: Vec<String>,
+    /// Nanoseconds per timestamp tick.
+    timestamp_period: f32,
+}
+
+impl GpuProfiler {
+    fn new(device: &wgpu::Device, queue: &wgpu::Queue, nodes: &[RenderGraphNode]) -> Option<Self> {
+        if !device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+
+        let node_names: Vec<_> = nodes
+            .iter()
+            .filter(|node| matches!(node.pipeline_state, NodePipelineState::Graphic { .. }))
+            .map(|node| node.name.clone())
+            .collect();
+        if node_names.is_empty() {
+            return None;
+        }
+
+        let query_count = node_names.len() as u32 * 2;
+        let buffer_size = query_count as wgpu::BufferAddress * size_of::<u64>() as wgpu::BufferAddress;
+
+        Some(Self {
+            query_set: device.create_query_set(&wgpu::QuerySetDescriptor {
+                label: Some("render graph timestamps"),
+                ty: wgpu::QueryType::Timestamp,
+                count: query_count,
+            }),
+            resolve_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("render graph timestamps resolve"),
+                size: buffer_size,
+                usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            }),
+            readback_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("render graph timestamps readback"),
+                size: buffer_size,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            }),
+            node_names,
+            timestamp_period: queue.get_timestamp_period(),
+        })
+    }
+
+    /// Only the last render pass begun by the node is measured.
+    fn timestamp_writes(&self, graphic_node_index: u32) -> wgpu::RenderPassTimestampWrites<'_> {
+        wgpu::RenderPassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(graphic_node_index * 2),
+            end_of_pass_write_index: Some(graphic_node_index * 2 + 1),
+        }
+    }
+
+    fn resolve(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.resolve_query_set(&self.query_set, 0..self.node_names.len() as u32 * 2, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(&self.resolve_buffer, 0, &self.readback_buffer, 0, self.resolve_buffer.size());
+    }
+
+    /// Block until the timestamps are read back.
+    fn read_timings(&self, device: &wgpu::Device) -> Vec<(String, f64)> {
+        let slice = self.readback_buffer.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |_| {});
+        if device.poll(wgpu::PollType::Wait).is_err() {
+            return vec![];
+        }
+
+        let timings = {
+            let timestamps: &[u64] = bytemuck::cast_slice(&slice.get_mapped_range());
+            self.node_names
+                .iter()
+                .zip(timestamps.chunks_exact(2))
+                // nodes which never begin a render pass leave their queries zeroed
+                .filter(|(_, pair)| pair[0] != 0 || pair[1] != 0)
+                .map(|(name, pair)| {
+                    let ticks = pair[1].saturating_sub(pair[0]);
+                    (name.clone(), ticks as f64 * self.timestamp_period as f64 / 1_000_000.)
+                })
+                .collect()
+        };
+        self.readback_buffer.unmap();
+        timings
     }
 }
 
-pub struct PresentableRenderGraph {}
+pub struct PresentableRenderGraph {
+    device: wgpu::Device,
+    profiler: Option<GpuProfiler>,
+}
 
 impl PresentableRenderGraph {
+    /// Return the GPU time in milliseconds of each profiled graphic node, keyed by node name.
+    /// Empty if profiling is NOT enabled or NOT supported by the device. Blocks until the graph finishes on GPU.
+    pub fn gpu_timings(&self) -> Vec<(String, f64)> {
+        self.profiler
+            .as_ref()
+            .map(|profiler| profiler.read_timings(&self.device))
+            .unwrap_or_default()
+    }
+
     pub fn present(self, present_surface: wgpu::SurfaceTexture) -> Result<(), Box<anyhow::Error>> {
         present_surface.present();
 
@@ -544,11 +1703,754 @@ impl PresentableRenderGraph {
 }
 
 pub(crate) mod utility {
+    use std::borrow::Cow;
+    use bytemuck::Pod;
     use crate::graph::ResourceStorage;
     use crate::resource::GraphResourceId;
 
+    #[inline]
+    pub(crate) fn write_uniform<T: Pod>(queue: &wgpu::Queue, buffer: &wgpu::Buffer, value: &T) {
+        assert!(
+            buffer.size() as usize >= size_of::<T>(),
+            "Uniform buffer is too small: {} byte(s), but {} byte(s) are required!", buffer.size(), size_of::<T>()
+        );
+        queue.write_buffer(buffer, 0, bytemuck::bytes_of(value));
+    }
+
+    /// Return the data zero padded to a multiple of `COPY_BUFFER_ALIGNMENT`, borrowed if already aligned.
+    fn aligned_bytes<T: Pod>(buffer: &wgpu::Buffer, data: &[T]) -> Cow<'_, [u8]> {
+        let bytes: &[u8] = bytemuck::cast_slice(data);
+        let aligned_size = (bytes.len() as wgpu::BufferAddress).next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT);
+        assert!(
+            aligned_size <= buffer.size(),
+            "Upload out of buffer bound: {} byte(s) (aligned to {}) to a buffer of {} byte(s)!", bytes.len(), aligned_size, buffer.size()
+        );
+
+        if aligned_size == bytes.len() as wgpu::BufferAddress {
+            Cow::Borrowed(bytes)
+        } else {
+            let mut padded = bytes.to_vec();
+            padded.resize(aligned_size as usize, 0);
+            Cow::Owned(padded)
+        }
+    }
+
+    pub(crate) fn upload_buffer<T: Pod>(queue: &wgpu::Queue, buffer: &wgpu::Buffer, data: &[T]) {
+        let bytes = aligned_bytes(buffer, data);
+        if !bytes.is_empty() {
+            queue.write_buffer(buffer, 0, &bytes);
+        }
+    }
+
+    pub(crate) fn upload_buffer_staged<T: Pod>(
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        buffer: &wgpu::Buffer,
+        data: &[T],
+    ) {
+        let bytes = aligned_bytes(buffer, data);
+        if let Some(size) = wgpu::BufferSize::new(bytes.len() as wgpu::BufferAddress) {
+            staging_belt
+                .write_buffer(encoder, buffer, 0, size, device)
+                .copy_from_slice(&bytes);
+        }
+    }
+
+    /// Check whether a binding resource is able to bind to a binding slot of a specific type.
+    pub(crate) fn is_binding_compatible(resource: &wgpu::BindingResource, ty: Option<wgpu::BindingType>) -> bool {
+        match ty {
+            None => false,
+            Some(wgpu::BindingType::Buffer { .. }) => {
+                matches!(resource, wgpu::BindingResource::Buffer(_) | wgpu::BindingResource::BufferArray(_))
+            }
+            // both filtering and comparison samplers are plain wgpu::Sampler, comparison is baked in the sampler
+            Some(wgpu::BindingType::Sampler(_)) => {
+                matches!(resource, wgpu::BindingResource::Sampler(_) | wgpu::BindingResource::SamplerArray(_))
+            }
+            Some(wgpu::BindingType::Texture { .. } | wgpu::BindingType::StorageTexture { .. }) => {
+                matches!(resource, wgpu::BindingResource::TextureView(_) | wgpu::BindingResource::TextureViewArray(_))
+            }
+            Some(_) => true,
+        }
+    }
+
     #[inline]
     pub(crate) fn resource_storage_ref(storage: &Vec<ResourceStorage>, id: GraphResourceId) -> &ResourceStorage {
         storage.get(id as usize).expect("Graph resource id out of bound!")
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use zenith_build::ShaderEntry;
+    use zenith_core::collections::SmallVec;
+    use zenith_render::{define_shader, GraphicShader, PipelineCache};
+    use wgpu::util::DeviceExt;
+    use crate::{BufferDesc, BufferState, ColorInfoBuilder, DepthStencilInfoBuilder, RenderGraphBuilder, RenderResource, TextureDesc, TextureState, Viewport};
+    use crate::interface::GraphResourceAccess;
+    use super::{CompileOptions, CompiledRenderGraph};
+
+    /// Tests on a device are ignored by default, run them with `--ignored` on machines with an adapter.
+    fn create_test_device() -> wgpu::Device {
+        create_test_device_and_queue().0
+    }
+
+    fn create_test_device_and_queue() -> (wgpu::Device, wgpu::Queue) {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))
+            .expect("No adapter to run GPU tests on");
+        pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default())).expect("Failed to create the test device")
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter, run with --ignored"]
+    fn depth_only_pipeline_compiles() {
+        let device = create_test_device();
+
+        define_shader! {
+            let shader = DepthOnly(shadow, "shadow.wgsl", ShaderEntry::Shadow, wgpu::VertexStepMode::Vertex, 1)
+        }
+        let shader = Arc::new(shader.unwrap());
+
+        let mut builder = RenderGraphBuilder::new();
+        let mut shadow_map = builder.create("shadow_map", TextureDesc {
+            label: Some("shadow map"),
+            size: wgpu::Extent3d {
+                width: 256,
+                height: 256,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Depth32Float,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        {
+            let mut node = builder.add_graphic_node("shadow");
+            let shadow_map = node.write(&mut shadow_map, wgpu::TextureUses::DEPTH_STENCIL_WRITE);
+
+            node.setup_pipeline()
+                .with_shader(shader)
+                .with_depth_only(shadow_map, DepthStencilInfoBuilder::default()
+                    .depth_write(true)
+                    .compare(wgpu::CompareFunction::Greater)
+                    .build()
+                    .unwrap());
+            node.execute(|_, _| {});
+        }
+
+        let mut pipeline_cache = PipelineCache::new();
+        let compiled = builder.build(&device).compile(&device, &mut pipeline_cache).unwrap();
+
+        assert_eq!(compiled.graphic_pipelines.len(), 1);
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter, run with --ignored"]
+    fn mismatched_attachment_fails_to_compile() {
+        let device = create_test_device();
+
+        define_shader! {
+            let shader = DepthOnly(shadow, "shadow.wgsl", ShaderEntry::Shadow, wgpu::VertexStepMode::Vertex, 1)
+        }
+        let shader = Arc::new(shader.unwrap());
+
+        let mut builder = RenderGraphBuilder::new();
+        let mut not_depth = builder.create("not_depth", TextureDesc {
+            label: Some("not depth"),
+            size: wgpu::Extent3d {
+                width: 64,
+                height: 64,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+
+        {
+            let mut node = builder.add_graphic_node("shadow");
+            let not_depth = node.write(&mut not_depth, wgpu::TextureUses::DEPTH_STENCIL_WRITE);
+
+            node.setup_pipeline()
+                .with_shader(shader)
+                .with_depth_only(not_depth, DepthStencilInfoBuilder::default()
+                    .depth_write(true)
+                    .compare(wgpu::CompareFunction::Greater)
+                    .build()
+                    .unwrap());
+            node.execute(|_, _| {});
+        }
+
+        let mut pipeline_cache = PipelineCache::new();
+        let Err(err) = builder.build(&device).compile(&device, &mut pipeline_cache) else {
+            panic!("Depth stencil attachment with a color format should NOT compile");
+        };
+        assert!(err.to_string().contains("non depth format"));
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter, run with --ignored"]
+    fn imported_buffer_transitions_in_every_graph() {
+        let device = create_test_device();
+
+        let vertex_buffer = RenderResource::new(device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("persistent vertices"),
+            size: 64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        }));
+
+        for _ in 0..2 {
+            let mut builder = RenderGraphBuilder::new();
+            let mut vb = builder.import("vertices", vertex_buffer.clone(), BufferState::VERTEX);
+            {
+                let mut node = builder.add_lambda_node("upload");
+                let _vb = node.write(&mut vb, BufferState::COPY_DST);
+                node.execute(|_, _| {});
+            }
+            let graph = builder.build(&device);
+
+            let node = &graph.nodes[0];
+            let accesses = node.outputs.iter().map(|access| (access.id, access.access));
+            let transitions = CompiledRenderGraph::plan_transitions(&graph.resources, accesses);
+            assert_eq!(transitions.as_slice(), [(vb.id, GraphResourceAccess::Buffer(BufferState::COPY_DST))]);
+
+            // returned to the import state, which the next graph starts from
+            let restores = CompiledRenderGraph::plan_transitions(&graph.resources, CompiledRenderGraph::import_states(&graph.resources));
+            assert_eq!(restores.as_slice(), [(vb.id, GraphResourceAccess::Buffer(BufferState::VERTEX))]);
+        }
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter, run with --ignored"]
+    fn lambda_node_copies_between_buffers() {
+        let (device, queue) = create_test_device_and_queue();
+
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("readback"),
+            size: 16,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut builder = RenderGraphBuilder::new();
+        let mut src = builder.create("src", crate::BufferDesc {
+            label: Some("src"),
+            size: 16,
+            usage: wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut dst = builder.import("readback", RenderResource::new(readback.clone()), BufferState::COPY_DST);
+        {
+            let mut node = builder.add_lambda_node("upload");
+            let src = node.write(&mut src, BufferState::COPY_DST);
+            node.execute(move |ctx, _| {
+                ctx.write_buffer_slice(&src, 0, &[1u32, 2, 3, 4]);
+            });
+        }
+        {
+            let mut node = builder.add_lambda_node("copy");
+            let src = node.read(&src, BufferState::COPY_SRC);
+            let dst = node.write(&mut dst, BufferState::COPY_DST);
+            node.execute(move |ctx, encoder| {
+                encoder.copy_buffer_to_buffer(&ctx.get_buffer(&src), 0, &ctx.get_buffer(&dst), 0, 16);
+            });
+        }
+
+        let mut pipeline_cache = PipelineCache::new();
+        builder.build(&device).compile(&device, &mut pipeline_cache).unwrap().execute(&device, &queue).unwrap();
+
+        readback.slice(..).map_async(wgpu::MapMode::Read, |result| result.unwrap());
+        device.poll(wgpu::PollType::Wait).unwrap();
+        let values: Vec<u32> = bytemuck::cast_slice(&readback.slice(..).get_mapped_range()).to_vec();
+        assert_eq!(values, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter, run with --ignored"]
+    fn dead_end_node_is_culled() {
+        let device = create_test_device();
+
+        let buffer_desc = |label| crate::BufferDesc {
+            label: Some(label),
+            size: 16,
+            usage: wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        };
+        let output = RenderResource::new(device.create_buffer(&buffer_desc("output")));
+
+        let mut builder = RenderGraphBuilder::new();
+        let mut output = builder.import("output", output, BufferState::COPY_DST);
+        let mut debug = builder.create("debug", buffer_desc("debug"));
+        let mut scratch = builder.create("scratch", buffer_desc("scratch"));
+        {
+            let mut node = builder.add_lambda_node("debug view");
+            let _debug = node.write(&mut debug, BufferState::COPY_DST);
+            node.execute(|_, _| {});
+        }
+        {
+            let mut node = builder.add_lambda_node("fill scratch");
+            let _scratch = node.write(&mut scratch, BufferState::COPY_DST);
+            node.execute(|_, _| {});
+        }
+        {
+            let mut node = builder.add_lambda_node("resolve");
+            let _scratch = node.read(&scratch, BufferState::COPY_SRC);
+            let _output = node.write(&mut output, BufferState::COPY_DST);
+            node.execute(|_, _| {});
+        }
+
+        let options = CompileOptions {
+            cull_unused: true,
+            ..Default::default()
+        };
+        let mut pipeline_cache = PipelineCache::new();
+        let graph = builder.build(&device).compile_with_options(&device, &mut pipeline_cache, options).unwrap();
+
+        let names = graph.nodes.iter().map(|node| node.name()).collect::<Vec<_>>();
+        assert_eq!(names, ["fill scratch", "resolve"]);
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter, run with --ignored"]
+    fn staged_upload_pads_unaligned_data() {
+        let (device, queue) = create_test_device_and_queue();
+
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("readback"),
+            size: 8,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut builder = RenderGraphBuilder::new();
+        let mut dst = builder.import("readback", RenderResource::new(readback.clone()), BufferState::COPY_DST);
+        {
+            let mut node = builder.add_lambda_node("upload");
+            let dst = node.write(&mut dst, BufferState::COPY_DST);
+            node.execute(move |ctx, encoder| {
+                ctx.upload_buffer_staged(encoder, &dst, &[1u8, 2, 3, 4, 5]);
+            });
+        }
+
+        let mut staging_belt = wgpu::util::StagingBelt::new(64);
+        let mut pipeline_cache = PipelineCache::new();
+        builder
+            .build(&device)
+            .compile(&device, &mut pipeline_cache)
+            .unwrap()
+            .execute_with_staging_belt(&device, &queue, &mut staging_belt)
+            .unwrap();
+        staging_belt.recall();
+
+        readback.slice(..).map_async(wgpu::MapMode::Read, |result| result.unwrap());
+        device.poll(wgpu::PollType::Wait).unwrap();
+        assert_eq!(readback.slice(..).get_mapped_range().to_vec(), [1, 2, 3, 4, 5, 0, 0, 0]);
+    }
+
+    fn transient_texture_desc() -> TextureDesc {
+        TextureDesc {
+            label: Some("transient"),
+            size: wgpu::Extent3d {
+                width: 64,
+                height: 64,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::COPY_SRC | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        }
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter, run with --ignored"]
+    fn sequential_transient_textures_share_allocation() {
+        let device = create_test_device();
+
+        let mut builder = RenderGraphBuilder::new();
+        let mut first = builder.create("first", transient_texture_desc());
+        let mut second = builder.create("second", transient_texture_desc());
+        let mut third = builder.create("third", transient_texture_desc());
+        {
+            let mut node = builder.add_lambda_node("use_first");
+            let _first = node.write(&mut first, TextureState::COPY_DST);
+            node.execute(|_, _| {});
+        }
+        {
+            let mut node = builder.add_lambda_node("second_to_third");
+            let _second = node.write(&mut second, TextureState::COPY_SRC);
+            let _third = node.write(&mut third, TextureState::COPY_DST);
+            node.execute(|_, _| {});
+        }
+
+        let mut pipeline_cache = PipelineCache::new();
+        let stats = builder.build(&device).compile(&device, &mut pipeline_cache).unwrap().memory_stats();
+
+        // second reuses first, third overlaps with second
+        let texture_bytes = 64 * 64 * 4;
+        assert_eq!(stats.logical_bytes, 3 * texture_bytes);
+        assert_eq!(stats.allocated_bytes, 2 * texture_bytes);
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter, run with --ignored"]
+    fn reorder_keeps_aliased_textures_apart() {
+        let device = create_test_device();
+
+        let staging = RenderResource::new(device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("staging"),
+            size: 4,
+            usage: wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        }));
+
+        let mut builder = RenderGraphBuilder::new();
+        let staging = builder.import("staging", staging, BufferState::COPY_DST);
+        let mut first = builder.create("first", transient_texture_desc());
+        let mut second = builder.create("second", transient_texture_desc());
+        {
+            let mut node = builder.add_lambda_node("write first");
+            let _staging = node.read(&staging, BufferState::COPY_SRC);
+            let _first = node.write(&mut first, TextureState::COPY_DST);
+            node.execute(|_, _| {});
+        }
+        {
+            let mut node = builder.add_lambda_node("read first");
+            let _first = node.read(&first, TextureState::COPY_SRC);
+            node.execute(|_, _| {});
+        }
+        {
+            let mut node = builder.add_lambda_node("write second");
+            let _second = node.write(&mut second, TextureState::COPY_DST);
+            node.execute(|_, _| {});
+        }
+
+        let options = CompileOptions {
+            reorder: true,
+            ..Default::default()
+        };
+        let mut pipeline_cache = PipelineCache::new();
+        let graph = builder.build(&device).compile_with_options(&device, &mut pipeline_cache, options).unwrap();
+
+        // "write second" needs fewer transitions, but moving it up would overwrite first while it is still read
+        assert_eq!(graph.memory_stats().allocated_bytes, 64 * 64 * 4);
+        let names = graph.nodes.iter().map(|node| node.name()).collect::<Vec<_>>();
+        assert_eq!(names, ["write first", "read first", "write second"]);
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter, run with --ignored"]
+    fn node_accesses_keep_declaration_order() {
+        let device = create_test_device();
+
+        let create_buffer = |label| RenderResource::new(device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: 16,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        }));
+
+        let mut builder = RenderGraphBuilder::new();
+        let a = builder.import("a", create_buffer("a"), BufferState::UNIFORM);
+        let b = builder.import("b", create_buffer("b"), BufferState::UNIFORM);
+        let c = builder.import("c", create_buffer("c"), BufferState::UNIFORM);
+        {
+            let mut node = builder.add_lambda_node("reads");
+            let _c = node.read(&c, BufferState::UNIFORM);
+            let _a = node.read(&a, BufferState::UNIFORM);
+            let _b = node.read(&b, BufferState::UNIFORM);
+            let _a_again = node.read(&a, BufferState::COPY_DST);
+            node.execute(|_, _| {});
+        }
+        let graph = builder.build(&device);
+
+        let inputs = graph.nodes[0].inputs.iter().map(|access| (access.id, access.access)).collect::<Vec<_>>();
+        assert_eq!(inputs, [
+            (c.id, GraphResourceAccess::Buffer(BufferState::UNIFORM)),
+            (a.id, GraphResourceAccess::Buffer(BufferState::UNIFORM)),
+            (b.id, GraphResourceAccess::Buffer(BufferState::UNIFORM)),
+        ]);
+    }
+
+    #[test]
+    fn viewport_pixel_rect_stays_inside_target() {
+        let right_half = Viewport::new(0.5, 0., 0.5, 1.);
+        assert_eq!(right_half.to_pixels(1280, 720), (640., 0., 640., 720.));
+        assert_eq!(right_half.to_pixel_rect(1281, 720), (641, 0, 640, 720));
+
+        let overflow = Viewport::new(-0.25, 0.75, 0.5, 0.5);
+        assert_eq!(overflow.to_pixel_rect(100, 100), (0, 75, 25, 25));
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter, run with --ignored"]
+    fn released_weak_resource_is_not_imported() {
+        let device = create_test_device();
+
+        let buffer = RenderResource::new(device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("weak"),
+            size: 16,
+            usage: wgpu::BufferUsages::VERTEX,
+            mapped_at_creation: false,
+        }));
+        let weak = buffer.downgrade();
+
+        let mut builder = RenderGraphBuilder::new();
+        assert!(builder.import_weak("alive", &weak, BufferState::VERTEX).is_some());
+        // the builder keeps the imported resource alive until the graph is gone
+        drop(buffer);
+        assert!(!weak.is_released());
+
+        drop(builder);
+        assert!(weak.is_released());
+
+        let mut builder = RenderGraphBuilder::new();
+        assert!(builder.import_weak("released", &weak, BufferState::VERTEX).is_none());
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter, run with --ignored"]
+    fn dot_dump_shows_accesses() {
+        let device = create_test_device();
+
+        let mut builder = RenderGraphBuilder::new();
+        let mut uniform = builder.create_uniform::<[f32; 4]>("frame \"uniforms\"");
+        {
+            let mut node = builder.add_lambda_node("upload");
+            let _uniform = node.write(&mut uniform, BufferState::COPY_DST);
+            node.execute(|_, _| {});
+        }
+        {
+            let mut node = builder.add_lambda_node("consume");
+            let _uniform = node.read(&uniform, BufferState::UNIFORM);
+            node.execute(|_, _| {});
+        }
+
+        let dot = builder.build(&device).to_dot();
+        assert!(dot.starts_with("digraph RenderGraph {"));
+        assert!(dot.contains(r#"r0 [label="frame \"uniforms\"\n(buffer)", shape=ellipse];"#));
+        assert!(dot.contains(r#"n0 -> r0 [label="write COPY_DST", color=red];"#));
+        assert!(dot.contains(r#"r0 -> n1 [label="read UNIFORM"];"#));
+    }
+
+    /// How [`draw_fullscreen_triangle`] records its draw.
+    enum DrawMode {
+        Pass,
+        /// Recorded into a render bundle instead of the render pass.
+        Bundle,
+        /// Indexed indirect draws with the arguments of each draw, written to a graph buffer by another node.
+        Indirect(Vec<wgpu::util::DrawIndexedIndirectArgs>),
+    }
+
+    /// Draw the counter-clockwise fullscreen triangle of blit.wgsl sampling white over red, return the drawn pixel.
+    fn draw_fullscreen_triangle(device: &wgpu::Device, queue: &wgpu::Queue, cull_mode: Option<wgpu::Face>, mode: DrawMode) -> [u8; 4] {
+        define_shader! {
+            let shader = Graphic(blit, "blit.wgsl", ShaderEntry::Blit, 1, 1)
+        }
+        let shader = Arc::new(shader.unwrap());
+
+        let texture_desc = |label| wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_SRC | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        };
+        let white = device.create_texture(&texture_desc("white"));
+        queue.write_texture(
+            white.as_image_copy(),
+            &[255; 4],
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(4),
+                rows_per_image: Some(1),
+            },
+            white.size(),
+        );
+        let target = device.create_texture(&texture_desc("target"));
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("readback"),
+            size: wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut builder = RenderGraphBuilder::new();
+        let white = builder.import("white", RenderResource::new(white), TextureState::COPY_DST);
+        let mut target = builder.import("target", RenderResource::new(target), TextureState::empty());
+        let sampler = builder.create_sampler("sampler", &Default::default());
+        let indirect = match &mode {
+            DrawMode::Indirect(draws) => {
+                let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("indices"),
+                    contents: bytemuck::cast_slice(&[0u32, 1, 2]),
+                    usage: wgpu::BufferUsages::INDEX,
+                });
+                let index_buffer = builder.import("indices", RenderResource::new(index_buffer), BufferState::INDEX);
+                let mut args = builder.create("draw args", BufferDesc {
+                    label: Some("draw args"),
+                    size: (draws.len() * size_of::<wgpu::util::DrawIndexedIndirectArgs>()) as wgpu::BufferAddress,
+                    usage: wgpu::BufferUsages::INDIRECT | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                });
+
+                let mut node = builder.add_lambda_node("write args");
+                let args_write = node.write(&mut args, BufferState::COPY_DST);
+                let bytes = draws.iter().flat_map(|draw| draw.as_bytes().to_vec()).collect::<Vec<_>>();
+                node.execute(move |ctx, _| {
+                    ctx.write_buffer_slice(&args_write, 0, &bytes);
+                });
+                let num_draws = draws.len() as u32;
+                Some((index_buffer, args, num_draws))
+            }
+            _ => None,
+        };
+        {
+            let mut node = builder.add_graphic_node("draw");
+            let white = node.read(&white, TextureState::RESOURCE);
+            let sampler = node.read(&sampler, ());
+            let target = node.write(&mut target, TextureState::COLOR_TARGET);
+            let indirect = indirect.map(|(index_buffer, args, num_draws)| {
+                (node.read(&index_buffer, BufferState::INDEX), node.read(&args, BufferState::INDIRECT), num_draws)
+            });
+
+            node.setup_pipeline()
+                .with_shader(shader)
+                .with_color(target, ColorInfoBuilder::default()
+                    .load_op(wgpu::LoadOp::Clear(wgpu::Color::RED))
+                    .store_op(wgpu::StoreOp::Store)
+                    .build()
+                    .unwrap())
+                .with_cull_mode(cull_mode);
+
+            if let DrawMode::Bundle = mode {
+                node.execute_bundle(move |ctx, bundle_encoder| {
+                    let white_view = ctx.get_texture(&white).create_view(&wgpu::TextureViewDescriptor::default());
+                    let sampler = ctx.get_sampler(&sampler);
+
+                    let bind_group = ctx.create_bind_group(0, &[
+                        wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&white_view) },
+                        wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(&sampler) },
+                    ]);
+                    bundle_encoder.set_bind_group(0, &bind_group, &[]);
+                    bundle_encoder.draw(0..3, 0..1);
+                });
+            } else {
+                node.execute(move |ctx, encoder| {
+                    let white_view = ctx.get_texture(&white).create_view(&wgpu::TextureViewDescriptor::default());
+                    let sampler = ctx.get_sampler(&sampler);
+
+                    let index_buffer = indirect.as_ref().map(|(index_buffer, _, _)| ctx.get_buffer(index_buffer));
+
+                    let mut render_pass = ctx.begin_render_pass(encoder);
+                    let mut bound = ctx.bind_pipeline(&mut render_pass)
+                        .with_binding(0, 0, wgpu::BindingResource::TextureView(&white_view))
+                        .with_binding(0, 1, wgpu::BindingResource::Sampler(&sampler))
+                        .bind();
+
+                    match (&indirect, index_buffer) {
+                        (Some((_, args, 1)), Some(index_buffer)) => {
+                            bound.render_pass().set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                            bound.draw_indexed_indirect(args, 0);
+                        }
+                        (Some((_, args, num_draws)), Some(index_buffer)) => {
+                            bound.render_pass().set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                            bound.multi_draw_indexed_indirect(args, 0, *num_draws);
+                        }
+                        _ => bound.render_pass().draw(0..3, 0..1),
+                    }
+                });
+            }
+        }
+        {
+            let mut dst = builder.import("readback", RenderResource::new(readback.clone()), BufferState::COPY_DST);
+            let mut node = builder.add_lambda_node("readback");
+            let target = node.read(&target, TextureState::COPY_SRC);
+            let dst = node.write(&mut dst, BufferState::COPY_DST);
+
+            node.execute(move |ctx, encoder| {
+                encoder.copy_texture_to_buffer(
+                    ctx.get_texture(&target).as_image_copy(),
+                    wgpu::TexelCopyBufferInfo {
+                        buffer: &ctx.get_buffer(&dst),
+                        layout: wgpu::TexelCopyBufferLayout {
+                            offset: 0,
+                            bytes_per_row: Some(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT),
+                            rows_per_image: Some(1),
+                        },
+                    },
+                    wgpu::Extent3d {
+                        width: 1,
+                        height: 1,
+                        depth_or_array_layers: 1,
+                    },
+                );
+            });
+        }
+
+        let mut pipeline_cache = PipelineCache::new();
+        builder.build(device).compile(device, &mut pipeline_cache).unwrap().execute(device, queue).unwrap();
+
+        readback.slice(..).map_async(wgpu::MapMode::Read, |result| result.unwrap());
+        device.poll(wgpu::PollType::Wait).unwrap();
+        let pixel = readback.slice(..4).get_mapped_range().to_vec();
+        pixel.try_into().unwrap()
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter, run with --ignored"]
+    fn cull_mode_keeps_the_other_side() {
+        let (device, queue) = create_test_device_and_queue();
+
+        // double-sided
+        assert_eq!(draw_fullscreen_triangle(&device, &queue, None, DrawMode::Pass), [255; 4]);
+        assert_eq!(draw_fullscreen_triangle(&device, &queue, Some(wgpu::Face::Back), DrawMode::Pass), [255; 4]);
+        // seen from behind
+        assert_eq!(draw_fullscreen_triangle(&device, &queue, Some(wgpu::Face::Front), DrawMode::Pass), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter, run with --ignored"]
+    fn bundle_node_draws_like_a_pass() {
+        let (device, queue) = create_test_device_and_queue();
+
+        assert_eq!(draw_fullscreen_triangle(&device, &queue, None, DrawMode::Bundle), [255; 4]);
+        assert_eq!(draw_fullscreen_triangle(&device, &queue, Some(wgpu::Face::Front), DrawMode::Bundle), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter, run with --ignored"]
+    fn indirect_draw_reads_args_written_by_another_node() {
+        let (device, queue) = create_test_device_and_queue();
+
+        let triangle = |instance_count| wgpu::util::DrawIndexedIndirectArgs {
+            index_count: 3,
+            instance_count,
+            first_index: 0,
+            base_vertex: 0,
+            first_instance: 0,
+        };
+        assert_eq!(draw_fullscreen_triangle(&device, &queue, None, DrawMode::Indirect(vec![triangle(1)])), [255; 4]);
+        assert_eq!(draw_fullscreen_triangle(&device, &queue, None, DrawMode::Indirect(vec![triangle(0)])), [255, 0, 0, 255]);
+        // only the second draw has an instance
+        assert_eq!(draw_fullscreen_triangle(&device, &queue, None, DrawMode::Indirect(vec![triangle(0), triangle(1)])), [255; 4]);
+    }
+}
@@ -1,5 +1,7 @@
-﻿use derive_more::{From, TryInto, Deref, DerefMut};
+﻿use derive_more::{From, TryInto};
 use std::marker::PhantomData;
+use std::ops::Deref;
+use std::sync::{Arc, Weak};
 use crate::builder::{RenderGraphBuilder};
 use crate::resource::{ExportedRenderGraphResource, GraphImportExportResource, GraphResource, GraphResourceDescriptor, RenderGraphResource, GraphResourceState};
 
@@ -57,16 +59,75 @@ macro_rules! render_graph_resource_interface {
 	};
 }
 
+// Sampler is immutable on GPU, so it has no state to transition.
 render_graph_resource_interface!(
     Buffer => wgpu::Buffer, BufferDesc => wgpu::BufferDescriptor<'static>, BufferState => wgpu::BufferUses,
-    Texture => wgpu::Texture, TextureDesc => wgpu::TextureDescriptor<'static>, TextureState => wgpu::TextureUses
+    Texture => wgpu::Texture, TextureDesc => wgpu::TextureDescriptor<'static>, TextureState => wgpu::TextureUses,
+    Sampler => wgpu::Sampler, SamplerDesc => wgpu::SamplerDescriptor<'static>, SamplerState => ()
 );
 
-#[derive(Deref, DerefMut, From, Clone, Debug)]
-pub struct RenderResource<T: GraphResource>(T);
+/// Shared handle of a GPU resource, the GPU memory is released once the last handle is dropped.
+/// A graph importing the resource holds a handle until it is executed.
+#[derive(Debug)]
+pub struct RenderResource<T: GraphResource>(Arc<T>);
 
 impl<T: GraphResource> RenderResource<T> {
     pub fn new(resource: T) -> Self {
-        Self(resource)
+        Self(Arc::new(resource))
+    }
+
+    /// Create a handle which does NOT keep the resource alive.
+    pub fn downgrade(&self) -> WeakRenderResource<T> {
+        WeakRenderResource(Arc::downgrade(&self.0))
+    }
+}
+
+impl<T: GraphResource> Clone for RenderResource<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T: GraphResource> Deref for RenderResource<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: GraphResource> From<T> for RenderResource<T> {
+    fn from(resource: T) -> Self {
+        Self::new(resource)
+    }
+}
+
+/// Handle of a [`RenderResource`] which does NOT keep it alive, e.g. for renderers drawing resources owned by an asset.
+/// Import it by [`RenderGraphBuilder::import_weak`], which skips released resources.
+#[derive(Debug)]
+pub struct WeakRenderResource<T: GraphResource>(Weak<T>);
+
+impl<T: GraphResource> WeakRenderResource<T> {
+    /// Return a handle keeping the resource alive, or None if it has been released.
+    pub fn upgrade(&self) -> Option<RenderResource<T>> {
+        self.0.upgrade().map(RenderResource)
+    }
+
+    #[inline]
+    pub fn is_released(&self) -> bool {
+        self.0.strong_count() == 0
+    }
+}
+
+impl<T: GraphResource> Clone for WeakRenderResource<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T: GraphResource> Default for WeakRenderResource<T> {
+    /// A handle which is always released.
+    fn default() -> Self {
+        Self(Weak::new())
     }
 }
\ No newline at end of file
@@ -4,8 +4,8 @@ mod graph;
 mod resource;
 mod interface;
 
-pub use interface::{Buffer, Texture, BufferDesc, TextureDesc, BufferState, TextureState, RenderResource};
+pub use interface::{Buffer, Texture, Sampler, BufferDesc, TextureDesc, SamplerDesc, BufferState, TextureState, SamplerState, RenderResource, WeakRenderResource};
 pub use resource::{RenderGraphResource, RenderGraphResourceAccess};
-pub use builder::{RenderGraphBuilder, GraphicNodeBuilder, GraphicPipelineBuilder};
-pub use node::{RenderGraphNode, GraphicPipelineDescriptor, ColorInfo, ColorInfoBuilder, ColorInfoBuilderError, DepthStencilInfo, DepthStencilInfoBuilder, DepthStencilInfoBuilderError};
-pub use graph::{RenderGraph, CompiledRenderGraph, PresentableRenderGraph, GraphicNodeExecutionContext, PipelineBinder};
\ No newline at end of file
+pub use builder::{RenderGraphBuilder, GraphicNodeBuilder, ComputeNodeBuilder, LambdaNodeBuilder, GraphicPipelineBuilder, ComputePipelineBuilder};
+pub use node::{RenderGraphNode, GraphicPipelineDescriptor, ComputePipelineDescriptor, Viewport, ColorInfo, ColorInfoBuilder, ColorInfoBuilderError, DepthStencilInfo, DepthStencilInfoBuilder, DepthStencilInfoBuilderError};
+pub use graph::{RenderGraph, CompileOptions, CompiledRenderGraph, GraphMemoryStats, PresentableRenderGraph, GraphExecutionError, GraphicNodeExecutionContext, ComputeNodeExecutionContext, LambdaNodeExecutionContext, BundleNodeExecutionContext, PipelineBinder, ComputePipelineBinder, BoundPipeline};
\ No newline at end of file
@@ -1,11 +1,62 @@
 use std::sync::Arc;
 use derive_builder::Builder;
-use zenith_render::GraphicShader;
-use crate::graph::{GraphicNodeExecutionContext, LambdaNodeExecutionContext};
+use zenith_render::{ComputeShader, GraphicShader};
+use crate::graph::{BundleNodeExecutionContext, ComputeNodeExecutionContext, GraphicNodeExecutionContext, LambdaNodeExecutionContext};
 use crate::builder::{ResourceAccessStorage};
 use crate::interface::Texture;
 use crate::resource::{RenderGraphResourceAccess, Rt};
 
+/// Region of a render target in normalized [0, 1] coordinates, origin at top-left.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Viewport {
+    /// The whole render target.
+    pub const FULL: Self = Self {
+        x: 0.,
+        y: 0.,
+        width: 1.,
+        height: 1.,
+    };
+
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Return (x, y, width, height) of this region in pixels of a `width` x `height` render target.
+    pub fn to_pixels(&self, width: u32, height: u32) -> (f32, f32, f32, f32) {
+        let (width, height) = (width as f32, height as f32);
+        (self.x * width, self.y * height, self.width * width, self.height * height)
+    }
+
+    /// Same as [`Viewport::to_pixels`], rounded to whole pixels inside the render target, e.g. for a scissor rect.
+    pub fn to_pixel_rect(&self, width: u32, height: u32) -> (u32, u32, u32, u32) {
+        let (x, y, w, h) = self.to_pixels(width, height);
+        let min_x = (x.round().max(0.) as u32).min(width);
+        let min_y = (y.round().max(0.) as u32).min(height);
+        let max_x = ((x + w).round().max(0.) as u32).clamp(min_x, width);
+        let max_y = ((y + h).round().max(0.) as u32).clamp(min_y, height);
+
+        (min_x, min_y, max_x - min_x, max_y - min_y)
+    }
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self::FULL
+    }
+}
+
 #[derive(Default, Debug, Builder)]
 #[builder(setter(into))]
 pub struct ColorInfo {
@@ -45,6 +96,13 @@ pub struct GraphicPipelineDescriptor {
     pub(crate) shader: Option<Arc<GraphicShader>>,
     pub(crate) color_attachments: Vec<(RenderGraphResourceAccess<Texture, Rt>, ColorInfo)>,
     pub(crate) depth_stencil_attachment: Option<(RenderGraphResourceAccess<Texture, Rt>, DepthStencilInfo)>,
+    /// Region of the attachments to draw into, set on the render pass when it begins.
+    pub(crate) viewport: Viewport,
+    /// Faces to cull, front faces wind counter-clockwise. None draws both sides.
+    pub(crate) cull_mode: Option<wgpu::Face>,
+    pub(crate) topology: wgpu::PrimitiveTopology,
+    /// Line and point modes need `POLYGON_MODE_LINE` and `POLYGON_MODE_POINT`.
+    pub(crate) polygon_mode: wgpu::PolygonMode,
 }
 
 impl GraphicPipelineDescriptor {
@@ -57,34 +115,45 @@ impl GraphicPipelineDescriptor {
     }
 
     pub fn valid(&self) -> bool {
-        self.shader.is_some() && !self.color_attachments.is_empty()
+        // depth-only pipeline (e.g. shadow map) has no color attachment
+        self.shader.is_some() && (!self.color_attachments.is_empty() || self.depth_stencil_attachment.is_some())
     }
 }
 
-#[derive(Default, Debug)]
+#[derive(Default)]
 pub struct ComputePipelineDescriptor {
+    pub(crate) shader: Option<Arc<ComputeShader>>,
 }
 
 impl ComputePipelineDescriptor {
-    #[allow(dead_code)]
     pub fn name(&self) -> &str {
-        "Unknown"
+        self
+            .shader
+            .as_ref()
+            .map(|shader| shader.name())
+            .unwrap_or("Unknown")
     }
 
     pub fn valid(&self) -> bool {
-        false
+        self.shader.is_some()
     }
 }
 
+/// Job of a graphic node, either recording its own render pass or a render bundle executed in a pass begun by the graph.
+pub(crate) enum GraphicNodeJob {
+    Pass(Box<dyn FnOnce(&mut GraphicNodeExecutionContext, &mut wgpu::CommandEncoder)>),
+    /// Recorded on a worker thread ahead of the node, see `GraphicNodeBuilder::execute_bundle`.
+    Bundle(Box<dyn FnOnce(&mut BundleNodeExecutionContext, &mut wgpu::RenderBundleEncoder) + Send>),
+}
+
 pub(crate) enum NodePipelineState {
     Graphic {
         pipeline_desc: GraphicPipelineDescriptor,
-        job_functor: Option<Box<dyn FnOnce(&mut GraphicNodeExecutionContext, &mut wgpu::CommandEncoder)>>,
+        job_functor: Option<GraphicNodeJob>,
     },
-    #[allow(dead_code)]
     Compute {
         pipeline_desc: ComputePipelineDescriptor,
-        job_functor: Option<Box<dyn FnOnce(&mut GraphicNodeExecutionContext, &mut wgpu::CommandEncoder)>>,
+        job_functor: Option<Box<dyn FnOnce(&mut ComputeNodeExecutionContext, &mut wgpu::CommandEncoder)>>,
     },
     Lambda {
         job_functor: Option<Box<dyn FnOnce(&mut LambdaNodeExecutionContext, &mut wgpu::CommandEncoder)>>,
@@ -111,6 +180,7 @@ pub struct RenderGraphNode {
     // TODO: debug only
     #[allow(dead_code)]
     pub(crate) name: String,
+    /// Resources read and written by this node in declaration order, a resource declared twice keeps its first access.
     pub(crate) inputs: Vec<ResourceAccessStorage>,
     pub(crate) outputs: Vec<ResourceAccessStorage>,
 
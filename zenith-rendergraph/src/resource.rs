@@ -2,7 +2,7 @@ use std::fmt::{Debug};
 use std::marker::PhantomData;
 use derive_more::From;
 use crate::builder::{RenderGraphBuilder, ResourceAccessStorage};
-use crate::interface::{Buffer, BufferState, GraphResourceAccess, ResourceDescriptor, Texture, TextureState};
+use crate::interface::{Buffer, BufferState, GraphResourceAccess, ResourceDescriptor, Sampler, SamplerState, Texture, TextureState};
 use crate::RenderResource;
 
 pub trait GraphResource: Clone {
@@ -82,8 +82,10 @@ impl<R: GraphResource> Copy for ExportedRenderGraphResource<R> {}
 pub(crate) enum InitialResourceStorage {
     ManagedBuffer(String, <Buffer as GraphResource>::Descriptor),
     ManagedTexture(String, <Texture as GraphResource>::Descriptor),
+    ManagedSampler(String, <Sampler as GraphResource>::Descriptor),
     ImportedBuffer(String, RenderResource<Buffer>, BufferState),
     ImportedTexture(String, RenderResource<Texture>, TextureState),
+    ImportedSampler(String, RenderResource<Sampler>, SamplerState),
 }
 
 impl InitialResourceStorage {
@@ -91,8 +93,10 @@ impl InitialResourceStorage {
         match self {
             InitialResourceStorage::ManagedBuffer(name, _) => &name,
             InitialResourceStorage::ManagedTexture(name, _) => &name,
+            InitialResourceStorage::ManagedSampler(name, _) => &name,
             InitialResourceStorage::ImportedBuffer(name, _, _) => &name,
             InitialResourceStorage::ImportedTexture(name, _, _) => &name,
+            InitialResourceStorage::ImportedSampler(name, _, _) => &name,
         }
     }
 }
@@ -0,0 +1,88 @@
+use std::env;
+use std::time::{Duration, Instant};
+use glam::Mat4;
+use zenith::asset::AssetHandle;
+use zenith::asset::manager::AssetManager;
+use zenith::asset::render::MeshCollection;
+use zenith::render::{PipelineCache, RenderDevice, RenderDeviceOptions};
+use zenith::renderer::{FrameUniformBuffer, ModelRenderer};
+use zenith::rendergraph::RenderGraphBuilder;
+
+/// Each view draws the model in a shadow and a main bundle node.
+const VIEWS: usize = 8;
+const WARMUP_FRAMES: u32 = 10;
+const FRAMES: u32 = 100;
+const WIDTH: u32 = 640;
+const HEIGHT: u32 = 360;
+
+/// Compare CPU frame times of a graph whose bundles are recorded serially vs in parallel on the task workers.
+fn main() -> Result<(), anyhow::Error> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 2 {
+        eprintln!("Example: {} mesh/cerberus/scene.gltf", args[0]);
+        std::process::exit(1);
+    }
+
+    zenith::task::initialize();
+    zenith::asset::initialize()?;
+
+    let render_device = RenderDevice::headless(RenderDeviceOptions::default())?;
+
+    let manager = AssetManager::new();
+    manager.request_load(&args[1])?.wait();
+    let collection = AssetHandle::<MeshCollection>::new(manager.asset_url(&args[1])?);
+    let Some(collection) = collection.get() else {
+        anyhow::bail!("Failed to load {}", args[1]);
+    };
+    let Some(renderer) = ModelRenderer::new(&render_device, &collection) else {
+        anyhow::bail!("Device can NOT bind texture arrays");
+    };
+
+    let frame_uniforms = FrameUniformBuffer::new(&render_device);
+    let mut pipeline_cache = PipelineCache::new();
+
+    let serial = average_frame_time(&render_device, &renderer, &frame_uniforms, &mut pipeline_cache, false)?;
+    let parallel = average_frame_time(&render_device, &renderer, &frame_uniforms, &mut pipeline_cache, true)?;
+
+    println!("Recorded {} bundles of [{}] per frame, average CPU time of {} frames", VIEWS * 2, args[1], FRAMES);
+    println!("  serial:   {:>12?}", serial);
+    println!("  parallel: {:>12?} ({:.2}x)", parallel, serial.as_secs_f64() / parallel.as_secs_f64().max(f64::EPSILON));
+
+    Ok(())
+}
+
+/// Build, compile and execute a graph drawing the model into every view, the GPU is waited on outside the timing.
+fn average_frame_time(
+    render_device: &RenderDevice,
+    renderer: &ModelRenderer,
+    frame_uniforms: &FrameUniformBuffer,
+    pipeline_cache: &mut PipelineCache,
+    parallel_bundles: bool,
+) -> Result<Duration, anyhow::Error> {
+    let device = render_device.device();
+    let queue = render_device.queue();
+    let mut total = Duration::ZERO;
+
+    for frame in 0..WARMUP_FRAMES + FRAMES {
+        let start = Instant::now();
+
+        let mut builder = RenderGraphBuilder::new();
+        let frame_uniforms = frame_uniforms.import(&mut builder);
+        for _ in 0..VIEWS {
+            let _output = renderer.build_render_graph(&mut builder, &frame_uniforms, Mat4::IDENTITY, WIDTH, HEIGHT);
+        }
+
+        builder
+            .build(device)
+            .compile(device, pipeline_cache)?
+            .with_parallel_bundles(parallel_bundles)
+            .execute(device, queue)?;
+
+        if frame >= WARMUP_FRAMES {
+            total += start.elapsed();
+        }
+        device.poll(wgpu::PollType::Wait)?;
+    }
+
+    Ok(total / FRAMES)
+}
@@ -0,0 +1,183 @@
+use std::sync::{Arc, Weak};
+use glam::{Vec2, Vec3, Vec4};
+use log::info;
+use winit::event::{DeviceEvent, WindowEvent};
+use winit::keyboard::KeyCode;
+use winit::window::Window;
+use zenith::{launch, App, RenderableApp, WindowConfig};
+use zenith::asset::{registry, AssetHandle, AssetUrl};
+use zenith::asset::render::{MaterialBuilder, Mesh, Vertex};
+use zenith::core::camera::{Camera, CameraController};
+use zenith::core::event::InputEvent;
+use zenith::core::input::InputActionMapper;
+use zenith::core::math::Transform;
+use zenith::render::RenderDevice;
+use zenith::renderer::{InstanceStats, InstancedSceneRenderer, MeshNode, MeshScene};
+use zenith::rendergraph::{Buffer, RenderGraphBuilder, RenderGraphResource, Texture};
+
+/// Cubes along each side of the grid, 10k in total.
+const GRID_SIZE: usize = 100;
+const GRID_SPACING: f32 = 4.0;
+const CUBE_MESH: &str = "mesh/culled_cubes/cube.mesh";
+const CUBE_COLORS: [[f32; 4]; 4] = [
+    [0.9, 0.3, 0.2, 1.0],
+    [0.3, 0.8, 0.3, 1.0],
+    [0.2, 0.4, 0.9, 1.0],
+    [0.9, 0.8, 0.3, 1.0],
+];
+
+/// Stress test of the instanced scene renderer, fly with WASD/QE and the mouse to see the visible instance count change.
+/// Press G to switch between CPU and GPU culling.
+pub struct CulledCubesApp {
+    scene: MeshScene,
+    renderer: Option<InstancedSceneRenderer>,
+    main_window: Option<Weak<Window>>,
+    last_stats: InstanceStats,
+    gpu_culling: bool,
+
+    camera: Camera,
+    controller: CameraController,
+    mapper: InputActionMapper,
+}
+
+/// Unit cube centered at the origin, with a normal per face.
+fn cube_mesh() -> Mesh {
+    let faces = [Vec3::X, Vec3::NEG_X, Vec3::Y, Vec3::NEG_Y, Vec3::Z, Vec3::NEG_Z];
+
+    let mut vertices = Vec::with_capacity(24);
+    let mut indices = Vec::with_capacity(36);
+    for normal in faces {
+        // two axes spanning the face, counter-clockwise seen from outside
+        let u = if normal.x.abs() > 0.5 { Vec3::Y * normal.x } else if normal.y.abs() > 0.5 { Vec3::Z * normal.y } else { Vec3::X * normal.z };
+        let v = normal.cross(u);
+        let tangent = Vec4::from((u, 1.0));
+
+        let first = vertices.len() as u32;
+        for (x, y) in [(-1., -1.), (1., -1.), (1., 1.), (-1., 1.)] {
+            let position = (normal + u * x + v * y) * 0.5;
+            vertices.push(Vertex::new(position, normal, Vec2::new(x * 0.5 + 0.5, y * 0.5 + 0.5), tangent));
+        }
+        indices.extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+    }
+
+    Mesh::new(vertices, indices, None)
+}
+
+impl App for CulledCubesApp {
+    fn window_config() -> WindowConfig {
+        WindowConfig {
+            title: "Zenith Culled Cubes".to_string(),
+            ..Default::default()
+        }
+    }
+
+    async fn new() -> Result<Self, anyhow::Error> {
+        // generated in memory instead of baked
+        registry().register(CUBE_MESH, cube_mesh());
+        let materials = CUBE_COLORS
+            .iter()
+            .enumerate()
+            .map(|(index, color)| {
+                let url = AssetUrl::new(format!("mesh/culled_cubes/cube_{}.mat", index));
+                registry().register(url.clone(), MaterialBuilder::default().base_color(*color).build()?);
+                Ok(url)
+            })
+            .collect::<Result<Vec<_>, anyhow::Error>>()?;
+
+        // a flat grid below the camera, half of it behind
+        let mut scene = MeshScene::new();
+        for index in 0..GRID_SIZE * GRID_SIZE {
+            let (x, y) = ((index % GRID_SIZE) as f32, (index / GRID_SIZE) as f32);
+            let offset = (GRID_SIZE as f32 - 1.0) * 0.5;
+            let translation = Vec3::new(x - offset, y - offset, -3.0) * Vec3::new(GRID_SPACING, GRID_SPACING, 1.0);
+
+            scene.add_node(None, format!("cube_{}", index), Transform::from_translation(translation), Some(MeshNode {
+                mesh: AssetHandle::new(AssetUrl::new(CUBE_MESH)),
+                material: AssetHandle::new(materials[index % materials.len()].clone()),
+            }));
+        }
+
+        let mut mapper = InputActionMapper::new();
+        mapper.register_axis("strafe", [KeyCode::KeyD], [KeyCode::KeyA], 0.5);
+        mapper.register_axis("walk", [KeyCode::KeyW], [KeyCode::KeyS], 0.5);
+        mapper.register_axis("lift", [KeyCode::KeyE], [KeyCode::KeyQ], 0.5);
+        mapper.register_action("toggle_gpu_culling", [KeyCode::KeyG]);
+
+        Ok(Self {
+            scene,
+            renderer: None,
+            main_window: None,
+            last_stats: InstanceStats::default(),
+            gpu_culling: false,
+
+            camera: Default::default(),
+            controller: Default::default(),
+            mapper,
+        })
+    }
+
+    fn on_input_event(&mut self, event: &InputEvent) {
+        self.mapper.on_input_event(event);
+    }
+
+    fn on_window_event(&mut self, event: &WindowEvent, window: &Window) {
+        self.controller.on_window_event(event, window);
+    }
+
+    fn on_device_event(&mut self, event: &DeviceEvent) {
+        self.controller.on_device_event(event);
+    }
+
+    fn fixed_tick(&mut self, delta_time: f32) {
+        self.mapper.tick(delta_time);
+
+        if self.mapper.is_action_just_pressed("toggle_gpu_culling") {
+            self.gpu_culling = !self.gpu_culling;
+        }
+
+        let forward_axis = self.mapper.get_axis("walk");
+        let right_axis = self.mapper.get_axis("strafe");
+        let up_axis = self.mapper.get_axis("lift");
+
+        self.controller.update_cameras(delta_time, forward_axis, right_axis, up_axis, [&mut self.camera]);
+    }
+}
+
+impl RenderableApp for CulledCubesApp {
+    fn prepare(&mut self, render_device: &mut RenderDevice, main_window: Arc<Window>) -> Result<(), anyhow::Error> {
+        self.main_window = Some(Arc::downgrade(&main_window));
+        self.renderer = Some(InstancedSceneRenderer::new(render_device, &self.scene));
+        Ok(())
+    }
+
+    fn camera(&self) -> Option<&Camera> {
+        Some(&self.camera)
+    }
+
+    fn render(&mut self, builder: &mut RenderGraphBuilder, frame_uniforms: &RenderGraphResource<Buffer>, _interpolation_alpha: f32) -> Option<RenderGraphResource<Texture>> {
+        let window = self.main_window.as_ref()?.upgrade()?;
+        let size = window.inner_size();
+        let renderer = self.renderer.as_mut()?;
+
+        if self.gpu_culling != renderer.gpu_culling() {
+            // stays on the CPU if the device can NOT cull on the GPU
+            self.gpu_culling = renderer.set_gpu_culling(self.gpu_culling);
+            info!("Culling on the {}", if self.gpu_culling { "GPU" } else { "CPU" });
+        }
+
+        let output = renderer.build_render_graph(builder, frame_uniforms, &self.scene, &self.camera, size.width, size.height);
+
+        // logged on change only, the count is stable while the camera stands still
+        let stats = renderer.stats();
+        if stats != self.last_stats {
+            info!("Visible instances: {} / {} in {} draws", stats.visible, stats.total, stats.draws);
+            self.last_stats = stats;
+        }
+
+        Some(output)
+    }
+}
+
+fn main() {
+    launch::<CulledCubesApp>().expect("Failed to launch zenith engine loop!");
+}
@@ -0,0 +1,29 @@
+use std::env;
+use std::path::Path;
+use zenith::asset::gltf_loader::{GltfLoader, LoadStats};
+
+fn main() -> Result<(), anyhow::Error> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 2 {
+        eprintln!("Example: {} content/mesh/cerberus/scene.gltf", args[0]);
+        std::process::exit(1);
+    }
+
+    let mut stats = LoadStats::default();
+    GltfLoader::load_with_stats(Path::new(&args[1]), &mut stats)?;
+
+    let total = stats.total();
+    let percent = |phase: std::time::Duration| phase.as_secs_f64() / total.as_secs_f64().max(f64::EPSILON) * 100.0;
+
+    println!("Loaded [{}] in {:?}", args[1], total);
+    println!("  parse:   {:>12?} ({:>5.1}%)", stats.parse, percent(stats.parse));
+    println!("  buffers: {:>12?} ({:>5.1}%), {} bytes ({} bytes memory mapped without copy)",
+             stats.buffers, percent(stats.buffers), stats.buffer_bytes, stats.mapped_buffer_bytes);
+    println!("  images:  {:>12?} ({:>5.1}%), {} bytes", stats.images, percent(stats.images), stats.total_image_bytes());
+
+    for (index, bytes) in stats.image_bytes.iter().enumerate() {
+        println!("    image[{index}]: {bytes} bytes");
+    }
+
+    Ok(())
+}
@@ -1,20 +1,31 @@
 ﻿use std::env;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Weak};
 use glam::{Quat, Vec3};
-use log::error;
+use log::{error, info, warn};
 use winit::event::{DeviceEvent, WindowEvent};
 use winit::keyboard::KeyCode;
 use winit::window::Window;
-use zenith::{launch, App, RenderableApp};
-use zenith::asset::manager::{AssetManager, AssetLoadTask};
+use zenith::{launch, App, RenderableApp, WindowConfig};
+use zenith::asset::{AssetHandle, AssetUrl};
+use zenith::asset::manager::AssetManager;
+use zenith::asset::render::{Material, Mesh, MeshCollection};
 use zenith::core::camera::{Camera, CameraController};
+use zenith::core::event::InputEvent;
 use zenith::core::input::InputActionMapper;
 use zenith::render::RenderDevice;
-use zenith::renderer::{MeshRenderData, SimpleMeshRenderer};
-use zenith::rendergraph::{RenderGraphBuilder, RenderGraphResource, Texture};
+use zenith::renderer::{DirectionalLight, MeshRenderData, SimpleMeshRenderer};
+use zenith::rendergraph::{Buffer, RenderGraphBuilder, RenderGraphResource, Texture};
+
+/// A gltf being loaded, shown once its load finishes.
+struct PendingModel {
+    asset_url: AssetUrl,
+    gltf_path: PathBuf,
+}
 
 pub struct GltfRendererApp {
-    asset_load_task: AssetLoadTask,
+    manager: AssetManager,
+    pending_model: Option<PendingModel>,
     
     main_window: Option<Weak<Window>>,
     mesh_renderer: Option<SimpleMeshRenderer>,
@@ -26,16 +37,24 @@ pub struct GltfRendererApp {
 }
 
 impl App for GltfRendererApp {
-    fn new() -> Result<Self, anyhow::Error> {
+    fn window_config() -> WindowConfig {
+        WindowConfig {
+            title: "Zenith glTF Model".to_string(),
+            ..Default::default()
+        }
+    }
+
+    async fn new() -> Result<Self, anyhow::Error> {
         let args: Vec<String> = env::args().collect();
         if args.len() != 2 {
             error!("Example: {} mesh/cerberus/scene.gltf", args[0]);
             std::process::exit(1);
         }
 
-        let gltf_path = args[1].clone();
+        let gltf_path = PathBuf::from(&args[1]);
         let manager = AssetManager::new();
-        let asset_load_task = manager.request_load(gltf_path);
+        manager.request_load(gltf_path.clone())?;
+        let asset_url = manager.asset_url(gltf_path.clone())?;
 
         let mut mapper = InputActionMapper::new();
         mapper.register_axis("strafe", [KeyCode::KeyD], [KeyCode::KeyA], 0.5);
@@ -43,7 +62,8 @@ impl App for GltfRendererApp {
         mapper.register_axis("lift", [KeyCode::KeyE], [KeyCode::KeyQ], 0.5);
 
         Ok(Self {
-            asset_load_task,
+            manager,
+            pending_model: Some(PendingModel { asset_url, gltf_path }),
             
             main_window: None,
             mesh_renderer: None,
@@ -55,8 +75,43 @@ impl App for GltfRendererApp {
         })
     }
 
+    fn on_input_event(&mut self, event: &InputEvent) {
+        self.mapper.on_input_event(event);
+
+        // drag feedback in the title bar
+        let title = match event {
+            InputEvent::FileHovered(path) => format!("Drop to load {}", path.display()),
+            InputEvent::FileHoverCancelled | InputEvent::FileDropped(_) => Self::window_config().title,
+            _ => return,
+        };
+        if let Some(window) = self.main_window.as_ref().and_then(|window| window.upgrade()) {
+            window.set_title(&title);
+        }
+    }
+
+    fn on_file_dropped(&mut self, path: &Path) {
+        // the asset manager loads raw resources relative to its content folder
+        let content_dir = self.manager.content_dir().canonicalize().unwrap_or_else(|_| self.manager.content_dir().to_path_buf());
+        let Ok(gltf_path) = path.strip_prefix(&content_dir) else {
+            warn!("Only files inside {} can be loaded, {} is dropped", content_dir.display(), path.display());
+            return;
+        };
+        if !gltf_path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("gltf")) {
+            warn!("Only gltf files can be loaded, {} is dropped", path.display());
+            return;
+        }
+
+        match self.manager.request_load(gltf_path).and_then(|_| self.manager.asset_url(gltf_path)) {
+            Ok(asset_url) => {
+                info!("Loading dropped model {}", gltf_path.display());
+                self.pending_model = Some(PendingModel { asset_url, gltf_path: gltf_path.to_path_buf() });
+            }
+            Err(err) => error!("Failed to load {}: {}", gltf_path.display(), err),
+        }
+    }
+
     fn on_window_event(&mut self, event: &WindowEvent, window: &Window) {
-        self.mapper.on_window_event(event);
+        // the controller grabs the cursor through the window
         self.controller.on_window_event(event, &window);
     }
 
@@ -64,7 +119,7 @@ impl App for GltfRendererApp {
         self.controller.on_device_event(event);
     }
 
-    fn tick(&mut self, delta_time: f32) {
+    fn fixed_tick(&mut self, delta_time: f32) {
         self.mapper.tick(delta_time);
 
         let forward_axis = self.mapper.get_axis("walk");
@@ -76,33 +131,73 @@ impl App for GltfRendererApp {
 }
 
 impl RenderableApp for GltfRendererApp {
-    fn prepare(&mut self, render_device: &mut RenderDevice, main_window: Arc<Window>) -> Result<(), anyhow::Error> {
-        let data = MeshRenderData::new("mesh/cerberus/scene");
-        self.asset_load_task.wait();
+    fn prepare(&mut self, _render_device: &mut RenderDevice, main_window: Arc<Window>) -> Result<(), anyhow::Error> {
+        // nothing is rendered until the model is loaded, see prepare_frame
+        self.main_window = Some(Arc::downgrade(&main_window));
+        Ok(())
+    }
+
+    fn prepare_frame(&mut self, render_device: &mut RenderDevice) {
+        let finished_loads = self.manager.drain_finished_loads();
+        let Some(pending_model) = &self.pending_model else {
+            return;
+        };
+        if !finished_loads.contains(&pending_model.asset_url) {
+            return;
+        }
+        let PendingModel { asset_url, gltf_path } = self.pending_model.take().unwrap();
+
+        // the first mesh of the gltf, a failed load is logged by the manager and leaves nothing registered
+        let collection = AssetHandle::<MeshCollection>::new(asset_url);
+        let Some(collection) = collection.get().filter(|collection| !collection.meshes.is_empty()) else {
+            error!("Failed to load any mesh of {}", gltf_path.display());
+            return;
+        };
+        let mesh = AssetHandle::<Mesh>::new(collection.meshes[0].clone());
+        let material = AssetHandle::<Material>::new(collection.materials[0].clone());
+        if mesh.get().is_none() || material.get().is_none() {
+            error!("Failed to load the first mesh of {}", gltf_path.display());
+            return;
+        }
+
+        let data = MeshRenderData::from_handles(mesh, material, collection.transform(0));
         let mut mesh_renderer = SimpleMeshRenderer::from_model(&render_device, data);
         mesh_renderer.set_base_color([0.7, 0.5, 0.3]);
+        mesh_renderer.set_light(DirectionalLight {
+            direction: Vec3::new(-0.5, 1.0, -1.0),
+            color: Vec3::new(1.0, 0.95, 0.9),
+            intensity: 3.0,
+        });
+        // the mesh never changes, replay its draw instead of recording it every frame
+        mesh_renderer.set_bundle_caching(&render_device, true);
 
-        self.main_window = Some(Arc::downgrade(&main_window));
         self.mesh_renderer = Some(mesh_renderer);
-        Ok(())
     }
 
-    fn render(&mut self, builder: &mut RenderGraphBuilder) -> Option<RenderGraphResource<Texture>> {
+    fn camera(&self) -> Option<&Camera> {
+        Some(&self.camera)
+    }
+
+    fn clear_color(&self) -> Option<wgpu::Color> {
+        // dark blue loading background until the model is drawn
+        Some(wgpu::Color { r: 0.02, g: 0.03, b: 0.08, a: 1.0 })
+    }
+
+    fn render(&mut self, builder: &mut RenderGraphBuilder, frame_uniforms: &RenderGraphResource<Buffer>, _interpolation_alpha: f32) -> Option<RenderGraphResource<Texture>> {
         let (width, height) = if let Some(window) = self.main_window.as_ref().and_then(|window| window.upgrade()) {
             (window.inner_size().width, window.inner_size().height)
         } else {
             return None;
         };
 
+        let mesh_renderer = self.mesh_renderer.as_mut()?;
         let model_matrix = glam::Mat4::from_scale_rotation_translation(Vec3::splat(0.5), Quat::IDENTITY, Vec3::new(0., 100.0, 0.));
+        // simplified meshes far from the camera
+        mesh_renderer.select_lod(&self.camera, model_matrix, height);
 
-        let view = self.camera.view();
-        let proj = self.camera.projection();
-
-        Some(self.mesh_renderer.as_ref().unwrap().build_render_graph(
+        Some(mesh_renderer.build_render_graph(
             builder,
-            view,
-            proj,
+            frame_uniforms,
             model_matrix,
             width,
             height
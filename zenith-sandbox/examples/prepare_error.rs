@@ -0,0 +1,43 @@
+use std::process::ExitCode;
+use std::sync::Arc;
+use anyhow::anyhow;
+use winit::window::Window;
+use zenith::render::RenderDevice;
+use zenith::rendergraph::{Buffer, RenderGraphBuilder, RenderGraphResource, Texture};
+use zenith::{launch, App, RenderableApp, WindowConfig};
+
+/// Fail to prepare, the window is closed and the error is returned from `launch` without panicking.
+pub struct FailingApp;
+
+impl App for FailingApp {
+    fn window_config() -> WindowConfig {
+        WindowConfig {
+            title: "Zenith Prepare Error".to_string(),
+            ..Default::default()
+        }
+    }
+
+    async fn new() -> Result<Self, anyhow::Error> {
+        Ok(Self)
+    }
+}
+
+impl RenderableApp for FailingApp {
+    fn prepare(&mut self, _render_device: &mut RenderDevice, _main_window: Arc<Window>) -> Result<(), anyhow::Error> {
+        Err(anyhow!("Asset mesh/missing/scene.gltf does NOT exist"))
+    }
+
+    fn render(&mut self, _builder: &mut RenderGraphBuilder, _frame_uniforms: &RenderGraphResource<Buffer>, _interpolation_alpha: f32) -> Option<RenderGraphResource<Texture>> {
+        unreachable!("Never rendered since prepare fails")
+    }
+}
+
+fn main() -> ExitCode {
+    match launch::<FailingApp>() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Exited with error: {:#}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use std::env;
+use std::sync::{Arc, Mutex};
+use glam::Vec2;
+use log::error;
+use winit::dpi::PhysicalSize;
+use winit::window::Window;
+use zenith::{launch, App, RenderableApp, WindowConfig};
+use zenith::render::RenderDevice;
+use zenith::renderer::{TextRenderer, TriangleRenderer};
+use zenith::rendergraph::{Buffer, RenderGraphBuilder, RenderGraphResource, Texture};
+
+pub struct TextApp {
+    font_bytes: Vec<u8>,
+    /// Updated by a slot of the render device resize signal.
+    size: Arc<Mutex<PhysicalSize<u32>>>,
+    triangle_renderer: Option<TriangleRenderer>,
+    text_renderer: Option<TextRenderer>,
+}
+
+impl App for TextApp {
+    fn window_config() -> WindowConfig {
+        WindowConfig {
+            title: "Zenith Text".to_string(),
+            ..Default::default()
+        }
+    }
+
+    async fn new() -> Result<Self, anyhow::Error> {
+        let args: Vec<String> = env::args().collect();
+        if args.len() != 2 {
+            error!("Example: {} path/to/font.ttf", args[0]);
+            std::process::exit(1);
+        }
+
+        Ok(Self {
+            font_bytes: std::fs::read(&args[1])?,
+            size: Arc::new(Mutex::new(PhysicalSize::new(0, 0))),
+            triangle_renderer: None,
+            text_renderer: None,
+        })
+    }
+}
+
+impl RenderableApp for TextApp {
+    fn prepare(&mut self, render_device: &mut RenderDevice, main_window: Arc<Window>) -> Result<(), anyhow::Error> {
+        *self.size.lock().unwrap() = main_window.inner_size();
+        let size = self.size.clone();
+        render_device.on_resized().connect(move |new_size| *size.lock().unwrap() = *new_size);
+
+        self.triangle_renderer = Some(TriangleRenderer::new(render_device));
+        self.text_renderer = Some(TextRenderer::with_scale_factor(render_device, &self.font_bytes, 48.0, main_window.scale_factor() as f32)?);
+        Ok(())
+    }
+
+    fn render(&mut self, builder: &mut RenderGraphBuilder, frame_uniforms: &RenderGraphResource<Buffer>, _interpolation_alpha: f32) -> Option<RenderGraphResource<Texture>> {
+        let size = *self.size.lock().unwrap();
+        if size.width == 0 || size.height == 0 {
+            return None;
+        }
+
+        let mut output = self.triangle_renderer.as_ref().unwrap().build_render_graph(builder, frame_uniforms, size.width, size.height);
+
+        let text_renderer = self.text_renderer.as_mut().unwrap();
+        text_renderer.draw_text(Vec2::new(24.0, 24.0), "Hello Zenith", [1.0, 1.0, 1.0, 1.0]);
+        text_renderer.build_render_graph(builder, &mut output);
+
+        Some(output)
+    }
+}
+
+fn main() {
+    launch::<TextApp>().expect("Failed to launch zenith engine loop!");
+}
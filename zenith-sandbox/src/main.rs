@@ -1,19 +1,29 @@
-use std::sync::{Arc, Weak};
+use std::env;
+use std::sync::Arc;
 use winit::window::Window;
 use zenith::render::RenderDevice;
 use zenith::renderer::TriangleRenderer;
-use zenith::rendergraph::{RenderGraphBuilder, RenderGraphResource, Texture};
-use zenith::{launch, App, RenderableApp};
+use zenith::rendergraph::{Buffer, RenderGraphBuilder, RenderGraphResource, Texture};
+use zenith::{block_on, launch, render_to_image, App, RenderableApp, WindowConfig};
 
 pub struct TriangleApp {
-    window: Option<Weak<Window>>,
+    width: u32,
+    height: u32,
     renderer: Option<TriangleRenderer>,
 }
 
 impl App for TriangleApp {
-    fn new() -> Result<Self, anyhow::Error> {
+    fn window_config() -> WindowConfig {
+        WindowConfig {
+            title: "Zenith Triangle".to_string(),
+            ..Default::default()
+        }
+    }
+
+    async fn new() -> Result<Self, anyhow::Error> {
         Ok(Self {
-            window: None,
+            width: 0,
+            height: 0,
             renderer: None,
         })
     }
@@ -21,22 +31,27 @@ impl App for TriangleApp {
 
 impl RenderableApp for TriangleApp {
     fn prepare(&mut self, render_device: &mut RenderDevice, main_window: Arc<Window>) -> Result<(), anyhow::Error> {
-        let triangle_renderer = TriangleRenderer::new(&render_device);
+        let window_size = main_window.inner_size();
+        self.prepare_headless(render_device, window_size.width, window_size.height)
+    }
+
+    fn prepare_headless(&mut self, render_device: &mut RenderDevice, width: u32, height: u32) -> Result<(), anyhow::Error> {
+        let triangle_renderer = TriangleRenderer::with_count(&render_device, 3);
 
-        self.window = Some(Arc::downgrade(&main_window));
+        self.width = width;
+        self.height = height;
         self.renderer = Some(triangle_renderer);
         Ok(())
     }
 
-    fn render(&mut self, builder: &mut RenderGraphBuilder) -> Option<RenderGraphResource<Texture>> {
-        let (width, height) = if let Some(window) = self.window.as_ref().and_then(|window| window.upgrade()) {
-            (window.inner_size().width, window.inner_size().height)
-        } else {
-            return None;
-        };
+    fn resize(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+    }
 
-        if width > 0 && height > 0 {
-            Some(self.renderer.as_ref().unwrap().build_render_graph(builder, width, height))
+    fn render(&mut self, builder: &mut RenderGraphBuilder, frame_uniforms: &RenderGraphResource<Buffer>, _interpolation_alpha: f32) -> Option<RenderGraphResource<Texture>> {
+        if self.width > 0 && self.height > 0 {
+            Some(self.renderer.as_ref().unwrap().build_render_graph(builder, frame_uniforms, self.width, self.height))
         } else {
             None
         }
@@ -44,5 +59,16 @@ impl RenderableApp for TriangleApp {
 }
 
 fn main() {
+    // Example: zenith-sandbox --screenshot triangle.png
+    let args: Vec<String> = env::args().collect();
+    if args.len() == 3 && args[1] == "--screenshot" {
+        zenith::core::log::initialize().expect("Failed to initialize log!");
+
+        let mut app = block_on(TriangleApp::new()).unwrap();
+        let image = render_to_image(&mut app, 1280, 720).expect("Failed to render triangle headless!");
+        image.save(&args[2]).expect("Failed to save screenshot!");
+        return;
+    }
+
     launch::<TriangleApp>().expect("Failed to launch zenith engine loop!");
 }
\ No newline at end of file
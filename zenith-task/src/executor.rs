@@ -7,7 +7,8 @@ use crossbeam_queue::SegQueue;
 use anyhow::{Result, anyhow};
 use zenith_core::collections::{SmallVec};
 use zenith_core::collections::hashmap::HashMap;
-use crate::task::{AsTaskState, BoxedTask, Task, TaskId, TaskResult, TaskState};
+use zenith_core::log::{debug, target::TASK};
+use crate::task::{AsTaskState, BoxedTask, Priority, Task, TaskId, TaskResult, TaskState};
 use crate::worker::WorkerThread;
 
 pub(crate) type UntypedCompletedFunc = Box<dyn FnOnce(Box<dyn Any + Send + 'static>)>;
@@ -43,6 +44,91 @@ impl QueuedTask {
     }
 }
 
+/// Tasks NOT bound to a thread, one FIFO queue per priority.
+#[derive(Debug, Default)]
+pub(crate) struct GlobalQueue {
+    queues: [SegQueue<QueuedTask>; Priority::COUNT],
+}
+
+impl GlobalQueue {
+    #[inline]
+    pub(crate) fn push(&self, task: QueuedTask, priority: Priority) {
+        self.queues[priority.index()].push(task);
+    }
+
+    /// Pop the first task ready to execute, from high to low priority.
+    /// Tasks still waiting for dependencies are pushed back to the end of their queue.
+    pub(crate) fn pop_ready(&self) -> Option<QueuedTask> {
+        for queue in &self.queues {
+            // visit each task at most once, waiting tasks must NOT block lower priorities
+            for _ in 0..queue.len() {
+                let Some(task) = queue.pop() else {
+                    break;
+                };
+
+                if task.ready_to_execute() {
+                    return Some(task);
+                }
+                queue.push(task);
+            }
+        }
+
+        None
+    }
+}
+
+/// Tasks NOT bound to a thread, shared by a schedular and its workers.
+/// Task states refer back to it, so continuations of a task run on the schedular which owns it.
+#[derive(Default)]
+pub(crate) struct GlobalTasks {
+    pub(crate) queue: GlobalQueue,
+    pub(crate) task_storage: Mutex<HashMap<TaskId, BoxedTask>>,
+    pub(crate) task_complete_handles: Mutex<HashMap<TaskId, UntypedCompletedFunc>>,
+}
+
+// Tasks and completion handles are only taken out and run by one worker at a time, behind the mutexes.
+unsafe impl Send for GlobalTasks {}
+unsafe impl Sync for GlobalTasks {}
+
+impl Debug for GlobalTasks {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&self.queue, f)?;
+        Debug::fmt(&self.task_storage, f)?;
+        Debug::fmt(&self.task_complete_handles.lock().keys(), f)
+    }
+}
+
+impl GlobalTasks {
+    pub(crate) fn submit_after<T>(self: &Arc<Self>, task: T, dependencies: &[Arc<TaskState>], priority: Priority) -> TaskResult<T::Output>
+    where
+        T: Task + 'static,
+        T::Output: Send + 'static,
+    {
+        let boxed_task = BoxedTask::new(task);
+        let task_id = boxed_task.id();
+
+        let task_state = self.register_task(boxed_task);
+        let handle: TaskResult<T::Output> = TaskResult::from_task(task_state, task_id);
+
+        self.queue.push(QueuedTask::from(task_id, dependencies), priority);
+
+        handle
+    }
+
+    fn register_task(self: &Arc<Self>, task: BoxedTask) -> Arc<TaskState> {
+        let task_id = task.id();
+        let task_state = Arc::new(TaskState::new(Arc::downgrade(self)));
+
+        self.task_storage.lock().insert(task_id, task);
+        let inner_task_state = task_state.clone();
+        self.task_complete_handles.lock().insert(task_id, Box::new(move |result| {
+            inner_task_state.set_result(result);
+        }));
+
+        task_state
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct ThreadInfo {
     shutdown: Arc<AtomicBool>,
@@ -85,11 +171,8 @@ impl Debug for ThreadLocalState {
 pub struct TaskSchedular {
     thread_registry: Arc<RwLock<HashMap<String, ThreadInfo>>>,
 
-    global_queue: Arc<SegQueue<QueuedTask>>,
+    global_tasks: Arc<GlobalTasks>,
     thread_local_states: Arc<RwLock<HashMap<String, Arc<ThreadLocalState>>>>,
-
-    task_storage: Arc<Mutex<HashMap<TaskId, BoxedTask>>>,
-    task_complete_handles: Arc<Mutex<HashMap<TaskId, UntypedCompletedFunc>>>,
 }
 
 unsafe impl Send for TaskSchedular {}
@@ -98,10 +181,8 @@ unsafe impl Sync for TaskSchedular {}
 impl Debug for TaskSchedular {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         Debug::fmt(&self.thread_registry, f)?;
-        Debug::fmt(&self.global_queue, f)?;
-        Debug::fmt(&self.thread_local_states, f)?;
-        Debug::fmt(&self.task_storage, f)?;
-        Debug::fmt(&self.task_complete_handles.lock().keys(), f)
+        Debug::fmt(&self.global_tasks, f)?;
+        Debug::fmt(&self.thread_local_states, f)
     }
 }
 
@@ -113,39 +194,35 @@ impl Default for TaskSchedular {
 
 impl TaskSchedular {
     pub fn new(thread_configs: &[(&str, usize)]) -> Self {
-        let thread_registry = Arc::new(RwLock::new(HashMap::new()));
-        let global_queue = Arc::new(SegQueue::new());
-        let thread_local_states = Arc::new(RwLock::new(HashMap::new()));
-        let task_storage = Arc::new(Mutex::new(HashMap::new()));
-        let task_complete_handles = Arc::new(Mutex::new(HashMap::new()));
+        let thread_registry = Arc::new(RwLock::new(HashMap::default()));
+        let global_tasks = Arc::new(GlobalTasks::default());
+        let thread_local_states = Arc::new(RwLock::new(HashMap::default()));
 
         let executor = Self {
             thread_registry,
 
-            global_queue,
+            global_tasks,
             thread_local_states,
-
-            task_storage,
-            task_complete_handles,
         };
         executor.spawn_threads(thread_configs);
         executor
     }
 
+    #[inline]
     pub fn submit<T>(&self, task: T) -> TaskResult<T::Output>
     where
         T: Task + 'static,
         T::Output: Send + 'static,
     {
-        let boxed_task = BoxedTask::new(task);
-        let task_id = boxed_task.id();
-
-        let task_state = self.register_task(boxed_task, None);
-        let handle: TaskResult<T::Output> = TaskResult::from_task(task_state, task_id);
+        self.submit_with_priority(task, Priority::Normal)
+    }
 
-        self.global_queue.push(QueuedTask::from(task_id, &[]));
-        
-        handle
+    pub fn submit_with_priority<T>(&self, task: T, priority: Priority) -> TaskResult<T::Output>
+    where
+        T: Task + 'static,
+        T::Output: Send + 'static,
+    {
+        self.global_tasks.submit_after(task, &[], priority)
     }
 
     pub fn submit_to<T>(
@@ -157,27 +234,11 @@ impl TaskSchedular {
         T: Task + 'static,
         T::Output: Send + 'static,
     {
-        if !self.thread_registry.read().contains_key(thread_name) {
-            return Err(anyhow!("Thread '{}' not found", thread_name));
-        }
-
         let boxed_task = BoxedTask::new(task);
         let task_id = boxed_task.id();
 
-        let task_state = self.register_task(boxed_task, Some(thread_name));
-        let handle: TaskResult<T::Output> = TaskResult::from_task(task_state, task_id);
-
-        // directly push into thread's local queue
-        {
-            let thread_local_states = self.thread_local_states.read();
-            if let Some(local_state) = thread_local_states.get(thread_name) {
-                local_state.local_queue.push(QueuedTask::from(task_id, &[]));
-            } else {
-                unreachable!("Try to submit to thread [{}] without registration into TaskExecutor.", thread_name);
-            }
-        }
-        
-        Ok(handle)
+        let task_state = self.submit_local(thread_name, boxed_task, &[])?;
+        Ok(TaskResult::from_task(task_state, task_id))
     }
 
     pub fn submit_after<T, const N: usize>(
@@ -189,19 +250,12 @@ impl TaskSchedular {
         T: Task + 'static,
         T::Output: Send + 'static,
     {
-        let boxed_task = BoxedTask::new(task);
-        let task_id = boxed_task.id();
-
-        let task_state = self.register_task(boxed_task, None);
-        let handle: TaskResult<T::Output> = TaskResult::from_task(task_state, task_id);
-
         let dependencies = dependencies
             .iter()
             .map(|dependency| dependency.as_state().clone())
             .collect::<SmallVec<[Arc<TaskState>; 4]>>();
-        self.global_queue.push(QueuedTask::from(task_id, &dependencies));
 
-        handle
+        self.global_tasks.submit_after(task, &dependencies, Priority::Normal)
     }
 
     pub fn submit_to_after<T, const N: usize>(
@@ -214,59 +268,45 @@ impl TaskSchedular {
         T: Task + 'static,
         T::Output: Send + 'static,
     {
-        if !self.thread_registry.read().contains_key(thread_name) {
-            return Err(anyhow!("Thread '{}' not found", thread_name));
-        }
-
         // TODO: check if all dependencies are in different threads
 
         let boxed_task = BoxedTask::new(task);
         let task_id = boxed_task.id();
 
-        let task_state = self.register_task(boxed_task, Some(thread_name));
-        let handle: TaskResult<T::Output> = TaskResult::from_task(task_state, task_id);
+        let dependencies = dependencies
+            .iter()
+            .map(|dependency| dependency.as_state().clone())
+            .collect::<SmallVec<[Arc<TaskState>; 4]>>();
 
-        // directly add to thread's local queue
-        {
-            let thread_local_states = self.thread_local_states.read();
-            if let Some(local_state) = thread_local_states.get(thread_name) {
-                let dependencies = dependencies
-                    .iter()
-                    .map(|dependency| dependency.as_state().clone())
-                    .collect::<SmallVec<[Arc<TaskState>; 4]>>();
-
-                local_state.local_queue.push(QueuedTask::from(task_id, &dependencies));
-            } else {
-                unreachable!("Try to submit to thread [{}] without registration into TaskExecutor.", thread_name);
-            }
-        }
+        let task_state = self.submit_local(thread_name, boxed_task, &dependencies)?;
+        Ok(TaskResult::from_task(task_state, task_id))
+    }
+
+    /// Register the task and push it directly into the local queue of the thread.
+    /// The thread states are locked until the task is queued, so reconfiguration can NOT drop it halfway.
+    fn submit_local(&self, thread_name: &str, task: BoxedTask, dependencies: &[Arc<TaskState>]) -> Result<Arc<TaskState>> {
+        let thread_local_states = self.thread_local_states.read();
+        let local_state = thread_local_states
+            .get(thread_name)
+            .ok_or_else(|| anyhow!("Thread '{}' not found", thread_name))?;
+
+        let task_id = task.id();
+        let task_state = self.register_local_task(task, local_state);
+        local_state.local_queue.push(QueuedTask::from(task_id, dependencies));
 
-        Ok(handle)
+        Ok(task_state)
     }
 
-    fn register_task(&self, task: BoxedTask, dedicate_thread: Option<&str>) -> Arc<TaskState> {
+    fn register_local_task(&self, task: BoxedTask, local_state: &ThreadLocalState) -> Arc<TaskState> {
         let task_id = task.id();
-        let task_state = Arc::new(TaskState::new());
-
-        if let Some(thread_name) = dedicate_thread {
-            let thread_local_states = self.thread_local_states.read();
-
-            let local_state = thread_local_states
-                .get(thread_name)
-                .expect(&format!("Try to submit to thread [{}] without registration into TaskExecutor", thread_name));
-
-            local_state.task_storage.lock().insert(task_id, task);
-            let inner_task_state = task_state.clone();
-            local_state.task_complete_handles.lock().insert(task_id, Box::new(move |result| {
-                inner_task_state.set_result(result);
-            }));
-        } else {
-            self.task_storage.lock().insert(task_id, task);
-            let inner_task_state = task_state.clone();
-            self.task_complete_handles.lock().insert(task_id, Box::new(move |result| {
-                inner_task_state.set_result(result);
-            }));
-        }
+        // continuations of a thread bound task run on any thread of this schedular
+        let task_state = Arc::new(TaskState::new(Arc::downgrade(&self.global_tasks)));
+
+        local_state.task_storage.lock().insert(task_id, task);
+        let inner_task_state = task_state.clone();
+        local_state.task_complete_handles.lock().insert(task_id, Box::new(move |result| {
+            inner_task_state.set_result(result);
+        }));
 
         task_state
     }
@@ -284,17 +324,40 @@ impl TaskSchedular {
     //     }
     // }
 
+    /// Respawn worker threads with new names and counts, NO submitted task is lost.
+    /// Tasks queued on a removed thread run on any thread afterwards, submitting to it returns an error.
     pub fn config(&self, thread_configs: &[(&str, usize)]) {
+        debug!(target: TASK, "Reconfigure worker threads: {:?}", thread_configs);
         self.join_all_workers();
         self.spawn_threads(thread_configs);
     }
 
+    /// Stop all worker threads after their current task.
+    /// Tasks still queued on a thread are moved to the global queue, so they run on any thread spawned later.
     pub fn join_all_workers(&self) {
         for (_, thread) in self.thread_registry.write().drain() {
             thread.request_shutdown();
             thread.join();
         }
-        self.thread_local_states.write().clear();
+
+        for (_, local_state) in self.thread_local_states.write().drain() {
+            self.requeue_local_tasks(&local_state);
+        }
+    }
+
+    fn requeue_local_tasks(&self, local_state: &ThreadLocalState) {
+        self.global_tasks.task_storage.lock().extend(local_state.task_storage.lock().drain());
+        self.global_tasks.task_complete_handles.lock().extend(local_state.task_complete_handles.lock().drain());
+
+        let mut num_requeued = 0;
+        while let Some(task) = local_state.local_queue.pop() {
+            self.global_tasks.queue.push(task, Priority::Normal);
+            num_requeued += 1;
+        }
+
+        if num_requeued > 0 {
+            debug!(target: TASK, "Moved {} queued task(s) of a stopped thread to the global queue", num_requeued);
+        }
     }
 
     fn spawn_threads(&self, thread_configs: &[(&str, usize)]) {
@@ -314,11 +377,8 @@ impl TaskSchedular {
                 let worker = WorkerThread::new(
                     shutdown.clone(),
 
-                    self.global_queue.clone(),
+                    self.global_tasks.clone(),
                     thread_local_state,
-
-                    self.task_storage.clone(),
-                    self.task_complete_handles.clone(),
                 );
 
                 let handle = std::thread::Builder::new()
@@ -332,6 +392,11 @@ impl TaskSchedular {
         }
     }
     
+    #[inline]
+    pub(crate) fn global_tasks(&self) -> &Arc<GlobalTasks> {
+        &self.global_tasks
+    }
+
     #[inline]
     pub fn num_worker_threads(&self) -> usize {
         self.thread_registry.read().len()
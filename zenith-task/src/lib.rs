@@ -7,18 +7,29 @@ mod task;
 mod executor;
 mod worker;
 
-use std::sync::{OnceLock};
-use crate::executor::TaskSchedular;
+use std::sync::{Arc, OnceLock};
+use crate::executor::{GlobalTasks, TaskSchedular};
 use crate::task::{AsTaskState, Task};
-pub use task::{TaskId, TaskResult, TaskHandle};
-use zenith_core::log::info;
+pub use task::{Priority, TaskId, TaskResult, TaskHandle};
+use zenith_core::log::{info, target::TASK};
 
 static UNIVERSAL_SCHEDULAR: OnceLock<TaskSchedular> = OnceLock::new();
 
 #[inline]
 pub fn initialize() {
     UNIVERSAL_SCHEDULAR.set(TaskSchedular::default()).expect("Failed to initialize zenith-task.");
-    info!("Initialized with {} worker thread(s)", UNIVERSAL_SCHEDULAR.get().unwrap().num_worker_threads());
+    info!(target: TASK, "Initialized with {} worker thread(s)", UNIVERSAL_SCHEDULAR.get().unwrap().num_worker_threads());
+}
+
+/// Return true once [`initialize`] has been called, so that libraries can fall back to run tasks in place.
+#[inline]
+pub fn is_initialized() -> bool {
+    UNIVERSAL_SCHEDULAR.get().is_some()
+}
+
+#[inline]
+pub(crate) fn global_tasks() -> &'static Arc<GlobalTasks> {
+    UNIVERSAL_SCHEDULAR.get().expect("zenith-task is NOT initialized!").global_tasks()
 }
 
 #[inline]
@@ -30,6 +41,16 @@ where
     UNIVERSAL_SCHEDULAR.get().unwrap().submit(task)
 }
 
+/// Submit a task picked before all queued tasks of lower priorities, `submit` uses `Priority::Normal`.
+#[inline]
+pub fn submit_with_priority<T>(task: T, priority: Priority) -> TaskResult<T::Output>
+where
+    T: Task + 'static,
+    T::Output: Send + 'static,
+{
+    UNIVERSAL_SCHEDULAR.get().unwrap().submit_with_priority(task, priority)
+}
+
 #[inline]
 pub fn submit_to<T>(thread_name: &str, task: T) -> anyhow::Result<TaskResult<T::Output>>
 where
@@ -87,12 +108,129 @@ mod tests {
 
         test_task_with_return_value();
         test_concurrent_tasks_with_return_values();
+        test_task_then();
 
         test_ring_loop();
 
         println!("\nAll tests completed！");
     }
 
+    #[test]
+    fn high_priority_task_runs_before_queued_normal_tasks() {
+        let schedular = TaskSchedular::new(&[("worker", 1)]);
+        let order = Arc::new(Mutex::new(Vec::new()));
+
+        // keep the only worker busy until all tasks are queued
+        schedular.submit(|| std::thread::sleep(Duration::from_millis(50))).detach();
+
+        for i in 0..1000 {
+            let order = order.clone();
+            schedular.submit(move || order.lock().push(i)).detach();
+        }
+
+        let high_order = order.clone();
+        let high = schedular.submit_with_priority(move || high_order.lock().push(-1), Priority::High);
+        high.wait();
+
+        let position = order.lock().iter().position(|&i| i == -1).unwrap();
+        assert!(position < 500, "high priority task ran after {} normal tasks", position);
+    }
+
+    #[test]
+    fn wait_timeout_reports_completion() {
+        let schedular = TaskSchedular::new(&[("worker", 1)]);
+
+        let result = schedular.submit(|| {
+            std::thread::sleep(Duration::from_millis(500));
+            42
+        });
+
+        assert!(!result.wait_timeout(Duration::from_millis(50)));
+        let result = result.get_timeout(Duration::from_millis(10)).unwrap_err();
+        assert!(result.wait_timeout(Duration::from_secs(5)));
+        assert_eq!(result.get_timeout(Duration::ZERO).ok(), Some(42));
+    }
+
+    #[test]
+    fn detached_task_keeps_running() {
+        let schedular = TaskSchedular::new(&[("worker", 1)]);
+        let executed = Arc::new(Mutex::new(false));
+
+        let inner_executed = executed.clone();
+        let result = schedular.submit(move || *inner_executed.lock() = true);
+        let waiter = result.clone();
+        result.detach();
+
+        waiter.wait();
+        assert!(*executed.lock());
+    }
+
+    #[test]
+    fn reconfiguration_keeps_queued_tasks() {
+        let schedular = TaskSchedular::new(&[("main", 1), ("worker", 1)]);
+
+        // keep the main thread busy so the following tasks stay in its local queue
+        schedular.submit_to("main", || std::thread::sleep(Duration::from_millis(50))).unwrap().detach();
+        let queued = (0..10)
+            .map(|i| schedular.submit_to("main", move || i * 2).unwrap())
+            .collect::<Vec<_>>();
+
+        schedular.config(&[("worker", 2)]);
+
+        let results = queued.into_iter().map(|result| result.get_result()).collect::<Vec<_>>();
+        assert_eq!(results, (0..10).map(|i| i * 2).collect::<Vec<_>>());
+        assert!(schedular.submit_to("main", || ()).is_err());
+    }
+
+    #[test]
+    fn submit_to_during_reconfiguration_never_panics() {
+        let schedular = Arc::new(TaskSchedular::new(&[("main", 1), ("worker", 1)]));
+
+        let submitter = {
+            let schedular = schedular.clone();
+            std::thread::spawn(move || {
+                (0..2000)
+                    .filter_map(|i| schedular.submit_to("main", move || i).ok())
+                    .collect::<Vec<_>>()
+            })
+        };
+
+        for _ in 0..5 {
+            schedular.config(&[("worker", 1)]);
+            schedular.config(&[("main", 1), ("worker", 1)]);
+        }
+
+        // every accepted task completes, rejected ones returned an error instead of panicking
+        for result in submitter.join().unwrap() {
+            result.wait();
+        }
+    }
+
+    #[test]
+    fn then_chains_outnumbering_workers_complete() {
+        let schedular = Arc::new(TaskSchedular::new(&[("worker", 2)]));
+
+        // every chain waits on inner tasks of the same schedular, blocking a worker per chain would starve it
+        let chains = (0..8)
+            .map(|chain| {
+                let mut result = schedular.submit(move || chain).map(|value| value * 10);
+                for _ in 0..4 {
+                    let inner = schedular.clone();
+                    result = result.then(move |value| inner.submit(move || {
+                        std::thread::sleep(Duration::from_millis(1));
+                        value + 1
+                    }));
+                }
+                result
+            })
+            .collect::<Vec<_>>();
+
+        for (chain, result) in chains.into_iter().enumerate() {
+            assert!(result.wait_timeout(Duration::from_secs(10)), "chain {} did NOT complete", chain);
+            assert_eq!(result.get_result(), chain * 10 + 4);
+        }
+    }
+
     fn calculation_task(val: i32, results_clone: Arc<Mutex<Vec<i32>>>) -> i32 {
         let result = val * val;
         println!("Task {} with calculation result: {}", val, result);
@@ -106,7 +244,7 @@ mod tests {
         let results = Arc::new(Mutex::new(Vec::new()));
         for i in 0..5 {
             let results_clone = Arc::clone(&results);
-            submit(move || calculation_task(i, results_clone));
+            submit(move || calculation_task(i, results_clone)).detach();
         }
 
         std::thread::sleep(Duration::from_millis(200));
@@ -164,7 +302,7 @@ mod tests {
                     msg.push(' ');
                 }
                 msg.push_str(word);
-            });
+            }).detach();
         }
 
         std::thread::sleep(Duration::from_millis(200));
@@ -264,6 +402,22 @@ mod tests {
         assert_eq!(results, [0, 1, 4, 9, 16]);
     }
 
+    fn test_task_then() {
+        println!("\n=== test_task_then() ===");
+
+        let handle = submit(|| 6)
+            .map(|value| value * 7)
+            .then(|value| submit(move || {
+                std::thread::sleep(Duration::from_millis(50));
+                format!("answer {}", value)
+            }));
+
+        let result = handle.get_result();
+        println!("Chained tasks finished: {}", result);
+
+        assert_eq!(result, "answer 42");
+    }
+
     fn test_ring_loop() {
         println!("\n=== test_ring_loop() ===");
 
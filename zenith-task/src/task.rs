@@ -1,8 +1,11 @@
-use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::any::Any;
 use std::fmt::{Debug, Formatter};
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
 use parking_lot::{Condvar, Mutex};
+use zenith_core::log::{warn, target::TASK};
+use crate::executor::GlobalTasks;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct TaskId(u64);
@@ -26,6 +29,30 @@ impl std::fmt::Display for TaskId {
     }
 }
 
+/// Order in which workers pick tasks from the global queue, tasks of the same priority run in submission order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub enum Priority {
+    /// Latency-sensitive work, e.g. recompiling the shader being edited.
+    High,
+    #[default]
+    Normal,
+    /// Background work, e.g. prefetching assets.
+    Low,
+}
+
+impl Priority {
+    pub(crate) const COUNT: usize = 3;
+
+    #[inline]
+    pub(crate) fn index(self) -> usize {
+        match self {
+            Priority::High => 0,
+            Priority::Normal => 1,
+            Priority::Low => 2,
+        }
+    }
+}
+
 pub(crate) type UntypedThreadSafeObject = Box<dyn Any + Send + 'static>;
 pub(crate) type UntypedExecuteFunc = Box<dyn FnOnce(Box<dyn Any + Send + 'static>) -> Box<dyn Any + Send + 'static>>;
 
@@ -94,17 +121,39 @@ pub struct TaskState {
     pub(crate) result: Mutex<Option<UntypedThreadSafeObject>>,
     completed: AtomicBool,
     condvar: Condvar,
+    /// Number of alive `TaskResult`s of this task.
+    result_handles: AtomicUsize,
+    /// Any `TaskResult` of this task was awaited or detached.
+    observed: AtomicBool,
+    /// Schedular which continuations of this task are submitted to, weak so that queued tasks do NOT keep it alive.
+    owner: Weak<GlobalTasks>,
 }
 
 impl TaskState {
-    pub(crate) fn new() -> Self {
+    pub(crate) fn new(owner: Weak<GlobalTasks>) -> Self {
         Self {
             result: Mutex::new(None),
             completed: AtomicBool::new(false),
             condvar: Condvar::new(),
+            result_handles: AtomicUsize::new(0),
+            observed: AtomicBool::new(false),
+            owner,
         }
     }
 
+    fn completed_placeholder() -> Self {
+        Self {
+            completed: AtomicBool::new(true),
+            observed: AtomicBool::new(true),
+            ..Self::new(Weak::new())
+        }
+    }
+
+    #[inline]
+    fn set_observed(&self) {
+        self.observed.store(true, Ordering::Relaxed);
+    }
+
     pub(crate) fn set_result(&self, result: UntypedThreadSafeObject) {
         *self.result.lock() = Some(result);
         self.set_completed();
@@ -129,8 +178,28 @@ impl TaskState {
             self.condvar.wait(&mut result);
         }
     }
+
+    /// Block until completed or the timeout elapses, return true if completed.
+    pub(crate) fn wait_timeout(&self, timeout: Duration) -> bool {
+        if self.completed.load(Ordering::Acquire) {
+            return true;
+        }
+
+        let deadline = Instant::now() + timeout;
+        let mut result = self.result.lock();
+        while !self.completed.load(Ordering::Acquire) {
+            if self.condvar.wait_until(&mut result, deadline).timed_out() {
+                return self.completed.load(Ordering::Acquire);
+            }
+        }
+
+        true
+    }
 }
 
+/// Result of a submitted task, the task keeps running if it is dropped.
+/// In debug builds, dropping the last result of a task which was never awaited logs a warning, call [`TaskResult::detach`] if it is intended.
+#[must_use = "the task result is discarded, call `detach()` if the result is NOT needed"]
 pub struct TaskResult<T> {
     id: TaskId,
     state: Arc<TaskState>,
@@ -139,10 +208,19 @@ pub struct TaskResult<T> {
 
 impl<T: Send + 'static> Clone for TaskResult<T> {
     fn clone(&self) -> Self {
-        Self {
-            state: self.state.clone(),
-            id: self.id,
-            _phantom: std::marker::PhantomData,
+        Self::from_task(self.state.clone(), self.id)
+    }
+}
+
+impl<T> Drop for TaskResult<T> {
+    fn drop(&mut self) {
+        let last_handle = self.state.result_handles.fetch_sub(1, Ordering::AcqRel) == 1;
+        if cfg!(debug_assertions) && last_handle && !self.state.observed.load(Ordering::Relaxed) {
+            if self.state.completed() {
+                warn!(target: TASK, "Result of {} is dropped without being taken, call detach() if it is NOT needed.", self.id);
+            } else {
+                warn!(target: TASK, "{} is still running while its result is dropped without being awaited, call detach() if it is intended.", self.id);
+            }
         }
     }
 }
@@ -153,6 +231,7 @@ impl<T: Clone + Send + 'static> TaskResult<T> {
         T: Send + 'static,
     {
         if self.state.completed.load(Ordering::Acquire) {
+            self.state.set_observed();
             self.state.result.lock().as_ref()?.downcast_ref().cloned()
         } else {
             None
@@ -180,18 +259,12 @@ impl<T: Clone + Send + 'static> TaskResult<T> {
 
 impl<T: Send + 'static> TaskResult<T> {
     pub fn placeholder() -> Self {
-        Self {
-            id: TaskId::INVALID,
-            state: Arc::new(TaskState {
-                result: Default::default(),
-                completed: AtomicBool::new(true),
-                condvar: Default::default(),
-            }),
-            _phantom: std::marker::PhantomData,
-        }
+        Self::from_task(Arc::new(TaskState::completed_placeholder()), TaskId::INVALID)
     }
 
     pub(crate) fn from_task(state: Arc<TaskState>, id: TaskId) -> Self {
+        state.result_handles.fetch_add(1, Ordering::AcqRel);
+
         Self {
             state,
             id,
@@ -199,16 +272,44 @@ impl<T: Send + 'static> TaskResult<T> {
         }
     }
 
+    /// Return true if the task is completed, polling until true counts as awaiting it.
     #[inline]
     pub fn completed(&self) -> bool {
-        self.state.completed.load(Ordering::Acquire)
+        let completed = self.state.completed.load(Ordering::Acquire);
+        if completed {
+            self.state.set_observed();
+        }
+        completed
     }
 
     #[inline]
     pub fn wait(&self) {
+        self.state.set_observed();
         self.state.wait();
     }
 
+    /// Block at most `timeout`, return true if the task is completed.
+    #[inline]
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        self.state.set_observed();
+        self.state.wait_timeout(timeout)
+    }
+
+    /// Take the result if the task completes within `timeout`, otherwise give the result back to retry later, e.g. next frame.
+    pub fn get_timeout(self, timeout: Duration) -> Result<T, TaskResult<T>> {
+        if self.wait_timeout(timeout) {
+            Ok(self.get_result())
+        } else {
+            Err(self)
+        }
+    }
+
+    /// Let the task run without anyone awaiting its result, e.g. fire-and-forget side effects.
+    #[inline]
+    pub fn detach(self) {
+        self.state.set_observed();
+    }
+
     pub fn get_result(&self) -> T
     where
         T: Send + 'static,
@@ -230,10 +331,61 @@ impl<T: Send + 'static> TaskResult<T> {
         self.id
     }
 
+    /// Transform the result on a worker thread of the schedular running this task, once it completes.
+    /// The result of this task is taken, do NOT get it from a clone.
+    pub fn map<U, F>(self, f: F) -> TaskResult<U>
+    where
+        U: Send + 'static,
+        F: FnOnce(T) -> U + Send + 'static,
+    {
+        let owner = self.owner();
+        let dependency = self.state.clone();
+        owner.submit_after(move || f(self.get_result()), &[dependency], Priority::Normal)
+    }
+
+    /// Start dependent work with the result once this task completes, e.g. load the material of a loaded mesh.
+    /// The returned result completes with the one returned by `f`, from a continuation queued after it,
+    /// so no thread is blocked waiting for it.
+    /// The result of this task is taken, do NOT get it from a clone.
+    pub fn then<U, F>(self, f: F) -> TaskResult<U>
+    where
+        U: Send + 'static,
+        F: FnOnce(T) -> TaskResult<U> + Send + 'static,
+    {
+        let owner = self.owner();
+        let state = Arc::new(TaskState::new(Arc::downgrade(&owner)));
+        let result = TaskResult::from_task(state.clone(), TaskId::new());
+
+        let dependency = self.state.clone();
+        let weak_owner = Arc::downgrade(&owner);
+        owner.submit_after(move || {
+            let inner = f(self.get_result());
+            let inner_dependency = inner.state.clone();
+
+            // workers of the schedular hold it, so it is alive while this task runs
+            let owner = weak_owner.upgrade().expect("Schedular is dropped while running its task!");
+            owner.submit_after(move || {
+                state.set_result(Box::new(inner.get_result()));
+            }, &[inner_dependency], Priority::Normal).detach();
+        }, &[dependency], Priority::Normal).detach();
+
+        result
+    }
+
+    /// Schedular running this task, the global one for placeholders.
+    fn owner(&self) -> Arc<GlobalTasks> {
+        self.state.owner
+            .upgrade()
+            .unwrap_or_else(|| crate::global_tasks().clone())
+    }
+
+    /// Convert into an untyped handle which is awaited instead, the result can NOT be taken anymore.
     pub fn into_handle(self) -> TaskHandle {
+        self.state.set_observed();
+
         TaskHandle {
             id: self.id,
-            state: self.state,
+            state: self.state.clone(),
         }
     }
 }
@@ -254,11 +406,7 @@ impl TaskHandle {
     pub fn placeholder() -> Self {
         Self {
             id: TaskId::INVALID,
-            state: Arc::new(TaskState {
-                result: Default::default(),
-                completed: AtomicBool::new(true),
-                condvar: Default::default(),
-            }),
+            state: Arc::new(TaskState::completed_placeholder()),
         }
     }
 
@@ -272,6 +420,12 @@ impl TaskHandle {
         self.state.wait()
     }
 
+    /// Block at most `timeout`, return true if the task is completed.
+    #[inline]
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        self.state.wait_timeout(timeout)
+    }
+
     #[inline]
     pub fn id(&self) -> TaskId {
         self.id
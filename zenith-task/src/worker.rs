@@ -1,20 +1,14 @@
 ﻿use std::sync::{Arc};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
-use crossbeam_queue::SegQueue;
-use parking_lot::{Mutex};
-use zenith_core::collections::hashmap::HashMap;
-use crate::executor::{QueuedTask, ThreadLocalState, UntypedCompletedFunc};
-use crate::task::{BoxedTask, TaskId};
+use crate::executor::{GlobalTasks, ThreadLocalState};
+use crate::task::TaskId;
 
 pub(crate) struct WorkerThread {
     shutdown: Arc<AtomicBool>,
 
-    global_queue: Arc<SegQueue<QueuedTask>>,
+    global_tasks: Arc<GlobalTasks>,
     local_state: Arc<ThreadLocalState>,
-
-    task_storage: Arc<Mutex<HashMap<TaskId, BoxedTask>>>,
-    task_complete_handles: Arc<Mutex<HashMap<TaskId, UntypedCompletedFunc>>>,
 }
 
 unsafe impl Send for WorkerThread {}
@@ -23,20 +17,14 @@ impl WorkerThread {
     pub(crate) fn new(
         shutdown: Arc<AtomicBool>,
 
-        global_queue: Arc<SegQueue<QueuedTask>>,
+        global_tasks: Arc<GlobalTasks>,
         local_state: Arc<ThreadLocalState>,
-
-        task_storage: Arc<Mutex<HashMap<TaskId, BoxedTask>>>,
-        task_complete_handles: Arc<Mutex<HashMap<TaskId, UntypedCompletedFunc>>>,
     ) -> Self {
         Self {
             shutdown,
 
-            global_queue,
+            global_tasks,
             local_state,
-
-            task_storage,
-            task_complete_handles,
         }
     }
 
@@ -60,21 +48,11 @@ impl WorkerThread {
             }
 
             let mut executed_global_task = false;
-            // 2. try to steal task from global queue if free from local queue.
+            // 2. try to steal task from global queue if free from local queue, higher priorities first.
             if !executed_local_task {
                 // find next available task (has no dependencies)
-                loop {
-                    if let Some(task) = self.global_queue.pop() {
-                        if task.ready_to_execute() {
-                            executed_global_task = self.execute_task(task.id());
-                            break;
-                        } else {
-                            // Not ready, put it back to the global queue
-                            self.global_queue.push(task);
-                        }
-                    } else {
-                        break;
-                    }
+                if let Some(task) = self.global_tasks.queue.pop_ready() {
+                    executed_global_task = self.execute_task(task.id());
                 }
             }
 
@@ -104,14 +82,14 @@ impl WorkerThread {
     }
 
     fn execute_task(&self, task_id: TaskId) -> bool {
-        let task = self.task_storage.lock().remove(&task_id);
+        let task = self.global_tasks.task_storage.lock().remove(&task_id);
 
         let mut executed_task = false;
         if let Some(task) = task {
             let result = task.execute();
 
             // notify task handles
-            if let Some(completed_fn) = self.task_complete_handles.lock().remove(&task_id) {
+            if let Some(completed_fn) = self.global_tasks.task_complete_handles.lock().remove(&task_id) {
                 completed_fn(result);
             }
 
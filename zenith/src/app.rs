@@ -1,18 +1,110 @@
-﻿use std::sync::Arc;
+﻿use std::future::Future;
+use std::path::Path;
+use std::sync::Arc;
 use winit::event::{DeviceEvent, WindowEvent};
-use winit::window::Window;
-use zenith_render::RenderDevice;
-use zenith_rendergraph::{RenderGraphBuilder, RenderGraphResource, Texture};
+use winit::dpi::LogicalSize;
+use winit::window::{Fullscreen, Window, WindowAttributes};
+use zenith_core::camera::Camera;
+use zenith_core::event::InputEvent;
+use zenith_render::{RenderDevice, RenderDeviceOptions};
+use zenith_rendergraph::{Buffer, RenderGraphBuilder, RenderGraphResource, Texture, Viewport};
+
+/// How the main window is created.
+#[derive(Debug, Clone)]
+pub struct WindowConfig {
+    pub title: String,
+    /// Size of the client area in logical pixels.
+    pub size: LogicalSize<u32>,
+    /// Minimal size of the client area in logical pixels, None for no limit.
+    pub min_size: Option<LogicalSize<u32>>,
+    pub resizable: bool,
+    /// Start in borderless fullscreen on the current monitor, see [`crate::Engine::set_fullscreen`].
+    pub fullscreen: bool,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            title: "Zenith Engine".to_string(),
+            size: LogicalSize::new(1280, 720),
+            min_size: None,
+            resizable: true,
+            fullscreen: false,
+        }
+    }
+}
+
+impl WindowConfig {
+    pub(crate) fn to_attributes(&self) -> WindowAttributes {
+        let mut attributes = Window::default_attributes()
+            .with_title(self.title.clone())
+            .with_inner_size(self.size)
+            .with_resizable(self.resizable)
+            .with_fullscreen(self.fullscreen.then_some(Fullscreen::Borderless(None)));
+        if let Some(min_size) = self.min_size {
+            attributes = attributes.with_min_inner_size(min_size);
+        }
+        attributes
+    }
+}
 
 pub trait App: Sized + 'static {
-    fn new() -> Result<Self, anyhow::Error>;
+    /// Create the app, implement it as `async fn new()`. The engine blocks on it before the loop starts.
+    fn new() -> impl Future<Output = Result<Self, anyhow::Error>>;
+    /// Options to create the main window.
+    fn window_config() -> WindowConfig { WindowConfig::default() }
+    /// Called once per frame before [`App::tick`] with each input event received since the last frame, in order.
+    fn on_input_event(&mut self, _event: &InputEvent) {}
+    /// Called once per frame before [`App::tick`] for each file dropped on the window, after its [`InputEvent::FileDropped`].
+    fn on_file_dropped(&mut self, _path: &Path) {}
+    /// Raw window event, called as soon as it is received. Prefer [`App::on_input_event`] unless winit specifics are needed.
     fn on_window_event(&mut self, _event: &WindowEvent, _window: &Window) {}
+    /// Raw device event, called as soon as it is received. Prefer [`App::on_input_event`] unless winit specifics are needed.
     fn on_device_event(&mut self, _event: &DeviceEvent) {}
+    /// Called once per frame with the variable frame time.
     fn tick(&mut self, _delta_time: f32) {}
+    /// Called zero or more times per frame with a constant [`crate::FIXED_DELTA_TIME`], for framerate-independent updates,
+    /// e.g. physics and input smoothing.
+    fn fixed_tick(&mut self, _fixed_delta_time: f32) {}
 }
 
 pub trait RenderableApp: App {
+    /// Options to create the render device, e.g. to request optional features.
+    fn render_device_options() -> RenderDeviceOptions { RenderDeviceOptions::default() }
     fn prepare(&mut self, render_device: &mut RenderDevice, main_window: Arc<Window>) -> Result<(), anyhow::Error>;
+    /// Prepare the app to render without window, see [`crate::render_to_image`].
+    fn prepare_headless(&mut self, _render_device: &mut RenderDevice, _width: u32, _height: u32) -> Result<(), anyhow::Error> {
+        Err(anyhow::anyhow!("{} does NOT support headless rendering", std::any::type_name::<Self>()))
+    }
     fn resize(&mut self, _width: u32, _height: u32) {}
-    fn render(&mut self, builder: &mut RenderGraphBuilder) -> Option<RenderGraphResource<Texture>>;
+    /// Called every frame before rendering, to create or update gpu resources, e.g. of assets finished loading.
+    fn prepare_frame(&mut self, _render_device: &mut RenderDevice) {}
+    /// Present mode wanted for the main window, queried every frame. Return None to keep the current one.
+    fn present_mode(&self) -> Option<wgpu::PresentMode> { None }
+    /// Background of the main window, queried every frame, see [`crate::Engine::set_clear_color`]. Return None to keep the current one.
+    fn clear_color(&self) -> Option<wgpu::Color> { None }
+    /// Camera the frame uniforms are filled from, queried every frame before rendering.
+    /// Return None to keep the last one (identity matrices if there was never a camera).
+    fn camera(&self) -> Option<&Camera> { None }
+    /// `frame_uniforms` is the engine owned [`zenith_renderer::FrameUniforms`] buffer of this frame, pass it to renderers.
+    /// `interpolation_alpha` in [0, 1) is how far the frame is between the last and the next fixed tick,
+    /// used to interpolate states updated in [`App::fixed_tick`].
+    fn render(&mut self, builder: &mut RenderGraphBuilder, frame_uniforms: &RenderGraphResource<Buffer>, interpolation_alpha: f32) -> Option<RenderGraphResource<Texture>>;
+    /// Render several outputs composited into regions of the main window in order, e.g. split-screen or debug overlays.
+    /// Default to the single output of [`RenderableApp::render`] covering the whole window.
+    fn render_outputs(&mut self, builder: &mut RenderGraphBuilder, frame_uniforms: &RenderGraphResource<Buffer>, interpolation_alpha: f32) -> Vec<(RenderGraphResource<Texture>, Viewport)> {
+        self.render(builder, frame_uniforms, interpolation_alpha).into_iter().map(|output| (output, Viewport::FULL)).collect()
+    }
+    /// Render the final pass straight into `swapchain`, the output texture of this frame with the size and format of the window.
+    /// Saves the full-screen copy of an offscreen output. Return false (default) to render with [`RenderableApp::render_outputs`] instead,
+    /// e.g. when rendering at a different resolution or format, `swapchain` must NOT be written then.
+    fn render_to_swapchain(
+        &mut self,
+        _builder: &mut RenderGraphBuilder,
+        _frame_uniforms: &RenderGraphResource<Buffer>,
+        _swapchain: &mut RenderGraphResource<Texture>,
+        _interpolation_alpha: f32,
+    ) -> bool {
+        false
+    }
 }
\ No newline at end of file
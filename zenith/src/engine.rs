@@ -1,99 +1,359 @@
 ﻿use std::sync::Arc;
-use winit::window::Window;
-use zenith_render::{RenderDevice, PipelineCache};
-use zenith_rendergraph::{RenderGraphBuilder, RenderResource, TextureState};
+use log::{error, info, warn};
+use winit::dpi::{LogicalPosition, LogicalSize, PhysicalPosition, PhysicalSize, Pixel};
+use winit::window::{Fullscreen, Window};
+use zenith_render::{RenderDevice, RenderDeviceOptions, PipelineCache};
+use zenith_core::camera::Camera;
+use zenith_renderer::{BlitRenderer, FrameUniformBuffer};
+use zenith_rendergraph::{RenderGraphBuilder, RenderGraphResource, RenderResource, Texture, TextureState, Viewport};
 use crate::RenderableApp;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FrameAdvance {
+    /// Tick with the measured frame time and render.
+    Run,
+    /// Tick with a fixed frame time and render once while paused.
+    Step,
+    /// Neither tick nor render.
+    Paused,
+}
+
+/// Uploads larger than a chunk get a dedicated chunk, this only bounds the reuse granularity.
+const STAGING_BELT_CHUNK_SIZE: wgpu::BufferAddress = 1 << 20;
+
 pub struct Engine {
     pub main_window: Arc<Window>,
     pub render_device: RenderDevice,
     
     pipeline_cache: PipelineCache,
+    /// Composite app outputs into the swapchain.
+    blit_renderer: BlitRenderer,
+    /// View, projection and time shared by all renderers of a frame.
+    frame_uniforms: FrameUniformBuffer,
+    /// Staging memory of `upload_buffer_staged`, recalled at the end of every frame.
+    staging_belt: wgpu::util::StagingBelt,
+    /// Background of the main window where no app output is composited, the whole window if the app renders nothing.
+    clear_color: wgpu::Color,
+    /// Ratio of physical to logical pixels of the main window, updated on `ScaleFactorChanged`.
+    scale_factor: f64,
+    /// Sum of all tick times in seconds, so the clock stops while paused.
+    elapsed: f32,
+    delta_time: f32,
 
     pub(crate) should_exit: bool,
+    paused: bool,
+    /// Frames to advance while paused, queued by [`Engine::step`].
+    pending_steps: u32,
+    max_fps: Option<f32>,
+    /// Profile the next frame on GPU and log the time of each pass.
+    log_gpu_timings: bool,
 }
 
 impl Engine {
-    pub fn new(main_window: Arc<Window>) -> Result<Self, anyhow::Error> {
-        let render_device = RenderDevice::new(main_window.clone())?;
+    pub fn new(main_window: Arc<Window>, device_options: RenderDeviceOptions) -> Result<Self, anyhow::Error> {
+        let render_device = RenderDevice::with_options(main_window.clone(), device_options)?;
         let pipeline_cache = PipelineCache::new();
+        let frame_uniforms = FrameUniformBuffer::new(&render_device);
+        let blit_renderer = BlitRenderer::new(&render_device);
 
         Ok(Self {
+            scale_factor: main_window.scale_factor(),
             main_window,
             render_device,
 
             pipeline_cache,
+            blit_renderer,
+            frame_uniforms,
+            staging_belt: wgpu::util::StagingBelt::new(STAGING_BELT_CHUNK_SIZE),
+            clear_color: wgpu::Color::BLACK,
+            elapsed: 0.,
+            delta_time: 0.,
 
             should_exit: false,
+            paused: false,
+            pending_steps: 0,
+            max_fps: None,
+            log_gpu_timings: false,
         })
     }
 
-    pub fn tick(&mut self, _delta_time: f32) {
+    /// Stop ticking and rendering, window and device events are still processed.
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.pending_steps = 0;
     }
 
-    pub fn render<A: RenderableApp>(&mut self, app: &mut A) {
+    #[inline]
+    pub fn is_paused(&self) -> bool { self.paused }
+
+    /// Advance exactly one tick and render, with [`crate::FIXED_DELTA_TIME`] as the frame time.
+    /// The engine is paused if it is running.
+    pub fn step(&mut self) {
+        self.paused = true;
+        self.pending_steps += 1;
+    }
+
+    /// Cap the frame rate by sleeping the rest of each frame, independent of vsync. None means uncapped.
+    pub fn set_max_fps(&mut self, max_fps: Option<f32>) {
+        self.max_fps = max_fps.filter(|fps| *fps > 0.);
+    }
+
+    #[inline]
+    pub fn max_fps(&self) -> Option<f32> { self.max_fps }
+
+    /// Log GPU time of each render pass of the next frame, if the device supports timestamp queries.
+    /// Reading the timings back stalls that frame, so do NOT request it every frame.
+    pub fn log_gpu_timings_of_next_frame(&mut self) {
+        self.log_gpu_timings = true;
+    }
+
+    /// Return how the next frame advances, consuming a queued step if paused.
+    pub(crate) fn advance_frame(&mut self) -> FrameAdvance {
+        if !self.paused {
+            FrameAdvance::Run
+        } else if self.pending_steps > 0 {
+            self.pending_steps -= 1;
+            FrameAdvance::Step
+        } else {
+            FrameAdvance::Paused
+        }
+    }
+
+    pub fn tick(&mut self, delta_time: f32) {
+        self.elapsed += delta_time;
+        self.delta_time = delta_time;
+    }
+
+    /// Seconds the engine has ticked, also available to shaders as `frame.time_seconds`.
+    #[inline]
+    pub fn elapsed(&self) -> f32 { self.elapsed }
+
+    /// Time of the last tick in seconds, also available to shaders as `frame.delta_seconds`.
+    #[inline]
+    pub fn delta_time(&self) -> f32 { self.delta_time }
+
+    /// Fill view and projection of the frame uniforms from the camera, kept until the next update.
+    pub fn update_frame_uniforms(&mut self, camera: &Camera) {
+        self.frame_uniforms.set_camera(camera);
+    }
+
+    pub fn render<A: RenderableApp>(&mut self, app: &mut A, interpolation_alpha: f32) {
+        app.prepare_frame(&mut self.render_device);
+
+        if let Some(camera) = app.camera() {
+            self.update_frame_uniforms(camera);
+        }
+        self.frame_uniforms.set_time(self.elapsed, self.delta_time);
+
         let device = self.render_device.device();
         let queue = self.render_device.queue();
 
         let mut builder = RenderGraphBuilder::new();
+        let frame_uniforms = self.frame_uniforms.import(&mut builder);
+
+        let surface_tex = match self.render_device.acquire_next_frame() {
+            Ok(surface_tex) => surface_tex,
+            Err(err @ (wgpu::SurfaceError::Outdated | wgpu::SurfaceError::Lost)) => {
+                warn!("Skip frame and reconfigure swapchain: {}", err);
+                self.render_device.reconfigure();
+                return;
+            }
+            Err(wgpu::SurfaceError::Timeout) => {
+                return;
+            }
+            Err(err) => {
+                error!("Skip frame: {}", err);
+                return;
+            }
+        };
+        let swapchain_tex = RenderResource::new(surface_tex.texture.clone());
+        let mut swapchain_tex = builder.import("swapchain.output", swapchain_tex, wgpu::TextureUses::PRESENT);
 
-        let app_output_tex = app.render(&mut builder);
-
-        if app_output_tex.is_some() {
-            let surface_tex = self.render_device.acquire_next_frame();
-            let swapchain_tex = RenderResource::new(surface_tex.texture.clone());
-            let app_output_tex = app_output_tex.unwrap();
-
-            {
-                let mut swapchain_tex = builder.import("swapchain.output", swapchain_tex.clone(), wgpu::TextureUses::PRESENT);
-
-                let mut node = builder.add_lambda_node("copy_output_to_swapchain");
-
-                let app_output_tex = node.read(&app_output_tex, TextureState::COPY_SRC);
-                let swapchain_tex = node.write(&mut swapchain_tex, TextureState::COPY_DST);
-
-                node.execute(move |ctx, encoder| {
-                    let src = ctx.get_texture(&app_output_tex);
-                    let dst = ctx.get_texture(&swapchain_tex);
-
-                    let width = dst.width();
-                    let height = dst.height();
-
-                    encoder.copy_texture_to_texture(
-                        wgpu::TexelCopyTextureInfo {
-                            texture: &src,
-                            mip_level: 0,
-                            origin: Default::default(),
-                            aspect: Default::default(),
-                        },
-                        wgpu::TexelCopyTextureInfo {
-                            texture: &dst,
-                            mip_level: 0,
-                            origin: Default::default(),
-                            aspect: Default::default(),
-                        },
-                        wgpu::Extent3d {
-                            width,
-                            height,
-                            depth_or_array_layers: 1,
-                        }
+        let rendered_to_swapchain = app.render_to_swapchain(&mut builder, &frame_uniforms, &mut swapchain_tex, interpolation_alpha);
+        let app_outputs = if rendered_to_swapchain {
+            vec![]
+        } else {
+            app.render_outputs(&mut builder, &frame_uniforms, interpolation_alpha)
+        };
+        match app_outputs.as_slice() {
+            // nothing rendered yet, e.g. assets are loading, present the background instead of stale contents
+            [] if !rendered_to_swapchain => Self::clear_swapchain(&mut builder, &mut swapchain_tex, self.clear_color),
+            [] => {}
+            // common case, a plain copy is enough
+            [(app_output_tex, viewport)] if *viewport == Viewport::FULL => Self::copy_to_swapchain(&mut builder, app_output_tex, &mut swapchain_tex),
+            _ => {
+                for (index, (app_output_tex, viewport)) in app_outputs.iter().enumerate() {
+                    let load_op = if index == 0 { wgpu::LoadOp::Clear(self.clear_color) } else { wgpu::LoadOp::Load };
+                    self.blit_renderer.build_viewport_render_graph(
+                        &mut builder,
+                        &format!("composite_output_{}", index),
+                        app_output_tex,
+                        &mut swapchain_tex,
+                        *viewport,
+                        load_op,
                     );
-                });
+                }
             }
+        }
 
-            let graph = builder.build(device);
-            let graph = graph.compile(device, &mut self.pipeline_cache);
-            let graph = graph.execute(device, queue);
+        let graph = builder.build(device);
+        let log_gpu_timings = std::mem::take(&mut self.log_gpu_timings);
+        let graph = match graph.compile(device, &mut self.pipeline_cache) {
+            Ok(graph) => graph.with_gpu_profiling(log_gpu_timings),
+            Err(err) => {
+                error!("Skip frame: {:#}", err);
+                return;
+            }
+        };
+        let graph = graph.execute_with_staging_belt(device, queue, &mut self.staging_belt);
+        // chunks are mapped again once the GPU is done with this frame
+        self.staging_belt.recall();
+        let graph = match graph {
+            Ok(graph) => graph,
+            Err(err) => {
+                error!("Skip frame: {}", err);
+                return;
+            }
+        };
 
-            self.main_window.pre_present_notify();
-            graph.present(surface_tex).unwrap();
+        if log_gpu_timings {
+            for (node_name, time_ms) in graph.gpu_timings() {
+                info!("GPU {}: {:.3} ms", node_name, time_ms);
+            }
         }
+
+        self.main_window.pre_present_notify();
+        graph.present(surface_tex).unwrap();
+    }
+
+    fn copy_to_swapchain(
+        builder: &mut RenderGraphBuilder,
+        app_output_tex: &RenderGraphResource<Texture>,
+        swapchain_tex: &mut RenderGraphResource<Texture>,
+    ) {
+        let mut node = builder.add_lambda_node("copy_output_to_swapchain");
+
+        let app_output_tex = node.read(app_output_tex, TextureState::COPY_SRC);
+        let swapchain_tex = node.write(swapchain_tex, TextureState::COPY_DST);
+
+        node.execute(move |ctx, encoder| {
+            let src = ctx.get_texture(&app_output_tex);
+            let dst = ctx.get_texture(&swapchain_tex);
+
+            let width = dst.width();
+            let height = dst.height();
+
+            encoder.copy_texture_to_texture(
+                wgpu::TexelCopyTextureInfo {
+                    texture: &src,
+                    mip_level: 0,
+                    origin: Default::default(),
+                    aspect: Default::default(),
+                },
+                wgpu::TexelCopyTextureInfo {
+                    texture: &dst,
+                    mip_level: 0,
+                    origin: Default::default(),
+                    aspect: Default::default(),
+                },
+                wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                }
+            );
+        });
+    }
+
+    fn clear_swapchain(builder: &mut RenderGraphBuilder, swapchain_tex: &mut RenderGraphResource<Texture>, color: wgpu::Color) {
+        let mut node = builder.add_lambda_node("clear_swapchain");
+
+        let swapchain_tex = node.write(swapchain_tex, TextureState::COLOR_TARGET);
+
+        node.execute(move |ctx, encoder| {
+            let view = ctx.get_texture(&swapchain_tex).create_view(&wgpu::TextureViewDescriptor::default());
+
+            // the pass only applies its load op
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("clear_swapchain"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(color),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+        });
     }
 
     pub fn resize(&mut self, width: u32, height: u32) {
         self.render_device.resize(width, height);
     }
 
+    /// Set the background of the main window, presented every frame the app renders nothing, e.g. while loading,
+    /// and around app outputs NOT covering the whole window.
+    pub fn set_clear_color(&mut self, clear_color: wgpu::Color) {
+        self.clear_color = clear_color;
+    }
+
+    #[inline]
+    pub fn clear_color(&self) -> wgpu::Color { self.clear_color }
+
+    #[inline]
+    pub fn present_mode(&self) -> wgpu::PresentMode { self.render_device.present_mode() }
+
+    /// Switch the present mode of main window, e.g. Immediate for uncapped frame rate.
+    /// Return the mode actually used, which fallbacks to Fifo if the mode is NOT supported.
+    pub fn set_present_mode(&mut self, present_mode: wgpu::PresentMode) -> wgpu::PresentMode {
+        self.render_device.set_present_mode(present_mode)
+    }
+
+    #[inline]
+    pub fn scale_factor(&self) -> f64 { self.scale_factor }
+
+    pub(crate) fn set_scale_factor(&mut self, scale_factor: f64) {
+        self.scale_factor = scale_factor;
+    }
+
+    /// Size of the main window in logical pixels, for ui layout.
+    /// Render targets must use the physical size, see [`Window::inner_size`].
+    pub fn logical_size(&self) -> LogicalSize<f64> {
+        self.main_window.inner_size().to_logical(self.scale_factor)
+    }
+
+    /// Convert a position in physical pixels, e.g. of the cursor, into logical pixels.
+    pub fn to_logical<P: Pixel>(&self, position: PhysicalPosition<P>) -> LogicalPosition<f64> {
+        position.to_logical(self.scale_factor)
+    }
+
+    /// Convert a position in logical pixels into physical pixels.
+    pub fn to_physical<P: Pixel>(&self, position: LogicalPosition<P>) -> PhysicalPosition<f64> {
+        position.to_physical(self.scale_factor)
+    }
+
+    /// Convert a size in logical pixels into physical pixels, rounded to whole pixels.
+    pub fn to_physical_size<P: Pixel>(&self, size: LogicalSize<P>) -> PhysicalSize<u32> {
+        size.to_physical(self.scale_factor)
+    }
+
+    /// Switch the main window between borderless fullscreen on its current monitor and windowed.
+    /// The swapchain is resized by the following resize event.
+    pub fn set_fullscreen(&mut self, fullscreen: bool) {
+        self.main_window.set_fullscreen(fullscreen.then_some(Fullscreen::Borderless(None)));
+    }
+
+    #[inline]
+    pub fn is_fullscreen(&self) -> bool { self.main_window.fullscreen().is_some() }
+
     #[inline]
     pub fn should_exit(&self) -> bool { self.should_exit }
 }
\ No newline at end of file
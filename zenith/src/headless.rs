@@ -0,0 +1,140 @@
+use std::sync::{Arc, OnceLock};
+use anyhow::anyhow;
+use image::RgbaImage;
+use zenith_render::{PipelineCache, RenderDevice};
+use zenith_renderer::FrameUniformBuffer;
+use zenith_rendergraph::{BufferState, RenderGraphBuilder, RenderResource, TextureDesc, TextureState};
+use crate::RenderableApp;
+
+/// Render one frame of the app without window, and read the output texture back to an image.
+/// Useful for golden-image comparison in tests or on CI servers.
+pub fn render_to_image<A: RenderableApp>(app: &mut A, width: u32, height: u32) -> Result<RgbaImage, anyhow::Error> {
+    let mut render_device = RenderDevice::headless(A::render_device_options())?;
+    let mut pipeline_cache = PipelineCache::new();
+
+    app.prepare_headless(&mut render_device, width, height)?;
+    app.resize(width, height);
+    app.prepare_frame(&mut render_device);
+
+    let mut frame_uniforms = FrameUniformBuffer::new(&render_device);
+    if let Some(camera) = app.camera() {
+        frame_uniforms.set_camera(camera);
+    }
+
+    let device = render_device.device();
+    let queue = render_device.queue();
+
+    let mut builder = RenderGraphBuilder::new();
+    let frame_uniforms = frame_uniforms.import(&mut builder);
+
+    // stands in for the swapchain of a window, see RenderableApp::render_to_swapchain
+    let mut swapchain_tex = builder.create("headless.swapchain", TextureDesc {
+        label: Some("headless swapchain"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: render_device.surface_format(),
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    let app_output_tex = if app.render_to_swapchain(&mut builder, &frame_uniforms, &mut swapchain_tex, 0.) {
+        swapchain_tex
+    } else {
+        app.render(&mut builder, &frame_uniforms, 0.).ok_or_else(|| anyhow!("App renders nothing to read back"))?
+    };
+
+    // rows of the copy destination must be aligned
+    let bytes_per_pixel = 4;
+    let unpadded_bytes_per_row = width * bytes_per_pixel;
+    let padded_bytes_per_row = unpadded_bytes_per_row.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+
+    let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("headless.readback"),
+        size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    // output texture is created inside the graph, so its format is only known on execution
+    let output_format = Arc::new(OnceLock::new());
+
+    {
+        let mut readback = builder.import("headless.readback", RenderResource::new(readback_buffer.clone()), BufferState::COPY_DST);
+
+        let mut node = builder.add_lambda_node("copy_output_to_readback");
+
+        let app_output_tex = node.read(&app_output_tex, TextureState::COPY_SRC);
+        let readback = node.write(&mut readback, BufferState::COPY_DST);
+
+        let output_format = output_format.clone();
+        node.execute(move |ctx, encoder| {
+            let src = ctx.get_texture(&app_output_tex);
+            let dst = ctx.get_buffer(&readback);
+            let _ = output_format.set(src.format());
+
+            encoder.copy_texture_to_buffer(
+                wgpu::TexelCopyTextureInfo {
+                    texture: &src,
+                    mip_level: 0,
+                    origin: Default::default(),
+                    aspect: Default::default(),
+                },
+                wgpu::TexelCopyBufferInfo {
+                    buffer: &dst,
+                    layout: wgpu::TexelCopyBufferLayout {
+                        offset: 0,
+                        bytes_per_row: Some(padded_bytes_per_row),
+                        rows_per_image: Some(height),
+                    },
+                },
+                wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                }
+            );
+        });
+    }
+
+    let graph = builder.build(device);
+    let graph = graph.compile(device, &mut pipeline_cache)?;
+    graph.execute(device, queue)?;
+
+    let buffer_slice = readback_buffer.slice(..);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    buffer_slice.map_async(wgpu::MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    device.poll(wgpu::PollType::Wait)?;
+    receiver.recv()??;
+
+    let output_format = *output_format.get().expect("Readback node is NOT executed!");
+    let is_bgra = match output_format {
+        wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+        _ => {
+            readback_buffer.unmap();
+            return Err(anyhow!("Unsupported output format to read back: {:?}", output_format));
+        }
+    };
+
+    let mut pixels = Vec::with_capacity((unpadded_bytes_per_row * height) as usize);
+    {
+        let mapped = buffer_slice.get_mapped_range();
+        for row in mapped.chunks_exact(padded_bytes_per_row as usize) {
+            pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+        }
+    }
+    readback_buffer.unmap();
+
+    if is_bgra {
+        pixels.chunks_exact_mut(4).for_each(|pixel| pixel.swap(0, 2));
+    }
+
+    RgbaImage::from_raw(width, height, pixels).ok_or_else(|| anyhow!("Readback size mismatch"))
+}
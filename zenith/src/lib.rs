@@ -3,11 +3,15 @@ use crate::main_loop::EngineLoop;
 mod engine;
 mod main_loop;
 mod app;
+mod headless;
 
-pub use app::{App, RenderableApp};
+pub use app::{App, RenderableApp, WindowConfig};
 pub use engine::Engine;
+pub use main_loop::FIXED_DELTA_TIME;
+pub use headless::render_to_image;
 
 pub use paste::paste;
+pub use smol::block_on;
 
 macro_rules! module_facade {
     ($name:ident) => {
@@ -32,7 +36,7 @@ pub fn launch<A: RenderableApp>() -> Result<(), anyhow::Error> {
     zenith_core::log::initialize()?;
     zenith_asset::initialize()?;
 
-    let app = A::new()?;
+    let app = block_on(A::new())?;
 
     let main_loop = EngineLoop::new(app)?;
     main_loop.run()?;
@@ -1,41 +1,68 @@
 ﻿use std::sync::Arc;
-use log::info;
+use std::time::{Duration, Instant};
+use log::{error, info};
 use winit::application::ApplicationHandler;
-use winit::event::{DeviceEvent, DeviceId, WindowEvent};
+use winit::event::{DeviceEvent, DeviceId, ElementState, KeyEvent, WindowEvent};
+use winit::keyboard::{KeyCode, PhysicalKey};
 use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
 use winit::window::{Window, WindowId};
+use zenith_core::event::{InputEvent, SystemEventCollector};
 use crate::app::{RenderableApp};
+use crate::engine::FrameAdvance;
 use crate::Engine;
 
+/// Time step in seconds of [`crate::App::fixed_tick`].
+pub const FIXED_DELTA_TIME: f32 = 1. / 60.;
+/// Frame time longer than this is dropped from the accumulator, so a hitch does NOT trigger a burst of fixed ticks.
+const MAX_ACCUMULATED_TIME: f32 = 0.25;
+/// Frame rate to poll events at while paused without a frame rate cap, avoid spinning the cpu.
+const PAUSED_FPS: f32 = 60.;
+/// Toggle between pause and resume.
+const PAUSE_KEY: KeyCode = KeyCode::Pause;
+/// Advance a single frame while paused.
+const STEP_KEY: KeyCode = KeyCode::F10;
+
 pub struct EngineLoop<A> {
     engine: Option<Engine>,
     app: A,
+    input_events: SystemEventCollector,
 
+    requested_present_mode: Option<wgpu::PresentMode>,
     frame_count: u64,
-    last_tick: std::time::Instant,
-    last_time_printed: std::time::Instant,
+    last_tick: Instant,
+    /// Frame time NOT consumed by fixed ticks yet.
+    fixed_time_accumulator: f32,
+    last_time_printed: Instant,
     should_exit: bool,
+    /// Failure to start the engine or the app, returned from [`EngineLoop::run`].
+    startup_error: Option<anyhow::Error>,
 }
 
 impl<A: RenderableApp> ApplicationHandler for EngineLoop<A> {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        // TODO: only renderable app should create window
-        let main_window = Arc::new(
-            event_loop
-                .create_window(Window::default_attributes())
-                .unwrap(),
-        );
-
-        let mut engine = Engine::new(main_window.clone()).unwrap();
-
-        self.app.prepare(&mut engine.render_device, main_window.clone()).unwrap();
-        self.engine = Some(engine);
+        if self.engine.is_some() || self.startup_error.is_some() {
+            return;
+        }
 
-        main_window.request_redraw();
+        match self.start(event_loop) {
+            Ok(engine) => {
+                engine.main_window.request_redraw();
+                self.engine = Some(engine);
+            }
+            Err(err) => {
+                // the surface and the window are destroyed with the engine, before the loop exits
+                error!("Failed to start: {:#}", err);
+                self.startup_error = Some(err);
+                event_loop.exit();
+            }
+        }
     }
 
     fn window_event(&mut self, event_loop: &ActiveEventLoop, _window_id: WindowId, event: WindowEvent) {
-        let engine = self.engine.as_mut().unwrap();
+        // events may still arrive from a window which failed to start
+        let Some(engine) = self.engine.as_mut() else {
+            return;
+        };
         if engine.should_exit() {
             event_loop.exit();
         }
@@ -44,11 +71,14 @@ impl<A: RenderableApp> ApplicationHandler for EngineLoop<A> {
     }
 
     fn device_event(&mut self, event_loop: &ActiveEventLoop, _device_id: DeviceId, event: DeviceEvent) {
-        let engine = self.engine.as_mut().unwrap();
+        let Some(engine) = self.engine.as_mut() else {
+            return;
+        };
         if engine.should_exit() {
             event_loop.exit();
         }
         
+        self.input_events.collect_device_event(&event);
         self.app.on_device_event(&event);
     }
 }
@@ -58,23 +88,52 @@ impl<A: RenderableApp> EngineLoop<A> {
         Ok(Self {
             engine: None,
             app,
+            input_events: SystemEventCollector::new(),
 
+            requested_present_mode: None,
             frame_count: 0u64,
-            last_tick: std::time::Instant::now(),
-            last_time_printed: std::time::Instant::now(),
+            last_tick: Instant::now(),
+            fixed_time_accumulator: 0.,
+            last_time_printed: Instant::now(),
             should_exit: false,
+            startup_error: None,
         })
     }
 
+    /// Run until the app exits. Return the error if the engine or the app failed to start.
     pub fn run(mut self) -> Result<(), anyhow::Error> {
         let event_loop = EventLoop::new()?;
         event_loop.set_control_flow(ControlFlow::Poll);
         event_loop.run_app(&mut self)?;
-        Ok(())
+
+        match self.startup_error.take() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    /// Create the main window and the engine, then prepare the app.
+    /// Dropping the returned engine destroys the surface and the window, if the app keeps no reference to it.
+    fn start(&mut self, event_loop: &ActiveEventLoop) -> Result<Engine, anyhow::Error> {
+        // TODO: only renderable app should create window
+        let main_window = Arc::new(event_loop.create_window(A::window_config().to_attributes())?);
+
+        let mut engine = Engine::new(main_window.clone(), A::render_device_options())?;
+
+        // the window may be created without any resize or scale event
+        let inner_size = main_window.inner_size();
+        self.input_events.push(InputEvent::Resized { width: inner_size.width, height: inner_size.height });
+        self.input_events.push(InputEvent::ScaleFactorChanged { scale_factor: main_window.scale_factor() as f32 });
+
+        self.app
+            .prepare(&mut engine.render_device, main_window)
+            .map_err(|err| err.context(format!("Failed to prepare {}", std::any::type_name::<A>())))?;
+        Ok(engine)
     }
     
     fn process_window_event(&mut self, event: &WindowEvent) {
         // TODO: multi-window support
+        self.input_events.collect_window_event(event);
         self.app.on_window_event(event, self.engine.as_ref().unwrap().main_window.as_ref());
         
         match event {
@@ -86,50 +145,125 @@ impl<A: RenderableApp> EngineLoop<A> {
                 engine.resize(inner_size.width, inner_size.height);
                 app.resize(inner_size.width, inner_size.height);
             }
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                // winit follows up with a Resized event if the physical size changes
+                self.engine.as_mut().unwrap().set_scale_factor(*scale_factor);
+            }
             WindowEvent::CloseRequested => {
                 let engine = self.engine.as_mut().unwrap();
 
                 engine.should_exit = true;
             }
+            WindowEvent::KeyboardInput { event: KeyEvent { physical_key: PhysicalKey::Code(key), state: ElementState::Pressed, repeat: false, .. }, .. } => {
+                let engine = self.engine.as_mut().unwrap();
+
+                match *key {
+                    PAUSE_KEY if engine.is_paused() => engine.resume(),
+                    PAUSE_KEY => engine.pause(),
+                    STEP_KEY => engine.step(),
+                    _ => {}
+                }
+            }
             WindowEvent::RedrawRequested => {
-                self.tick();
+                match self.engine.as_mut().unwrap().advance_frame() {
+                    FrameAdvance::Run => self.tick(None),
+                    FrameAdvance::Step => self.tick(Some(FIXED_DELTA_TIME)),
+                    FrameAdvance::Paused => {
+                        // the app still sees input while paused, e.g. to resume from its own ui
+                        self.dispatch_input_events();
+
+                        // time spent paused must NOT show up as a huge delta on resume
+                        self.last_tick = Instant::now();
+
+                        let engine = self.engine.as_ref().unwrap();
+                        engine.main_window.request_redraw();
+                        Self::limit_frame_rate(self.last_tick, engine.max_fps().or(Some(PAUSED_FPS)));
+                        return;
+                    }
+                }
 
                 let engine = self.engine.as_mut().unwrap();
                 let app = &mut self.app;
 
-                engine.render(app);
+                // only apply on change, the actual mode may fallback to Fifo
+                let present_mode = app.present_mode();
+                if present_mode.is_some() && present_mode != self.requested_present_mode {
+                    engine.set_present_mode(present_mode.unwrap());
+                    self.requested_present_mode = present_mode;
+                }
+                if let Some(clear_color) = app.clear_color() {
+                    engine.set_clear_color(clear_color);
+                }
+
+                let interpolation_alpha = self.fixed_time_accumulator / FIXED_DELTA_TIME;
+                engine.render(app, interpolation_alpha);
                 engine.main_window.request_redraw();
+
+                Self::limit_frame_rate(self.last_tick, engine.max_fps());
             }
             _ => {}
         }
     }
 
-    fn tick(&mut self) {
+    /// Sleep the rest of the frame budget of `max_fps`, counted from `frame_start`.
+    fn limit_frame_rate(frame_start: Instant, max_fps: Option<f32>) {
+        let Some(max_fps) = max_fps else {
+            return;
+        };
+
+        let frame_budget = Duration::from_secs_f32(1. / max_fps);
+        if let Some(remaining) = frame_budget.checked_sub(frame_start.elapsed()) {
+            std::thread::sleep(remaining);
+        }
+    }
+
+    fn dispatch_input_events(&mut self) {
+        for event in self.input_events.drain() {
+            self.app.on_input_event(&event);
+            if let InputEvent::FileDropped(path) = &event {
+                self.app.on_file_dropped(path);
+            }
+        }
+    }
+
+    /// Tick with `fixed_delta_time` if set, otherwise with the time measured since the last tick.
+    fn tick(&mut self, fixed_delta_time: Option<f32>) {
         if self.should_exit {
             return;
         }
 
+        self.dispatch_input_events();
+
         let delta_time = {
-            let now = std::time::Instant::now();
+            let now = Instant::now();
             let delta_time = now - self.last_tick;
             self.last_tick = now;
 
             let last_time_print_elapsed = (now - self.last_time_printed).as_secs_f32();
             if last_time_print_elapsed > 1. {
-                info!("Frame rate: {} fps", self.frame_count as f32 / last_time_print_elapsed);
+                let engine = self.engine.as_mut().unwrap();
+                info!("Frame rate: {} fps ({:?})", self.frame_count as f32 / last_time_print_elapsed, engine.present_mode());
+                engine.log_gpu_timings_of_next_frame();
                 self.last_time_printed = now;
                 self.frame_count = 0;
             }
 
-            delta_time.as_secs_f32()
+            fixed_delta_time.unwrap_or(delta_time.as_secs_f32())
         };
 
         let engine = self.engine.as_mut().unwrap();
         let app = &mut self.app;
-        
+
+        zenith_asset::registry().begin_frame();
         engine.tick(delta_time);
         app.tick(delta_time);
 
+        self.fixed_time_accumulator = (self.fixed_time_accumulator + delta_time).min(MAX_ACCUMULATED_TIME);
+        while self.fixed_time_accumulator >= FIXED_DELTA_TIME {
+            app.fixed_tick(FIXED_DELTA_TIME);
+            self.fixed_time_accumulator -= FIXED_DELTA_TIME;
+        }
+
         self.frame_count += 1;
     }
 }
\ No newline at end of file